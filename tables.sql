@@ -2,6 +2,9 @@ DROP TABLE users cascade;
 DROP TABLE tracked_wallets cascade;
 DROP TABLE copy_trade_settings cascade;
 DROP TABLE transactions cascade;
+DROP TABLE monitor_cursors cascade;
+DROP TABLE control_commands cascade;
+DROP TABLE trading_switch cascade;
 
 
 CREATE TABLE users (
@@ -16,6 +19,8 @@ CREATE TABLE tracked_wallets (
   user_id TEXT REFERENCES users(wallet_address),
   wallet_address TEXT NOT NULL,
   is_active BOOLEAN DEFAULT true,
+  label TEXT,
+  notes TEXT,
   created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
   updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
   UNIQUE(user_id, wallet_address)
@@ -35,6 +40,9 @@ CREATE TABLE copy_trade_settings (
   allowed_tokens TEXT[],
   use_allowed_tokens_list BOOLEAN DEFAULT false,
   min_sol_balance DECIMAL(18, 9) DEFAULT 0.01,
+  route_unsupported_venues BOOLEAN DEFAULT false,
+  min_transfer_amount DECIMAL(36, 9) DEFAULT 0,
+  skip_simulation BOOLEAN DEFAULT false,
   created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
   updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
   UNIQUE(user_id, tracked_wallet_id)
@@ -61,4 +69,96 @@ CREATE TABLE transactions (
   amount DECIMAL(18, 9) NOT NULL,
   price_sol DECIMAL(18, 9) NOT NULL,
   timestamp TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
-);
\ No newline at end of file
+);
+
+CREATE TABLE trade_executions (
+  id UUID DEFAULT uuid_generate_v4() PRIMARY KEY,
+  user_id TEXT REFERENCES users(wallet_address),
+  trade_type TEXT NOT NULL,
+  dex_type TEXT NOT NULL,
+  signal_dex_type TEXT,
+  transaction_type TEXT NOT NULL,
+  token_address TEXT NOT NULL,
+  tracked_wallet_id UUID REFERENCES tracked_wallets(id),
+  amount_sol DECIMAL(18, 9) NOT NULL DEFAULT 0,
+  amount_token DECIMAL(36, 9) NOT NULL DEFAULT 0,
+  signature TEXT,
+  child_signatures TEXT[] NOT NULL DEFAULT '{}',
+  route TEXT,
+  status TEXT NOT NULL,
+  error TEXT,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX trade_executions_created_at_idx ON trade_executions (user_id, created_at);
+CREATE INDEX trade_executions_signature_idx ON trade_executions (signature);
+
+CREATE TABLE watchlists (
+  id UUID DEFAULT uuid_generate_v4() PRIMARY KEY,
+  user_id TEXT REFERENCES users(wallet_address),
+  name TEXT NOT NULL,
+  description TEXT,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(user_id, name)
+);
+
+CREATE TABLE watchlist_tokens (
+  id UUID DEFAULT uuid_generate_v4() PRIMARY KEY,
+  watchlist_id UUID NOT NULL REFERENCES watchlists(id) ON DELETE CASCADE,
+  token_address TEXT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(watchlist_id, token_address)
+);
+
+CREATE TABLE positions (
+  id UUID DEFAULT uuid_generate_v4() PRIMARY KEY,
+  user_id TEXT REFERENCES users(wallet_address),
+  token_address TEXT NOT NULL,
+  amount_token DECIMAL(36, 9) NOT NULL DEFAULT 0,
+  cost_basis_sol DECIMAL(18, 9) NOT NULL DEFAULT 0,
+  realized_pnl_sol DECIMAL(18, 9) NOT NULL DEFAULT 0,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(user_id, token_address)
+);
+
+CREATE TABLE monitor_cursors (
+  id UUID DEFAULT uuid_generate_v4() PRIMARY KEY,
+  user_id TEXT REFERENCES users(wallet_address),
+  wallet_address TEXT NOT NULL,
+  last_signature TEXT NOT NULL,
+  last_slot BIGINT NOT NULL,
+  last_block_time BIGINT NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(user_id, wallet_address)
+);
+
+CREATE TABLE event_audit (
+  id UUID DEFAULT uuid_generate_v4() PRIMARY KEY,
+  user_id TEXT REFERENCES users(wallet_address),
+  event_id UUID NOT NULL,
+  event_type TEXT NOT NULL,
+  correlation_id TEXT,
+  payload JSONB NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX event_audit_created_at_idx ON event_audit (user_id, created_at);
+
+CREATE TABLE control_commands (
+  id UUID DEFAULT uuid_generate_v4() PRIMARY KEY,
+  user_id TEXT REFERENCES users(wallet_address),
+  command TEXT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+  applied_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX control_commands_pending_idx ON control_commands (user_id, created_at) WHERE applied_at IS NULL;
+
+CREATE TABLE trading_switch (
+  user_id TEXT PRIMARY KEY REFERENCES users(wallet_address),
+  halted BOOLEAN NOT NULL DEFAULT FALSE,
+  reason TEXT,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -10,32 +10,51 @@ use solana_sdk::signer::Signer;
 use std::net::SocketAddr;
 use std::{env, sync::Arc};
 use tokio::net::TcpListener;
-use trading_common::{data::get_server_keypair, SupabaseClient};
+use tracing::{info, warn};
+use trading_common::{
+    confirmation_tracker::{ConfirmationTracker, ConfirmationTrackerConfig},
+    data::get_server_keypair,
+    event_system::EventSystem,
+    kill_switch::kill_switch,
+    logging::{init_tracing, redact_url},
+    rpc::create_rpc_client,
+    SupabaseClient,
+};
 mod routes;
 
 #[derive(Clone)]
 struct AppState {
     rpc_client: Arc<ArcSwap<RpcClient>>,
     supabase_client: SupabaseClient,
+    // Bearer token for the control routes, which are refused while it is unset
+    control_token: Option<Arc<str>>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
+    init_tracing();
 
     let supabase_url = env::var("SUPABASE_URL").context("SUPABASE_URL must be set")?;
     let supabase_service_role_key =
         env::var("SUPABASE_SERVICE_ROLE_KEY").context("SUPABASE_SERVICE_ROLE_KEY must be set")?;
 
-
-    let supabase_key = env::var("SUPABASE_ANON_PUBLIC_KEY").context("SUPABASE_ANON_PUBLIC_KEY must be set")?;
+    let supabase_key =
+        env::var("SUPABASE_ANON_PUBLIC_KEY").context("SUPABASE_ANON_PUBLIC_KEY must be set")?;
 
     let rpc_url = env::var("SOLANA_RPC_HTTP_URL").context("SOLANA_RPC_HTTP_URL must be set")?;
-    println!("rpc_url: {}", rpc_url);
+    info!(
+        "rpc_url: {}",
+        rpc_url
+            .split(',')
+            .map(|url| redact_url(url.trim()))
+            .collect::<Vec<_>>()
+            .join(",")
+    );
 
     let server_keypair = get_server_keypair();
     let user_id = server_keypair.pubkey().to_string();
-    println!("user_id: {}", user_id);
+    info!("user_id: {}", user_id);
 
     let supabase_client = SupabaseClient::new(
         &supabase_url,
@@ -43,12 +62,36 @@ async fn main() -> Result<()> {
         &supabase_service_role_key,
         &user_id,
     );
-    let rpc_client = RpcClient::new(rpc_url);
+    let rpc_client = create_rpc_client(&rpc_url);
     let shared_rpc_client = Arc::new(ArcSwap::from_pointee(rpc_client));
 
+    // Nothing consumes events here, the tracker only keeps trade executions up to date
+    let (confirmation_tracker, _confirmation_tracker_handle) = ConfirmationTracker::spawn(
+        ConfirmationTrackerConfig::from_env(),
+        Arc::new(create_rpc_client(&rpc_url)),
+        Arc::new(EventSystem::new()),
+        Some(Arc::new(supabase_client.clone())),
+    );
+    confirmation_tracker.install();
+
+    let control_token = env::var("API_CONTROL_TOKEN")
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+        .map(Arc::from);
+    if control_token.is_none() {
+        warn!("API_CONTROL_TOKEN is not set, control routes are disabled");
+    }
+
+    // Trading stays refused until the switch has been read, see ensure_trading_enabled
+    if let Err(e) = kill_switch().sync(&supabase_client).await {
+        warn!("Failed to read the trading switch: {}", e);
+    }
+
     let state = AppState {
         rpc_client: shared_rpc_client,
         supabase_client,
+        control_token,
     };
 
     let app = Router::new()
@@ -84,16 +127,46 @@ async fn main() -> Result<()> {
             delete(routes::delete_copy_trade_settings),
         )
         .route("/transaction_history", get(routes::get_transaction_history))
+        .route("/trade_history", get(routes::get_trade_history))
+        .route("/wallet_stats", get(routes::get_wallet_stats))
+        .route("/watchlists", get(routes::get_watchlists))
+        .route("/watchlists", post(routes::create_watchlist))
+        .route("/watchlists/:watchlist_id", get(routes::get_watchlist))
+        .route("/watchlists/:watchlist_id", put(routes::update_watchlist))
+        .route(
+            "/watchlists/:watchlist_id",
+            delete(routes::delete_watchlist),
+        )
+        .route(
+            "/watchlists/:watchlist_id/tokens",
+            post(routes::add_watchlist_token),
+        )
+        .route(
+            "/watchlists/:watchlist_id/tokens/:token_address",
+            delete(routes::remove_watchlist_token),
+        )
         .route("/pump_fun/buy", post(routes::pump_fun_buy))
         .route("/pump_fun/sell", post(routes::pump_fun_sell))
         .route("/raydium/buy", post(routes::raydium_buy))
         .route("/raydium/sell", post(routes::raydium_sell))
+        .route(
+            "/control/commands",
+            get(routes::get_pending_control_commands),
+        )
+        .route("/control/commands", post(routes::queue_control_command))
+        .route(
+            "/control/spend_guard/resume",
+            post(routes::resume_spend_guard),
+        )
+        .route("/control/trading", get(routes::get_trading_switch))
+        .route("/control/trading/halt", post(routes::halt_trading))
+        .route("/control/trading/resume", post(routes::resume_trading))
         .with_state(state);
 
     let port = env::var("API_PORT").unwrap_or_else(|_| "3000".to_string());
     let addr = SocketAddr::from(([0, 0, 0, 0], port.parse()?));
 
-    println!("Server running on {}", addr);
+    info!("Server running on {}", addr);
     let listener = TcpListener::bind(addr)
         .await
         .context("Failed to bind to address")?;
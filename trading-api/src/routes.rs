@@ -1,18 +1,28 @@
 use crate::AppState;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::{header::AUTHORIZATION, HeaderMap},
     Json,
 };
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
 use serde_json::json;
+use tracing::{debug, info, warn};
 use trading_common::{
+    chunked_execution::process_chunked_buy_request,
     data::get_server_keypair,
+    dex::DexType,
     error::AppError,
-    models::{BuyRequest, BuyResponse, SellRequest, SellResponse},
-    pumpdotfun::{buy::process_buy_request, sell::process_sell_request},
-    raydium::{
-        buy::process_buy_request as process_raydium_buy,
-        sell::process_sell_request as process_raydium_sell,
+    kill_switch::kill_switch,
+    models::{
+        BuyRequest, BuyResponse, ControlCommand, ControlCommandRecord, Page, PageRequest,
+        SellRequest, SellResponse, TradeExecution, TradeExecutionFilter, TradeSource, TradeStatus,
+        TradingSwitch, TransactionType, WalletStats, Watchlist, WatchlistRequest, WatchlistToken,
+        WatchlistTokenRequest, WatchlistWithTokens,
     },
+    pumpdotfun::sell::process_sell_request,
+    raydium::sell::process_sell_request as process_raydium_sell,
+    spend_guard::spend_guard,
     CopyTradeSettings, TrackedWallet, TransactionLog,
 };
 use uuid::Uuid;
@@ -26,8 +36,9 @@ pub async fn get_tracked_wallets(
 
 pub async fn add_tracked_wallet(
     State(state): State<AppState>,
-    Json(wallet): Json<TrackedWallet>,
+    Json(mut wallet): Json<TrackedWallet>,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    wallet.validate()?;
     let result = state.supabase_client.add_tracked_wallet(wallet).await?;
     Ok(Json(
         json!({ "success": true, "tracked_wallet_id": result }),
@@ -69,11 +80,12 @@ pub async fn delete_tracked_wallet(
 
 pub async fn update_tracked_wallet(
     State(state): State<AppState>,
-    Json(update): Json<TrackedWallet>,
+    Json(mut update): Json<TrackedWallet>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    println!("update_tracked_wallet() called");
+    info!("update_tracked_wallet() called");
+    update.validate()?;
     let result = state.supabase_client.update_tracked_wallet(update).await?;
-    println!("update_tracked_wallet() result: {:?}", result);
+    info!("update_tracked_wallet() result: {:?}", result);
     Ok(Json(
         json!({ "success": true, "tracked_wallet_id": result }),
     ))
@@ -88,8 +100,9 @@ pub async fn get_copy_trade_settings(
 
 pub async fn create_copy_trade_settings(
     State(state): State<AppState>,
-    Json(settings): Json<CopyTradeSettings>,
+    Json(mut settings): Json<CopyTradeSettings>,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    settings.validate()?;
     let result = state
         .supabase_client
         .create_copy_trade_settings(settings)
@@ -99,8 +112,9 @@ pub async fn create_copy_trade_settings(
 
 pub async fn update_copy_trade_settings(
     State(state): State<AppState>,
-    Json(settings): Json<CopyTradeSettings>,
+    Json(mut settings): Json<CopyTradeSettings>,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    settings.validate()?;
     let result = state
         .supabase_client
         .update_copy_trade_settings(settings)
@@ -126,15 +140,139 @@ pub async fn get_transaction_history(
     Ok(Json(transactions))
 }
 
+pub async fn get_watchlists(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<WatchlistWithTokens>>, AppError> {
+    let watchlists = state.supabase_client.get_watchlists().await?;
+    Ok(Json(watchlists))
+}
+
+pub async fn get_watchlist(
+    State(state): State<AppState>,
+    Path(watchlist_id): Path<Uuid>,
+) -> Result<Json<WatchlistWithTokens>, AppError> {
+    let watchlist = state.supabase_client.get_watchlist(watchlist_id).await?;
+    Ok(Json(watchlist))
+}
+
+pub async fn create_watchlist(
+    State(state): State<AppState>,
+    Json(request): Json<WatchlistRequest>,
+) -> Result<Json<Watchlist>, AppError> {
+    let watchlist = state
+        .supabase_client
+        .create_watchlist(&request.name, request.description.as_deref())
+        .await?;
+    Ok(Json(watchlist))
+}
+
+pub async fn update_watchlist(
+    State(state): State<AppState>,
+    Path(watchlist_id): Path<Uuid>,
+    Json(request): Json<WatchlistRequest>,
+) -> Result<Json<Watchlist>, AppError> {
+    let watchlist = state
+        .supabase_client
+        .rename_watchlist(watchlist_id, &request.name, request.description.as_deref())
+        .await?;
+    Ok(Json(watchlist))
+}
+
+pub async fn delete_watchlist(
+    State(state): State<AppState>,
+    Path(watchlist_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    state.supabase_client.delete_watchlist(watchlist_id).await?;
+    Ok(Json(
+        json!({ "success": true, "watchlist_id": watchlist_id }),
+    ))
+}
+
+pub async fn add_watchlist_token(
+    State(state): State<AppState>,
+    Path(watchlist_id): Path<Uuid>,
+    Json(request): Json<WatchlistTokenRequest>,
+) -> Result<Json<WatchlistToken>, AppError> {
+    let token = state
+        .supabase_client
+        .add_watchlist_token(watchlist_id, &request.token_address)
+        .await?;
+    Ok(Json(token))
+}
+
+pub async fn remove_watchlist_token(
+    State(state): State<AppState>,
+    Path((watchlist_id, token_address)): Path<(Uuid, String)>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    state
+        .supabase_client
+        .remove_watchlist_token(watchlist_id, &token_address)
+        .await?;
+    Ok(Json(
+        json!({ "success": true, "token_address": token_address }),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TradeHistoryQuery {
+    pub tracked_wallet_id: Option<Uuid>,
+    pub token_address: Option<String>,
+    pub status: Option<TradeStatus>,
+    pub trade_type: Option<TradeSource>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+pub async fn get_trade_history(
+    State(state): State<AppState>,
+    Query(query): Query<TradeHistoryQuery>,
+) -> Result<Json<Page<TradeExecution>>, AppError> {
+    let filter = TradeExecutionFilter {
+        tracked_wallet_id: query.tracked_wallet_id,
+        token_address: query.token_address,
+        status: query.status,
+        trade_type: query.trade_type,
+        from: query.from,
+        to: query.to,
+        page: PageRequest {
+            limit: query.limit.unwrap_or(PageRequest::default().limit),
+            cursor: query.cursor,
+        },
+    };
+    let trades = state.supabase_client.get_trade_executions(&filter).await?;
+    Ok(Json(trades))
+}
+
+pub async fn get_wallet_stats(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<WalletStats>>, AppError> {
+    let stats = state.supabase_client.get_wallet_stats().await?;
+    Ok(Json(stats))
+}
+
 pub async fn pump_fun_buy(
     State(state): State<AppState>,
     Json(request): Json<BuyRequest>,
 ) -> Result<Json<BuyResponse>, AppError> {
     let rpc_client = state.rpc_client.load();
     let server_keypair = get_server_keypair();
-    println!("request: {:?}", request);
-    let response = process_buy_request(&rpc_client, &server_keypair, request).await?;
-    Ok(Json(response))
+    debug!("request: {:?}", request);
+
+    ensure_trading_enabled(&state).await?;
+    let mut execution = start_trade(
+        &state,
+        DexType::PumpFun,
+        TransactionType::Buy,
+        &request.token_address,
+    )
+    .await;
+    let result =
+        process_chunked_buy_request(&rpc_client, &server_keypair, &request, &DexType::PumpFun)
+            .await;
+    finish_buy(&state, &mut execution, &result).await;
+    Ok(Json(result?))
 }
 
 pub async fn pump_fun_sell(
@@ -143,9 +281,19 @@ pub async fn pump_fun_sell(
 ) -> Result<Json<SellResponse>, AppError> {
     let rpc_client = state.rpc_client.load();
     let server_keypair = get_server_keypair();
-    println!("request: {:?}", request);
-    let response = process_sell_request(&rpc_client, &server_keypair, request).await?;
-    Ok(Json(response))
+    debug!("request: {:?}", request);
+
+    ensure_trading_enabled(&state).await?;
+    let mut execution = start_trade(
+        &state,
+        DexType::PumpFun,
+        TransactionType::Sell,
+        &request.token_address,
+    )
+    .await;
+    let result = process_sell_request(&rpc_client, &server_keypair, request).await;
+    finish_sell(&state, &mut execution, &result).await;
+    Ok(Json(result?))
 }
 
 pub async fn raydium_buy(
@@ -155,9 +303,20 @@ pub async fn raydium_buy(
     let rpc_client = state.rpc_client.load();
     let server_keypair = get_server_keypair();
 
-    println!("Processing Raydium buy request: {:?}", request);
-    let response = process_raydium_buy(&rpc_client, &server_keypair, &request).await?;
-    Ok(Json(response))
+    debug!("Processing Raydium buy request: {:?}", request);
+    ensure_trading_enabled(&state).await?;
+    let mut execution = start_trade(
+        &state,
+        DexType::Raydium,
+        TransactionType::Buy,
+        &request.token_address,
+    )
+    .await;
+    let result =
+        process_chunked_buy_request(&rpc_client, &server_keypair, &request, &DexType::Raydium)
+            .await;
+    finish_buy(&state, &mut execution, &result).await;
+    Ok(Json(result?))
 }
 
 pub async fn raydium_sell(
@@ -167,7 +326,218 @@ pub async fn raydium_sell(
     let rpc_client = state.rpc_client.load();
     let server_keypair = get_server_keypair();
 
-    println!("Processing Raydium sell request: {:?}", request);
-    let response = process_raydium_sell(&rpc_client, &server_keypair, &request).await?;
-    Ok(Json(response))
+    debug!("Processing Raydium sell request: {:?}", request);
+    ensure_trading_enabled(&state).await?;
+    let mut execution = start_trade(
+        &state,
+        DexType::Raydium,
+        TransactionType::Sell,
+        &request.token_address,
+    )
+    .await;
+    let result = process_raydium_sell(&rpc_client, &server_keypair, &request).await;
+    finish_sell(&state, &mut execution, &result).await;
+    Ok(Json(result?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ControlRequest {
+    pub command: ControlCommand,
+}
+
+// The bot polls the queue and applies commands in order
+pub async fn queue_control_command(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<ControlRequest>,
+) -> Result<Json<ControlCommandRecord>, AppError> {
+    authorize_control(&state, &headers)?;
+    let record = state
+        .supabase_client
+        .queue_control_command(request.command)
+        .await?;
+    info!(
+        "Queued control command {:?} ({})",
+        record.command, record.id
+    );
+    Ok(Json(record))
+}
+
+pub async fn get_pending_control_commands(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ControlCommandRecord>>, AppError> {
+    authorize_control(&state, &headers)?;
+    let commands = state.supabase_client.get_pending_control_commands().await?;
+    Ok(Json(commands))
+}
+
+// Manual buys go through this process's own spend guard, which the bot's
+// control commands can't reach
+pub async fn resume_spend_guard(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, AppError> {
+    authorize_control(&state, &headers)?;
+    let was_paused = spend_guard().is_paused();
+    spend_guard().resume();
+    Ok(Json(json!({ "success": true, "was_paused": was_paused })))
+}
+
+pub async fn get_trading_switch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<TradingSwitch>, AppError> {
+    authorize_control(&state, &headers)?;
+    let switch = state.supabase_client.get_trading_switch().await?;
+    Ok(Json(switch))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HaltRequest {
+    pub reason: String,
+}
+
+// Halts trading in every process. The bot picks it up on its next control poll,
+// and it stays in effect across restarts until resumed
+pub async fn halt_trading(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<HaltRequest>,
+) -> Result<Json<TradingSwitch>, AppError> {
+    authorize_control(&state, &headers)?;
+    let reason = request.reason.trim();
+    if reason.is_empty() {
+        return Err(AppError::BadRequest(
+            "A reason is required to halt trading".to_string(),
+        ));
+    }
+
+    let switch = state
+        .supabase_client
+        .set_trading_switch(true, Some(reason))
+        .await?;
+    kill_switch().apply(switch.clone());
+    Ok(Json(switch))
+}
+
+pub async fn resume_trading(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<TradingSwitch>, AppError> {
+    authorize_control(&state, &headers)?;
+    let switch = state
+        .supabase_client
+        .set_trading_switch(false, None)
+        .await?;
+    kill_switch().apply(switch.clone());
+    Ok(Json(switch))
+}
+
+// Reads the switch fresh so a halt from any process applies to the next manual
+// trade. If it can't be read the last known state decides
+async fn ensure_trading_enabled(state: &AppState) -> Result<(), AppError> {
+    if let Err(e) = kill_switch().sync(&state.supabase_client).await {
+        warn!("Failed to read the trading switch: {}", e);
+    }
+    kill_switch().check()
+}
+
+fn authorize_control(state: &AppState, headers: &HeaderMap) -> Result<(), AppError> {
+    let Some(expected) = state.control_token.as_deref() else {
+        return Err(AppError::Unauthorized(
+            "Control routes are disabled, API_CONTROL_TOKEN is not set".to_string(),
+        ));
+    };
+    let provided = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+
+    if constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        Ok(())
+    } else {
+        Err(AppError::Unauthorized("Invalid control token".to_string()))
+    }
+}
+
+// Looks at every byte so the response time doesn't reveal how much matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// History writes never fail the trade itself, errors are only logged
+async fn start_trade(
+    state: &AppState,
+    dex_type: DexType,
+    transaction_type: TransactionType,
+    token_address: &str,
+) -> TradeExecution {
+    let execution = TradeExecution::pending(
+        TradeSource::Manual,
+        dex_type,
+        transaction_type,
+        token_address,
+    );
+    if let Err(e) = state
+        .supabase_client
+        .insert_trade_execution(&execution)
+        .await
+    {
+        warn!("Failed to record trade execution: {}", e);
+    }
+    execution
+}
+
+async fn finish_buy(
+    state: &AppState,
+    execution: &mut TradeExecution,
+    result: &Result<BuyResponse, AppError>,
+) {
+    match result {
+        Ok(response) if response.success => {
+            execution.succeeded(
+                &response.signature,
+                response.sol_spent,
+                response.token_quantity,
+            );
+            execution.child_signatures = response.child_signatures.clone();
+            execution.route = response.route.clone();
+        }
+        Ok(response) => execution.failed(response.error.clone().unwrap_or_default()),
+        Err(e) => execution.failed(e.to_string()),
+    }
+    finish_trade(state, execution).await;
+}
+
+async fn finish_sell(
+    state: &AppState,
+    execution: &mut TradeExecution,
+    result: &Result<SellResponse, AppError>,
+) {
+    match result {
+        Ok(response) if response.success => {
+            execution.succeeded(
+                &response.signature,
+                response.sol_received,
+                response.token_quantity,
+            );
+            execution.child_signatures = response.child_signatures.clone();
+            execution.route = response.route.clone();
+        }
+        Ok(response) => execution.failed(response.error.clone().unwrap_or_default()),
+        Err(e) => execution.failed(e.to_string()),
+    }
+    finish_trade(state, execution).await;
+}
+
+async fn finish_trade(state: &AppState, execution: &TradeExecution) {
+    if let Err(e) = state
+        .supabase_client
+        .update_trade_execution(execution)
+        .await
+    {
+        warn!("Failed to update trade execution {}: {}", execution.id, e);
+    }
 }
@@ -0,0 +1,53 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use trading_common::database::SupabaseClient;
+use trading_common::event_system::{Event, EventSystem};
+use trading_common::kill_switch::kill_switch;
+
+// Delivers commands queued through the API's control routes. Each command is
+// emitted as a ControlCommand event and then marked applied. A failed mark
+// means the command is emitted again on the next poll, which is harmless since
+// every command only sets state. The kill switch is read on the same schedule
+pub fn spawn_control_poller(
+    supabase_client: SupabaseClient,
+    event_system: Arc<EventSystem>,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = kill_switch().sync(&supabase_client).await {
+                warn!("Failed to read the trading switch: {}", e);
+            }
+
+            let commands = match supabase_client.get_pending_control_commands().await {
+                Ok(commands) => commands,
+                Err(e) => {
+                    warn!("Failed to poll control commands: {}", e);
+                    continue;
+                }
+            };
+
+            for record in commands {
+                info!(
+                    "Applying queued control command {:?} ({})",
+                    record.command, record.id
+                );
+                event_system.emit(Event::ControlCommand(record.command));
+                if let Err(e) = supabase_client
+                    .mark_control_command_applied(record.id)
+                    .await
+                {
+                    warn!(
+                        "Failed to mark control command {} applied: {}",
+                        record.id, e
+                    );
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,193 @@
+use chrono::{Duration as ChronoDuration, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::warn;
+use trading_common::database::SupabaseClient;
+use trading_common::event_system::{Event, EventKind, EventSystem};
+use trading_common::models::EventAuditRecord;
+use uuid::Uuid;
+
+const AUDITED_KINDS: &[EventKind] = &[
+    EventKind::CopyTradeExecution,
+    EventKind::TransactionLogged,
+    EventKind::WalletStateChange,
+    EventKind::InsufficientBalance,
+    EventKind::UnsupportedVenue,
+    EventKind::ConnectionStatus,
+    EventKind::ConnectionAlert,
+    EventKind::CopyTradingBreaker,
+    EventKind::TradingSwitch,
+    EventKind::Error,
+];
+
+#[derive(Debug, Clone)]
+pub struct EventAuditConfig {
+    pub enabled: bool,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+    pub queue_capacity: usize,
+    // Rows older than this are deleted once on startup, None keeps everything
+    pub retention_days: Option<i64>,
+}
+
+impl Default for EventAuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            batch_size: 50,
+            flush_interval: Duration::from_secs(5),
+            queue_capacity: 1_000,
+            retention_days: Some(30),
+        }
+    }
+}
+
+// Writes selected events to the event_audit table. Events go through a bounded
+// queue and are dropped when it is full, so a slow database never backs up trading
+pub fn spawn_event_audit(
+    config: EventAuditConfig,
+    event_system: Arc<EventSystem>,
+    supabase_client: SupabaseClient,
+) -> JoinHandle<()> {
+    let dropped = Arc::new(AtomicU64::new(0));
+    let (sender, receiver) = mpsc::channel(config.queue_capacity);
+
+    let mut events = event_system.subscribe_filtered(AUDITED_KINDS);
+    let reader_dropped = Arc::clone(&dropped);
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let Some(record) = audit_record(&event) {
+                        if sender.try_send(record).is_err() {
+                            reader_dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    reader_dropped.fetch_add(skipped, Ordering::Relaxed);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    tokio::spawn(run_writer(config, receiver, supabase_client, dropped))
+}
+
+async fn run_writer(
+    config: EventAuditConfig,
+    mut receiver: mpsc::Receiver<EventAuditRecord>,
+    supabase_client: SupabaseClient,
+    dropped: Arc<AtomicU64>,
+) {
+    if let Some(retention_days) = config.retention_days {
+        let cutoff = Utc::now() - ChronoDuration::days(retention_days);
+        if let Err(e) = supabase_client.delete_event_audit_before(cutoff).await {
+            warn!("Failed to apply event audit retention: {}", e);
+        }
+    }
+
+    // Failed batches are retried, but only this many records are kept pending
+    let max_pending = config.batch_size * 4;
+    let mut batch = Vec::with_capacity(config.batch_size);
+    let mut flush_interval = tokio::time::interval(config.flush_interval);
+    let mut reported_dropped = 0;
+
+    loop {
+        tokio::select! {
+            record = receiver.recv() => {
+                match record {
+                    Some(record) => {
+                        batch.push(record);
+                        if batch.len() >= config.batch_size {
+                            flush(&supabase_client, &mut batch, max_pending, &dropped).await;
+                        }
+                    }
+                    None => {
+                        flush(&supabase_client, &mut batch, max_pending, &dropped).await;
+                        break;
+                    }
+                }
+            }
+            _ = flush_interval.tick() => {
+                flush(&supabase_client, &mut batch, max_pending, &dropped).await;
+
+                let total_dropped = dropped.load(Ordering::Relaxed);
+                if total_dropped > reported_dropped {
+                    warn!("Event audit dropped {} events so far", total_dropped);
+                    reported_dropped = total_dropped;
+                }
+            }
+        }
+    }
+}
+
+async fn flush(
+    supabase_client: &SupabaseClient,
+    batch: &mut Vec<EventAuditRecord>,
+    max_pending: usize,
+    dropped: &AtomicU64,
+) {
+    if batch.is_empty() {
+        return;
+    }
+
+    match supabase_client.insert_event_audit(batch).await {
+        Ok(()) => batch.clear(),
+        Err(e) => {
+            warn!("Failed to write {} audit events: {}", batch.len(), e);
+            if batch.len() > max_pending {
+                let excess = batch.len() - max_pending;
+                batch.drain(..excess);
+                dropped.fetch_add(excess as u64, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+fn audit_record(event: &Event) -> Option<EventAuditRecord> {
+    match event {
+        Event::CopyTradeExecution(n) => {
+            record("copy_trade_execution", n.event_id, &n.correlation_id, n)
+        }
+        Event::TransactionLogged(n) => {
+            record("transaction_logged", n.event_id, &n.correlation_id, n)
+        }
+        Event::WalletStateChange(n) => {
+            record("wallet_state_change", n.event_id, &n.correlation_id, n)
+        }
+        Event::InsufficientBalance(n) => {
+            record("insufficient_balance", n.event_id, &n.correlation_id, n)
+        }
+        Event::UnsupportedVenue(n) => record("unsupported_venue", n.event_id, &n.correlation_id, n),
+        Event::ConnectionStatus(n) => record("connection_status", n.event_id, &n.correlation_id, n),
+        Event::ConnectionAlert(n) => record("connection_alert", n.event_id, &n.correlation_id, n),
+        Event::CopyTradingBreaker(n) => {
+            record("copy_trading_breaker", n.event_id, &n.correlation_id, n)
+        }
+        Event::TradingSwitch(n) => record("trading_switch", n.event_id, &n.correlation_id, n),
+        Event::Error(n) => record("error", n.event_id, &n.correlation_id, n),
+        _ => None,
+    }
+}
+
+fn record<T: Serialize>(
+    event_type: &str,
+    event_id: Uuid,
+    correlation_id: &Option<String>,
+    notification: &T,
+) -> Option<EventAuditRecord> {
+    Some(EventAuditRecord {
+        event_id,
+        event_type: event_type.to_string(),
+        correlation_id: correlation_id.clone(),
+        payload: serde_json::to_value(notification).ok()?,
+        created_at: Utc::now(),
+    })
+}
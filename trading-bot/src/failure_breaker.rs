@@ -0,0 +1,172 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct FailureBreakerConfig {
+    // Consecutive failed copy trades that pause copy trading, zero disables the breaker
+    pub max_consecutive_failures: u32,
+    // A failure further than this from the first of the streak starts a new streak
+    pub window: Duration,
+    // None keeps copy trading paused until it is resumed by hand
+    pub cooldown: Option<Duration>,
+}
+
+impl Default for FailureBreakerConfig {
+    fn default() -> Self {
+        Self {
+            max_consecutive_failures: 5,
+            window: Duration::from_secs(10 * 60),
+            cooldown: Some(Duration::from_secs(15 * 60)),
+        }
+    }
+}
+
+// Counts consecutive copy trade failures. Skipped trades are never reported to
+// it, so only executions that were attempted and failed move the count
+pub struct FailureBreaker {
+    config: FailureBreakerConfig,
+    failures: u32,
+    streak_started: Option<Instant>,
+    tripped_at: Option<Instant>,
+}
+
+impl FailureBreaker {
+    pub fn new(config: FailureBreakerConfig) -> Self {
+        Self {
+            config,
+            failures: 0,
+            streak_started: None,
+            tripped_at: None,
+        }
+    }
+
+    pub fn config(&self) -> &FailureBreakerConfig {
+        &self.config
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.tripped_at.is_some()
+    }
+
+    pub fn record_success(&mut self) {
+        self.failures = 0;
+        self.streak_started = None;
+    }
+
+    // Returns true when this failure trips the breaker
+    pub fn record_failure(&mut self) -> bool {
+        if self.config.max_consecutive_failures == 0 || self.is_tripped() {
+            return false;
+        }
+
+        match self.streak_started {
+            Some(started) if started.elapsed() <= self.config.window => self.failures += 1,
+            _ => {
+                self.streak_started = Some(Instant::now());
+                self.failures = 1;
+            }
+        }
+
+        if self.failures < self.config.max_consecutive_failures {
+            return false;
+        }
+        self.tripped_at = Some(Instant::now());
+        true
+    }
+
+    // True once, when a tripped breaker's cool-down has run out. The breaker is
+    // reset so the next streak starts from zero
+    pub fn cooldown_expired(&mut self) -> bool {
+        match (self.tripped_at, self.config.cooldown) {
+            (Some(tripped_at), Some(cooldown)) if tripped_at.elapsed() >= cooldown => {
+                self.reset();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Copy trading was paused or resumed by hand, which supersedes the cool-down
+    pub fn reset(&mut self) {
+        self.failures = 0;
+        self.streak_started = None;
+        self.tripped_at = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(max_consecutive_failures: u32, cooldown: Option<Duration>) -> FailureBreaker {
+        FailureBreaker::new(FailureBreakerConfig {
+            max_consecutive_failures,
+            window: Duration::from_secs(3600),
+            cooldown,
+        })
+    }
+
+    #[test]
+    fn trips_once_at_the_threshold() {
+        let mut breaker = breaker(3, None);
+
+        assert!(!breaker.record_failure());
+        assert!(!breaker.record_failure());
+        assert!(breaker.record_failure());
+        assert!(breaker.is_tripped());
+        assert_eq!(breaker.failures(), 3);
+
+        // Already tripped, further failures don't report a new trip
+        assert!(!breaker.record_failure());
+        assert_eq!(breaker.failures(), 3);
+    }
+
+    #[test]
+    fn success_clears_the_streak() {
+        let mut breaker = breaker(2, None);
+
+        assert!(!breaker.record_failure());
+        breaker.record_success();
+        assert_eq!(breaker.failures(), 0);
+        assert!(!breaker.record_failure());
+        assert!(!breaker.is_tripped());
+    }
+
+    #[test]
+    fn zero_threshold_disables_the_breaker() {
+        let mut breaker = breaker(0, None);
+
+        for _ in 0..10 {
+            assert!(!breaker.record_failure());
+        }
+        assert!(!breaker.is_tripped());
+    }
+
+    #[test]
+    fn cooldown_resets_the_breaker_once() {
+        let mut breaker = breaker(1, Some(Duration::ZERO));
+
+        assert!(!breaker.cooldown_expired());
+        assert!(breaker.record_failure());
+        assert!(breaker.cooldown_expired());
+        assert!(!breaker.is_tripped());
+        assert_eq!(breaker.failures(), 0);
+        assert!(!breaker.cooldown_expired());
+    }
+
+    #[test]
+    fn without_cooldown_only_reset_clears_a_trip() {
+        let mut breaker = breaker(1, None);
+
+        assert!(breaker.record_failure());
+        assert!(!breaker.cooldown_expired());
+        assert!(breaker.is_tripped());
+
+        breaker.reset();
+        assert!(!breaker.is_tripped());
+        assert!(breaker.record_failure());
+    }
+}
@@ -0,0 +1,47 @@
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+use trading_common::health::{ReadinessCheck, ReadinessReport};
+
+// /healthz only says the process is up, /readyz is what orchestrators should
+// use to decide whether the bot is actually doing its job
+pub fn spawn_health_server(readiness: Arc<ReadinessCheck>, port: u16) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let app = Router::new()
+            .route("/healthz", get(liveness))
+            .route("/readyz", get(readiness_report))
+            .with_state(readiness);
+        let addr = SocketAddr::from(([0, 0, 0, 0], port));
+
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to bind health server on {}: {}", addr, e);
+                return;
+            }
+        };
+        info!("Health server running on {}", addr);
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("Health server failed: {}", e);
+        }
+    })
+}
+
+async fn liveness() -> &'static str {
+    "ok"
+}
+
+async fn readiness_report(
+    State(readiness): State<Arc<ReadinessCheck>>,
+) -> (StatusCode, Json<ReadinessReport>) {
+    let report = readiness.report();
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
@@ -1,19 +1,43 @@
+mod control_poller;
+mod event_audit;
+mod failure_breaker;
+mod health_server;
+mod message_queue;
+mod metrics_server;
 mod wallet_monitor;
 use anyhow::{Context, Result};
+use control_poller::spawn_control_poller;
 use dotenv::dotenv;
-use solana_client::rpc_client::RpcClient;
+use event_audit::{spawn_event_audit, EventAuditConfig};
+use failure_breaker::FailureBreakerConfig;
+use health_server::spawn_health_server;
+use message_queue::{MessageQueueConfig, QueueFullPolicy};
+use metrics_server::spawn_metrics_server;
 use solana_sdk::signer::Signer;
 use solana_sdk::{pubkey::Pubkey, signature::Keypair};
-use std::{env, sync::Arc};
+use std::{env, sync::Arc, time::Duration};
 use tokio::signal;
-use trading_common::database::SupabaseClient;
+use tracing::{error, info, warn};
+use trading_common::confirmation_tracker::{ConfirmationTracker, ConfirmationTrackerConfig};
+use trading_common::connection_monitor::ConnectionMonitor;
+use trading_common::database::{QueryEventConfig, SupabaseClient};
 use trading_common::event_system::EventSystem;
+use trading_common::health::{ReadinessCheck, ReadinessConfig};
+use trading_common::kill_switch::kill_switch;
+use trading_common::logging::init_tracing;
+use trading_common::metrics::Metrics;
+use trading_common::models::ConnectionType;
+use trading_common::notifier::{spawn_notifier, NotifierConfig};
+use trading_common::query_latency::{spawn_query_latency_aggregator, QueryLatencyConfig};
+use trading_common::rpc::create_rpc_client;
 use trading_common::server_wallet_manager::ServerWalletManager;
-use wallet_monitor::WalletMonitor;
+use trading_common::spend_guard::spend_guard;
+use wallet_monitor::{CursorConfig, WalletMonitor};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
+    init_tracing();
 
     let rpc_http_url = env::var("SOLANA_RPC_HTTP_URL").context("SOLANA_RPC_URL must be set")?;
     let rpc_ws_url = env::var("SOLANA_RPC_WS_URL").context("SOLANA_RPC_WS_URL must be set")?;
@@ -25,6 +49,69 @@ async fn main() -> Result<()> {
     let supabase_service_role_key =
         env::var("SUPABASE_SERVICE_ROLE_KEY").context("SUPABASE_SERVICE_ROLE_KEY must be set")?;
 
+    let shutdown_timeout = env::var("SHUTDOWN_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(30));
+
+    let default_queue_config = MessageQueueConfig::default();
+    let queue_config = MessageQueueConfig {
+        capacity: env::var("MESSAGE_QUEUE_CAPACITY")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(default_queue_config.capacity),
+        full_policy: match env::var("MESSAGE_QUEUE_FULL_POLICY").ok().as_deref() {
+            Some("reject") => QueueFullPolicy::Reject,
+            Some("drop_oldest") => QueueFullPolicy::DropOldest,
+            _ => default_queue_config.full_policy,
+        },
+        max_message_age: env::var("MAX_MESSAGE_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(default_queue_config.max_message_age),
+        max_slot_lag: env::var("MAX_SLOT_LAG")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .or(default_queue_config.max_slot_lag),
+    };
+
+    let default_cursor_config = CursorConfig::default();
+    let cursor_config = CursorConfig {
+        backfill_on_startup: env::var("BACKFILL_ON_STARTUP")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok())
+            .unwrap_or(default_cursor_config.backfill_on_startup),
+        max_age: env::var("CURSOR_MAX_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(default_cursor_config.max_age),
+    };
+
+    let default_breaker_config = FailureBreakerConfig::default();
+    let breaker_config = FailureBreakerConfig {
+        max_consecutive_failures: env::var("COPY_TRADE_MAX_CONSECUTIVE_FAILURES")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(default_breaker_config.max_consecutive_failures),
+        window: env::var("COPY_TRADE_FAILURE_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(default_breaker_config.window),
+        // Zero keeps copy trading paused until it is resumed by hand
+        cooldown: match env::var("COPY_TRADE_FAILURE_COOLDOWN_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => default_breaker_config.cooldown,
+        },
+    };
+
     let server_keypair = Keypair::from_base58_string(&server_secret_key);
     if server_keypair.pubkey() == Pubkey::default() {
         return Err(anyhow::anyhow!("Invalid server secret key"));
@@ -38,9 +125,66 @@ async fn main() -> Result<()> {
         &user_id,
     );
 
-    let rpc_client = Arc::new(RpcClient::new(rpc_http_url));
+    let rpc_client = Arc::new(create_rpc_client(&rpc_http_url));
+
+    let event_system = match env::var("EVENT_REPLAY_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+    {
+        Some(capacity) => Arc::new(EventSystem::with_replay_capacity(capacity)),
+        None => Arc::new(EventSystem::new()),
+    };
+
+    // Installed before anything records so early connection states are captured
+    let metrics = Arc::new(Metrics::new().context("Failed to create metrics registry")?);
+    Arc::clone(&metrics).install();
+    let metrics_port = env::var("METRICS_PORT")
+        .ok()
+        .and_then(|v| v.parse::<u16>().ok())
+        .unwrap_or(9091);
+    let _metrics_server = spawn_metrics_server(metrics, metrics_port);
 
-    let event_system = Arc::new(EventSystem::new());
+    let connection_status_debounce = env::var("CONNECTION_STATUS_DEBOUNCE_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(Duration::from_secs(2));
+    let health_hysteresis = env::var("HEALTH_STATUS_HYSTERESIS_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(10));
+    let health_snapshot_interval = env::var("HEALTH_SNAPSHOT_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(30));
+    let mut connection_monitor =
+        ConnectionMonitor::new(event_system.clone(), connection_status_debounce)
+            .with_hysteresis(health_hysteresis);
+    for (connection_type, key) in [
+        (
+            ConnectionType::WebSocket,
+            "CONNECTION_ALERT_AFTER_SECS_WEBSOCKET",
+        ),
+        (
+            ConnectionType::Database,
+            "CONNECTION_ALERT_AFTER_SECS_DATABASE",
+        ),
+    ] {
+        if let Some(threshold) = env::var(key).ok().and_then(|v| v.parse::<u64>().ok()) {
+            connection_monitor = connection_monitor
+                .with_alert_threshold(connection_type, Duration::from_secs(threshold));
+        }
+    }
+    let connection_monitor = Arc::new(connection_monitor);
+    let _connection_flusher = connection_monitor.spawn_flusher();
+    let _health_reporter = connection_monitor.spawn_health_reporter(health_snapshot_interval);
+    let supabase_client = supabase_client
+        .with_connection_monitor(Arc::clone(&connection_monitor))
+        .with_event_system(event_system.clone(), QueryEventConfig::from_env());
+    let _query_latency =
+        spawn_query_latency_aggregator(QueryLatencyConfig::from_env(), event_system.clone());
 
     // Initialize wallet manager
     let server_wallet_manager = Arc::new(tokio::sync::Mutex::new(
@@ -55,21 +199,96 @@ async fn main() -> Result<()> {
 
     // Print initial wallet state
     {
-        let wallet_manager = server_wallet_manager.lock().await;
-        println!("Server Wallet Address: {}", server_keypair.pubkey());
-        println!(
+        let mut wallet_manager = server_wallet_manager.lock().await;
+        match wallet_manager
+            .close_orphaned_wsol_accounts(&server_keypair)
+            .await
+        {
+            Ok(0) => {}
+            Ok(closed) => info!("Closed {} orphaned WSOL accounts", closed),
+            Err(e) => warn!("Failed to close orphaned WSOL accounts: {}", e),
+        }
+
+        // Restore entry prices so PnL survives restarts
+        match supabase_client.get_positions().await {
+            Ok(positions) => wallet_manager.load_positions(positions),
+            Err(e) => warn!("Failed to load positions: {}", e),
+        }
+
+        info!("Server Wallet Address: {}", server_keypair.pubkey());
+        info!(
             "SOL Balance: {} SOL",
             wallet_manager.get_sol_balance().await?
         );
-        println!("Token Balances:");
+        info!("Token Balances:");
         for token_info in wallet_manager.get_token_values() {
-            println!(
+            info!(
                 "  {}: {} {}",
                 token_info.name, token_info.balance, token_info.symbol
             );
         }
     }
 
+    let default_audit_config = EventAuditConfig::default();
+    let audit_config = EventAuditConfig {
+        enabled: env::var("EVENT_AUDIT_ENABLED")
+            .ok()
+            .and_then(|v| v.parse::<bool>().ok())
+            .unwrap_or(default_audit_config.enabled),
+        batch_size: env::var("EVENT_AUDIT_BATCH_SIZE")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(default_audit_config.batch_size),
+        flush_interval: env::var("EVENT_AUDIT_FLUSH_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(default_audit_config.flush_interval),
+        queue_capacity: env::var("EVENT_AUDIT_QUEUE_CAPACITY")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(default_audit_config.queue_capacity),
+        retention_days: match env::var("EVENT_AUDIT_RETENTION_DAYS") {
+            Ok(v) => v.parse::<i64>().ok().filter(|days| *days > 0),
+            Err(_) => default_audit_config.retention_days,
+        },
+    };
+    let _event_audit = if audit_config.enabled {
+        info!("Event audit enabled: {:?}", audit_config);
+        Some(spawn_event_audit(
+            audit_config,
+            event_system.clone(),
+            supabase_client.clone(),
+        ))
+    } else {
+        None
+    };
+
+    // Follows every sent transaction until it is finalized, failed or dropped
+    let (confirmation_tracker, _confirmation_tracker_handle) = ConfirmationTracker::spawn(
+        ConfirmationTrackerConfig::from_env(),
+        Arc::clone(&rpc_client),
+        event_system.clone(),
+        Some(Arc::new(supabase_client.clone())),
+    );
+    confirmation_tracker.install();
+    spend_guard().set_event_system(event_system.clone());
+    kill_switch().set_event_system(event_system.clone());
+    let _notifier = spawn_notifier(NotifierConfig::from_env(), event_system.clone());
+
+    // Commands queued through the API's control routes
+    let control_poll_interval = env::var("CONTROL_POLL_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(5));
+    let _control_poller = spawn_control_poller(
+        supabase_client.clone(),
+        event_system.clone(),
+        control_poll_interval,
+    );
+
     // Initialize and start wallet monitor
     let mut monitor = WalletMonitor::new(
         Arc::clone(&rpc_client),
@@ -77,10 +296,29 @@ async fn main() -> Result<()> {
         supabase_client,
         server_keypair,
         event_system.clone(),
+        Arc::clone(&connection_monitor),
         Arc::clone(&server_wallet_manager),
+        queue_config,
+        cursor_config,
+        breaker_config,
     )
     .await?;
 
+    // Supabase only reports when its circuit opens, the websocket must have connected
+    let readiness =
+        ReadinessCheck::new(ReadinessConfig::from_env(), Arc::clone(&connection_monitor))
+            .require_connection(ConnectionType::WebSocket)
+            .with_heartbeat("message_processor", monitor.message_processor_heartbeat())
+            .with_heartbeat("websocket_monitor", monitor.websocket_heartbeat())
+            .with_flag("copy_trading_paused", monitor.copy_trading_paused_flag())
+            .with_flag("monitoring_paused", monitor.monitoring_paused_flag())
+            .with_flag("trading_halted", kill_switch().halted_flag());
+    let health_port = env::var("HEALTH_PORT")
+        .ok()
+        .and_then(|v| v.parse::<u16>().ok())
+        .unwrap_or(8081);
+    let _health_server = spawn_health_server(Arc::new(readiness), health_port);
+
     let mut shutdown_monitor = monitor.clone();
 
     // Create signal handler before select
@@ -89,28 +327,28 @@ async fn main() -> Result<()> {
 
     let monitor_handle = tokio::spawn(async move {
         if let Err(e) = monitor.start().await {
-            eprintln!("Wallet monitor error: {:?}", e);
+            error!("Wallet monitor error: {:?}", e);
         }
     });
 
     // Handle shutdown signals
     tokio::select! {
         _ = signal::ctrl_c() => {
-            println!("\nReceived Ctrl+C, initiating graceful shutdown...");
+            info!("Received Ctrl+C, initiating graceful shutdown...");
         }
         _ = sigterm.recv() => {
-            println!("\nReceived termination signal, initiating graceful shutdown...");
+            info!("Received termination signal, initiating graceful shutdown...");
         }
         _ = monitor_handle => {
-            println!("\nMonitor task completed.");
+            info!("Monitor task completed.");
         }
     }
 
     // Perform graceful shutdown
-    if let Err(e) = shutdown_monitor.stop().await {
-        eprintln!("Error during shutdown: {:?}", e);
+    if let Err(e) = shutdown_monitor.stop(shutdown_timeout).await {
+        error!("Error during shutdown: {:?}", e);
     }
-    println!("Shutdown complete.");
+    info!("Shutdown complete.");
 
     Ok(())
 }
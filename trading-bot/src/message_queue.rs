@@ -0,0 +1,227 @@
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueueFullPolicy {
+    DropOldest,
+    Reject,
+}
+
+#[derive(Debug, Clone)]
+pub struct MessageQueueConfig {
+    pub capacity: usize,
+    pub full_policy: QueueFullPolicy,
+    pub max_message_age: Duration,
+    // Messages received further behind the chain tip than this are skipped
+    pub max_slot_lag: Option<u64>,
+}
+
+impl Default for MessageQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1_000,
+            full_policy: QueueFullPolicy::DropOldest,
+            max_message_age: Duration::from_secs(60),
+            max_slot_lag: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PushOutcome {
+    Queued,
+    DroppedOldest,
+    Rejected,
+    Closed,
+}
+
+struct QueueState<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+pub struct MessageQueue<T> {
+    state: Mutex<QueueState<T>>,
+    notify: Notify,
+    capacity: usize,
+    full_policy: QueueFullPolicy,
+    dropped: AtomicU64,
+    rejected: AtomicU64,
+    stale: AtomicU64,
+}
+
+impl<T> MessageQueue<T> {
+    pub fn new(capacity: usize, full_policy: QueueFullPolicy) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                items: VecDeque::with_capacity(capacity),
+                closed: false,
+            }),
+            notify: Notify::new(),
+            capacity: capacity.max(1),
+            full_policy,
+            dropped: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            stale: AtomicU64::new(0),
+        }
+    }
+
+    pub fn push(&self, item: T) -> PushOutcome {
+        let outcome = {
+            let mut state = self.state.lock();
+            if state.closed {
+                return PushOutcome::Closed;
+            }
+
+            if state.items.len() < self.capacity {
+                state.items.push_back(item);
+                PushOutcome::Queued
+            } else {
+                match self.full_policy {
+                    QueueFullPolicy::DropOldest => {
+                        state.items.pop_front();
+                        state.items.push_back(item);
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        PushOutcome::DroppedOldest
+                    }
+                    QueueFullPolicy::Reject => {
+                        self.rejected.fetch_add(1, Ordering::Relaxed);
+                        return PushOutcome::Rejected;
+                    }
+                }
+            }
+        };
+
+        self.notify.notify_one();
+        outcome
+    }
+
+    // Waits for the next item, returns None once the queue is closed
+    pub async fn recv(&self) -> Option<T> {
+        loop {
+            {
+                let mut state = self.state.lock();
+                if state.closed {
+                    return None;
+                }
+                if let Some(item) = state.items.pop_front() {
+                    return Some(item);
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    // Stops accepting new items and returns how many queued items were discarded
+    pub fn close(&self) -> usize {
+        let discarded = {
+            let mut state = self.state.lock();
+            state.closed = true;
+            let discarded = state.items.len();
+            state.items.clear();
+            discarded
+        };
+        self.notify.notify_waiters();
+        discarded
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().items.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    // Staleness is decided by the consumer, the queue only keeps the count
+    pub fn record_stale(&self) -> u64 {
+        self.stale.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn stale(&self) -> u64 {
+        self.stale.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_the_newest_items() {
+        let queue = MessageQueue::new(2, QueueFullPolicy::DropOldest);
+
+        assert_eq!(queue.push(1), PushOutcome::Queued);
+        assert_eq!(queue.push(2), PushOutcome::Queued);
+        assert_eq!(queue.push(3), PushOutcome::DroppedOldest);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.rejected(), 0);
+
+        assert_eq!(queue.recv().await, Some(2));
+        assert_eq!(queue.recv().await, Some(3));
+    }
+
+    #[tokio::test]
+    async fn reject_keeps_the_queued_items() {
+        let queue = MessageQueue::new(2, QueueFullPolicy::Reject);
+
+        assert_eq!(queue.push(1), PushOutcome::Queued);
+        assert_eq!(queue.push(2), PushOutcome::Queued);
+        assert_eq!(queue.push(3), PushOutcome::Rejected);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.rejected(), 1);
+        assert_eq!(queue.dropped(), 0);
+
+        assert_eq!(queue.recv().await, Some(1));
+        assert_eq!(queue.recv().await, Some(2));
+    }
+
+    #[test]
+    fn zero_capacity_holds_one_item() {
+        let queue = MessageQueue::new(0, QueueFullPolicy::Reject);
+
+        assert_eq!(queue.capacity(), 1);
+        assert_eq!(queue.push(1), PushOutcome::Queued);
+        assert_eq!(queue.push(2), PushOutcome::Rejected);
+    }
+
+    #[tokio::test]
+    async fn close_discards_queued_items_and_wakes_receivers() {
+        let queue = std::sync::Arc::new(MessageQueue::new(4, QueueFullPolicy::DropOldest));
+        let receiver = {
+            let queue = std::sync::Arc::clone(&queue);
+            tokio::spawn(async move { queue.recv().await })
+        };
+        tokio::task::yield_now().await;
+
+        queue.push(1);
+        assert_eq!(receiver.await.unwrap(), Some(1));
+
+        queue.push(2);
+        queue.push(3);
+        assert_eq!(queue.close(), 2);
+        assert_eq!(queue.push(4), PushOutcome::Closed);
+        assert_eq!(queue.recv().await, None);
+    }
+
+    #[test]
+    fn counts_stale_messages() {
+        let queue = MessageQueue::<u8>::new(1, QueueFullPolicy::DropOldest);
+
+        assert_eq!(queue.record_stale(), 1);
+        assert_eq!(queue.record_stale(), 2);
+        assert_eq!(queue.stale(), 2);
+    }
+}
@@ -0,0 +1,36 @@
+use axum::{extract::State, http::StatusCode, routing::get, Router};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+use trading_common::metrics::Metrics;
+
+// Serves the Prometheus scrape endpoint on its own port, apart from the API
+pub fn spawn_metrics_server(metrics: Arc<Metrics>, port: u16) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let app = Router::new()
+            .route("/metrics", get(render_metrics))
+            .with_state(metrics);
+        let addr = SocketAddr::from(([0, 0, 0, 0], port));
+
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to bind metrics server on {}: {}", addr, e);
+                return;
+            }
+        };
+        info!("Metrics server running on {}", addr);
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("Metrics server failed: {}", e);
+        }
+    })
+}
+
+async fn render_metrics(State(metrics): State<Arc<Metrics>>) -> Result<String, StatusCode> {
+    metrics.encode().map_err(|e| {
+        error!("Failed to encode metrics: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
@@ -1,36 +1,111 @@
 use anyhow::{Context, Result};
+use chrono::Utc;
 use parking_lot::{Mutex, RwLock};
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::{signature::Keypair, signer::Signer};
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 use std::{sync::Arc, time::Duration};
-use tokio::sync::mpsc;
 use tokio_tungstenite::tungstenite::Message;
-use tracing::error;
+use tracing::{debug, error, info, instrument, warn};
 use trading_common::error::AppError;
-use trading_common::websocket::{WebSocketConfig, WebSocketConnectionManager};
-use trading_common::{data::get_server_keypair, event_system::EventSystem};
+use trading_common::websocket::{
+    SlotLagConfig, SlotLagTracker, WalletSubscriptions, WebSocketConfig, WebSocketConnectionManager,
+};
+use trading_common::{
+    connection_monitor::ConnectionMonitor,
+    data::get_server_keypair,
+    event_system::{Event, EventKind, EventSystem},
+};
 use trading_common::{
     database::SupabaseClient,
-    models::{ClientTxInfo, CopyTradeSettings, TrackedWallet, TrackedWalletNotification},
+    models::{
+        ClientTxInfo, ConnectionState, ConnectionType, ControlCommand, CopyTradeNotification,
+        CopyTradeSettings, CopyTradingBreaker, CopyTradingBreakerNotification,
+        CopyTradingBreakerState, ErrorContext, ErrorInfo, ErrorSeverity, FetcherStatsNotification,
+        MonitorCursor, MonitorHealth, MonitorHealthNotification, PageRequest, QueueStats,
+        QueueStatsNotification, SubscriptionFailureNotification, TrackedWallet,
+        TrackedWalletNotification, TradeExecution, TradeSource, TransactionLog, TransactionType,
+        UnsupportedVenueNotification, WalletStateChange, WalletStateChangeType,
+        WalletStateNotification, WalletStatsNotification,
+    },
     server_wallet_manager::ServerWalletManager,
     utils::{
-        copy_trade::{execute_copy_trade, should_copy_trade},
-        transaction::process_websocket_message,
+        copy_trade::{
+            execute_copy_trade, resolve_execution_venue, should_copy_trade, within_price_impact,
+            CopyTradeResult,
+        },
+        health::Heartbeat,
+        metrics::{metrics, record_skip, with_signal_time},
+        spend_guard::spend_guard,
+        transaction::{extract_log_signature, process_signature},
+        transaction_fetcher::{TransactionFetcher, TransactionFetcherConfig},
+        transaction_log_writer::{TransactionLogWriter, TransactionLogWriterConfig},
+        wallet_stats::WalletStatsAccumulator,
     },
 };
+use uuid::Uuid;
+
+use crate::failure_breaker::{FailureBreaker, FailureBreakerConfig};
+use crate::message_queue::{MessageQueue, MessageQueueConfig, PushOutcome};
+
+const RECENT_SIGNATURE_CAPACITY: usize = 1_000;
+// Signatures requested per getSignaturesForAddress page
+const BACKFILL_PAGE_SIZE: usize = 100;
+// Most signatures replayed per wallet, older ones are skipped with a warning
+const BACKFILL_SIGNATURE_LIMIT: usize = 500;
+// Transactions fetched between pauses, one fetcher batch
+const BACKFILL_FETCH_BATCH: usize = 20;
+const BACKFILL_REQUEST_DELAY: Duration = Duration::from_millis(250);
+const FETCHER_STATS_INTERVAL: Duration = Duration::from_secs(60);
+const SUBSCRIPTION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct CursorConfig {
+    pub backfill_on_startup: bool,
+    pub max_age: Duration,
+}
+
+impl Default for CursorConfig {
+    fn default() -> Self {
+        Self {
+            backfill_on_startup: true,
+            max_age: Duration::from_secs(60 * 60),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct WalletMonitor {
     rpc_client: Arc<RpcClient>,
+    transaction_fetcher: Arc<TransactionFetcher>,
     ws_url: String,
     tracked_wallets: Arc<RwLock<Option<Vec<TrackedWallet>>>>,
     copy_trade_settings: Arc<RwLock<Option<Vec<CopyTradeSettings>>>>,
     event_system: Arc<EventSystem>,
-    message_queue: mpsc::UnboundedSender<ClientTxInfo>,
-    message_receiver: Arc<Mutex<Option<mpsc::UnboundedReceiver<ClientTxInfo>>>>,
+    connection_monitor: Arc<ConnectionMonitor>,
+    message_queue: Arc<MessageQueue<ClientTxInfo>>,
+    queue_config: MessageQueueConfig,
     stop_signal: Arc<tokio::sync::watch::Sender<bool>>,
     stop_receiver: Arc<tokio::sync::watch::Receiver<bool>>,
     server_wallet_manager: Arc<tokio::sync::Mutex<ServerWalletManager>>,
+    supabase_client: Arc<SupabaseClient>,
+    subscription_signal: Arc<tokio::sync::Notify>,
+    message_processor: Arc<Mutex<Option<tokio::task::JoinHandle<usize>>>>,
+    copy_trading_paused: Arc<AtomicBool>,
+    monitoring_paused: Arc<AtomicBool>,
+    failure_breaker: Arc<Mutex<FailureBreaker>>,
+    initial_cursors: Vec<MonitorCursor>,
+    message_processor_heartbeat: Arc<Heartbeat>,
+    websocket_heartbeat: Arc<Heartbeat>,
 }
 
 pub struct MessageProcessorContext {
@@ -39,29 +114,79 @@ pub struct MessageProcessorContext {
     server_wallet_manager: Arc<tokio::sync::Mutex<ServerWalletManager>>,
     stop_receiver: Arc<tokio::sync::watch::Receiver<bool>>,
     copy_trade_settings: Arc<RwLock<Option<Vec<CopyTradeSettings>>>>,
-    message_receiver: mpsc::UnboundedReceiver<ClientTxInfo>,
+    message_queue: Arc<MessageQueue<ClientTxInfo>>,
+    max_message_age: Duration,
+    max_slot_lag: Option<u64>,
     server_keypair: Keypair,
+    supabase_client: Arc<SupabaseClient>,
+    transaction_log_writer: TransactionLogWriter,
+    transaction_log_writer_handle: tokio::task::JoinHandle<()>,
+    copy_trading_paused: Arc<AtomicBool>,
+    monitoring_paused: Arc<AtomicBool>,
+    failure_breaker: Arc<Mutex<FailureBreaker>>,
+    heartbeat: Arc<Heartbeat>,
 }
 
 pub struct WebSocketContext {
-    message_queue: mpsc::UnboundedSender<ClientTxInfo>,
+    message_queue: Arc<MessageQueue<ClientTxInfo>>,
+    event_system: Arc<EventSystem>,
+    connection_monitor: Arc<ConnectionMonitor>,
     stop_receiver: Arc<tokio::sync::watch::Receiver<bool>>,
     tracked_wallets: Arc<RwLock<Option<Vec<TrackedWallet>>>>,
     rpc_client: Arc<RpcClient>,
+    transaction_fetcher: Arc<TransactionFetcher>,
     connection_manager: WebSocketConnectionManager,
+    subscription_signal: Arc<tokio::sync::Notify>,
+    initial_cursors: Vec<MonitorCursor>,
+    heartbeat: Arc<Heartbeat>,
+}
+
+// Tracks recently processed signatures and the last one seen per tracked wallet
+#[derive(Default)]
+struct SignatureTracker {
+    recent: HashSet<String>,
+    order: VecDeque<String>,
+    last_seen: HashMap<String, String>,
+}
+
+impl SignatureTracker {
+    // Returns false if the signature was already processed
+    fn insert(&mut self, signature: &str) -> bool {
+        if !self.recent.insert(signature.to_string()) {
+            return false;
+        }
+
+        self.order.push_back(signature.to_string());
+        if self.order.len() > RECENT_SIGNATURE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.recent.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    fn record_last_seen(&mut self, wallet_address: &str, signature: &str) {
+        self.last_seen
+            .insert(wallet_address.to_string(), signature.to_string());
+    }
 }
 
 impl WalletMonitor {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         rpc_client: Arc<RpcClient>,
         ws_url: String,
         supabase_client: SupabaseClient,
         server_keypair: Keypair,
         event_system: Arc<EventSystem>,
+        connection_monitor: Arc<ConnectionMonitor>,
         server_wallet_manager: Arc<tokio::sync::Mutex<ServerWalletManager>>,
+        queue_config: MessageQueueConfig,
+        cursor_config: CursorConfig,
+        breaker_config: FailureBreakerConfig,
     ) -> Result<Self> {
         let user_id = server_keypair.pubkey().to_string();
-        println!("Initializing WalletMonitor for user: {}", user_id);
+        info!("Initializing WalletMonitor for user: {}", user_id);
 
         Self::ensure_user_exists(&supabase_client, &user_id).await?;
 
@@ -71,32 +196,135 @@ impl WalletMonitor {
                 AppError::InitializationError(format!("Failed to fetch wallets: {}", e))
             })?;
 
-        let copy_trade_settings = Self::fetch_copy_trade_settings(&supabase_client)
+        let copy_trade_settings = Self::fetch_copy_trade_settings(&supabase_client, &event_system)
             .await
             .map_err(|e| {
                 AppError::InitializationError(format!("Failed to fetch settings: {}", e))
             })?;
 
-        println!("Fetched {} tracked wallets", tracked_wallets.len());
-        println!("Fetched {} copy trade settings", copy_trade_settings.len());
+        info!("Fetched {} tracked wallets", tracked_wallets.len());
+        info!("Fetched {} copy trade settings", copy_trade_settings.len());
+
+        let initial_cursors = if cursor_config.backfill_on_startup {
+            Self::load_cursors(&supabase_client, cursor_config.max_age).await
+        } else {
+            Vec::new()
+        };
 
-        let (tx, rx) = mpsc::unbounded_channel();
+        let message_queue = MessageQueue::new(queue_config.capacity, queue_config.full_policy);
         let (stop_tx, stop_rx) = tokio::sync::watch::channel(false);
+        let transaction_fetcher = Arc::new(TransactionFetcher::new(
+            Arc::clone(&rpc_client),
+            TransactionFetcherConfig::default(),
+        ));
 
         Ok(Self {
             rpc_client,
+            transaction_fetcher,
             ws_url,
             tracked_wallets: Arc::new(RwLock::new(Some(tracked_wallets))),
             copy_trade_settings: Arc::new(RwLock::new(Some(copy_trade_settings))),
             event_system,
-            message_queue: tx,
-            message_receiver: Arc::new(Mutex::new(Some(rx))),
+            connection_monitor,
+            message_queue: Arc::new(message_queue),
+            queue_config,
             stop_signal: Arc::new(stop_tx),
             stop_receiver: Arc::new(stop_rx),
             server_wallet_manager,
+            supabase_client: Arc::new(supabase_client),
+            subscription_signal: Arc::new(tokio::sync::Notify::new()),
+            message_processor: Arc::new(Mutex::new(None)),
+            copy_trading_paused: Arc::new(AtomicBool::new(false)),
+            monitoring_paused: Arc::new(AtomicBool::new(false)),
+            failure_breaker: Arc::new(Mutex::new(FailureBreaker::new(breaker_config))),
+            initial_cursors,
+            message_processor_heartbeat: Arc::new(Heartbeat::new()),
+            websocket_heartbeat: Arc::new(Heartbeat::new()),
+        })
+    }
+
+    pub fn message_processor_heartbeat(&self) -> Arc<Heartbeat> {
+        Arc::clone(&self.message_processor_heartbeat)
+    }
+
+    pub fn websocket_heartbeat(&self) -> Arc<Heartbeat> {
+        Arc::clone(&self.websocket_heartbeat)
+    }
+
+    pub fn copy_trading_paused_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.copy_trading_paused)
+    }
+
+    pub fn monitoring_paused_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.monitoring_paused)
+    }
+
+    // Missing or unusable cursors fall back to starting from now
+    async fn load_cursors(
+        supabase_client: &SupabaseClient,
+        max_age: Duration,
+    ) -> Vec<MonitorCursor> {
+        let cursors = match supabase_client.get_monitor_cursors().await {
+            Ok(cursors) => cursors,
+            Err(e) => {
+                warn!("failed to load monitor cursors, starting from now: {}", e);
+                return Vec::new();
+            }
+        };
+
+        Self::usable_cursors(cursors, max_age, Utc::now().timestamp())
+    }
+
+    fn usable_cursors(
+        cursors: Vec<MonitorCursor>,
+        max_age: Duration,
+        now: i64,
+    ) -> Vec<MonitorCursor> {
+        cursors
+            .into_iter()
+            .filter(|cursor| {
+                if Signature::from_str(&cursor.last_signature).is_err() {
+                    warn!(
+                        "invalid cursor signature for {}, starting from now",
+                        cursor.wallet_address
+                    );
+                    return false;
+                }
+                if now - cursor.last_block_time > max_age.as_secs() as i64 {
+                    info!(
+                        "Cursor for {} is older than {:?}, starting from now",
+                        cursor.wallet_address, max_age
+                    );
+                    return false;
+                }
+                true
+            })
+            .collect()
+    }
+
+    async fn save_cursor(supabase_client: &SupabaseClient, cursor: MonitorCursor) {
+        if let Err(e) = supabase_client.save_monitor_cursor(&cursor).await {
+            warn!("failed to save cursor for {}: {}", cursor.wallet_address, e);
+        }
+    }
+
+    // None for messages that don't belong to a tracked wallet
+    fn message_cursor(client_message: &ClientTxInfo) -> Option<MonitorCursor> {
+        (!client_message.tracked_wallet_address.is_empty()).then(|| MonitorCursor {
+            wallet_address: client_message.tracked_wallet_address.clone(),
+            last_signature: client_message.signature.clone(),
+            last_slot: client_message.slot,
+            last_block_time: client_message.timestamp,
+            updated_at: None,
         })
     }
 
+    async fn advance_cursor(supabase_client: &SupabaseClient, cursor: Option<MonitorCursor>) {
+        if let Some(cursor) = cursor {
+            Self::save_cursor(supabase_client, cursor).await;
+        }
+    }
+
     async fn ensure_user_exists(
         supabase_client: &SupabaseClient,
         user_id: &str,
@@ -104,111 +332,474 @@ impl WalletMonitor {
         let exists = supabase_client.user_exists(user_id).await?;
 
         if !exists {
-            println!("Creating new user in database");
+            info!("Creating new user in database");
             supabase_client.create_user(user_id).await.map_err(|e| {
                 AppError::InitializationError(format!("Failed to create user: {}", e))
             })?;
-            println!("User created successfully");
+            info!("User created successfully");
         }
 
         Ok(())
     }
 
     pub async fn start(&mut self) -> Result<(), AppError> {
-        println!("Starting WalletMonitor...");
+        info!("Starting WalletMonitor...");
 
         // Reset stop signal
         let _ = self.stop_signal.send(false);
-        println!("Stop signal set to false");
+        info!("Stop signal set to false");
 
         // Start tasks
         let message_processor = self.start_message_processor().await?;
+        *self.message_processor.lock() = Some(message_processor);
         let websocket_monitor = self.start_websocket_monitor().await?;
 
-        println!("WalletMonitor started successfully. Waiting for tasks...");
+        info!("WalletMonitor started successfully. Waiting for tasks...");
+
+        let wallet_refresher = self.start_wallet_refresher();
 
         // Wait for both tasks to complete or stop signal
         let mut rx = (*self.stop_receiver).clone();
+        let mut events = self
+            .event_system
+            .subscribe_filtered(&[EventKind::WalletStateChange, EventKind::ControlCommand]);
+        let mut status_interval = tokio::time::interval(Duration::from_secs(1));
+        let mut fetcher_stats_interval = tokio::time::interval(FETCHER_STATS_INTERVAL);
         loop {
             tokio::select! {
                 result = rx.changed() => {
                     if result.is_ok() && *rx.borrow() {
-                        println!("Stop signal received, shutting down...");
+                        info!("Stop signal received, shutting down...");
                         break;
                     }
                 }
-                _ = tokio::time::sleep(tokio::time::Duration::from_secs(1)) => {
+                event = events.recv() => {
+                    match event {
+                        Ok(Event::WalletStateChange(notification)) => {
+                            self.apply_wallet_state_change(notification.data);
+                        }
+                        Ok(Event::ControlCommand(command)) => {
+                            self.apply_control_command(command);
+                        }
+                        Ok(_) => {}
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                            // Missed state changes, resync from the current wallet list
+                            info!("Skipped {} events ({} total), resyncing subscriptions",
+                                skipped,
+                                events.dropped()
+                            );
+                            self.subscription_signal.notify_one();
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+                _ = fetcher_stats_interval.tick() => {
+                    let stats = self.transaction_fetcher.stats();
+                    info!("Transaction fetcher stats: {:?}", stats);
+                    self.event_system
+                        .handle_fetcher_stats(FetcherStatsNotification {
+                            data: stats,
+                            type_: "fetcher_stats".to_string(),
+                            event_id: Uuid::new_v4(),
+                            correlation_id: None,
+                        })
+                        .await;
+                }
+                _ = status_interval.tick() => {
                     // Check task status
-                    if message_processor.is_finished() || websocket_monitor.is_finished() {
-                        println!("One of the tasks finished unexpectedly");
+                    let processor_finished = self
+                        .message_processor
+                        .lock()
+                        .as_ref()
+                        .is_none_or(|handle| handle.is_finished());
+                    if processor_finished || websocket_monitor.is_finished() {
+                        info!("One of the tasks finished unexpectedly");
                         break;
                     }
                 }
             }
         }
 
+        wallet_refresher.abort();
+
         Ok(())
     }
 
-    pub async fn stop(&mut self) -> Result<(), AppError> {
-        println!("Stopping WalletMonitor...");
-        let _ = self.stop_signal.send(true);
+    pub fn is_copy_trading_paused(&self) -> bool {
+        self.copy_trading_paused.load(Ordering::SeqCst)
+    }
+
+    pub fn is_monitoring_paused(&self) -> bool {
+        self.monitoring_paused.load(Ordering::SeqCst)
+    }
+
+    fn apply_control_command(&self, command: ControlCommand) {
+        // A manual pause or resume takes over from an automatic one
+        if command != ControlCommand::CleanupDust {
+            self.failure_breaker.lock().reset();
+        }
+
+        match command {
+            ControlCommand::PauseCopyTrading => {
+                self.copy_trading_paused.store(true, Ordering::SeqCst);
+            }
+            ControlCommand::ResumeCopyTrading => {
+                self.copy_trading_paused.store(false, Ordering::SeqCst);
+                spend_guard().resume();
+            }
+            ControlCommand::PauseAll => {
+                self.copy_trading_paused.store(true, Ordering::SeqCst);
+                self.monitoring_paused.store(true, Ordering::SeqCst);
+            }
+            ControlCommand::ResumeAll => {
+                self.copy_trading_paused.store(false, Ordering::SeqCst);
+                self.monitoring_paused.store(false, Ordering::SeqCst);
+                spend_guard().resume();
+            }
+            ControlCommand::CleanupDust => {
+                // Results are reported through the DustCleanup event
+                let server_wallet_manager = Arc::clone(&self.server_wallet_manager);
+                tokio::spawn(async move {
+                    let server_keypair = get_server_keypair();
+                    let mut wallet_manager = server_wallet_manager.lock().await;
+                    if let Err(e) = wallet_manager.cleanup_dust(&server_keypair).await {
+                        warn!("Dust cleanup failed: {}", e);
+                    }
+                });
+            }
+        }
+
+        info!(
+            "Control command {:?} applied (copy trading paused: {}, monitoring paused: {})",
+            command,
+            self.is_copy_trading_paused(),
+            self.is_monitoring_paused()
+        );
+    }
+
+    fn apply_wallet_state_change(&self, change: WalletStateChange) {
+        info!(
+            "Tracked wallet {} changed: {:?}",
+            change.wallet_address, change.change_type
+        );
+
+        if Self::update_tracked_wallets(&self.tracked_wallets, change) {
+            self.subscription_signal.notify_one();
+        }
+    }
+
+    // Returns whether the set of active wallets may have changed
+    fn update_tracked_wallets(
+        tracked_wallets: &RwLock<Option<Vec<TrackedWallet>>>,
+        change: WalletStateChange,
+    ) -> bool {
+        let mut tracked_wallets = tracked_wallets.write();
+        let wallets = tracked_wallets.get_or_insert_with(Vec::new);
+
+        match change.change_type {
+            WalletStateChangeType::Deleted => {
+                wallets.retain(|w| w.wallet_address != change.wallet_address);
+            }
+            WalletStateChangeType::Added
+            | WalletStateChangeType::Archived
+            | WalletStateChangeType::Unarchived => {
+                let is_active = change.change_type != WalletStateChangeType::Archived;
+                let position = wallets
+                    .iter()
+                    .position(|w| w.wallet_address == change.wallet_address);
+
+                match (position, change.wallet) {
+                    (Some(index), Some(mut wallet)) => {
+                        wallet.is_active = is_active;
+                        wallets[index] = wallet;
+                    }
+                    (Some(index), None) => wallets[index].is_active = is_active,
+                    (None, Some(mut wallet)) => {
+                        wallet.is_active = is_active;
+                        wallets.push(wallet);
+                    }
+                    (None, None) => wallets.push(TrackedWallet {
+                        id: None,
+                        user_id: None,
+                        wallet_address: change.wallet_address.clone(),
+                        is_active,
+                        label: None,
+                        notes: None,
+                        created_at: None,
+                        updated_at: None,
+                    }),
+                }
+            }
+            WalletStateChangeType::Updated => {
+                if let Some(wallet) = change.wallet {
+                    match wallets
+                        .iter_mut()
+                        .find(|w| w.wallet_address == change.wallet_address)
+                    {
+                        Some(existing) => {
+                            existing.label = wallet.label;
+                            existing.notes = wallet.notes;
+                        }
+                        None => wallets.push(wallet),
+                    }
+                }
+                // Attribution reads the list on every transaction, nothing to resubscribe
+                return false;
+            }
+        }
 
-        println!("Waiting for tasks to complete...");
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        true
+    }
+
+    fn start_wallet_refresher(&self) -> tokio::task::JoinHandle<()> {
+        let supabase_client = Arc::clone(&self.supabase_client);
+        let tracked_wallets = Arc::clone(&self.tracked_wallets);
+        let event_system = Arc::clone(&self.event_system);
+        let stop_receiver = Arc::clone(&self.stop_receiver);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(30));
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+                if *stop_receiver.borrow() {
+                    break;
+                }
+
+                let latest = match Self::fetch_tracked_wallets(&supabase_client).await {
+                    Ok(wallets) => wallets,
+                    Err(e) => {
+                        error!("Failed to refresh tracked wallets: {}", e);
+                        continue;
+                    }
+                };
+
+                let current = tracked_wallets.read().clone().unwrap_or_default();
+                for change in Self::diff_tracked_wallets(&current, &latest) {
+                    event_system.emit(Event::WalletStateChange(WalletStateNotification {
+                        data: change,
+                        type_: "wallet_state_change".to_string(),
+                        event_id: Uuid::new_v4(),
+                        correlation_id: None,
+                    }));
+                }
+            }
+        })
+    }
+
+    fn diff_tracked_wallets(
+        current: &[TrackedWallet],
+        latest: &[TrackedWallet],
+    ) -> Vec<WalletStateChange> {
+        let mut changes = Vec::new();
+
+        for wallet in latest {
+            let change_type = match current
+                .iter()
+                .find(|w| w.wallet_address == wallet.wallet_address)
+            {
+                None => WalletStateChangeType::Added,
+                Some(existing) if existing.is_active && !wallet.is_active => {
+                    WalletStateChangeType::Archived
+                }
+                Some(existing) if !existing.is_active && wallet.is_active => {
+                    WalletStateChangeType::Unarchived
+                }
+                Some(existing)
+                    if existing.label != wallet.label || existing.notes != wallet.notes =>
+                {
+                    WalletStateChangeType::Updated
+                }
+                Some(_) => continue,
+            };
+
+            changes.push(WalletStateChange {
+                wallet_address: wallet.wallet_address.clone(),
+                change_type,
+                wallet: Some(wallet.clone()),
+            });
+        }
+
+        for wallet in current {
+            if !latest
+                .iter()
+                .any(|w| w.wallet_address == wallet.wallet_address)
+            {
+                changes.push(WalletStateChange {
+                    wallet_address: wallet.wallet_address.clone(),
+                    change_type: WalletStateChangeType::Deleted,
+                    wallet: None,
+                });
+            }
+        }
+
+        changes
+    }
+
+    pub async fn stop(&mut self, timeout: Duration) -> Result<(), AppError> {
+        info!("Stopping WalletMonitor...");
+
+        let message_processor = self.message_processor.lock().take();
+        match message_processor {
+            Some(handle) => {
+                info!("Waiting for in-flight transactions to complete...");
+                if let Some(discarded) =
+                    stop_message_processor(&self.stop_signal, handle, timeout).await
+                {
+                    info!("Discarded {} queued messages", discarded);
+                }
+            }
+            None => {
+                let _ = self.stop_signal.send(true);
+            }
+        }
 
-        println!("WalletMonitor stopped");
+        info!("WalletMonitor stopped");
         Ok(())
     }
 
-    async fn start_message_processor(&mut self) -> Result<tokio::task::JoinHandle<()>, AppError> {
+    async fn start_message_processor(
+        &mut self,
+    ) -> Result<tokio::task::JoinHandle<usize>, AppError> {
+        let (transaction_log_writer, transaction_log_writer_handle) = TransactionLogWriter::spawn(
+            TransactionLogWriterConfig::from_env(),
+            Arc::clone(&self.supabase_client),
+            Arc::clone(&self.event_system),
+        );
+
         let context = MessageProcessorContext {
             event_system: Arc::clone(&self.event_system),
             rpc_client: Arc::clone(&self.rpc_client),
             server_wallet_manager: Arc::clone(&self.server_wallet_manager),
             stop_receiver: Arc::clone(&self.stop_receiver),
             copy_trade_settings: Arc::clone(&self.copy_trade_settings),
-            message_receiver: self.message_receiver.lock().take().ok_or_else(|| {
-                AppError::InitializationError("Message receiver not available".to_string())
-            })?,
+            message_queue: Arc::clone(&self.message_queue),
+            max_message_age: self.queue_config.max_message_age,
+            max_slot_lag: self.queue_config.max_slot_lag,
             server_keypair: get_server_keypair(),
+            supabase_client: Arc::clone(&self.supabase_client),
+            transaction_log_writer,
+            transaction_log_writer_handle,
+            copy_trading_paused: Arc::clone(&self.copy_trading_paused),
+            monitoring_paused: Arc::clone(&self.monitoring_paused),
+            failure_breaker: Arc::clone(&self.failure_breaker),
+            heartbeat: Arc::clone(&self.message_processor_heartbeat),
         };
 
         Ok(tokio::spawn(Self::run_message_processor(context)))
     }
 
-    async fn run_message_processor(context: MessageProcessorContext) {
+    async fn run_message_processor(context: MessageProcessorContext) -> usize {
         let MessageProcessorContext {
             event_system,
             rpc_client,
             server_wallet_manager,
             stop_receiver,
             copy_trade_settings,
-            mut message_receiver,
+            message_queue,
+            max_message_age,
+            max_slot_lag,
             server_keypair,
+            supabase_client,
+            transaction_log_writer,
+            transaction_log_writer_handle,
+            copy_trading_paused,
+            monitoring_paused,
+            failure_breaker,
+            heartbeat,
         } = context;
 
-        println!("Message processor started");
+        // Seeded from stored executions so live stats include earlier sessions
+        let mut wallet_stats = match supabase_client.get_copy_trade_executions().await {
+            Ok(executions) => WalletStatsAccumulator::from_executions(&executions),
+            Err(e) => {
+                warn!("Failed to load wallet stats history: {}", e);
+                WalletStatsAccumulator::new()
+            }
+        };
+
+        info!("Message processor started");
+        let mut stop_rx = (*stop_receiver).clone();
         loop {
-            if *stop_receiver.borrow() {
-                println!("Message processor received stop signal");
+            heartbeat.beat();
+            if *stop_rx.borrow() {
+                info!("Message processor received stop signal");
                 break;
             }
+            if failure_breaker.lock().cooldown_expired() {
+                info!("Failure cool-down over, resuming copy trading");
+                copy_trading_paused.store(false, Ordering::SeqCst);
+                Self::emit_breaker(
+                    &event_system,
+                    CopyTradingBreaker {
+                        state: CopyTradingBreakerState::Reset,
+                        consecutive_failures: 0,
+                        cooldown_secs: None,
+                        last_error: None,
+                    },
+                    None,
+                );
+            }
 
+            // The current transaction always runs to completion before the stop check
             tokio::select! {
-            Some(client_message) = message_receiver.recv() => {
-                println!("Processing message: {}", client_message.signature);
+            result = stop_rx.changed() => {
+                if result.is_err() {
+                    break;
+                }
+            }
+            Some(client_message) = message_queue.recv() => {
+                if let Some(metrics) = metrics() {
+                    metrics.message_queue_depth.set(message_queue.len() as i64);
+                }
+                // Skipped messages still move the cursor, otherwise every
+                // restart would backfill and skip them again
+                let cursor = Self::message_cursor(&client_message);
+                if monitoring_paused.load(Ordering::SeqCst) {
+                    warn!("Monitoring paused, skipping message: {}", client_message.signature);
+                    record_skip("monitoring_paused");
+                    Self::advance_cursor(&supabase_client, cursor).await;
+                    continue;
+                }
+
+                let stale = Self::is_stale(&client_message, max_message_age, max_slot_lag);
+                if stale {
+                    let stale_count = message_queue.record_stale();
+                    Self::emit_queue_stats(&event_system, &message_queue).await;
+                    if !client_message.backfilled {
+                        record_skip("stale");
+                        warn!("Skipping stale transaction {} ({} stale so far)",
+                            client_message.signature, stale_count
+                        );
+                        Self::advance_cursor(&supabase_client, cursor).await;
+                        continue;
+                    }
+                    info!("Backfilled transaction {} is too old to copy, recording it only",
+                        client_message.signature
+                    );
+                }
+
+                info!("Processing message: {}", client_message.signature);
                 let settings = copy_trade_settings.read().clone();
-                println!("Current copy trade settings: {:?}", settings);
+                info!("Current copy trade settings: {:?}", settings);
                 if let Err(e) = Self::handle_transaction(
                     &rpc_client,
                     &server_keypair,
                     &event_system,
+                    &transaction_log_writer,
+                    &supabase_client,
+                    &mut wallet_stats,
                     &server_wallet_manager,
                     &settings,
+                    &copy_trading_paused,
+                    &failure_breaker,
+                    stale,
                     client_message,
                 ).await {
-                    println!("Error processing transaction: {}", e);
+                    error!("Error processing transaction: {}", e);
+                } else {
+                    Self::advance_cursor(&supabase_client, cursor).await;
                 }
             }
             _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {
@@ -216,54 +807,163 @@ impl WalletMonitor {
                 }
             }
         }
-        println!("Message processor shutting down");
+        // Stop accepting new messages and drop whatever is still queued
+        let discarded = message_queue.close();
+
+        // Let the writer flush (or spill) what it still holds before we report done
+        drop(transaction_log_writer);
+        if let Err(e) = transaction_log_writer_handle.await {
+            warn!("Transaction log writer failed: {}", e);
+        }
+
+        info!("Message processor shutting down");
+        discarded
+    }
+
+    fn is_stale(
+        client_message: &ClientTxInfo,
+        max_message_age: Duration,
+        max_slot_lag: Option<u64>,
+    ) -> bool {
+        if let (Some(slot_lag), Some(max_slot_lag)) = (client_message.slot_lag, max_slot_lag) {
+            if slot_lag > max_slot_lag {
+                return true;
+            }
+        }
+
+        // Block time is unknown for some transactions, those are never considered stale
+        if client_message.timestamp <= 0 {
+            return false;
+        }
+
+        let age = Utc::now().timestamp() - client_message.timestamp;
+        age > max_message_age.as_secs() as i64
+    }
+
+    async fn emit_queue_stats(
+        event_system: &Arc<EventSystem>,
+        message_queue: &Arc<MessageQueue<ClientTxInfo>>,
+    ) {
+        event_system
+            .handle_queue_stats(QueueStatsNotification {
+                data: QueueStats {
+                    queue_len: message_queue.len(),
+                    capacity: message_queue.capacity(),
+                    dropped: message_queue.dropped(),
+                    rejected: message_queue.rejected(),
+                    stale: message_queue.stale(),
+                },
+                type_: "queue_stats".to_string(),
+                event_id: Uuid::new_v4(),
+                correlation_id: None,
+            })
+            .await;
     }
 
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(
+        name = "tracked_transaction",
+        skip_all,
+        fields(
+            signature = %client_message.signature,
+            wallet = %client_message.tracked_wallet_address
+        )
+    )]
     async fn handle_transaction(
         rpc_client: &Arc<RpcClient>,
         server_keypair: &Keypair,
         event_system: &Arc<EventSystem>,
+        transaction_log_writer: &TransactionLogWriter,
+        supabase_client: &SupabaseClient,
+        wallet_stats: &mut WalletStatsAccumulator,
         server_wallet_manager: &Arc<tokio::sync::Mutex<ServerWalletManager>>,
         copy_trade_settings: &Option<Vec<CopyTradeSettings>>,
+        copy_trading_paused: &AtomicBool,
+        failure_breaker: &Mutex<FailureBreaker>,
+        too_old_to_copy: bool,
         client_message: ClientTxInfo,
     ) -> Result<(), AppError> {
-        println!("----------------------");
-        println!("Handling transaction: {}", client_message.signature);
-        println!("Transaction type: {:?}", client_message.transaction_type);
-        println!(
+        wallet_stats.observe(&client_message);
+        server_wallet_manager.lock().await.update_price(
+            &client_message.token_address,
+            client_message.price_per_token,
+        );
+        info!("Handling transaction: {}", client_message.signature);
+        info!("Transaction type: {:?}", client_message.transaction_type);
+        info!(
             "Token: {} ({}) - {}",
             client_message.token_name, client_message.token_symbol, client_message.token_address
         );
 
-        println!("Transaction Details:");
-        println!(
+        info!("Transaction Details:");
+        info!(
             "  Amount Token: {} {}",
             client_message.amount_token, client_message.token_symbol
         );
-        println!("  Amount SOL: {} SOL", client_message.amount_sol);
-        println!("  Price per Token: {} SOL", client_message.price_per_token);
-        println!("  Seller: {}", client_message.seller);
-        println!("  Buyer: {}", client_message.buyer);
-        println!("  DEX Type: {:?}", client_message.dex_type);
-
-        // Check copy trading settings
-        if let Some(settings) = copy_trade_settings.as_ref().and_then(|s| s.first()) {
-            println!("Copy trading settings found:");
-            println!("  Enabled: {}", settings.is_enabled);
-            println!("  Trade amount: {} SOL", settings.trade_amount_sol);
-            println!("  Max slippage: {}%", settings.max_slippage * 100.0);
-            println!("  Max open positions: {}", settings.max_open_positions);
-            println!(
+        info!("  Amount SOL: {} SOL", client_message.amount_sol);
+        info!("  Price per Token: {} SOL", client_message.price_per_token);
+        info!("  Seller: {}", client_message.seller);
+        info!("  Buyer: {}", client_message.buyer);
+        info!("  DEX Type: {:?}", client_message.dex_type);
+        info!(
+            "  Tracked Wallet: {} ({:?})",
+            client_message.tracked_wallet_address, client_message.tracked_wallet_id
+        );
+
+        // Transfers are only reported, never copied
+        if client_message.transaction_type == TransactionType::Transfer {
+            record_skip("transfer");
+            let min_transfer_amount = copy_trade_settings
+                .as_ref()
+                .and_then(|s| s.first())
+                .map(|s| s.min_transfer_amount)
+                .unwrap_or(0.0);
+            if client_message.amount_token < min_transfer_amount {
+                info!(
+                    "Ignoring transfer of {} {} below minimum {}",
+                    client_message.amount_token, client_message.token_symbol, min_transfer_amount
+                );
+                return Ok(());
+            }
+
+            info!(
+                "Tracked wallet transfer: {} {} from {} to {}",
+                client_message.amount_token,
+                client_message.token_symbol,
+                client_message.seller,
+                client_message.buyer
+            );
+        } else if let Some(settings) = copy_trade_settings.as_ref().and_then(|s| s.first()) {
+            // Check copy trading settings
+            info!("Copy trading settings found:");
+            info!("  Enabled: {}", settings.is_enabled);
+            info!("  Trade amount: {} SOL", settings.trade_amount_sol);
+            info!("  Max slippage: {}%", settings.max_slippage * 100.0);
+            info!("  Max open positions: {}", settings.max_open_positions);
+            info!(
                 "  Allow additional buys: {}",
                 settings.allow_additional_buys
             );
 
-            if settings.is_enabled {
+            if !settings.is_enabled {
+                record_skip("copy_trading_disabled");
+            } else if too_old_to_copy {
+                record_skip("stale");
+            } else if copy_trading_paused.load(Ordering::SeqCst) {
+                warn!("Copy trading paused, skipping copy trade");
+                record_skip("copy_trading_paused");
+            } else {
                 Self::process_copy_trade(
                     rpc_client,
                     server_keypair,
+                    event_system,
+                    transaction_log_writer,
+                    supabase_client,
+                    wallet_stats,
                     server_wallet_manager,
                     settings,
+                    copy_trading_paused,
+                    failure_breaker,
                     &client_message,
                 )
                 .await
@@ -271,6 +971,8 @@ impl WalletMonitor {
                     AppError::MessageProcessingError(format!("Copy trade failed: {}", e))
                 })?;
             }
+        } else {
+            record_skip("no_copy_trade_settings");
         }
 
         Self::send_notification(event_system, client_message)
@@ -279,51 +981,340 @@ impl WalletMonitor {
                 AppError::MessageProcessingError(format!("Failed to send notification: {}", e))
             })?;
 
-        println!("----------------------");
-
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn process_copy_trade(
         rpc_client: &Arc<RpcClient>,
         server_keypair: &Keypair,
+        event_system: &Arc<EventSystem>,
+        transaction_log_writer: &TransactionLogWriter,
+        supabase_client: &SupabaseClient,
+        wallet_stats: &mut WalletStatsAccumulator,
         server_wallet_manager: &Arc<tokio::sync::Mutex<ServerWalletManager>>,
         settings: &CopyTradeSettings,
+        copy_trading_paused: &AtomicBool,
+        failure_breaker: &Mutex<FailureBreaker>,
         client_message: &ClientTxInfo,
     ) -> Result<(), AppError> {
         if !should_copy_trade(client_message, settings, server_wallet_manager).await? {
             return Ok(());
         }
 
-        execute_copy_trade(
-            rpc_client,
-            server_keypair,
+        let venue = match resolve_execution_venue(rpc_client, client_message, settings).await {
+            Some(venue) => venue,
+            None => {
+                warn!(
+                    "Unsupported venue {:?} for {}, skipping copy trade",
+                    client_message.dex_type, client_message.signature
+                );
+                record_skip("unsupported_venue");
+                event_system
+                    .handle_unsupported_venue(UnsupportedVenueNotification {
+                        data: client_message.clone(),
+                        type_: "unsupported_venue".to_string(),
+                        event_id: Uuid::new_v4(),
+                        correlation_id: Some(client_message.signature.clone()),
+                    })
+                    .await;
+                return Ok(());
+            }
+        };
+
+        if !within_price_impact(rpc_client, client_message, settings, &venue).await {
+            return Ok(());
+        }
+
+        let mut execution = TradeExecution::pending(
+            TradeSource::Copy,
+            venue.clone(),
+            client_message.transaction_type.clone(),
+            &client_message.token_address,
+        );
+        execution.tracked_wallet_id = client_message.tracked_wallet_id;
+        if venue != client_message.dex_type {
+            execution.signal_dex_type = Some(client_message.dex_type.clone());
+        }
+        if let Err(e) = supabase_client.insert_trade_execution(&execution).await {
+            warn!("Failed to record trade execution: {}", e);
+            Self::report_error(
+                event_system,
+                ErrorSeverity::Warning,
+                "database_write_failed",
+                format!("Failed to record trade execution: {}", e),
+                client_message,
+            );
+        }
+
+        let dex = format!("{:?}", venue);
+        if let Some(metrics) = metrics() {
+            metrics
+                .copy_trades_attempted
+                .with_label_values(&[&dex])
+                .inc();
+        }
+        let copy_trade =
+            execute_copy_trade(rpc_client, server_keypair, client_message, settings, venue);
+        let result = match client_message.observed_at {
+            Some(observed_at) => with_signal_time(observed_at, copy_trade).await,
+            None => copy_trade.await,
+        };
+        // No signature means the executor skipped the trade, which is neither outcome
+        let skipped = matches!(
+            result,
+            Ok(CopyTradeResult {
+                signature: None,
+                ..
+            })
+        );
+        if let Some(metrics) = metrics() {
+            match &result {
+                Ok(CopyTradeResult {
+                    signature: Some(_), ..
+                }) => metrics
+                    .copy_trades_succeeded
+                    .with_label_values(&[&dex])
+                    .inc(),
+                Ok(_) => record_skip("not_executed"),
+                Err(_) => metrics.copy_trades_failed.with_label_values(&[&dex]).inc(),
+            }
+        }
+        Self::record_breaker_outcome(
+            event_system,
+            copy_trading_paused,
+            failure_breaker,
+            &result,
+            client_message,
+        );
+        Self::finish_trade_execution(
+            supabase_client,
+            event_system,
+            &mut execution,
+            &result,
             client_message,
-            settings,
-            client_message.dex_type.clone(),
         )
-        .await
-        .map_err(|e| {
+        .await;
+        if let Some(stats) = wallet_stats.record_execution(&execution) {
+            event_system
+                .handle_wallet_stats(WalletStatsNotification {
+                    data: stats,
+                    type_: "wallet_stats".to_string(),
+                    event_id: Uuid::new_v4(),
+                    correlation_id: Some(client_message.signature.clone()),
+                })
+                .await;
+        }
+        if skipped {
+            info!("No copy trade executed for {}", client_message.signature);
+            return Ok(());
+        }
+        let CopyTradeResult { slippage, .. } = result.map_err(|e| {
+            Self::report_error(
+                event_system,
+                ErrorSeverity::Error,
+                "copy_trade_failed",
+                format!("Copy trade failed: {}", e),
+                client_message,
+            );
             AppError::MessageProcessingError(format!("Execute copy trade failed: {}", e))
         })?;
 
-        let mut wallet_manager = server_wallet_manager.lock().await;
-        wallet_manager
-            .handle_trade_execution(client_message)
-            .await
-            .map_err(|e| {
-                AppError::MessageProcessingError(format!("Wallet update failed: {}", e))
-            })?;
+        let position = {
+            let mut wallet_manager = server_wallet_manager.lock().await;
+            // Recorded before the balance refresh so the fill isn't mistaken for a transfer
+            wallet_manager.record_fill(
+                &execution.transaction_type,
+                &execution.token_address,
+                execution.amount_sol,
+                execution.amount_token,
+            );
+            wallet_manager
+                .handle_trade_execution(client_message)
+                .await
+                .map_err(|e| {
+                    AppError::MessageProcessingError(format!("Wallet update failed: {}", e))
+                })?;
+            wallet_manager.position(&execution.token_address).cloned()
+        };
+        if let Some(position) = position {
+            if let Err(e) = supabase_client.save_position(&position).await {
+                warn!(
+                    "Failed to save position for {}: {}",
+                    position.token_address, e
+                );
+                Self::report_error(
+                    event_system,
+                    ErrorSeverity::Warning,
+                    "database_write_failed",
+                    format!("Failed to save position: {}", e),
+                    client_message,
+                );
+            }
+        }
+
+        event_system
+            .handle_copy_trade_executed(CopyTradeNotification {
+                data: client_message.clone(),
+                type_: "copy_trade_execution".to_string(),
+                event_id: Uuid::new_v4(),
+                correlation_id: Some(client_message.signature.clone()),
+                slippage,
+            })
+            .await;
+
+        Self::log_copy_trade(transaction_log_writer, server_keypair, client_message);
 
         Ok(())
     }
 
+    async fn finish_trade_execution(
+        supabase_client: &SupabaseClient,
+        event_system: &EventSystem,
+        execution: &mut TradeExecution,
+        result: &Result<CopyTradeResult>,
+        client_message: &ClientTxInfo,
+    ) {
+        match result {
+            Ok(CopyTradeResult {
+                signature: Some(signature),
+                amount_sol,
+                amount_token,
+                child_signatures,
+                route,
+                ..
+            }) => {
+                execution.succeeded(signature, *amount_sol, *amount_token);
+                execution.child_signatures = child_signatures.clone();
+                execution.route = route.clone();
+            }
+            Ok(_) => execution.skipped("No trade was executed".to_string()),
+            Err(e) => execution.failed(e.to_string()),
+        }
+
+        if let Err(e) = supabase_client.update_trade_execution(execution).await {
+            warn!("Failed to update trade execution {}: {}", execution.id, e);
+            Self::report_error(
+                event_system,
+                ErrorSeverity::Warning,
+                "database_write_failed",
+                format!("Failed to update trade execution: {}", e),
+                client_message,
+            );
+        }
+    }
+
+    // Only executor errors count as failures, a result without a signature is a skip
+    fn record_breaker_outcome(
+        event_system: &EventSystem,
+        copy_trading_paused: &AtomicBool,
+        failure_breaker: &Mutex<FailureBreaker>,
+        result: &Result<CopyTradeResult>,
+        client_message: &ClientTxInfo,
+    ) {
+        let last_error = match result {
+            Ok(CopyTradeResult {
+                signature: Some(_), ..
+            }) => {
+                failure_breaker.lock().record_success();
+                return;
+            }
+            Ok(_) => return,
+            Err(e) => e.to_string(),
+        };
+
+        let (failures, cooldown) = {
+            let mut breaker = failure_breaker.lock();
+            if !breaker.record_failure() {
+                return;
+            }
+            (breaker.failures(), breaker.config().cooldown)
+        };
+
+        copy_trading_paused.store(true, Ordering::SeqCst);
+        let resume = match cooldown {
+            Some(cooldown) => format!("resuming in {}s", cooldown.as_secs()),
+            None => "resume it manually".to_string(),
+        };
+        let message = format!(
+            "Copy trading paused after {} consecutive failed copy trades, {}. Last error: {}",
+            failures, resume, last_error
+        );
+        error!("{}", message);
+        Self::report_error(
+            event_system,
+            ErrorSeverity::Error,
+            "copy_trading_auto_paused",
+            message,
+            client_message,
+        );
+        Self::emit_breaker(
+            event_system,
+            CopyTradingBreaker {
+                state: CopyTradingBreakerState::Tripped,
+                consecutive_failures: failures,
+                cooldown_secs: cooldown.map(|cooldown| cooldown.as_secs()),
+                last_error: Some(last_error),
+            },
+            Some(client_message.signature.clone()),
+        );
+    }
+
+    fn emit_breaker(
+        event_system: &EventSystem,
+        breaker: CopyTradingBreaker,
+        correlation_id: Option<String>,
+    ) {
+        event_system.emit(Event::CopyTradingBreaker(CopyTradingBreakerNotification {
+            data: breaker,
+            type_: "copy_trading_breaker".to_string(),
+            event_id: Uuid::new_v4(),
+            correlation_id,
+        }));
+    }
+
+    fn report_error(
+        event_system: &EventSystem,
+        severity: ErrorSeverity,
+        code: &str,
+        message: String,
+        client_message: &ClientTxInfo,
+    ) {
+        event_system.report_error(
+            ErrorInfo::new(severity, "wallet_monitor", code, message)
+                .with_context(ErrorContext::for_transaction(client_message)),
+            Some(client_message.signature.clone()),
+        );
+    }
+
+    fn log_copy_trade(
+        transaction_log_writer: &TransactionLogWriter,
+        server_keypair: &Keypair,
+        client_message: &ClientTxInfo,
+    ) {
+        let transaction = TransactionLog {
+            id: Uuid::new_v4(),
+            user_id: server_keypair.pubkey().to_string(),
+            tracked_wallet_id: client_message.tracked_wallet_id,
+            signature: client_message.signature.clone(),
+            transaction_type: format!("{:?}", client_message.transaction_type),
+            token_address: client_message.token_address.clone(),
+            amount: client_message.amount_token,
+            price_sol: client_message.price_per_token,
+            timestamp: Utc::now(),
+        };
+
+        transaction_log_writer.log(transaction);
+    }
+
     async fn send_notification(
         event_system: &Arc<EventSystem>,
         client_message: ClientTxInfo,
     ) -> Result<(), AppError> {
         let notification = TrackedWalletNotification {
             type_: "tracked_wallet_trade".to_string(),
+            event_id: Uuid::new_v4(),
+            correlation_id: Some(client_message.signature.clone()),
             data: client_message,
         };
 
@@ -333,23 +1324,24 @@ impl WalletMonitor {
     }
 
     async fn start_websocket_monitor(&mut self) -> Result<tokio::task::JoinHandle<()>, AppError> {
-        let ws_config = WebSocketConfig {
-            health_check_interval: Duration::from_secs(30),
-            connection_timeout: Duration::from_secs(5),
-            initial_backoff: Duration::from_secs(1),
-            max_backoff: Duration::from_secs(60),
-            max_retries: 3,
-        };
+        let ws_config = WebSocketConfig::from_env();
+        info!("WebSocket config: {:?}", ws_config);
 
         let context = WebSocketContext {
-            message_queue: self.message_queue.clone(),
+            message_queue: Arc::clone(&self.message_queue),
+            event_system: Arc::clone(&self.event_system),
+            connection_monitor: Arc::clone(&self.connection_monitor),
             stop_receiver: Arc::clone(&self.stop_receiver),
             tracked_wallets: Arc::clone(&self.tracked_wallets),
             rpc_client: Arc::clone(&self.rpc_client),
+            transaction_fetcher: Arc::clone(&self.transaction_fetcher),
             connection_manager: WebSocketConnectionManager::new(
                 self.ws_url.clone(),
                 Some(ws_config),
             ),
+            subscription_signal: Arc::clone(&self.subscription_signal),
+            initial_cursors: self.initial_cursors.clone(),
+            heartbeat: Arc::clone(&self.websocket_heartbeat),
         };
 
         Ok(tokio::spawn(Self::run_websocket_monitor(context)))
@@ -358,29 +1350,38 @@ impl WalletMonitor {
     async fn run_websocket_monitor(context: WebSocketContext) {
         let WebSocketContext {
             message_queue,
+            event_system,
+            connection_monitor,
             stop_receiver,
             tracked_wallets,
             rpc_client,
+            transaction_fetcher,
             mut connection_manager,
+            subscription_signal,
+            initial_cursors,
+            heartbeat,
         } = context;
 
+        // Restored cursors make the first backfill pick up where the last run stopped
+        let mut signature_tracker = SignatureTracker::default();
+        let mut slot_lag = SlotLagTracker::new(SlotLagConfig::from_env());
+        for cursor in initial_cursors {
+            signature_tracker.record_last_seen(&cursor.wallet_address, &cursor.last_signature);
+        }
+
         loop {
+            heartbeat.beat();
             if *stop_receiver.borrow() {
                 break;
             }
 
-            let wallet_addresses: Vec<String> = tracked_wallets
-                .read()
-                .as_ref()
-                .map(|w| {
-                    w.iter()
-                        .map(|wallet| wallet.wallet_address.clone())
-                        .collect()
-                })
-                .unwrap_or_default();
+            let wallet_addresses = Self::active_wallet_addresses(&tracked_wallets);
 
             if wallet_addresses.is_empty() {
-                tokio::time::sleep(Duration::from_secs(5)).await;
+                tokio::select! {
+                    _ = subscription_signal.notified() => {}
+                    _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                }
                 continue;
             }
 
@@ -389,39 +1390,144 @@ impl WalletMonitor {
                     // Try to subscribe
                     if let Err(e) = connection_manager.subscribe(wallet_addresses).await {
                         error!("Failed to subscribe to wallets: {}", e);
+                        connection_monitor.reconnecting(
+                            ConnectionType::WebSocket,
+                            connection_manager.reconnect_attempts(),
+                            Some(e.to_string()),
+                        );
                         continue;
                     }
+                    connection_monitor.update(
+                        ConnectionType::WebSocket,
+                        ConnectionState::Connected,
+                        None,
+                    );
+
+                    // Replay anything the tracked wallets did while we were disconnected
+                    Self::backfill_missed_transactions(
+                        &rpc_client,
+                        &transaction_fetcher,
+                        &tracked_wallets,
+                        &message_queue,
+                        &event_system,
+                        &mut signature_tracker,
+                    )
+                    .await;
 
                     // Process messages until error or closure
+                    let mut disconnect_error = None;
+                    let mut subscription_check = tokio::time::interval(SUBSCRIPTION_CHECK_INTERVAL);
+                    let mut slot_lag_check =
+                        tokio::time::interval(slot_lag.config().check_interval);
+                    slot_lag.reset();
                     loop {
+                        heartbeat.beat();
                         if *stop_receiver.borrow() {
                             break;
                         }
 
-                        match connection_manager.receive_message().await {
-                            Ok(Some(Message::Text(text))) => {
-                                if let Err(e) = Self::handle_websocket_message(
-                                    Message::Text(text),
+                        tokio::select! {
+                            _ = subscription_signal.notified() => {
+                                if let Err(e) = Self::sync_subscriptions(
+                                    &mut connection_manager,
+                                    &tracked_wallets,
+                                )
+                                .await
+                                {
+                                    error!("Failed to update subscriptions: {}", e);
+                                    disconnect_error = Some(e.to_string());
+                                    break;
+                                }
+                            }
+                            _ = slot_lag_check.tick() => {
+                                Self::check_slot_lag(
                                     &rpc_client,
-                                    &message_queue,
+                                    &connection_manager,
+                                    &connection_monitor,
+                                    &event_system,
+                                    &mut slot_lag,
+                                )
+                                .await;
+
+                                if slot_lag.should_failover() {
+                                    error!(
+                                        "WebSocket feed lagging {:?} slots behind, failing over",
+                                        slot_lag.current_lag()
+                                    );
+                                    connection_manager.failover().await;
+                                    disconnect_error = Some("WebSocket feed lagging behind the chain".to_string());
+                                    break;
+                                }
+                            }
+                            _ = subscription_check.tick() => {
+                                if let Err(e) = Self::check_subscriptions(
+                                    &mut connection_manager,
+                                    &event_system,
                                 )
                                 .await
                                 {
-                                    error!("Message handling error: {}", e);
+                                    error!("Failed to resubscribe stale wallets: {}", e);
+                                    disconnect_error = Some(e.to_string());
+                                    break;
                                 }
                             }
-                            Ok(Some(Message::Close(_))) => break,
-                            Ok(None) => break, // Connection closed
-                            Err(e) => {
-                                error!("WebSocket error: {}", e);
-                                break;
+                            message = connection_manager.receive_message() => {
+                                match message {
+                                    Ok(Some(Message::Text(text))) => {
+                                        let message_lag = if text.contains("logsNotification") {
+                                            connection_manager
+                                                .last_notification_slot()
+                                                .and_then(|slot| slot_lag.observe(slot))
+                                        } else {
+                                            None
+                                        };
+                                        if let Err(e) = Self::handle_websocket_message(
+                                            Message::Text(text),
+                                            message_lag,
+                                            &rpc_client,
+                                            &transaction_fetcher,
+                                            &tracked_wallets,
+                                            &message_queue,
+                                            &event_system,
+                                            &mut signature_tracker,
+                                        )
+                                        .await
+                                        {
+                                            error!("Message handling error: {}", e);
+                                        }
+                                    }
+                                    Ok(Some(Message::Close(_))) => break,
+                                    Ok(None) => break, // Connection closed
+                                    Err(e) => {
+                                        error!("WebSocket error: {}", e);
+                                        disconnect_error = Some(e.to_string());
+                                        break;
+                                    }
+                                    _ => continue,
+                                }
                             }
-                            _ => continue,
                         }
                     }
+
+                    if !*stop_receiver.borrow() {
+                        connection_monitor.reconnecting(
+                            ConnectionType::WebSocket,
+                            connection_manager.reconnect_attempts(),
+                            disconnect_error,
+                        );
+                    }
                 }
                 Err(e) => {
                     error!("Failed to ensure connection: {}", e);
+                    if connection_manager.retries_exhausted() {
+                        error!("WebSocket retries exhausted, stopping wallet monitoring");
+                        break;
+                    }
+                    connection_monitor.reconnecting(
+                        ConnectionType::WebSocket,
+                        connection_manager.reconnect_attempts(),
+                        Some(e.to_string()),
+                    );
                     tokio::time::sleep(Duration::from_secs(1)).await;
                 }
             }
@@ -429,55 +1535,1146 @@ impl WalletMonitor {
 
         // Cleanup on exit
         connection_manager.shutdown().await.ok();
+        connection_monitor.update(
+            ConnectionType::WebSocket,
+            ConnectionState::Disconnected,
+            None,
+        );
+        connection_monitor.flush_pending();
+    }
+
+    async fn check_slot_lag(
+        rpc_client: &Arc<RpcClient>,
+        connection_manager: &WebSocketConnectionManager,
+        connection_monitor: &Arc<ConnectionMonitor>,
+        event_system: &Arc<EventSystem>,
+        slot_lag: &mut SlotLagTracker,
+    ) {
+        let rpc_slot = match rpc_client.get_slot() {
+            Ok(slot) => {
+                slot_lag.record_tip(slot);
+                Some(slot)
+            }
+            Err(e) => {
+                warn!("Failed to fetch RPC slot for lag check: {}", e);
+                None
+            }
+        };
+
+        connection_monitor.set_slot_lag(ConnectionType::WebSocket, slot_lag.current_lag());
+        event_system
+            .handle_monitor_health(MonitorHealthNotification {
+                data: MonitorHealth {
+                    rpc_slot,
+                    notification_slot: connection_manager.last_notification_slot(),
+                    slot_lag: slot_lag.current_lag(),
+                    lagging: slot_lag.is_lagging(),
+                },
+                type_: "monitor_health".to_string(),
+                event_id: Uuid::new_v4(),
+                correlation_id: None,
+            })
+            .await;
     }
 
+    // Reports rejected subscriptions and resubscribes wallets that went quiet
+    // while the rest of the feed kept delivering
+    async fn check_subscriptions(
+        connection_manager: &mut WebSocketConnectionManager,
+        event_system: &Arc<EventSystem>,
+    ) -> Result<(), AppError> {
+        for failure in connection_manager.take_subscription_failures() {
+            event_system
+                .handle_subscription_failure(SubscriptionFailureNotification {
+                    data: failure,
+                    type_: "subscription_failure".to_string(),
+                    event_id: Uuid::new_v4(),
+                    correlation_id: None,
+                })
+                .await;
+        }
+
+        let stale = connection_manager.stale_subscriptions();
+        if !stale.is_empty() {
+            info!("Resubscribing silent wallets: {:?}", stale);
+            connection_manager.resubscribe(stale).await?;
+        }
+
+        Ok(())
+    }
+
+    fn active_wallet_addresses(
+        tracked_wallets: &Arc<RwLock<Option<Vec<TrackedWallet>>>>,
+    ) -> Vec<String> {
+        let addresses: Vec<String> = tracked_wallets
+            .read()
+            .as_ref()
+            .map(|w| {
+                w.iter()
+                    .filter(|wallet| wallet.is_active)
+                    .map(|wallet| wallet.wallet_address.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if let Some(metrics) = metrics() {
+            metrics.tracked_wallets.set(addresses.len() as i64);
+        }
+        addresses
+    }
+
+    async fn sync_subscriptions(
+        connection_manager: &mut impl WalletSubscriptions,
+        tracked_wallets: &Arc<RwLock<Option<Vec<TrackedWallet>>>>,
+    ) -> Result<(), AppError> {
+        let desired = Self::active_wallet_addresses(tracked_wallets);
+        let subscribed = connection_manager.subscribed_wallets();
+
+        let to_add: Vec<String> = desired
+            .iter()
+            .filter(|w| !subscribed.contains(w))
+            .cloned()
+            .collect();
+        let to_remove: Vec<String> = subscribed
+            .into_iter()
+            .filter(|w| !desired.contains(w))
+            .collect();
+
+        if !to_remove.is_empty() {
+            info!("Unsubscribing from wallets: {:?}", to_remove);
+            connection_manager.unsubscribe(to_remove).await?;
+        }
+
+        if !to_add.is_empty() {
+            info!("Subscribing to wallets: {:?}", to_add);
+            connection_manager.add_subscriptions(to_add).await?;
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn handle_websocket_message(
         message: Message,
+        slot_lag: Option<u64>,
         rpc_client: &Arc<RpcClient>,
-        message_queue: &mpsc::UnboundedSender<ClientTxInfo>,
+        transaction_fetcher: &Arc<TransactionFetcher>,
+        tracked_wallets: &Arc<RwLock<Option<Vec<TrackedWallet>>>>,
+        message_queue: &Arc<MessageQueue<ClientTxInfo>>,
+        event_system: &Arc<EventSystem>,
+        signature_tracker: &mut SignatureTracker,
     ) -> Result<(), AppError> {
         match message {
             Message::Text(text) => {
-                println!("Received WebSocket message: {}", text);
-                if let Some(tx_info) = process_websocket_message(text.as_str(), rpc_client)
-                    .await
-                    .map_err(|e| {
-                        AppError::WebSocketError(format!("Failed to process message: {}", e))
-                    })?
-                {
-                    println!("Processed transaction info: {:?}", tx_info);
-                    message_queue.send(tx_info).map_err(|e| {
-                        AppError::MessageProcessingError(format!("Failed to queue message: {}", e))
-                    })?;
+                info!("Received WebSocket message: {}", text);
+                let signature = extract_log_signature(text.as_str()).map_err(|e| {
+                    event_system.report_error(
+                        ErrorInfo::new(
+                            ErrorSeverity::Warning,
+                            "wallet_monitor",
+                            "websocket_parse_failed",
+                            format!("Failed to parse websocket message: {}", e),
+                        ),
+                        None,
+                    );
+                    AppError::WebSocketError(format!("Failed to process message: {}", e))
+                })?;
+
+                if let Some(signature) = signature {
+                    Self::process_and_queue(
+                        &signature,
+                        slot_lag,
+                        false,
+                        rpc_client,
+                        transaction_fetcher,
+                        tracked_wallets,
+                        message_queue,
+                        event_system,
+                        signature_tracker,
+                    )
+                    .await?;
                 }
             }
             Message::Close(_) => {
                 return Err(AppError::WebSocketError("WebSocket closed".to_string()));
             }
             _ => {
-                println!("Received non-text message: {:?}", message);
+                info!("Received non-text message: {:?}", message);
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(name = "tracked_transaction", skip_all, fields(signature = %signature))]
+    async fn process_and_queue(
+        signature: &str,
+        slot_lag: Option<u64>,
+        backfilled: bool,
+        rpc_client: &Arc<RpcClient>,
+        transaction_fetcher: &Arc<TransactionFetcher>,
+        tracked_wallets: &Arc<RwLock<Option<Vec<TrackedWallet>>>>,
+        message_queue: &Arc<MessageQueue<ClientTxInfo>>,
+        event_system: &Arc<EventSystem>,
+        signature_tracker: &mut SignatureTracker,
+    ) -> Result<(), AppError> {
+        let observed_at = Instant::now();
+        if let Some(metrics) = metrics() {
+            metrics.transactions_observed.inc();
+        }
+        if !signature_tracker.insert(signature) {
+            debug!("Skipping already processed transaction: {}", signature);
+            record_skip("duplicate");
+            return Ok(());
+        }
+
+        let wallets = tracked_wallets.read().clone().unwrap_or_default();
+        if let Some(mut tx_info) =
+            process_signature(signature, rpc_client, transaction_fetcher, &wallets)
+                .await
+                .map_err(|e| {
+                    event_system.report_error(
+                        ErrorInfo::new(
+                            ErrorSeverity::Error,
+                            "wallet_monitor",
+                            "transaction_parse_failed",
+                            format!("Failed to process tracked transaction: {}", e),
+                        )
+                        .with_context(ErrorContext {
+                            signature: Some(signature.to_string()),
+                            ..Default::default()
+                        }),
+                        Some(signature.to_string()),
+                    );
+                    AppError::WebSocketError(format!("Failed to process message: {}", e))
+                })?
+        {
+            if let Some(metrics) = metrics() {
+                metrics.transactions_parsed.inc();
+            }
+            tx_info.slot_lag = slot_lag;
+            tx_info.backfilled = backfilled;
+            tx_info.observed_at = Some(observed_at);
+            info!("Processed transaction info: {:?}", tx_info);
+            if !tx_info.tracked_wallet_address.is_empty() {
+                signature_tracker.record_last_seen(&tx_info.tracked_wallet_address, signature);
             }
+            let outcome = message_queue.push(tx_info);
+            if let Some(metrics) = metrics() {
+                metrics.message_queue_depth.set(message_queue.len() as i64);
+            }
+            match outcome {
+                PushOutcome::Queued => {}
+                PushOutcome::DroppedOldest => {
+                    warn!("Message queue full, dropped oldest message");
+                    record_skip("queue_dropped");
+                    Self::emit_queue_stats(event_system, message_queue).await;
+                }
+                PushOutcome::Rejected => {
+                    warn!("Message queue full, rejected {}", signature);
+                    record_skip("queue_rejected");
+                    Self::emit_queue_stats(event_system, message_queue).await;
+                }
+                PushOutcome::Closed => {
+                    return Err(AppError::MessageProcessingError(
+                        "Failed to queue message: queue closed".to_string(),
+                    ));
+                }
+            }
+        } else {
+            record_skip("not_a_trade");
         }
+
         Ok(())
     }
 
+    async fn backfill_missed_transactions(
+        rpc_client: &Arc<RpcClient>,
+        transaction_fetcher: &Arc<TransactionFetcher>,
+        tracked_wallets: &Arc<RwLock<Option<Vec<TrackedWallet>>>>,
+        message_queue: &Arc<MessageQueue<ClientTxInfo>>,
+        event_system: &Arc<EventSystem>,
+        signature_tracker: &mut SignatureTracker,
+    ) {
+        let mut missed = Vec::new();
+
+        for wallet_address in Self::active_wallet_addresses(tracked_wallets) {
+            let until = match signature_tracker.last_seen.get(&wallet_address) {
+                Some(signature) => signature.clone(),
+                None => continue,
+            };
+
+            let (pubkey, until) = match (
+                Pubkey::from_str(&wallet_address),
+                Signature::from_str(&until),
+            ) {
+                (Ok(pubkey), Ok(until)) => (pubkey, until),
+                _ => continue,
+            };
+
+            let signatures = collect_signatures_until(
+                |before, limit| {
+                    rpc_client.get_signatures_for_address_with_config(
+                        &pubkey,
+                        GetConfirmedSignaturesForAddress2Config {
+                            before,
+                            until: Some(until),
+                            limit: Some(limit),
+                            commitment: Some(CommitmentConfig::confirmed()),
+                        },
+                    )
+                },
+                BACKFILL_PAGE_SIZE,
+                BACKFILL_SIGNATURE_LIMIT,
+                BACKFILL_REQUEST_DELAY,
+            )
+            .await;
+
+            match signatures {
+                Ok((signatures, truncated)) => {
+                    if truncated {
+                        warn!(
+                            "More than {} signatures missed for {}, older ones are not backfilled",
+                            BACKFILL_SIGNATURE_LIMIT, wallet_address
+                        );
+                    }
+                    missed.extend(signatures.into_iter().filter(|s| s.err.is_none()));
+                }
+                Err(e) => error!("Failed to fetch signatures for {}: {}", wallet_address, e),
+            }
+
+            tokio::time::sleep(BACKFILL_REQUEST_DELAY).await;
+        }
+
+        if missed.is_empty() {
+            return;
+        }
+
+        // Replay oldest first so trades are copied in the order they happened
+        missed.sort_by_key(|s| (s.slot, s.block_time));
+        info!("Backfilling {} missed transactions", missed.len());
+
+        for chunk in missed.chunks(BACKFILL_FETCH_BATCH) {
+            // Fetch the chunk together so the lookups go out as one batch and
+            // the replay below is served from the cache
+            futures_util::future::join_all(
+                chunk
+                    .iter()
+                    .map(|status| transaction_fetcher.fetch(&status.signature)),
+            )
+            .await;
+
+            for status in chunk {
+                if let Err(e) = Self::process_and_queue(
+                    &status.signature,
+                    None,
+                    true,
+                    rpc_client,
+                    transaction_fetcher,
+                    tracked_wallets,
+                    message_queue,
+                    event_system,
+                    signature_tracker,
+                )
+                .await
+                {
+                    error!("Failed to backfill {}: {}", status.signature, e);
+                }
+            }
+
+            tokio::time::sleep(BACKFILL_REQUEST_DELAY).await;
+        }
+    }
+
     async fn fetch_tracked_wallets(
         supabase_client: &SupabaseClient,
     ) -> Result<Vec<TrackedWallet>, AppError> {
-        supabase_client
-            .get_tracked_wallets()
-            .await
-            .context("Failed to fetch tracked wallets")
-            .map_err(|e| AppError::DatabaseError(format!("Failed to fetch wallets: {}", e)))
+        let mut wallets = Vec::new();
+        let mut page = PageRequest::default();
+        loop {
+            let result = supabase_client
+                .get_tracked_wallets_page(&page)
+                .await
+                .context("Failed to fetch tracked wallets")
+                .map_err(|e| AppError::DatabaseError(format!("Failed to fetch wallets: {}", e)))?;
+            wallets.extend(result.items);
+
+            match result.next_cursor {
+                Some(cursor) => page.cursor = Some(cursor),
+                None => return Ok(wallets),
+            }
+        }
     }
 
+    // Rows that fail validation are left out rather than applied, so a bad
+    // allowlist can't silently block every trade
     async fn fetch_copy_trade_settings(
         supabase_client: &SupabaseClient,
+        event_system: &EventSystem,
     ) -> Result<Vec<CopyTradeSettings>, AppError> {
-        supabase_client
-            .get_copy_trade_settings()
+        let mut settings = Vec::new();
+        let mut page = PageRequest::default();
+        loop {
+            let result = supabase_client
+                .get_copy_trade_settings_page(&page)
+                .await
+                .context("Failed to fetch copy trade settings")
+                .map_err(|e| AppError::DatabaseError(format!("Failed to fetch settings: {}", e)))?;
+            for mut item in result.items {
+                match item.validate() {
+                    Ok(()) => settings.push(item),
+                    Err(e) => {
+                        warn!(
+                            "Ignoring copy trade settings for tracked wallet {}: {}",
+                            item.tracked_wallet_id, e
+                        );
+                        event_system.report_error(
+                            ErrorInfo::new(
+                                ErrorSeverity::Error,
+                                "wallet_monitor",
+                                "invalid_copy_trade_settings",
+                                format!(
+                                    "Copy trade settings for tracked wallet {} rejected: {}",
+                                    item.tracked_wallet_id, e
+                                ),
+                            ),
+                            None,
+                        );
+                    }
+                }
+            }
+
+            match result.next_cursor {
+                Some(cursor) => page.cursor = Some(cursor),
+                None => return Ok(settings),
+            }
+        }
+    }
+}
+
+// Pages back through a wallet's signatures, newest first, until the request's
+// `until` signature is reached. A page shorter than asked for means it was.
+// Returns the signatures and whether max_signatures ran out before `until`
+async fn collect_signatures_until<F, E>(
+    mut fetch_page: F,
+    page_size: usize,
+    max_signatures: usize,
+    delay: Duration,
+) -> Result<(Vec<RpcConfirmedTransactionStatusWithSignature>, bool), E>
+where
+    F: FnMut(
+        Option<Signature>,
+        usize,
+    ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, E>,
+{
+    let mut signatures = Vec::new();
+    let mut before = None;
+    loop {
+        let limit = page_size.min(max_signatures - signatures.len());
+        let page = fetch_page(before, limit)?;
+        let reached_until = page.len() < limit;
+        signatures.extend(page);
+
+        if reached_until {
+            return Ok((signatures, false));
+        }
+        if signatures.len() >= max_signatures {
+            return Ok((signatures, true));
+        }
+
+        before = signatures
+            .last()
+            .and_then(|status| Signature::from_str(&status.signature).ok());
+        if before.is_none() {
+            return Ok((signatures, false));
+        }
+        tokio::time::sleep(delay).await;
+    }
+}
+
+// Signals every task to stop, then waits for the message processor to finish
+// its current transaction
+async fn stop_message_processor(
+    stop_signal: &tokio::sync::watch::Sender<bool>,
+    handle: tokio::task::JoinHandle<usize>,
+    timeout: Duration,
+) -> Option<usize> {
+    let _ = stop_signal.send(true);
+    await_message_processor(handle, timeout).await
+}
+
+// Waits for the processor to finish its current transaction and returns how many
+// queued messages it discarded. A processor still busy after the timeout is aborted
+async fn await_message_processor(
+    mut handle: tokio::task::JoinHandle<usize>,
+    timeout: Duration,
+) -> Option<usize> {
+    match tokio::time::timeout(timeout, &mut handle).await {
+        Ok(Ok(discarded)) => Some(discarded),
+        Ok(Err(e)) => {
+            warn!("Message processor failed: {}", e);
+            None
+        }
+        Err(_) => {
+            info!(
+                "Message processor did not finish within {:?}, aborting",
+                timeout
+            );
+            handle.abort();
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_queue::QueueFullPolicy;
+    use axum::response::IntoResponse;
+    use serde_json::{json, Value};
+    use trading_common::database::DatabasePolicy;
+    use trading_common::dex::DexType;
+
+    fn cursor(wallet_address: &str, last_signature: &str, last_block_time: i64) -> MonitorCursor {
+        MonitorCursor {
+            wallet_address: wallet_address.to_string(),
+            last_signature: last_signature.to_string(),
+            last_slot: 1,
+            last_block_time,
+            updated_at: None,
+        }
+    }
+
+    fn wallet(wallet_address: &str, is_active: bool) -> TrackedWallet {
+        TrackedWallet {
+            id: None,
+            user_id: None,
+            wallet_address: wallet_address.to_string(),
+            is_active,
+            label: None,
+            notes: None,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn change_types(changes: &[WalletStateChange]) -> Vec<(&str, WalletStateChangeType)> {
+        changes
+            .iter()
+            .map(|change| (change.wallet_address.as_str(), change.change_type.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn diff_detects_every_change_type() {
+        let current = vec![
+            wallet("unchanged", true),
+            wallet("archived", true),
+            wallet("unarchived", false),
+            wallet("relabelled", true),
+            wallet("deleted", true),
+        ];
+        let mut relabelled = wallet("relabelled", true);
+        relabelled.label = Some("whale".to_string());
+        let latest = vec![
+            wallet("unchanged", true),
+            wallet("archived", false),
+            wallet("unarchived", true),
+            relabelled,
+            wallet("added", true),
+        ];
+
+        let changes = WalletMonitor::diff_tracked_wallets(&current, &latest);
+        assert_eq!(
+            change_types(&changes),
+            vec![
+                ("archived", WalletStateChangeType::Archived),
+                ("unarchived", WalletStateChangeType::Unarchived),
+                ("relabelled", WalletStateChangeType::Updated),
+                ("added", WalletStateChangeType::Added),
+                ("deleted", WalletStateChangeType::Deleted),
+            ]
+        );
+        assert!(changes
+            .iter()
+            .filter(|change| change.change_type != WalletStateChangeType::Deleted)
+            .all(|change| change.wallet.is_some()));
+        assert!(changes.last().unwrap().wallet.is_none());
+    }
+
+    #[test]
+    fn diff_of_identical_lists_is_empty() {
+        let wallets = vec![wallet("a", true), wallet("b", false)];
+        assert!(WalletMonitor::diff_tracked_wallets(&wallets, &wallets).is_empty());
+        assert!(WalletMonitor::diff_tracked_wallets(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn keeps_recent_cursors_with_valid_signatures() {
+        let signature = Signature::default().to_string();
+        let now = 10_000;
+        let cursors = vec![
+            cursor("recent", &signature, now - 60),
+            cursor("at_limit", &signature, now - 3_600),
+            cursor("too_old", &signature, now - 3_601),
+            cursor("invalid", "not-a-signature", now),
+        ];
+
+        let usable = WalletMonitor::usable_cursors(cursors, Duration::from_secs(3_600), now);
+        let wallets = usable
+            .iter()
+            .map(|cursor| cursor.wallet_address.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(wallets, vec!["recent", "at_limit"]);
+    }
+
+    #[tokio::test]
+    async fn stop_waits_for_the_in_flight_transaction() {
+        let started = Instant::now();
+        let handle = tokio::spawn(async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            3
+        });
+
+        let discarded = await_message_processor(handle, Duration::from_secs(5)).await;
+        assert_eq!(discarded, Some(3));
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn stop_aborts_a_processor_past_the_timeout() {
+        let (finished_tx, finished_rx) = tokio::sync::oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            let _ = finished_tx.send(());
+            0
+        });
+
+        let discarded = await_message_processor(handle, Duration::from_millis(50)).await;
+        assert_eq!(discarded, None);
+        // The aborted task dropped its sender without ever finishing
+        assert!(finished_rx.await.is_err());
+    }
+
+    // Signatures newer than the cursor, newest first, served like the RPC does
+    struct SignatureHistory {
+        signatures: Vec<String>,
+        requests: Vec<(Option<Signature>, usize)>,
+    }
+
+    impl SignatureHistory {
+        fn new(count: usize) -> Self {
+            Self {
+                signatures: (0..count)
+                    .map(|_| Signature::new_unique().to_string())
+                    .collect(),
+                requests: Vec::new(),
+            }
+        }
+
+        fn page(
+            &mut self,
+            before: Option<Signature>,
+            limit: usize,
+        ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, String> {
+            self.requests.push((before, limit));
+            let start = match before {
+                Some(before) => {
+                    self.signatures
+                        .iter()
+                        .position(|s| *s == before.to_string())
+                        .unwrap()
+                        + 1
+                }
+                None => 0,
+            };
+            Ok(self
+                .signatures
+                .iter()
+                .enumerate()
+                .skip(start)
+                .take(limit)
+                .map(
+                    |(index, signature)| RpcConfirmedTransactionStatusWithSignature {
+                        signature: signature.clone(),
+                        slot: 1_000 - index as u64,
+                        err: None,
+                        memo: None,
+                        block_time: None,
+                        confirmation_status: None,
+                    },
+                )
+                .collect())
+        }
+
+        fn befores(&self) -> Vec<Option<String>> {
+            self.requests
+                .iter()
+                .map(|(before, _)| before.map(|before| before.to_string()))
+                .collect()
+        }
+    }
+
+    fn returned(signatures: &[RpcConfirmedTransactionStatusWithSignature]) -> Vec<String> {
+        signatures.iter().map(|s| s.signature.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn backfill_pages_back_to_the_cursor() {
+        let mut history = SignatureHistory::new(250);
+
+        let (signatures, truncated) = collect_signatures_until(
+            |before, limit| history.page(before, limit),
+            100,
+            500,
+            Duration::ZERO,
+        )
+        .await
+        .unwrap();
+
+        assert!(!truncated);
+        assert_eq!(returned(&signatures), history.signatures);
+        assert_eq!(
+            history.befores(),
+            vec![
+                None,
+                Some(history.signatures[99].clone()),
+                Some(history.signatures[199].clone()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn backfill_reaching_the_cursor_on_a_page_boundary_is_not_truncated() {
+        let mut history = SignatureHistory::new(200);
+
+        let (signatures, truncated) = collect_signatures_until(
+            |before, limit| history.page(before, limit),
+            100,
+            500,
+            Duration::ZERO,
+        )
+        .await
+        .unwrap();
+
+        assert!(!truncated);
+        assert_eq!(signatures.len(), 200);
+        // The empty third page is what shows the cursor was reached
+        assert_eq!(history.requests.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn backfill_stops_at_the_limit_and_reports_truncation() {
+        let mut history = SignatureHistory::new(250);
+
+        let (signatures, truncated) = collect_signatures_until(
+            |before, limit| history.page(before, limit),
+            100,
+            150,
+            Duration::ZERO,
+        )
+        .await
+        .unwrap();
+
+        assert!(truncated);
+        // The newest signatures are kept
+        assert_eq!(returned(&signatures), history.signatures[..150].to_vec());
+        let limits = history
+            .requests
+            .iter()
+            .map(|(_, limit)| *limit)
+            .collect::<Vec<_>>();
+        assert_eq!(limits, vec![100, 50]);
+    }
+
+    #[tokio::test]
+    async fn backfill_page_errors_are_returned() {
+        let mut pages = 0;
+        let result = collect_signatures_until(
+            |_, limit| {
+                pages += 1;
+                match pages {
+                    1 => SignatureHistory::new(limit).page(None, limit),
+                    _ => Err("rate limited".to_string()),
+                }
+            },
+            100,
+            500,
+            Duration::ZERO,
+        )
+        .await;
+
+        assert_eq!(result.unwrap_err(), "rate limited");
+        assert_eq!(pages, 2);
+    }
+
+    #[derive(Debug, Clone)]
+    struct RecordedRequest {
+        method: String,
+        path: String,
+        body: String,
+    }
+
+    #[derive(Clone)]
+    struct MockState {
+        requests: Arc<Mutex<Vec<RecordedRequest>>>,
+        table_delay: Duration,
+    }
+
+    // One endpoint standing in for both the RPC node and PostgREST. JSON-RPC
+    // calls are posted to the root, everything else is a table request that
+    // answers with no rows after table_delay
+    struct MockBackend {
+        url: String,
+        requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    }
+
+    impl MockBackend {
+        async fn start(table_delay: Duration) -> Self {
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let router = axum::Router::new()
+                .fallback(mock_handler)
+                .with_state(MockState {
+                    requests: Arc::clone(&requests),
+                    table_delay,
+                });
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            tokio::spawn(async move {
+                axum::serve(listener, router).await.unwrap();
+            });
+            Self { url, requests }
+        }
+
+        fn table_requests(&self, method: &str, table: &str) -> Vec<RecordedRequest> {
+            self.requests
+                .lock()
+                .iter()
+                .filter(|request| request.method == method && request.path == format!("/{}", table))
+                .cloned()
+                .collect()
+        }
+
+        fn saved_cursors(&self) -> Vec<String> {
+            self.table_requests("POST", "monitor_cursors")
+                .iter()
+                .map(|request| {
+                    let body: Value = serde_json::from_str(&request.body).unwrap();
+                    body["last_signature"].as_str().unwrap().to_string()
+                })
+                .collect()
+        }
+
+        async fn wait_until(&self, condition: impl Fn(&Self) -> bool) {
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while !condition(self) {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("condition not reached in time");
+        }
+    }
+
+    async fn mock_handler(
+        axum::extract::State(state): axum::extract::State<MockState>,
+        method: axum::http::Method,
+        uri: axum::http::Uri,
+        body: String,
+    ) -> axum::response::Response {
+        let json_body = |body: String| {
+            (
+                [(axum::http::header::CONTENT_TYPE, "application/json")],
+                body,
+            )
+                .into_response()
+        };
+
+        if uri.path() == "/" {
+            let request: Value = serde_json::from_str(&body).unwrap_or_default();
+            let result = match request["method"].as_str() {
+                Some("getBalance") => json!({ "context": { "slot": 1 }, "value": 0 }),
+                Some("getTokenAccountsByOwner") => json!({ "context": { "slot": 1 }, "value": [] }),
+                _ => Value::Null,
+            };
+            return json_body(
+                json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }).to_string(),
+            );
+        }
+
+        state.requests.lock().push(RecordedRequest {
+            method: method.to_string(),
+            path: uri.path().to_string(),
+            body,
+        });
+        tokio::time::sleep(state.table_delay).await;
+        json_body("[]".to_string())
+    }
+
+    struct ProcessorHarness {
+        event_system: Arc<EventSystem>,
+        message_queue: Arc<MessageQueue<ClientTxInfo>>,
+        stop_signal: tokio::sync::watch::Sender<bool>,
+        handle: tokio::task::JoinHandle<usize>,
+    }
+
+    impl ProcessorHarness {
+        // The wallet manager's RPC client blocks, so tests using this need the
+        // multi-threaded runtime
+        async fn start(backend: &MockBackend, max_message_age: Duration) -> Self {
+            let event_system = Arc::new(EventSystem::new());
+            let rpc_client = Arc::new(RpcClient::new(backend.url.clone()));
+            let supabase_client = Arc::new(SupabaseClient::with_policy(
+                &backend.url,
+                "",
+                "service-role",
+                "user",
+                DatabasePolicy {
+                    max_retries: 0,
+                    ..DatabasePolicy::default()
+                },
+            ));
+            let server_wallet_manager = ServerWalletManager::new(
+                Arc::clone(&rpc_client),
+                Pubkey::new_unique(),
+                Arc::clone(&event_system),
+            )
             .await
-            .context("Failed to fetch copy trade settings")
-            .map_err(|e| AppError::DatabaseError(format!("Failed to fetch settings: {}", e)))
+            .unwrap();
+            let (transaction_log_writer, transaction_log_writer_handle) =
+                TransactionLogWriter::spawn(
+                    TransactionLogWriterConfig {
+                        spill_path: None,
+                        ..TransactionLogWriterConfig::default()
+                    },
+                    Arc::clone(&supabase_client),
+                    Arc::clone(&event_system),
+                );
+            let message_queue = Arc::new(MessageQueue::new(100, QueueFullPolicy::DropOldest));
+            let (stop_signal, stop_receiver) = tokio::sync::watch::channel(false);
+
+            let context = MessageProcessorContext {
+                event_system: Arc::clone(&event_system),
+                rpc_client,
+                server_wallet_manager: Arc::new(tokio::sync::Mutex::new(server_wallet_manager)),
+                stop_receiver: Arc::new(stop_receiver),
+                copy_trade_settings: Arc::new(RwLock::new(None)),
+                message_queue: Arc::clone(&message_queue),
+                max_message_age,
+                max_slot_lag: None,
+                server_keypair: Keypair::new(),
+                supabase_client,
+                transaction_log_writer,
+                transaction_log_writer_handle,
+                copy_trading_paused: Arc::new(AtomicBool::new(false)),
+                monitoring_paused: Arc::new(AtomicBool::new(false)),
+                failure_breaker: Arc::new(Mutex::new(FailureBreaker::new(
+                    FailureBreakerConfig::default(),
+                ))),
+                heartbeat: Arc::new(Heartbeat::new()),
+            };
+
+            Self {
+                event_system,
+                message_queue,
+                stop_signal,
+                handle: tokio::spawn(WalletMonitor::run_message_processor(context)),
+            }
+        }
+    }
+
+    fn tracked_trade(signature: &str, age: Duration, backfilled: bool) -> ClientTxInfo {
+        ClientTxInfo {
+            signature: signature.to_string(),
+            token_address: "Mint1111111111111111111111111111111111pump".to_string(),
+            token_name: "Token".to_string(),
+            token_symbol: "TKN".to_string(),
+            transaction_type: TransactionType::Buy,
+            amount_token: 1000.0,
+            amount_sol: 0.1,
+            price_per_token: 0.0001,
+            token_image_uri: String::new(),
+            market_cap: 0.0,
+            usd_market_cap: 0.0,
+            timestamp: Utc::now().timestamp() - age.as_secs() as i64,
+            seller: "BondingCurve111111111111111111111111111111".to_string(),
+            buyer: "Trader1111111111111111111111111111111111111".to_string(),
+            dex_type: DexType::PumpFun,
+            tracked_wallet_id: None,
+            tracked_wallet_address: "Trader1111111111111111111111111111111111111".to_string(),
+            tracked_wallet_label: None,
+            slot: 1,
+            pool_address: None,
+            fee_lamports: 5_000,
+            priority_fee_lamports: None,
+            slot_lag: None,
+            observed_at: None,
+            backfilled,
+        }
+    }
+
+    fn recorded_trades(events: &mut tokio::sync::broadcast::Receiver<Event>) -> Vec<String> {
+        let mut signatures = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let Event::TrackedWalletTransaction(notification) = event {
+                signatures.push(notification.data.signature);
+            }
+        }
+        signatures
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn old_backfilled_message_is_recorded_and_moves_the_cursor() {
+        let backend = MockBackend::start(Duration::ZERO).await;
+        let harness = ProcessorHarness::start(&backend, Duration::from_secs(60)).await;
+        let mut events = harness.event_system.subscribe();
+
+        let ten_minutes = Duration::from_secs(600);
+        harness
+            .message_queue
+            .push(tracked_trade("backfilled", ten_minutes, true));
+        harness
+            .message_queue
+            .push(tracked_trade("live", ten_minutes, false));
+        backend
+            .wait_until(|backend| backend.saved_cursors().len() == 2)
+            .await;
+
+        let _ = harness.stop_signal.send(true);
+        assert_eq!(
+            await_message_processor(harness.handle, Duration::from_secs(5)).await,
+            Some(0)
+        );
+
+        // Both move the cursor, only the backfilled one is recorded
+        assert_eq!(backend.saved_cursors(), vec!["backfilled", "live"]);
+        assert_eq!(recorded_trades(&mut events), vec!["backfilled"]);
+        assert_eq!(harness.message_queue.stale(), 2);
+        assert!(backend
+            .table_requests("POST", "trade_executions")
+            .is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stop_finishes_the_in_flight_message_and_discards_the_rest() {
+        let cursor_delay = Duration::from_millis(300);
+        let backend = MockBackend::start(cursor_delay).await;
+        let harness = ProcessorHarness::start(&backend, Duration::from_secs(60)).await;
+
+        // Stale live messages are skipped, saving the cursor is the slow part
+        let ten_minutes = Duration::from_secs(600);
+        for signature in ["in_flight", "queued_1", "queued_2"] {
+            harness
+                .message_queue
+                .push(tracked_trade(signature, ten_minutes, false));
+        }
+        backend
+            .wait_until(|backend| !backend.table_requests("POST", "monitor_cursors").is_empty())
+            .await;
+
+        let started = Instant::now();
+        let discarded =
+            stop_message_processor(&harness.stop_signal, harness.handle, Duration::from_secs(5))
+                .await;
+
+        assert_eq!(discarded, Some(2));
+        // Stop waited for the cursor write that was under way
+        assert!(started.elapsed() >= cursor_delay / 2);
+        assert_eq!(backend.saved_cursors(), vec!["in_flight"]);
+    }
+
+    #[derive(Default)]
+    struct FakeSubscriptions {
+        subscribed: HashSet<String>,
+        added: Vec<String>,
+        removed: Vec<String>,
+    }
+
+    #[async_trait::async_trait]
+    impl WalletSubscriptions for FakeSubscriptions {
+        fn subscribed_wallets(&self) -> Vec<String> {
+            self.subscribed.iter().cloned().collect()
+        }
+
+        async fn add_subscriptions(&mut self, wallets: Vec<String>) -> Result<(), AppError> {
+            for wallet in wallets {
+                if self.subscribed.insert(wallet.clone()) {
+                    self.added.push(wallet);
+                }
+            }
+            Ok(())
+        }
+
+        async fn unsubscribe(&mut self, wallets: Vec<String>) -> Result<(), AppError> {
+            for wallet in wallets {
+                if self.subscribed.remove(&wallet) {
+                    self.removed.push(wallet);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn sorted(wallets: &HashSet<String>) -> Vec<&str> {
+        let mut wallets: Vec<&str> = wallets.iter().map(String::as_str).collect();
+        wallets.sort();
+        wallets
+    }
+
+    #[tokio::test]
+    async fn subscriptions_follow_add_archive_and_unarchive() {
+        let tracked_wallets = Arc::new(RwLock::new(Some(vec![wallet("kept", true)])));
+        let mut subscriptions = FakeSubscriptions::default();
+        WalletMonitor::sync_subscriptions(&mut subscriptions, &tracked_wallets)
+            .await
+            .unwrap();
+        assert_eq!(sorted(&subscriptions.subscribed), vec!["kept"]);
+
+        let steps = [
+            (WalletStateChangeType::Added, vec!["kept", "new"]),
+            (WalletStateChangeType::Archived, vec!["kept"]),
+            (WalletStateChangeType::Unarchived, vec!["kept", "new"]),
+        ];
+        for (change_type, expected) in steps {
+            let resubscribe = WalletMonitor::update_tracked_wallets(
+                &tracked_wallets,
+                WalletStateChange {
+                    wallet_address: "new".to_string(),
+                    change_type,
+                    wallet: Some(wallet("new", true)),
+                },
+            );
+            assert!(resubscribe);
+            WalletMonitor::sync_subscriptions(&mut subscriptions, &tracked_wallets)
+                .await
+                .unwrap();
+            assert_eq!(sorted(&subscriptions.subscribed), expected);
+        }
+
+        assert_eq!(subscriptions.added, vec!["kept", "new", "new"]);
+        assert_eq!(subscriptions.removed, vec!["new"]);
+        // Archiving keeps the wallet listed, only inactive
+        assert_eq!(tracked_wallets.read().as_ref().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn label_updates_leave_subscriptions_alone() {
+        let tracked_wallets = Arc::new(RwLock::new(Some(vec![wallet("kept", true)])));
+        let mut renamed = wallet("kept", true);
+        renamed.label = Some("renamed".to_string());
+
+        let resubscribe = WalletMonitor::update_tracked_wallets(
+            &tracked_wallets,
+            WalletStateChange {
+                wallet_address: "kept".to_string(),
+                change_type: WalletStateChangeType::Updated,
+                wallet: Some(renamed),
+            },
+        );
+
+        assert!(!resubscribe);
+        assert_eq!(
+            tracked_wallets.read().as_ref().unwrap()[0].label.as_deref(),
+            Some("renamed")
+        );
     }
 }
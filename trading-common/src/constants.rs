@@ -16,3 +16,7 @@ pub const CONFIG_ACCOUNT: &str = "36Eru7v11oU5Pfrojyn5oY3nETA1a1iqsw2WUu6afkM9";
 pub const MOONSHOT_PROGRAM: &str = "MoonCVVNZFSYkqNXP6bxHLPL6QQJiMagDL3qcqUQTrG";
 pub const METADATA_PROGRAM_ID: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
 pub const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+
+// Fee and rent estimates used for balance checks before trading
+pub const BASE_TRANSACTION_FEE_LAMPORTS: u64 = 5_000;
+pub const TOKEN_ACCOUNT_RENT_LAMPORTS: u64 = 2_039_280;
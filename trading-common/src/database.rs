@@ -1,22 +1,132 @@
-use postgrest::Postgrest;
+use parking_lot::Mutex;
+use postgrest::{Builder, Postgrest};
+use reqwest::Response;
+use serde::de::DeserializeOwned;
 use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, instrument, warn};
 use uuid::Uuid;
 
 use crate::{
+    connection_monitor::ConnectionMonitor,
     error::AppError,
-    models::{CopyTradeSettings, TrackedWallet, TransactionLog, User},
+    event_system::{Event, EventSystem},
+    metrics::metrics,
+    models::{
+        ConnectionState, ConnectionType, ControlCommand, ControlCommandRecord, CopyTradeSettings,
+        DatabaseQuery, DatabaseQueryNotification, EventAuditRecord, MonitorCursor, Page,
+        PageRequest, Position, TrackedWallet, TradeExecution, TradeExecutionFilter, TradeSource,
+        TradeStatus, TradingSwitch, TransactionLog, TransactionLogFilter, User, WalletStats,
+        Watchlist, WatchlistToken, WatchlistWithTokens,
+    },
+    wallet_stats::WalletStatsAccumulator,
 };
 use anyhow::{Context, Result};
 
+#[derive(Debug, Clone)]
+pub struct DatabasePolicy {
+    pub request_timeout: Duration,
+    // Retries after the first attempt, only for 429, 5xx and connection errors.
+    // Plain inserts are never retried, see is_idempotent
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // Consecutive failed requests before the circuit opens
+    pub failure_threshold: u32,
+    // How long the circuit stays open before a probe request is let through
+    pub open_duration: Duration,
+}
+
+impl Default for DatabasePolicy {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(10),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(5),
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryEventConfig {
+    pub enabled: bool,
+    // Emit one DatabaseQuery event per this many successful requests. Failed
+    // requests are always emitted
+    pub sample_every: u64,
+}
+
+impl Default for QueryEventConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sample_every: 1,
+        }
+    }
+}
+
+impl QueryEventConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            enabled: std::env::var("DB_QUERY_EVENTS_ENABLED")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.enabled),
+            sample_every: std::env::var("DB_QUERY_EVENT_SAMPLE_EVERY")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|every| *every > 0)
+                .unwrap_or(default.sample_every),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum CircuitState {
+    Closed { consecutive_failures: u32 },
+    Open { until: Instant },
+    HalfOpen,
+}
+
 #[derive(Clone)]
 pub struct SupabaseClient {
     client: Postgrest,
     user_id: String,
+    policy: DatabasePolicy,
+    circuit: Arc<Mutex<CircuitState>>,
+    connection_monitor: Option<Arc<ConnectionMonitor>>,
+    event_system: Option<Arc<EventSystem>>,
+    query_events: QueryEventConfig,
+    successful_queries: Arc<AtomicU64>,
 }
 
 impl SupabaseClient {
-    pub fn new(url: &str, _api_key: &str, service_role_key: &str, user_id: &str) -> Self {
-        println!("New Postgrest client created!");
+    pub fn new(url: &str, api_key: &str, service_role_key: &str, user_id: &str) -> Self {
+        Self::with_policy(
+            url,
+            api_key,
+            service_role_key,
+            user_id,
+            DatabasePolicy::default(),
+        )
+    }
+
+    pub fn with_policy(
+        url: &str,
+        _api_key: &str,
+        service_role_key: &str,
+        user_id: &str,
+        policy: DatabasePolicy,
+    ) -> Self {
+        debug!("New Postgrest client created!");
         let client = Postgrest::new(url)
             .insert_header("apikey", service_role_key)
             .insert_header("Authorization", format!("Bearer {}", service_role_key));
@@ -24,27 +134,252 @@ impl SupabaseClient {
         Self {
             client,
             user_id: user_id.to_string(),
+            policy,
+            circuit: Arc::new(Mutex::new(CircuitState::Closed {
+                consecutive_failures: 0,
+            })),
+            connection_monitor: None,
+            event_system: None,
+            query_events: QueryEventConfig::default(),
+            successful_queries: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Circuit transitions are reported as ConnectionType::Database
+    pub fn with_connection_monitor(mut self, connection_monitor: Arc<ConnectionMonitor>) -> Self {
+        self.connection_monitor = Some(connection_monitor);
+        self
+    }
+
+    // Every request is timed and reported as a DatabaseQuery event
+    pub fn with_event_system(
+        mut self,
+        event_system: Arc<EventSystem>,
+        query_events: QueryEventConfig,
+    ) -> Self {
+        self.event_system = Some(event_system);
+        self.query_events = query_events;
+        self
+    }
+
+    #[instrument(name = "db_query", skip(self, query), fields(duration_ms, rows))]
+    async fn execute(
+        &self,
+        table: &str,
+        operation: &str,
+        query: Builder,
+    ) -> Result<Response, AppError> {
+        let started = Instant::now();
+        let result = self
+            .execute_with_policy(query, is_idempotent(operation))
+            .await;
+        self.report_query(table, operation, started.elapsed(), &result);
+        result
+    }
+
+    fn report_query(
+        &self,
+        table: &str,
+        operation: &str,
+        elapsed: Duration,
+        result: &Result<Response, AppError>,
+    ) {
+        let (rows, error) = match result {
+            Ok(resp) if resp.status().is_success() => (content_range_rows(resp), None),
+            Ok(resp) => (None, Some(format!("status {}", resp.status()))),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        let success = error.is_none();
+        let duration_ms = elapsed.as_millis() as u64;
+
+        let span = tracing::Span::current();
+        span.record("duration_ms", duration_ms);
+        if let Some(rows) = rows {
+            span.record("rows", rows);
+        }
+        match &error {
+            Some(error) => debug!("Database {} on {} failed: {}", operation, table, error),
+            None => debug!("Database {} on {} took {}ms", operation, table, duration_ms),
+        }
+
+        if let Some(metrics) = metrics() {
+            metrics
+                .database_queries
+                .with_label_values(&[table, operation, if success { "ok" } else { "error" }])
+                .inc();
+        }
+
+        let Some(event_system) = &self.event_system else {
+            return;
+        };
+        if !self.query_events.enabled {
+            return;
+        }
+        if success
+            && self.successful_queries.fetch_add(1, Ordering::Relaxed)
+                % self.query_events.sample_every
+                != 0
+        {
+            return;
+        }
+
+        event_system.emit(Event::DatabaseQuery(DatabaseQueryNotification {
+            data: DatabaseQuery {
+                operation: operation.to_string(),
+                table: table.to_string(),
+                duration_ms,
+                success,
+                rows,
+                error,
+            },
+            type_: "database_query".to_string(),
+            event_id: Uuid::new_v4(),
+            correlation_id: None,
+        }));
+    }
+
+    // Runs a request under the shared policy: per-request timeout, retries with
+    // backoff on retryable failures and a circuit breaker across all requests.
+    // Non-retryable responses (4xx other than 429) are returned to the caller.
+    // A timed out insert may still have been written, so it gets one attempt
+    async fn execute_with_policy(
+        &self,
+        query: Builder,
+        retryable: bool,
+    ) -> Result<Response, AppError> {
+        self.acquire_circuit()?;
+
+        let max_retries = if retryable {
+            self.policy.max_retries
+        } else {
+            0
+        };
+
+        let mut backoff = self.policy.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            let error =
+                match tokio::time::timeout(self.policy.request_timeout, query.clone().execute())
+                    .await
+                {
+                    Ok(Ok(resp)) if !is_retryable_status(resp.status()) => {
+                        self.record_success();
+                        return Ok(resp);
+                    }
+                    Ok(Ok(resp)) => format!("status {}", resp.status()),
+                    Ok(Err(e)) => e.to_string(),
+                    Err(_) => format!("timed out after {:?}", self.policy.request_timeout),
+                };
+
+            if attempt >= max_retries {
+                self.record_failure(&error);
+                return Err(AppError::PostgrestError(format!(
+                    "Request failed after {} attempts: {}",
+                    attempt + 1,
+                    error
+                )));
+            }
+
+            attempt += 1;
+            warn!(
+                "Database request failed ({}), retry {} of {} in {:?}",
+                error, attempt, max_retries, backoff
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.policy.max_backoff);
+        }
+    }
+
+    fn acquire_circuit(&self) -> Result<(), AppError> {
+        let mut circuit = self.circuit.lock();
+        match *circuit {
+            CircuitState::Closed { .. } => Ok(()),
+            CircuitState::Open { until } if Instant::now() >= until => {
+                // Let this request through as the probe
+                *circuit = CircuitState::HalfOpen;
+                Ok(())
+            }
+            CircuitState::Open { .. } | CircuitState::HalfOpen => Err(AppError::DatabaseError(
+                "Database circuit breaker is open".to_string(),
+            )),
+        }
+    }
+
+    fn record_success(&self) {
+        let recovered = {
+            let mut circuit = self.circuit.lock();
+            let recovered = !matches!(*circuit, CircuitState::Closed { .. });
+            *circuit = CircuitState::Closed {
+                consecutive_failures: 0,
+            };
+            recovered
+        };
+
+        if recovered {
+            info!("Database circuit breaker closed");
+            self.report(ConnectionState::Connected, None);
+        }
+    }
+
+    fn record_failure(&self, error: &str) {
+        let opened = {
+            let mut circuit = self.circuit.lock();
+            let failures = match *circuit {
+                CircuitState::Closed {
+                    consecutive_failures,
+                } => consecutive_failures + 1,
+                // A failed probe reopens immediately
+                CircuitState::HalfOpen | CircuitState::Open { .. } => self.policy.failure_threshold,
+            };
+
+            if failures >= self.policy.failure_threshold {
+                *circuit = CircuitState::Open {
+                    until: Instant::now() + self.policy.open_duration,
+                };
+                true
+            } else {
+                *circuit = CircuitState::Closed {
+                    consecutive_failures: failures,
+                };
+                false
+            }
+        };
+
+        if opened {
+            info!(
+                "Database circuit breaker open for {:?}: {}",
+                self.policy.open_duration, error
+            );
+            self.report(ConnectionState::Disconnected, Some(error.to_string()));
+        }
+    }
+
+    fn report(&self, state: ConnectionState, error: Option<String>) {
+        if let Some(connection_monitor) = &self.connection_monitor {
+            connection_monitor.update(ConnectionType::Database, state, error);
         }
     }
 
     pub async fn user_exists(&self, user_id: &str) -> Result<bool, AppError> {
-        println!("Checking if user exists: {}", user_id);
+        debug!("Checking if user exists: {}", user_id);
 
         let result = self
-            .client
-            .from("users")
-            .select("*")
-            .eq("wallet_address", user_id)
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .execute(
+                "users",
+                "select",
+                self.client
+                    .from("users")
+                    .select("*")
+                    .eq("wallet_address", user_id),
+            )
+            .await?;
 
         let body = result
             .text()
             .await
             .map_err(|e| AppError::RequestError(e.to_string()))?;
 
-        println!("User exists check response body: {}", body);
+        debug!("User exists check response body: {}", body);
 
         // Parse body as JSON array and check if it's not empty
         let users: Vec<serde_json::Value> = serde_json::from_str(&body).map_err(|e| {
@@ -55,27 +390,27 @@ impl SupabaseClient {
     }
 
     pub async fn create_user(&self, user_id: &str) -> Result<Uuid, AppError> {
-        println!("Attempting to create user with wallet address: {}", user_id);
+        info!("Attempting to create user with wallet address: {}", user_id);
 
         let insert_data = json!({"wallet_address": user_id});
-        println!("Insert data: {}", insert_data);
+        debug!("Insert data: {}", insert_data);
 
         let resp = self
-            .client
-            .from("users")
-            .insert(insert_data.to_string())
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .execute(
+                "users",
+                "insert",
+                self.client.from("users").insert(insert_data.to_string()),
+            )
+            .await?;
 
         let status = resp.status();
-        println!("Create user response status: {}", status);
+        debug!("Create user response status: {}", status);
 
         let body = resp
             .text()
             .await
             .map_err(|e| AppError::RequestError(e.to_string()))?;
-        println!("Create user response body: {}", body);
+        debug!("Create user response body: {}", body);
 
         if status != 201 && status != 200 {
             return Err(AppError::DatabaseError(format!(
@@ -99,13 +434,15 @@ impl SupabaseClient {
 
     pub async fn get_tracked_wallets(&self) -> Result<Vec<TrackedWallet>, AppError> {
         let resp = self
-            .client
-            .from("tracked_wallets")
-            .select("*")
-            .eq("user_id", &self.user_id)
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .execute(
+                "tracked_wallets",
+                "select",
+                self.client
+                    .from("tracked_wallets")
+                    .select("*")
+                    .eq("user_id", &self.user_id),
+            )
+            .await?;
 
         let body = resp
             .text()
@@ -116,30 +453,49 @@ impl SupabaseClient {
             .map_err(|e| AppError::JsonParseError(format!("Failed to parse wallets: {}", e)))?;
 
         if wallets.is_empty() {
-            println!("No wallets found");
+            info!("No wallets found");
         } else {
-            println!("Found wallets: {:?}", wallets);
+            debug!("Found wallets: {:?}", wallets);
         }
 
         Ok(wallets)
     }
 
+    pub async fn get_tracked_wallets_page(
+        &self,
+        page: &PageRequest,
+    ) -> Result<Page<TrackedWallet>, AppError> {
+        let query = self
+            .client
+            .from("tracked_wallets")
+            .select("*")
+            .eq("user_id", &self.user_id)
+            .order("created_at.asc,id.asc");
+
+        self.fetch_page("tracked_wallets", query, page, "tracked wallets")
+            .await
+    }
+
     pub async fn add_tracked_wallet(&self, mut wallet: TrackedWallet) -> Result<Uuid, AppError> {
         wallet.user_id = Some(self.user_id.clone());
 
         let insert_data = serde_json::json!({
             "user_id": wallet.user_id,
             "wallet_address": wallet.wallet_address,
-            "is_active": wallet.is_active
+            "is_active": wallet.is_active,
+            "label": wallet.label,
+            "notes": wallet.notes
         });
 
         let resp = self
-            .client
-            .from("tracked_wallets")
-            .insert(insert_data.to_string())
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .execute(
+                "tracked_wallets",
+                "insert",
+                self.client
+                    .from("tracked_wallets")
+                    .insert(insert_data.to_string()),
+            )
+            .await?;
 
         let body = resp
             .text()
@@ -160,14 +516,16 @@ impl SupabaseClient {
 
     pub async fn archive_tracked_wallet(&self, wallet_address: &str) -> Result<String, AppError> {
         let resp = self
-            .client
-            .from("tracked_wallets")
-            .update(json!({"is_active": false}).to_string())
-            .eq("user_id", &self.user_id)
-            .eq("wallet_address", wallet_address)
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .execute(
+                "tracked_wallets",
+                "update",
+                self.client
+                    .from("tracked_wallets")
+                    .update(json!({"is_active": false}).to_string())
+                    .eq("user_id", &self.user_id)
+                    .eq("wallet_address", wallet_address),
+            )
+            .await?;
 
         let body = resp
             .text()
@@ -184,14 +542,16 @@ impl SupabaseClient {
 
     pub async fn unarchive_tracked_wallet(&self, wallet_address: &str) -> Result<String, AppError> {
         let resp = self
-            .client
-            .from("tracked_wallets")
-            .update(json!({"is_active": true}).to_string())
-            .eq("user_id", &self.user_id)
-            .eq("wallet_address", wallet_address)
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .execute(
+                "tracked_wallets",
+                "update",
+                self.client
+                    .from("tracked_wallets")
+                    .update(json!({"is_active": true}).to_string())
+                    .eq("user_id", &self.user_id)
+                    .eq("wallet_address", wallet_address),
+            )
+            .await?;
 
         let body = resp
             .text()
@@ -208,14 +568,16 @@ impl SupabaseClient {
 
     pub async fn delete_tracked_wallet(&self, wallet_address: &str) -> Result<String, AppError> {
         let resp = self
-            .client
-            .from("tracked_wallets")
-            .delete()
-            .eq("user_id", &self.user_id)
-            .eq("wallet_address", wallet_address)
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .execute(
+                "tracked_wallets",
+                "delete",
+                self.client
+                    .from("tracked_wallets")
+                    .delete()
+                    .eq("user_id", &self.user_id)
+                    .eq("wallet_address", wallet_address),
+            )
+            .await?;
 
         if resp.status().is_success() {
             let body = resp
@@ -252,22 +614,26 @@ impl SupabaseClient {
             .ok_or_else(|| AppError::BadRequest("Wallet ID is required for update".to_string()))?;
 
         let resp = self
-            .client
-            .from("tracked_wallets")
-            .update(
-                json!({
-                    "id": wallet_id,
-                    "user_id": wallet.user_id,
-                    "wallet_address": wallet.wallet_address,
-                    "is_active": wallet.is_active
-                })
-                .to_string(),
+            .execute(
+                "tracked_wallets",
+                "update",
+                self.client
+                    .from("tracked_wallets")
+                    .update(
+                        json!({
+                            "id": wallet_id,
+                            "user_id": wallet.user_id,
+                            "wallet_address": wallet.wallet_address,
+                            "is_active": wallet.is_active,
+                            "label": wallet.label,
+                            "notes": wallet.notes
+                        })
+                        .to_string(),
+                    )
+                    .eq("user_id", &self.user_id)
+                    .eq("id", wallet_id.to_string()),
             )
-            .eq("user_id", &self.user_id)
-            .eq("id", wallet_id.to_string())
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .await?;
 
         let body = resp
             .text()
@@ -284,20 +650,22 @@ impl SupabaseClient {
 
     pub async fn get_copy_trade_settings(&self) -> Result<Vec<CopyTradeSettings>, AppError> {
         let resp = self
-            .client
-            .from("copy_trade_settings")
-            .select("*")
-            .eq("user_id", &self.user_id)
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .execute(
+                "copy_trade_settings",
+                "select",
+                self.client
+                    .from("copy_trade_settings")
+                    .select("*")
+                    .eq("user_id", &self.user_id),
+            )
+            .await?;
 
         let body = resp
             .text()
             .await
             .map_err(|e| AppError::RequestError(e.to_string()))?;
 
-        println!("Raw copy trade settings response: {}", body);
+        debug!("Raw copy trade settings response: {}", body);
 
         let settings: Vec<CopyTradeSettings> = serde_json::from_str(&body).map_err(|e| {
             AppError::JsonParseError(format!(
@@ -309,32 +677,50 @@ impl SupabaseClient {
         Ok(settings)
     }
 
+    pub async fn get_copy_trade_settings_page(
+        &self,
+        page: &PageRequest,
+    ) -> Result<Page<CopyTradeSettings>, AppError> {
+        let query = self
+            .client
+            .from("copy_trade_settings")
+            .select("*")
+            .eq("user_id", &self.user_id)
+            .order("id.asc");
+
+        self.fetch_page("copy_trade_settings", query, page, "copy trade settings")
+            .await
+    }
+
     pub async fn create_copy_trade_settings(
         &self,
         settings: CopyTradeSettings,
     ) -> Result<Uuid, AppError> {
         let resp = self
-            .client
-            .from("copy_trade_settings")
-            .insert(
-                json!({
-                    "user_id": self.user_id,
-                    "tracked_wallet_id": settings.tracked_wallet_id,
-                    "is_enabled": settings.is_enabled,
-                    "trade_amount_sol": settings.trade_amount_sol,
-                    "max_slippage": settings.max_slippage,
-                    "max_open_positions": settings.max_open_positions,
-                    "allowed_tokens": settings.allowed_tokens,
-                    "use_allowed_tokens_list": settings.use_allowed_tokens_list,
-                    "allow_additional_buys": settings.allow_additional_buys,
-                    "match_sell_percentage": settings.match_sell_percentage,
-                    "min_sol_balance": settings.min_sol_balance
-                })
-                .to_string(),
+            .execute(
+                "copy_trade_settings",
+                "insert",
+                self.client.from("copy_trade_settings").insert(
+                    json!({
+                        "user_id": self.user_id,
+                        "tracked_wallet_id": settings.tracked_wallet_id,
+                        "is_enabled": settings.is_enabled,
+                        "trade_amount_sol": settings.trade_amount_sol,
+                        "max_slippage": settings.max_slippage,
+                        "max_open_positions": settings.max_open_positions,
+                        "allowed_tokens": settings.allowed_tokens,
+                        "use_allowed_tokens_list": settings.use_allowed_tokens_list,
+                        "allow_additional_buys": settings.allow_additional_buys,
+                        "match_sell_percentage": settings.match_sell_percentage,
+                        "min_sol_balance": settings.min_sol_balance,
+                        "route_unsupported_venues": settings.route_unsupported_venues,
+                        "min_transfer_amount": settings.min_transfer_amount,
+                        "skip_simulation": settings.skip_simulation
+                    })
+                    .to_string(),
+                ),
             )
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .await?;
 
         let body = resp
             .text()
@@ -353,27 +739,32 @@ impl SupabaseClient {
         settings: CopyTradeSettings,
     ) -> Result<Uuid, AppError> {
         let resp = self
-            .client
-            .from("copy_trade_settings")
-            .update(
-                json!({
-                    "is_enabled": settings.is_enabled,
-                    "trade_amount_sol": settings.trade_amount_sol,
-                    "max_slippage": settings.max_slippage,
-                    "max_open_positions": settings.max_open_positions,
-                    "allowed_tokens": settings.allowed_tokens,
-                    "use_allowed_tokens_list": settings.use_allowed_tokens_list,
-                    "allow_additional_buys": settings.allow_additional_buys,
-                    "match_sell_percentage": settings.match_sell_percentage,
-                    "min_sol_balance": settings.min_sol_balance
-                })
-                .to_string(),
+            .execute(
+                "copy_trade_settings",
+                "update",
+                self.client
+                    .from("copy_trade_settings")
+                    .update(
+                        json!({
+                            "is_enabled": settings.is_enabled,
+                            "trade_amount_sol": settings.trade_amount_sol,
+                            "max_slippage": settings.max_slippage,
+                            "max_open_positions": settings.max_open_positions,
+                            "allowed_tokens": settings.allowed_tokens,
+                            "use_allowed_tokens_list": settings.use_allowed_tokens_list,
+                            "allow_additional_buys": settings.allow_additional_buys,
+                            "match_sell_percentage": settings.match_sell_percentage,
+                            "min_sol_balance": settings.min_sol_balance,
+                            "route_unsupported_venues": settings.route_unsupported_venues,
+                            "min_transfer_amount": settings.min_transfer_amount,
+                            "skip_simulation": settings.skip_simulation
+                        })
+                        .to_string(),
+                    )
+                    .eq("user_id", &self.user_id)
+                    .eq("tracked_wallet_id", settings.tracked_wallet_id.to_string()),
             )
-            .eq("user_id", &self.user_id)
-            .eq("tracked_wallet_id", settings.tracked_wallet_id.to_string())
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .await?;
 
         let body = resp
             .text()
@@ -392,14 +783,16 @@ impl SupabaseClient {
         tracked_wallet_id: Uuid,
     ) -> Result<String, AppError> {
         let resp = self
-            .client
-            .from("copy_trade_settings")
-            .delete()
-            .eq("user_id", &self.user_id)
-            .eq("tracked_wallet_id", tracked_wallet_id.to_string())
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .execute(
+                "copy_trade_settings",
+                "delete",
+                self.client
+                    .from("copy_trade_settings")
+                    .delete()
+                    .eq("user_id", &self.user_id)
+                    .eq("tracked_wallet_id", tracked_wallet_id.to_string()),
+            )
+            .await?;
 
         if resp.status() == 204 {
             Ok("Copy trade settings deleted successfully".to_string())
@@ -412,13 +805,15 @@ impl SupabaseClient {
 
     pub async fn get_transaction_history(&self) -> Result<Vec<TransactionLog>, AppError> {
         let resp = self
-            .client
-            .from("transactions")
-            .select("*")
-            .eq("user_id", &self.user_id)
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .execute(
+                "transactions",
+                "select",
+                self.client
+                    .from("transactions")
+                    .select("*")
+                    .eq("user_id", &self.user_id),
+            )
+            .await?;
 
         let body = resp
             .text()
@@ -432,21 +827,92 @@ impl SupabaseClient {
         Ok(transactions)
     }
 
-    pub async fn log_transaction(&self, transaction: TransactionLog) -> Result<Uuid> {
-        let resp = self
+    // Newest first
+    pub async fn get_transaction_logs(
+        &self,
+        filter: &TransactionLogFilter,
+    ) -> Result<Page<TransactionLog>, AppError> {
+        let mut query = self
             .client
             .from("transactions")
-            .insert(
-                json!({
-                    "id": transaction.id,
-                    "user_id": self.user_id,
-                    "tracked_wallet_id": transaction.tracked_wallet_id,
-                    "transaction_type": transaction.transaction_type,
-                    "signature": transaction.signature
-                })
-                .to_string(),
+            .select("*")
+            .eq("user_id", &self.user_id);
+
+        if let Some(tracked_wallet_id) = filter.tracked_wallet_id {
+            query = query.eq("tracked_wallet_id", tracked_wallet_id.to_string());
+        }
+        if let Some(token_address) = &filter.token_address {
+            query = query.eq("token_address", token_address);
+        }
+        if let Some(transaction_type) = &filter.transaction_type {
+            query = query.eq("transaction_type", transaction_type);
+        }
+        if let Some(from) = filter.from {
+            query = query.gte("timestamp", from.to_rfc3339());
+        }
+        if let Some(to) = filter.to {
+            query = query.lte("timestamp", to.to_rfc3339());
+        }
+
+        let query = query.order("timestamp.desc,id.desc");
+        self.fetch_page("transactions", query, &filter.page, "transactions")
+            .await
+    }
+
+    // The cursor is the offset of the next row. One extra row is requested so we
+    // know whether another page exists without a separate count query
+    async fn fetch_page<T: DeserializeOwned>(
+        &self,
+        table: &str,
+        query: Builder,
+        page: &PageRequest,
+        what: &str,
+    ) -> Result<Page<T>, AppError> {
+        let (offset, limit) = page_bounds(page)?;
+
+        let resp = self
+            .execute(table, "select", query.range(offset, offset + limit))
+            .await?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| AppError::RequestError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(AppError::DatabaseError(format!(
+                "Failed to fetch {}. Status: {}, Body: {}",
+                what, status, body
+            )));
+        }
+
+        let items: Vec<T> = serde_json::from_str(&body)
+            .map_err(|e| AppError::JsonParseError(format!("Failed to parse {}: {}", what, e)))?;
+
+        Ok(into_page(items, offset, limit))
+    }
+
+    // Upserts on id so a retried request returns the row already written
+    pub async fn log_transaction(&self, transaction: TransactionLog) -> Result<Uuid> {
+        let resp = self
+            .execute(
+                "transactions",
+                "upsert",
+                self.client
+                    .from("transactions")
+                    .upsert(
+                        json!({
+                            "id": transaction.id,
+                            "user_id": self.user_id,
+                            "tracked_wallet_id": transaction.tracked_wallet_id,
+                            "transaction_type": transaction.transaction_type,
+                            "signature": transaction.signature
+                        })
+                        .to_string(),
+                    )
+                    .on_conflict("id"),
             )
-            .execute()
             .await
             .context("Failed to log transaction")?;
 
@@ -461,24 +927,1142 @@ impl SupabaseClient {
             .ok_or_else(|| anyhow::anyhow!("Failed to get ID of inserted transaction"))
     }
 
-    // Helper function to verify table schema matches our struct
-    pub async fn verify_copy_trade_settings_schema(&self) -> Result<(), AppError> {
+    // Upserts on id so a batch retried after an ambiguous failure isn't duplicated
+    pub async fn insert_transaction_logs(
+        &self,
+        transactions: &[TransactionLog],
+    ) -> Result<(), AppError> {
+        let rows = transactions
+            .iter()
+            .map(|transaction| {
+                json!({
+                    "id": transaction.id,
+                    "user_id": self.user_id,
+                    "tracked_wallet_id": transaction.tracked_wallet_id,
+                    "signature": transaction.signature,
+                    "transaction_type": transaction.transaction_type,
+                    "token_address": transaction.token_address,
+                    "amount": transaction.amount,
+                    "price_sol": transaction.price_sol,
+                    "timestamp": transaction.timestamp
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let resp = self
+            .execute(
+                "transactions",
+                "upsert",
+                self.client
+                    .from("transactions")
+                    .upsert(serde_json::Value::Array(rows).to_string())
+                    .on_conflict("id"),
+            )
+            .await?;
+
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            Err(AppError::DatabaseError(format!(
+                "Failed to insert transaction logs. Status: {}, Body: {}",
+                status, body
+            )))
+        }
+    }
+
+    // Upserts on the execution's own id, so retrying a request that was written
+    // but timed out doesn't record the trade twice
+    pub async fn insert_trade_execution(&self, execution: &TradeExecution) -> Result<(), AppError> {
+        let resp = self
+            .execute(
+                "trade_executions",
+                "upsert",
+                self.client
+                    .from("trade_executions")
+                    .upsert(
+                        json!({
+                            "id": execution.id,
+                            "user_id": self.user_id,
+                            "trade_type": execution.trade_type,
+                            "dex_type": execution.dex_type,
+                            "signal_dex_type": execution.signal_dex_type,
+                            "transaction_type": execution.transaction_type,
+                            "token_address": execution.token_address,
+                            "tracked_wallet_id": execution.tracked_wallet_id,
+                            "amount_sol": execution.amount_sol,
+                            "amount_token": execution.amount_token,
+                            "signature": execution.signature,
+                            "child_signatures": execution.child_signatures,
+                            "route": execution.route,
+                            "status": execution.status,
+                            "error": execution.error,
+                            "created_at": execution.created_at,
+                            "updated_at": execution.updated_at
+                        })
+                        .to_string(),
+                    )
+                    .on_conflict("id"),
+            )
+            .await?;
+
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            Err(AppError::DatabaseError(format!(
+                "Failed to insert trade execution. Status: {}, Body: {}",
+                status, body
+            )))
+        }
+    }
+
+    // Writes the outcome of a row created by insert_trade_execution
+    pub async fn update_trade_execution(&self, execution: &TradeExecution) -> Result<(), AppError> {
+        let resp = self
+            .execute(
+                "trade_executions",
+                "update",
+                self.client
+                    .from("trade_executions")
+                    .eq("id", execution.id.to_string())
+                    .eq("user_id", &self.user_id)
+                    .update(
+                        json!({
+                            "amount_sol": execution.amount_sol,
+                            "amount_token": execution.amount_token,
+                            "signature": execution.signature,
+                            "child_signatures": execution.child_signatures,
+                            "route": execution.route,
+                            "status": execution.status,
+                            "error": execution.error,
+                            "updated_at": chrono::Utc::now()
+                        })
+                        .to_string(),
+                    ),
+            )
+            .await?;
+
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(AppError::DatabaseError(format!(
+                "Failed to update trade execution {}. Status: {}",
+                execution.id,
+                resp.status()
+            )))
+        }
+    }
+
+    pub async fn update_trade_execution_by_signature(
+        &self,
+        signature: &str,
+        status: TradeStatus,
+        error: Option<&str>,
+    ) -> Result<(), AppError> {
         let resp = self
+            .execute(
+                "trade_executions",
+                "update",
+                self.client
+                    .from("trade_executions")
+                    .eq("signature", signature)
+                    .eq("user_id", &self.user_id)
+                    .update(
+                        json!({
+                            "status": status,
+                            "error": error,
+                            "updated_at": chrono::Utc::now()
+                        })
+                        .to_string(),
+                    ),
+            )
+            .await?;
+
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(AppError::DatabaseError(format!(
+                "Failed to update trade execution {}. Status: {}",
+                signature,
+                resp.status()
+            )))
+        }
+    }
+
+    // Newest first
+    pub async fn get_trade_executions(
+        &self,
+        filter: &TradeExecutionFilter,
+    ) -> Result<Page<TradeExecution>, AppError> {
+        let mut query = self
             .client
-            .from("copy_trade_settings")
+            .from("trade_executions")
             .select("*")
-            .limit(0)
-            .execute()
-            .await
-            .map_err(|e| AppError::PostgrestError(e.to_string()))?;
+            .eq("user_id", &self.user_id);
 
-        let schema = resp
-            .text()
+        if let Some(tracked_wallet_id) = filter.tracked_wallet_id {
+            query = query.eq("tracked_wallet_id", tracked_wallet_id.to_string());
+        }
+        if let Some(token_address) = &filter.token_address {
+            query = query.eq("token_address", token_address);
+        }
+        if let Some(status) = filter.status {
+            query = query.eq("status", status.as_str());
+        }
+        if let Some(trade_type) = filter.trade_type {
+            query = query.eq("trade_type", trade_type.as_str());
+        }
+        if let Some(from) = filter.from {
+            query = query.gte("created_at", from.to_rfc3339());
+        }
+        if let Some(to) = filter.to {
+            query = query.lte("created_at", to.to_rfc3339());
+        }
+
+        let query = query.order("created_at.desc,id.desc");
+        self.fetch_page("trade_executions", query, &filter.page, "trade executions")
+            .await
+    }
+
+    pub async fn get_wallet_stats(&self) -> Result<Vec<WalletStats>, AppError> {
+        let executions = self.get_copy_trade_executions().await?;
+        Ok(WalletStatsAccumulator::from_executions(&executions).all_stats())
+    }
+
+    // Every stored copy execution, fine for the volumes a single user produces
+    pub async fn get_copy_trade_executions(&self) -> Result<Vec<TradeExecution>, AppError> {
+        let mut filter = TradeExecutionFilter {
+            trade_type: Some(TradeSource::Copy),
+            page: PageRequest {
+                limit: 1000,
+                cursor: None,
+            },
+            ..TradeExecutionFilter::default()
+        };
+
+        let mut executions = Vec::new();
+        loop {
+            let page = self.get_trade_executions(&filter).await?;
+            executions.extend(page.items);
+            match page.next_cursor {
+                Some(cursor) => filter.page.cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        Ok(executions)
+    }
+
+    pub async fn get_watchlists(&self) -> Result<Vec<WatchlistWithTokens>, AppError> {
+        let resp = self
+            .execute(
+                "watchlists",
+                "select",
+                self.client
+                    .from("watchlists")
+                    .select("*,watchlist_tokens(*)")
+                    .eq("user_id", &self.user_id)
+                    .order("created_at.asc"),
+            )
+            .await?;
+
+        Self::read_rows(resp, "watchlists").await
+    }
+
+    // Tokens are embedded in the same request
+    pub async fn get_watchlist(&self, watchlist_id: Uuid) -> Result<WatchlistWithTokens, AppError> {
+        let resp = self
+            .execute(
+                "watchlists",
+                "select",
+                self.client
+                    .from("watchlists")
+                    .select("*,watchlist_tokens(*)")
+                    .eq("user_id", &self.user_id)
+                    .eq("id", watchlist_id.to_string()),
+            )
+            .await?;
+
+        Self::read_rows(resp, "watchlist")
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::BadRequest(format!("Watchlist {} not found", watchlist_id)))
+    }
+
+    pub async fn create_watchlist(
+        &self,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<Watchlist, AppError> {
+        let resp = self
+            .execute(
+                "watchlists",
+                "insert",
+                self.client.from("watchlists").insert(
+                    json!({
+                        "user_id": self.user_id,
+                        "name": name,
+                        "description": description
+                    })
+                    .to_string(),
+                ),
+            )
+            .await?;
+
+        Self::read_rows(resp, "watchlist")
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::DatabaseError("No watchlist was inserted".to_string()))
+    }
+
+    pub async fn rename_watchlist(
+        &self,
+        watchlist_id: Uuid,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<Watchlist, AppError> {
+        let resp = self
+            .execute(
+                "watchlists",
+                "update",
+                self.client
+                    .from("watchlists")
+                    .update(
+                        json!({
+                            "name": name,
+                            "description": description,
+                            "updated_at": chrono::Utc::now()
+                        })
+                        .to_string(),
+                    )
+                    .eq("user_id", &self.user_id)
+                    .eq("id", watchlist_id.to_string()),
+            )
+            .await?;
+
+        Self::read_rows(resp, "watchlist")
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::BadRequest(format!("Watchlist {} not found", watchlist_id)))
+    }
+
+    // Tokens are removed with the watchlist by the foreign key cascade
+    pub async fn delete_watchlist(&self, watchlist_id: Uuid) -> Result<(), AppError> {
+        let resp = self
+            .execute(
+                "watchlists",
+                "delete",
+                self.client
+                    .from("watchlists")
+                    .delete()
+                    .eq("user_id", &self.user_id)
+                    .eq("id", watchlist_id.to_string()),
+            )
+            .await?;
+
+        let deleted: Vec<Watchlist> = Self::read_rows(resp, "watchlist").await?;
+        if deleted.is_empty() {
+            Err(AppError::BadRequest(format!(
+                "Watchlist {} not found",
+                watchlist_id
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn add_watchlist_token(
+        &self,
+        watchlist_id: Uuid,
+        token_address: &str,
+    ) -> Result<WatchlistToken, AppError> {
+        Pubkey::from_str(token_address).map_err(|_| {
+            AppError::BadRequest(format!("Invalid token address: {}", token_address))
+        })?;
+        // Also rejects watchlists owned by another user
+        self.get_watchlist(watchlist_id).await?;
+
+        let resp = self
+            .execute(
+                "watchlist_tokens",
+                "upsert",
+                self.client
+                    .from("watchlist_tokens")
+                    .upsert(
+                        json!({
+                            "watchlist_id": watchlist_id,
+                            "token_address": token_address
+                        })
+                        .to_string(),
+                    )
+                    .on_conflict("watchlist_id,token_address"),
+            )
+            .await?;
+
+        Self::read_rows(resp, "watchlist token")
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::DatabaseError("No watchlist token was inserted".to_string()))
+    }
+
+    pub async fn remove_watchlist_token(
+        &self,
+        watchlist_id: Uuid,
+        token_address: &str,
+    ) -> Result<(), AppError> {
+        self.get_watchlist(watchlist_id).await?;
+
+        let resp = self
+            .execute(
+                "watchlist_tokens",
+                "delete",
+                self.client
+                    .from("watchlist_tokens")
+                    .delete()
+                    .eq("watchlist_id", watchlist_id.to_string())
+                    .eq("token_address", token_address),
+            )
+            .await?;
+
+        let deleted: Vec<WatchlistToken> = Self::read_rows(resp, "watchlist token").await?;
+        if deleted.is_empty() {
+            Err(AppError::BadRequest(format!(
+                "Token {} is not in watchlist {}",
+                token_address, watchlist_id
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn read_rows<T: DeserializeOwned>(
+        resp: Response,
+        what: &str,
+    ) -> Result<Vec<T>, AppError> {
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| AppError::RequestError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(AppError::DatabaseError(format!(
+                "Failed to query {}. Status: {}, Body: {}",
+                what, status, body
+            )));
+        }
+
+        serde_json::from_str(&body)
+            .map_err(|e| AppError::JsonParseError(format!("Failed to parse {}: {}", what, e)))
+    }
+
+    pub async fn get_monitor_cursors(&self) -> Result<Vec<MonitorCursor>, AppError> {
+        let resp = self
+            .execute(
+                "monitor_cursors",
+                "select",
+                self.client
+                    .from("monitor_cursors")
+                    .select("*")
+                    .eq("user_id", &self.user_id),
+            )
+            .await?;
+
+        let body = resp
+            .text()
             .await
             .map_err(|e| AppError::RequestError(e.to_string()))?;
 
-        println!("Copy trade settings schema: {}", schema);
+        serde_json::from_str(&body)
+            .map_err(|e| AppError::JsonParseError(format!("Failed to parse cursors: {}", e)))
+    }
+
+    pub async fn save_monitor_cursor(&self, cursor: &MonitorCursor) -> Result<(), AppError> {
+        let resp = self
+            .execute(
+                "monitor_cursors",
+                "upsert",
+                self.client
+                    .from("monitor_cursors")
+                    .upsert(
+                        json!({
+                            "user_id": self.user_id,
+                            "wallet_address": cursor.wallet_address,
+                            "last_signature": cursor.last_signature,
+                            "last_slot": cursor.last_slot,
+                            "last_block_time": cursor.last_block_time,
+                            "updated_at": chrono::Utc::now()
+                        })
+                        .to_string(),
+                    )
+                    .on_conflict("user_id,wallet_address"),
+            )
+            .await?;
+
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(AppError::DatabaseError(format!(
+                "Failed to save monitor cursor. Status: {}",
+                resp.status()
+            )))
+        }
+    }
+
+    pub async fn get_positions(&self) -> Result<Vec<Position>, AppError> {
+        let resp = self
+            .execute(
+                "positions",
+                "select",
+                self.client
+                    .from("positions")
+                    .select("*")
+                    .eq("user_id", &self.user_id),
+            )
+            .await?;
+
+        Self::read_rows(resp, "positions").await
+    }
+
+    pub async fn save_position(&self, position: &Position) -> Result<(), AppError> {
+        let resp = self
+            .execute(
+                "positions",
+                "upsert",
+                self.client
+                    .from("positions")
+                    .upsert(
+                        json!({
+                            "user_id": self.user_id,
+                            "token_address": position.token_address,
+                            "amount_token": position.amount_token,
+                            "cost_basis_sol": position.cost_basis_sol,
+                            "realized_pnl_sol": position.realized_pnl_sol,
+                            "updated_at": position.updated_at
+                        })
+                        .to_string(),
+                    )
+                    .on_conflict("user_id,token_address"),
+            )
+            .await?;
+
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(AppError::DatabaseError(format!(
+                "Failed to save position for {}. Status: {}",
+                position.token_address,
+                resp.status()
+            )))
+        }
+    }
+
+    pub async fn insert_event_audit(&self, records: &[EventAuditRecord]) -> Result<(), AppError> {
+        let rows = records
+            .iter()
+            .map(|record| {
+                json!({
+                    "user_id": self.user_id,
+                    "event_id": record.event_id,
+                    "event_type": record.event_type,
+                    "correlation_id": record.correlation_id,
+                    "payload": record.payload,
+                    "created_at": record.created_at
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let resp = self
+            .execute(
+                "event_audit",
+                "insert",
+                self.client
+                    .from("event_audit")
+                    .insert(serde_json::Value::Array(rows).to_string()),
+            )
+            .await?;
+
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(AppError::DatabaseError(format!(
+                "Failed to insert event audit rows. Status: {}",
+                resp.status()
+            )))
+        }
+    }
+
+    pub async fn delete_event_audit_before(
+        &self,
+        cutoff: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), AppError> {
+        let resp = self
+            .execute(
+                "event_audit",
+                "delete",
+                self.client
+                    .from("event_audit")
+                    .eq("user_id", &self.user_id)
+                    .lt("created_at", cutoff.to_rfc3339())
+                    .delete(),
+            )
+            .await?;
+
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(AppError::DatabaseError(format!(
+                "Failed to delete old event audit rows. Status: {}",
+                resp.status()
+            )))
+        }
+    }
+
+    pub async fn queue_control_command(
+        &self,
+        command: ControlCommand,
+    ) -> Result<ControlCommandRecord, AppError> {
+        let resp = self
+            .execute(
+                "control_commands",
+                "insert",
+                self.client.from("control_commands").insert(
+                    json!({
+                        "id": Uuid::new_v4(),
+                        "user_id": self.user_id,
+                        "command": command
+                    })
+                    .to_string(),
+                ),
+            )
+            .await?;
+
+        Self::read_rows(resp, "control command")
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::DatabaseError("No control command was inserted".to_string()))
+    }
+
+    pub async fn get_pending_control_commands(
+        &self,
+    ) -> Result<Vec<ControlCommandRecord>, AppError> {
+        let resp = self
+            .execute(
+                "control_commands",
+                "select",
+                self.client
+                    .from("control_commands")
+                    .select("*")
+                    .eq("user_id", &self.user_id)
+                    .is("applied_at", "null")
+                    .order("created_at.asc,id.asc"),
+            )
+            .await?;
+
+        Self::read_rows(resp, "control commands").await
+    }
+
+    pub async fn mark_control_command_applied(&self, id: Uuid) -> Result<(), AppError> {
+        let resp = self
+            .execute(
+                "control_commands",
+                "update",
+                self.client
+                    .from("control_commands")
+                    .eq("id", id.to_string())
+                    .eq("user_id", &self.user_id)
+                    .update(json!({ "applied_at": chrono::Utc::now() }).to_string()),
+            )
+            .await?;
+
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(AppError::DatabaseError(format!(
+                "Failed to mark control command {} applied. Status: {}",
+                id,
+                resp.status()
+            )))
+        }
+    }
+
+    pub async fn get_trading_switch(&self) -> Result<TradingSwitch, AppError> {
+        let resp = self
+            .execute(
+                "trading_switch",
+                "select",
+                self.client
+                    .from("trading_switch")
+                    .select("halted,reason,updated_at")
+                    .eq("user_id", &self.user_id),
+            )
+            .await?;
+
+        Ok(Self::read_rows(resp, "trading switch")
+            .await?
+            .into_iter()
+            .next()
+            .unwrap_or_default())
+    }
+
+    pub async fn set_trading_switch(
+        &self,
+        halted: bool,
+        reason: Option<&str>,
+    ) -> Result<TradingSwitch, AppError> {
+        let resp = self
+            .execute(
+                "trading_switch",
+                "upsert",
+                self.client
+                    .from("trading_switch")
+                    .upsert(
+                        json!({
+                            "user_id": self.user_id,
+                            "halted": halted,
+                            "reason": reason,
+                            "updated_at": chrono::Utc::now()
+                        })
+                        .to_string(),
+                    )
+                    .on_conflict("user_id"),
+            )
+            .await?;
+
+        Self::read_rows(resp, "trading switch")
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::DatabaseError("Trading switch was not saved".to_string()))
+    }
+
+    // Helper function to verify table schema matches our struct
+    pub async fn verify_copy_trade_settings_schema(&self) -> Result<(), AppError> {
+        let resp = self
+            .execute(
+                "copy_trade_settings",
+                "select",
+                self.client.from("copy_trade_settings").select("*").limit(0),
+            )
+            .await?;
+
+        let schema = resp
+            .text()
+            .await
+            .map_err(|e| AppError::RequestError(e.to_string()))?;
+
+        debug!("Copy trade settings schema: {}", schema);
 
         Ok(())
     }
 }
+
+// PostgREST reports the returned range as "0-24/*", or "*/0" when empty
+fn content_range_rows(resp: &Response) -> Option<usize> {
+    let range = resp.headers().get("content-range")?.to_str().ok()?;
+    let (range, _total) = range.split_once('/')?;
+    if range == "*" {
+        return Some(0);
+    }
+    let (start, end) = range.split_once('-')?;
+    let start = start.parse::<usize>().ok()?;
+    let end = end.parse::<usize>().ok()?;
+    (end + 1).checked_sub(start)
+}
+
+// Whether repeating a request that may already have been applied leaves the
+// same rows behind. Upserts on a client-generated id are, plain inserts aren't
+fn is_idempotent(operation: &str) -> bool {
+    matches!(operation, "select" | "update" | "upsert" | "delete")
+}
+
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+// Offset and limit of the page a request asks for
+fn page_bounds(page: &PageRequest) -> Result<(usize, usize), AppError> {
+    let offset = match &page.cursor {
+        Some(cursor) => cursor
+            .parse::<usize>()
+            .map_err(|_| AppError::BadRequest(format!("Invalid page cursor: {}", cursor)))?,
+        None => 0,
+    };
+    Ok((offset, page.limit.max(1)))
+}
+
+// Rows beyond the limit only signal that another page exists
+fn into_page<T>(mut items: Vec<T>, offset: usize, limit: usize) -> Page<T> {
+    let next_cursor = if items.len() > limit {
+        items.truncate(limit);
+        Some((offset + limit).to_string())
+    } else {
+        None
+    };
+    Page { items, next_cursor }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use std::collections::VecDeque;
+
+    fn request(limit: usize, cursor: Option<&str>) -> PageRequest {
+        PageRequest {
+            limit,
+            cursor: cursor.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn page_bounds_start_at_the_cursor() {
+        assert_eq!(page_bounds(&request(25, None)).unwrap(), (0, 25));
+        assert_eq!(page_bounds(&request(25, Some("50"))).unwrap(), (50, 25));
+        // A zero limit still returns one row per page
+        assert_eq!(page_bounds(&request(0, None)).unwrap(), (0, 1));
+    }
+
+    #[test]
+    fn page_bounds_reject_invalid_cursors() {
+        for cursor in ["abc", "-1", ""] {
+            assert!(matches!(
+                page_bounds(&request(25, Some(cursor))),
+                Err(AppError::BadRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn extra_row_produces_the_next_cursor() {
+        let page = into_page(vec![1, 2, 3], 10, 2);
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor.as_deref(), Some("12"));
+    }
+
+    #[test]
+    fn last_page_has_no_cursor() {
+        let page = into_page(vec![1, 2], 10, 2);
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor, None);
+
+        let empty = into_page(Vec::<u8>::new(), 0, 2);
+        assert!(empty.items.is_empty());
+        assert_eq!(empty.next_cursor, None);
+    }
+
+    #[test]
+    fn pages_walk_every_row_once() {
+        let rows = (0..7).collect::<Vec<_>>();
+        let mut page_request = request(3, None);
+        let mut seen = Vec::new();
+
+        loop {
+            let (offset, limit) = page_bounds(&page_request).unwrap();
+            // PostgREST ranges are inclusive, offset..=offset + limit
+            let fetched = rows
+                .iter()
+                .copied()
+                .skip(offset)
+                .take(limit + 1)
+                .collect::<Vec<_>>();
+            let page = into_page(fetched, offset, limit);
+            seen.extend(page.items);
+            match page.next_cursor {
+                Some(cursor) => page_request.cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        assert_eq!(seen, rows);
+    }
+
+    #[test]
+    fn only_idempotent_operations_are_retried() {
+        for operation in ["select", "update", "upsert", "delete"] {
+            assert!(is_idempotent(operation), "{}", operation);
+        }
+        assert!(!is_idempotent("insert"));
+    }
+
+    #[derive(Debug, Clone)]
+    struct MockRequest {
+        method: String,
+        path: String,
+        range: Option<String>,
+    }
+
+    // Stands in for PostgREST, answering with the scripted statuses in order
+    // and 200 once they run out
+    #[derive(Clone)]
+    struct MockPostgrest {
+        url: String,
+        statuses: Arc<Mutex<VecDeque<u16>>>,
+        requests: Arc<Mutex<Vec<MockRequest>>>,
+    }
+
+    #[derive(Clone)]
+    struct MockState {
+        statuses: Arc<Mutex<VecDeque<u16>>>,
+        requests: Arc<Mutex<Vec<MockRequest>>>,
+        body: String,
+    }
+
+    impl MockPostgrest {
+        async fn start(statuses: &[u16], body: &str) -> Self {
+            let statuses = Arc::new(Mutex::new(
+                statuses.iter().copied().collect::<VecDeque<_>>(),
+            ));
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let router = axum::Router::new()
+                .fallback(mock_handler)
+                .with_state(MockState {
+                    statuses: Arc::clone(&statuses),
+                    requests: Arc::clone(&requests),
+                    body: body.to_string(),
+                });
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            tokio::spawn(async move {
+                axum::serve(listener, router).await.unwrap();
+            });
+            Self {
+                url,
+                statuses,
+                requests,
+            }
+        }
+
+        fn script(&self, statuses: &[u16]) {
+            self.statuses.lock().extend(statuses);
+        }
+
+        fn requests(&self) -> Vec<MockRequest> {
+            self.requests.lock().clone()
+        }
+
+        fn client(&self, policy: DatabasePolicy) -> SupabaseClient {
+            SupabaseClient::with_policy(&self.url, "", "service-role", "user", policy)
+        }
+    }
+
+    async fn mock_handler(
+        axum::extract::State(state): axum::extract::State<MockState>,
+        method: axum::http::Method,
+        uri: axum::http::Uri,
+        headers: axum::http::HeaderMap,
+    ) -> axum::response::Response {
+        state.requests.lock().push(MockRequest {
+            method: method.to_string(),
+            path: uri.path().to_string(),
+            range: headers
+                .get("range")
+                .and_then(|range| range.to_str().ok())
+                .map(str::to_string),
+        });
+        let status = state.statuses.lock().pop_front().unwrap_or(200);
+        (
+            axum::http::StatusCode::from_u16(status).unwrap(),
+            [(axum::http::header::CONTENT_TYPE, "application/json")],
+            state.body,
+        )
+            .into_response()
+    }
+
+    fn tiny_policy() -> DatabasePolicy {
+        DatabasePolicy {
+            request_timeout: Duration::from_secs(5),
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+            failure_threshold: 2,
+            open_duration: Duration::from_millis(50),
+        }
+    }
+
+    async fn select(client: &SupabaseClient) -> Result<Response, AppError> {
+        client
+            .execute("things", "select", client.client.from("things").select("*"))
+            .await
+    }
+
+    async fn insert(client: &SupabaseClient) -> Result<Response, AppError> {
+        client
+            .execute(
+                "things",
+                "insert",
+                client.client.from("things").insert("{}"),
+            )
+            .await
+    }
+
+    fn database_state(connection_monitor: &ConnectionMonitor) -> Option<ConnectionState> {
+        connection_monitor
+            .snapshot()
+            .into_iter()
+            .find(|status| status.connection_type == ConnectionType::Database)
+            .map(|status| status.state)
+    }
+
+    #[tokio::test]
+    async fn retryable_statuses_are_retried() {
+        let mock = MockPostgrest::start(&[503, 429], "[]").await;
+        let client = mock.client(tiny_policy());
+
+        let resp = select(&client).await.unwrap();
+
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_returned_without_retrying() {
+        let mock = MockPostgrest::start(&[404], "[]").await;
+        let client = mock.client(tiny_policy());
+
+        let resp = select(&client).await.unwrap();
+
+        assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn inserts_get_a_single_attempt() {
+        let mock = MockPostgrest::start(&[503], "[]").await;
+        let client = mock.client(tiny_policy());
+
+        assert!(insert(&client).await.is_err());
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/things");
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_after_the_failure_threshold() {
+        let mock = MockPostgrest::start(&[500, 500], "[]").await;
+        let connection_monitor = Arc::new(ConnectionMonitor::new(
+            Arc::new(EventSystem::new()),
+            Duration::ZERO,
+        ));
+        let client = mock
+            .client(DatabasePolicy {
+                max_retries: 0,
+                ..tiny_policy()
+            })
+            .with_connection_monitor(Arc::clone(&connection_monitor));
+
+        assert!(select(&client).await.is_err());
+        assert_eq!(database_state(&connection_monitor), None);
+        assert!(select(&client).await.is_err());
+        assert_eq!(
+            database_state(&connection_monitor),
+            Some(ConnectionState::Disconnected)
+        );
+
+        // Rejected without reaching the server while open
+        let error = select(&client).await.unwrap_err();
+        assert!(error.to_string().contains("circuit breaker is open"));
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn successful_probe_closes_the_circuit() {
+        let mock = MockPostgrest::start(&[500, 500], "[]").await;
+        let connection_monitor = Arc::new(ConnectionMonitor::new(
+            Arc::new(EventSystem::new()),
+            Duration::ZERO,
+        ));
+        let policy = DatabasePolicy {
+            max_retries: 0,
+            ..tiny_policy()
+        };
+        let open_duration = policy.open_duration;
+        let client = mock
+            .client(policy)
+            .with_connection_monitor(Arc::clone(&connection_monitor));
+        assert!(select(&client).await.is_err());
+        assert!(select(&client).await.is_err());
+
+        tokio::time::sleep(open_duration).await;
+        assert!(select(&client).await.is_ok());
+
+        assert_eq!(
+            database_state(&connection_monitor),
+            Some(ConnectionState::Connected)
+        );
+        assert!(select(&client).await.is_ok());
+        assert_eq!(mock.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn failed_probe_reopens_the_circuit() {
+        let mock = MockPostgrest::start(&[500, 500], "[]").await;
+        let policy = DatabasePolicy {
+            max_retries: 0,
+            ..tiny_policy()
+        };
+        let open_duration = policy.open_duration;
+        let client = mock.client(policy);
+        assert!(select(&client).await.is_err());
+        assert!(select(&client).await.is_err());
+
+        tokio::time::sleep(open_duration).await;
+        mock.script(&[500]);
+        assert!(select(&client).await.is_err());
+
+        // One failed probe is enough to open it again
+        let error = select(&client).await.unwrap_err();
+        assert!(error.to_string().contains("circuit breaker is open"));
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn fetch_page_requests_one_extra_row() {
+        let mock = MockPostgrest::start(&[], "[1, 2, 3]").await;
+        let client = mock.client(tiny_policy());
+
+        let page: Page<u32> = client
+            .fetch_page(
+                "things",
+                client.client.from("things").select("*"),
+                &request(2, Some("4")),
+                "things",
+            )
+            .await
+            .unwrap();
+
+        // Inclusive range, rows 4 and 5 plus one to detect the next page
+        assert_eq!(mock.requests()[0].range.as_deref(), Some("4-6"));
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor.as_deref(), Some("6"));
+    }
+
+    #[tokio::test]
+    async fn fetch_page_without_the_extra_row_is_the_last() {
+        let mock = MockPostgrest::start(&[], "[1, 2]").await;
+        let client = mock.client(tiny_policy());
+
+        let page: Page<u32> = client
+            .fetch_page(
+                "things",
+                client.client.from("things").select("*"),
+                &request(2, None),
+                "things",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(mock.requests()[0].range.as_deref(), Some("0-2"));
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor, None);
+    }
+}
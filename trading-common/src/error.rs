@@ -7,6 +7,8 @@ use solana_sdk::{program_error::ProgramError, pubkey::ParsePubkeyError};
 use thiserror::Error;
 use tokio::sync::mpsc;
 
+use crate::models::FieldError;
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Database error: {0}")]
@@ -15,6 +17,12 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Validation failed: {}", describe_field_errors(.0))]
+    ValidationError(Vec<FieldError>),
+
     #[error("Postgrest error: {0}")]
     PostgrestError(String),
 
@@ -51,6 +59,21 @@ pub enum AppError {
     #[error("Transaction error: {0}")]
     TransactionError(String),
 
+    #[error("Slippage exceeded: {0}")]
+    SlippageExceeded(String),
+
+    #[error("Spend limit exceeded: {0}")]
+    SpendLimitExceeded(String),
+
+    #[error("Trading halted: {0}")]
+    TradingHalted(String),
+
+    #[error("Account not initialized: {0}")]
+    AccountNotInitialized(String),
+
+    #[error("Simulation failed: {0}")]
+    SimulationFailed(String),
+
     #[error("Pubkey parse error: {source}")]
     PubkeyParseError {
         #[from]
@@ -99,9 +122,18 @@ pub enum AppError {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let fields = match &self {
+            AppError::ValidationError(errors) => Some(errors.clone()),
+            _ => None,
+        };
+
         let (status, error_message) = match self {
             AppError::DatabaseError(message) => (StatusCode::INTERNAL_SERVER_ERROR, message),
             AppError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            AppError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message),
+            AppError::ValidationError(errors) => {
+                (StatusCode::BAD_REQUEST, describe_field_errors(&errors))
+            }
             AppError::PostgrestError(message) => (StatusCode::INTERNAL_SERVER_ERROR, message),
             AppError::JsonParseError(message) => (StatusCode::BAD_REQUEST, message),
             AppError::RequestError(message) => (StatusCode::BAD_REQUEST, message),
@@ -113,6 +145,11 @@ impl IntoResponse for AppError {
             AppError::TokenAccountError(message) => (StatusCode::BAD_REQUEST, message),
             AppError::InsufficientBalanceError(message) => (StatusCode::BAD_REQUEST, message),
             AppError::TransactionError(message) => (StatusCode::BAD_REQUEST, message),
+            AppError::SlippageExceeded(message) => (StatusCode::BAD_REQUEST, message),
+            AppError::SpendLimitExceeded(message) => (StatusCode::FORBIDDEN, message),
+            AppError::TradingHalted(message) => (StatusCode::SERVICE_UNAVAILABLE, message),
+            AppError::AccountNotInitialized(message) => (StatusCode::BAD_REQUEST, message),
+            AppError::SimulationFailed(message) => (StatusCode::BAD_REQUEST, message),
             AppError::PubkeyParseError { source } => (StatusCode::BAD_REQUEST, source.to_string()),
             AppError::ProgramError { source } => (StatusCode::BAD_REQUEST, source.to_string()),
             AppError::WebSocketConnectionError(msg) => (StatusCode::BAD_GATEWAY, msg),
@@ -132,15 +169,26 @@ impl IntoResponse for AppError {
             AppError::TaskError(message) => (StatusCode::BAD_REQUEST, message),
         };
 
-        let body = serde_json::json!({
+        let mut body = serde_json::json!({
             "error": error_message,
             "status": status.as_u16()
         });
+        if let Some(fields) = fields {
+            body["fields"] = serde_json::json!(fields);
+        }
 
         (status, axum::Json(body)).into_response()
     }
 }
 
+fn describe_field_errors(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("{}: {}", error.field, error.message))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 impl From<serde_json::Error> for AppError {
     fn from(err: serde_json::Error) -> Self {
         AppError::JsonParseError(err.to_string())
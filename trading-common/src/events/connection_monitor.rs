@@ -0,0 +1,485 @@
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::event_system::{Event, EventSystem};
+use crate::metrics::record_connection_state;
+use crate::models::{
+    ConnectionAlert, ConnectionAlertKind, ConnectionAlertNotification, ConnectionState,
+    ConnectionStatus, ConnectionStatusNotification, ConnectionType, HealthSnapshot,
+    HealthSnapshotNotification, OverallStatus,
+};
+
+struct TrackedStatus {
+    current: ConnectionStatus,
+    emitted: Option<(ConnectionState, u32)>,
+    last_emit: Option<Instant>,
+    outage: Option<Outage>,
+}
+
+// Time since the connection was last connected, reconnect attempts included
+struct Outage {
+    started: Instant,
+    down_since: DateTime<Utc>,
+    alerted: bool,
+}
+
+struct OverallTracker {
+    reported: OverallStatus,
+    since: DateTime<Utc>,
+    // A different status seen since this instant, adopted once it has held
+    candidate: Option<(OverallStatus, Instant)>,
+}
+
+// Tracks the state of each external connection and emits ConnectionStatus events
+// on transitions. A transition within `debounce` of the previous emission is held
+// back, and dropped entirely if the connection flaps back before it is flushed.
+// A connection down past its alert threshold raises a ConnectionAlert instead
+pub struct ConnectionMonitor {
+    event_system: Arc<EventSystem>,
+    debounce: Duration,
+    statuses: Mutex<HashMap<ConnectionType, TrackedStatus>>,
+    hysteresis: Duration,
+    overall: Mutex<OverallTracker>,
+    alert_after: HashMap<ConnectionType, Duration>,
+}
+
+impl ConnectionMonitor {
+    pub fn new(event_system: Arc<EventSystem>, debounce: Duration) -> Self {
+        Self {
+            event_system,
+            debounce,
+            statuses: Mutex::new(HashMap::new()),
+            hysteresis: Duration::from_secs(10),
+            overall: Mutex::new(OverallTracker {
+                reported: OverallStatus::Down,
+                since: Utc::now(),
+                candidate: None,
+            }),
+            alert_after: HashMap::from([
+                (ConnectionType::WebSocket, Duration::from_secs(60)),
+                (ConnectionType::Database, Duration::from_secs(120)),
+            ]),
+        }
+    }
+
+    // A connection down for longer than this raises a ConnectionAlert
+    pub fn with_alert_threshold(
+        mut self,
+        connection_type: ConnectionType,
+        threshold: Duration,
+    ) -> Self {
+        self.alert_after.insert(connection_type, threshold);
+        self
+    }
+
+    // How long the combined status has to hold before overall_status reports it
+    pub fn with_hysteresis(mut self, hysteresis: Duration) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    pub fn update(
+        &self,
+        connection_type: ConnectionType,
+        state: ConnectionState,
+        error: Option<String>,
+    ) {
+        self.record(connection_type, state, 0, error);
+    }
+
+    pub fn reconnecting(
+        &self,
+        connection_type: ConnectionType,
+        attempt: u32,
+        error: Option<String>,
+    ) {
+        self.record(
+            connection_type,
+            ConnectionState::Reconnecting,
+            attempt,
+            error,
+        );
+    }
+
+    fn record(
+        &self,
+        connection_type: ConnectionType,
+        state: ConnectionState,
+        attempt: u32,
+        error: Option<String>,
+    ) {
+        record_connection_state(connection_type, state);
+        let (notification, recovered) = {
+            let mut statuses = self.statuses.lock();
+            let tracked = statuses
+                .entry(connection_type)
+                .or_insert_with(|| TrackedStatus {
+                    current: ConnectionStatus {
+                        connection_type,
+                        state,
+                        attempt,
+                        error: None,
+                        slot_lag: None,
+                        since: Utc::now(),
+                    },
+                    emitted: None,
+                    last_emit: None,
+                    outage: None,
+                });
+
+            if tracked.current.state != state {
+                tracked.current.state = state;
+                tracked.current.since = Utc::now();
+            }
+            tracked.current.attempt = attempt;
+            tracked.current.error = error;
+
+            let recovered = if state == ConnectionState::Connected {
+                tracked.outage.take().filter(|outage| outage.alerted)
+            } else {
+                if tracked.outage.is_none() {
+                    tracked.outage = Some(Outage {
+                        started: Instant::now(),
+                        down_since: Utc::now(),
+                        alerted: false,
+                    });
+                }
+                None
+            };
+
+            (self.take_pending(tracked), recovered)
+        };
+
+        if let Some(notification) = notification {
+            self.emit(notification);
+        }
+        if let Some(outage) = recovered {
+            self.emit_alert(ConnectionAlert {
+                connection_type,
+                kind: ConnectionAlertKind::Recovered,
+                outage_secs: outage.started.elapsed().as_secs(),
+                down_since: outage.down_since,
+                error: None,
+            });
+        }
+    }
+
+    // Raises an alert, once per outage, for connections down past their threshold
+    pub fn check_outages(&self) {
+        let alerts = self
+            .statuses
+            .lock()
+            .values_mut()
+            .filter_map(|tracked| {
+                let threshold = self.alert_after.get(&tracked.current.connection_type)?;
+                let outage = tracked.outage.as_mut()?;
+                if outage.alerted || outage.started.elapsed() < *threshold {
+                    return None;
+                }
+                outage.alerted = true;
+                Some(ConnectionAlert {
+                    connection_type: tracked.current.connection_type,
+                    kind: ConnectionAlertKind::Down,
+                    outage_secs: outage.started.elapsed().as_secs(),
+                    down_since: outage.down_since,
+                    error: tracked.current.error.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for alert in alerts {
+            self.emit_alert(alert);
+        }
+    }
+
+    // Lag is reported in snapshots and MonitorHealth events, it never triggers a transition
+    pub fn set_slot_lag(&self, connection_type: ConnectionType, slot_lag: Option<u64>) {
+        if let Some(tracked) = self.statuses.lock().get_mut(&connection_type) {
+            tracked.current.slot_lag = slot_lag;
+        }
+    }
+
+    // Emits transitions that were held back by the debounce window
+    pub fn flush_pending(&self) {
+        let notifications = self
+            .statuses
+            .lock()
+            .values_mut()
+            .filter_map(|tracked| self.take_pending(tracked))
+            .collect::<Vec<_>>();
+
+        for notification in notifications {
+            self.emit(notification);
+        }
+    }
+
+    // Current state of every connection seen so far, for clients that just connected
+    pub fn snapshot(&self) -> Vec<ConnectionStatus> {
+        self.statuses
+            .lock()
+            .values()
+            .map(|tracked| tracked.current.clone())
+            .collect()
+    }
+
+    // Whether the system can copy trade, see ConnectionType::is_critical. A
+    // reconnect blip shorter than the hysteresis doesn't flip it
+    pub fn overall_status(&self) -> OverallStatus {
+        self.evaluate_overall().0
+    }
+
+    pub fn health_snapshot(&self) -> HealthSnapshot {
+        let (status, since, _) = self.evaluate_overall();
+        HealthSnapshot {
+            status,
+            since,
+            connections: self.snapshot(),
+        }
+    }
+
+    // Emits a HealthSnapshot every `interval` and as soon as the overall status
+    // changes. Connection transitions emit one as well
+    pub fn spawn_health_reporter(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let monitor = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut check = tokio::time::interval(Duration::from_secs(1));
+            let mut last_emit: Option<Instant> = None;
+            loop {
+                check.tick().await;
+                let (_, _, changed) = monitor.evaluate_overall();
+                let due = match last_emit {
+                    Some(last_emit) => last_emit.elapsed() >= interval,
+                    None => true,
+                };
+                if changed || due {
+                    monitor.emit_health_snapshot();
+                    last_emit = Some(Instant::now());
+                }
+            }
+        })
+    }
+
+    // Returns the reported status, when it last changed and whether this call changed it
+    fn evaluate_overall(&self) -> (OverallStatus, DateTime<Utc>, bool) {
+        let current = combined_status(&self.snapshot());
+        let mut overall = self.overall.lock();
+
+        if current == overall.reported {
+            overall.candidate = None;
+            return (overall.reported, overall.since, false);
+        }
+
+        let candidate_since = match overall.candidate {
+            Some((candidate, since)) if candidate == current => since,
+            _ => {
+                let now = Instant::now();
+                overall.candidate = Some((current, now));
+                now
+            }
+        };
+        if candidate_since.elapsed() < self.hysteresis {
+            return (overall.reported, overall.since, false);
+        }
+
+        info!(
+            "Overall status changed from {:?} to {:?}",
+            overall.reported, current
+        );
+        overall.reported = current;
+        overall.since = Utc::now();
+        overall.candidate = None;
+        (overall.reported, overall.since, true)
+    }
+
+    fn emit_health_snapshot(&self) {
+        self.event_system
+            .emit(Event::HealthSnapshot(HealthSnapshotNotification {
+                data: self.health_snapshot(),
+                type_: "health_snapshot".to_string(),
+                event_id: Uuid::new_v4(),
+                correlation_id: None,
+            }));
+    }
+
+    pub fn spawn_flusher(self: &Arc<Self>) -> JoinHandle<()> {
+        let monitor = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(monitor.debounce);
+            loop {
+                interval.tick().await;
+                monitor.flush_pending();
+                monitor.check_outages();
+            }
+        })
+    }
+
+    fn take_pending(&self, tracked: &mut TrackedStatus) -> Option<ConnectionStatusNotification> {
+        let key = (tracked.current.state, tracked.current.attempt);
+        if tracked.emitted == Some(key) {
+            return None;
+        }
+        if let Some(last_emit) = tracked.last_emit {
+            if last_emit.elapsed() < self.debounce {
+                return None;
+            }
+        }
+
+        tracked.emitted = Some(key);
+        tracked.last_emit = Some(Instant::now());
+
+        Some(ConnectionStatusNotification {
+            data: tracked.current.clone(),
+            type_: "connection_status".to_string(),
+            event_id: Uuid::new_v4(),
+            correlation_id: None,
+        })
+    }
+
+    fn emit(&self, notification: ConnectionStatusNotification) {
+        info!(
+            "Connection status: {:?} is {:?}",
+            notification.data.connection_type, notification.data.state
+        );
+        self.event_system
+            .emit(Event::ConnectionStatus(notification));
+        self.emit_health_snapshot();
+    }
+
+    fn emit_alert(&self, alert: ConnectionAlert) {
+        match alert.kind {
+            ConnectionAlertKind::Down => warn!(
+                "{:?} connection down for {}s",
+                alert.connection_type, alert.outage_secs
+            ),
+            ConnectionAlertKind::Recovered => info!(
+                "{:?} connection recovered after {}s",
+                alert.connection_type, alert.outage_secs
+            ),
+        }
+        self.event_system
+            .emit(Event::ConnectionAlert(ConnectionAlertNotification {
+                data: alert,
+                type_: "connection_alert".to_string(),
+                event_id: Uuid::new_v4(),
+                correlation_id: None,
+            }));
+    }
+}
+
+// Critical connections that aren't connected, or haven't reported yet, take
+// the system down. Any other connection that isn't connected degrades it
+fn combined_status(statuses: &[ConnectionStatus]) -> OverallStatus {
+    let critical_down = ConnectionType::ALL
+        .iter()
+        .filter(|connection_type| connection_type.is_critical())
+        .any(|connection_type| {
+            !statuses.iter().any(|status| {
+                status.connection_type == *connection_type
+                    && status.state == ConnectionState::Connected
+            })
+        });
+    if critical_down {
+        return OverallStatus::Down;
+    }
+
+    if statuses
+        .iter()
+        .all(|status| status.state == ConnectionState::Connected)
+    {
+        OverallStatus::Healthy
+    } else {
+        OverallStatus::Degraded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast::Receiver;
+
+    fn monitor(alert_after: Duration) -> (ConnectionMonitor, Receiver<Event>) {
+        let event_system = Arc::new(EventSystem::new());
+        let events = event_system.subscribe();
+        let monitor = ConnectionMonitor::new(event_system, Duration::ZERO)
+            .with_alert_threshold(ConnectionType::WebSocket, alert_after);
+        (monitor, events)
+    }
+
+    fn alerts(events: &mut Receiver<Event>) -> Vec<ConnectionAlertKind> {
+        let mut kinds = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let Event::ConnectionAlert(notification) = event {
+                assert_eq!(notification.data.connection_type, ConnectionType::WebSocket);
+                kinds.push(notification.data.kind);
+            }
+        }
+        kinds
+    }
+
+    #[test]
+    fn short_outage_raises_no_alert() {
+        let (monitor, mut events) = monitor(Duration::from_secs(3600));
+
+        monitor.update(ConnectionType::WebSocket, ConnectionState::Connected, None);
+        monitor.update(
+            ConnectionType::WebSocket,
+            ConnectionState::Disconnected,
+            None,
+        );
+        monitor.check_outages();
+        monitor.update(ConnectionType::WebSocket, ConnectionState::Connected, None);
+
+        assert!(alerts(&mut events).is_empty());
+    }
+
+    #[test]
+    fn alerts_once_per_outage_and_on_recovery() {
+        let (monitor, mut events) = monitor(Duration::ZERO);
+
+        monitor.update(
+            ConnectionType::WebSocket,
+            ConnectionState::Disconnected,
+            None,
+        );
+        monitor.check_outages();
+        // Reconnect attempts are part of the same outage
+        monitor.reconnecting(ConnectionType::WebSocket, 1, Some("timeout".to_string()));
+        monitor.check_outages();
+        assert_eq!(alerts(&mut events), vec![ConnectionAlertKind::Down]);
+
+        monitor.update(ConnectionType::WebSocket, ConnectionState::Connected, None);
+        monitor.check_outages();
+        assert_eq!(alerts(&mut events), vec![ConnectionAlertKind::Recovered]);
+    }
+
+    #[test]
+    fn a_new_outage_alerts_again() {
+        let (monitor, mut events) = monitor(Duration::ZERO);
+
+        for _ in 0..2 {
+            monitor.update(
+                ConnectionType::WebSocket,
+                ConnectionState::Disconnected,
+                None,
+            );
+            monitor.check_outages();
+            monitor.update(ConnectionType::WebSocket, ConnectionState::Connected, None);
+        }
+
+        assert_eq!(
+            alerts(&mut events),
+            vec![
+                ConnectionAlertKind::Down,
+                ConnectionAlertKind::Recovered,
+                ConnectionAlertKind::Down,
+                ConnectionAlertKind::Recovered,
+            ]
+        );
+    }
+}
@@ -1,8 +1,22 @@
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
+use uuid::Uuid;
 
 use crate::models::{
-    CopyTradeNotification, TrackedWalletNotification, TransactionLoggedNotification,
-    WalletUpdateNotification,
+    ConnectionAlertNotification, ConnectionStatusNotification, ControlCommand,
+    CopyTradeNotification, CopyTradingBreakerNotification, DatabaseOperationNotification,
+    DatabaseQueryNotification, DustCleanupNotification, ErrorInfo, ErrorNotification,
+    FetcherStatsNotification, HealthSnapshotNotification, InsufficientBalanceNotification,
+    MonitorHealthNotification, PortfolioNotification, QueueStatsNotification,
+    SubscriptionFailureNotification, TrackedWalletNotification, TradingSwitchNotification,
+    TransactionLoggedNotification, TransactionStateNotification, UnsupportedVenueNotification,
+    WalletStateNotification, WalletStatsNotification, WalletUpdateNotification,
 };
 
 #[derive(Clone)]
@@ -11,25 +25,201 @@ pub enum Event {
     CopyTradeExecution(CopyTradeNotification),
     WalletUpdate(WalletUpdateNotification),
     TransactionLogged(TransactionLoggedNotification),
+    InsufficientBalance(InsufficientBalanceNotification),
+    WalletStateChange(WalletStateNotification),
+    ControlCommand(ControlCommand),
+    TradingSwitch(TradingSwitchNotification),
+    CopyTradingBreaker(CopyTradingBreakerNotification),
+    QueueStats(QueueStatsNotification),
+    UnsupportedVenue(UnsupportedVenueNotification),
+    FetcherStats(FetcherStatsNotification),
+    ConnectionStatus(ConnectionStatusNotification),
+    ConnectionAlert(ConnectionAlertNotification),
+    HealthSnapshot(HealthSnapshotNotification),
+    SubscriptionFailure(SubscriptionFailureNotification),
+    MonitorHealth(MonitorHealthNotification),
+    DatabaseOperation(DatabaseOperationNotification),
+    DatabaseQuery(DatabaseQueryNotification),
+    WalletStats(WalletStatsNotification),
+    Portfolio(PortfolioNotification),
+    DustCleanup(DustCleanupNotification),
+    TransactionState(TransactionStateNotification),
+    Error(ErrorNotification),
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    TrackedWalletTransaction,
+    CopyTradeExecution,
+    WalletUpdate,
+    TransactionLogged,
+    InsufficientBalance,
+    WalletStateChange,
+    ControlCommand,
+    TradingSwitch,
+    CopyTradingBreaker,
+    QueueStats,
+    UnsupportedVenue,
+    FetcherStats,
+    ConnectionStatus,
+    ConnectionAlert,
+    HealthSnapshot,
+    SubscriptionFailure,
+    MonitorHealth,
+    DatabaseOperation,
+    DatabaseQuery,
+    WalletStats,
+    Portfolio,
+    DustCleanup,
+    TransactionState,
+    Error,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::TrackedWalletTransaction(_) => EventKind::TrackedWalletTransaction,
+            Event::CopyTradeExecution(_) => EventKind::CopyTradeExecution,
+            Event::WalletUpdate(_) => EventKind::WalletUpdate,
+            Event::TransactionLogged(_) => EventKind::TransactionLogged,
+            Event::InsufficientBalance(_) => EventKind::InsufficientBalance,
+            Event::WalletStateChange(_) => EventKind::WalletStateChange,
+            Event::ControlCommand(_) => EventKind::ControlCommand,
+            Event::TradingSwitch(_) => EventKind::TradingSwitch,
+            Event::CopyTradingBreaker(_) => EventKind::CopyTradingBreaker,
+            Event::QueueStats(_) => EventKind::QueueStats,
+            Event::UnsupportedVenue(_) => EventKind::UnsupportedVenue,
+            Event::FetcherStats(_) => EventKind::FetcherStats,
+            Event::ConnectionStatus(_) => EventKind::ConnectionStatus,
+            Event::ConnectionAlert(_) => EventKind::ConnectionAlert,
+            Event::HealthSnapshot(_) => EventKind::HealthSnapshot,
+            Event::SubscriptionFailure(_) => EventKind::SubscriptionFailure,
+            Event::MonitorHealth(_) => EventKind::MonitorHealth,
+            Event::DatabaseOperation(_) => EventKind::DatabaseOperation,
+            Event::DatabaseQuery(_) => EventKind::DatabaseQuery,
+            Event::WalletStats(_) => EventKind::WalletStats,
+            Event::Portfolio(_) => EventKind::Portfolio,
+            Event::DustCleanup(_) => EventKind::DustCleanup,
+            Event::TransactionState(_) => EventKind::TransactionState,
+            Event::Error(_) => EventKind::Error,
+        }
+    }
+}
+
+// Receives only the requested kinds, the rest are skipped inside recv so the
+// consumer's select loop never wakes for them
+pub struct FilteredReceiver {
+    receiver: broadcast::Receiver<Event>,
+    kinds: Vec<EventKind>,
+    dropped: Arc<AtomicU64>,
+    total_dropped: Arc<AtomicU64>,
+}
+
+impl FilteredReceiver {
+    pub async fn recv(&mut self) -> Result<Event, RecvError> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.kinds.contains(&event.kind()) => return Ok(event),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    self.dropped.fetch_add(skipped, Ordering::Relaxed);
+                    self.total_dropped.fetch_add(skipped, Ordering::Relaxed);
+                    return Err(RecvError::Lagged(skipped));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Events lost because this receiver lagged behind the channel
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+const DEFAULT_REPLAY_CAPACITY: usize = 500;
+
+#[derive(Clone)]
+pub struct SequencedEvent {
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub event: Event,
+}
+
 pub struct EventSystem {
     sender: broadcast::Sender<Event>,
+    dropped: Arc<AtomicU64>,
+    // Recent events kept so late subscribers can catch up on what they missed
+    replay: Mutex<VecDeque<SequencedEvent>>,
+    replay_capacity: usize,
+    next_seq: AtomicU64,
 }
 
 impl EventSystem {
     pub fn new() -> Self {
+        Self::with_replay_capacity(DEFAULT_REPLAY_CAPACITY)
+    }
+
+    pub fn with_replay_capacity(replay_capacity: usize) -> Self {
         let (sender, _) = broadcast::channel(100);
-        Self { sender }
+        Self {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+            replay: Mutex::new(VecDeque::with_capacity(replay_capacity)),
+            replay_capacity,
+            next_seq: AtomicU64::new(1),
+        }
     }
 
     pub fn subscribe(&self) -> broadcast::Receiver<Event> {
         self.sender.subscribe()
     }
 
+    pub fn subscribe_filtered(&self, kinds: &[EventKind]) -> FilteredReceiver {
+        FilteredReceiver {
+            receiver: self.sender.subscribe(),
+            kinds: kinds.to_vec(),
+            dropped: Arc::new(AtomicU64::new(0)),
+            total_dropped: Arc::clone(&self.dropped),
+        }
+    }
+
+    // Total events dropped across all filtered receivers
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
     pub fn emit(&self, event: Event) {
+        if self.replay_capacity > 0 {
+            let mut replay = self.replay.lock();
+            if replay.len() >= self.replay_capacity {
+                replay.pop_front();
+            }
+            replay.push_back(SequencedEvent {
+                seq: self.next_seq.fetch_add(1, Ordering::Relaxed),
+                timestamp: Utc::now(),
+                event: event.clone(),
+            });
+        }
+
         let _ = self.sender.send(event);
     }
 
+    // Events with a sequence id greater than `seq`, oldest first. Anything older
+    // than the buffer capacity is gone
+    pub fn events_since(&self, seq: u64) -> Vec<SequencedEvent> {
+        self.replay
+            .lock()
+            .iter()
+            .filter(|event| event.seq > seq)
+            .cloned()
+            .collect()
+    }
+
+    pub fn last_sequence(&self) -> u64 {
+        self.next_seq.load(Ordering::Relaxed) - 1
+    }
+
     pub async fn handle_transaction_logged(&self, notification: TransactionLoggedNotification) {
         self.emit(Event::TransactionLogged(notification));
     }
@@ -39,11 +229,69 @@ impl EventSystem {
     }
 
     pub async fn handle_tracked_wallet_trade(&self, notification: TrackedWalletNotification) {
-        println!("Handling tracked wallet trade notification...");
-        println!("{:?}", notification);
+        info!("Handling tracked wallet trade notification...");
+        info!("{:?}", notification);
         self.emit(Event::TrackedWalletTransaction(notification));
     }
 
+    pub async fn handle_insufficient_balance(&self, notification: InsufficientBalanceNotification) {
+        self.emit(Event::InsufficientBalance(notification));
+    }
+
+    pub async fn handle_error(&self, notification: ErrorNotification) {
+        self.emit(Event::Error(notification));
+    }
+
+    // Shorthand for error paths that only have the error at hand
+    pub fn report_error(&self, error: ErrorInfo, correlation_id: Option<String>) {
+        self.emit(Event::Error(ErrorNotification {
+            data: error,
+            type_: "error".to_string(),
+            event_id: Uuid::new_v4(),
+            correlation_id,
+        }));
+    }
+
+    pub async fn handle_wallet_state_change(&self, notification: WalletStateNotification) {
+        self.emit(Event::WalletStateChange(notification));
+    }
+
+    pub async fn handle_control_command(&self, command: ControlCommand) {
+        self.emit(Event::ControlCommand(command));
+    }
+
+    pub async fn handle_queue_stats(&self, notification: QueueStatsNotification) {
+        self.emit(Event::QueueStats(notification));
+    }
+
+    pub async fn handle_unsupported_venue(&self, notification: UnsupportedVenueNotification) {
+        self.emit(Event::UnsupportedVenue(notification));
+    }
+
+    pub async fn handle_fetcher_stats(&self, notification: FetcherStatsNotification) {
+        self.emit(Event::FetcherStats(notification));
+    }
+
+    pub async fn handle_subscription_failure(&self, notification: SubscriptionFailureNotification) {
+        self.emit(Event::SubscriptionFailure(notification));
+    }
+
+    pub async fn handle_monitor_health(&self, notification: MonitorHealthNotification) {
+        self.emit(Event::MonitorHealth(notification));
+    }
+
+    pub async fn handle_database_query(&self, notification: DatabaseQueryNotification) {
+        self.emit(Event::DatabaseQuery(notification));
+    }
+
+    pub async fn handle_wallet_stats(&self, notification: WalletStatsNotification) {
+        self.emit(Event::WalletStats(notification));
+    }
+
+    pub async fn handle_transaction_state(&self, notification: TransactionStateNotification) {
+        self.emit(Event::TransactionState(notification));
+    }
+
     pub async fn handle_wallet_updated(&self, notification: WalletUpdateNotification) {
         self.emit(Event::WalletUpdate(notification));
     }
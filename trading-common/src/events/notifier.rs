@@ -0,0 +1,388 @@
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::event_system::{Event, EventKind, EventSystem, FilteredReceiver};
+use crate::metrics::metrics;
+use crate::models::{ConnectionAlertKind, CopyTradingBreakerState, ErrorSeverity, TransactionType};
+
+const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+// Discord rejects longer messages, Telegram allows 4096
+const MAX_MESSAGE_LENGTH: usize = 1_900;
+const MAX_DIGEST_LINES: usize = 20;
+const QUEUE_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct NotifierConfig {
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    pub discord_webhook_url: Option<String>,
+    pub notify_copy_trades: bool,
+    pub notify_errors: bool,
+    pub notify_insufficient_balance: bool,
+    // Connection alerts fire after the ConnectionMonitor's per-connection thresholds
+    pub notify_connection_loss: bool,
+    // Messages arriving within this long of the last delivery are collapsed into a digest
+    pub min_interval: Duration,
+    pub max_retries: u32,
+}
+
+impl Default for NotifierConfig {
+    fn default() -> Self {
+        Self {
+            telegram_bot_token: None,
+            telegram_chat_id: None,
+            discord_webhook_url: None,
+            notify_copy_trades: true,
+            notify_errors: true,
+            notify_insufficient_balance: true,
+            notify_connection_loss: true,
+            min_interval: Duration::from_secs(10),
+            max_retries: 3,
+        }
+    }
+}
+
+impl NotifierConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let non_empty = |key: &str| std::env::var(key).ok().filter(|v| !v.is_empty());
+        let flag = |key: &str, default: bool| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<bool>().ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            telegram_bot_token: non_empty("TELEGRAM_BOT_TOKEN"),
+            telegram_chat_id: non_empty("TELEGRAM_CHAT_ID"),
+            discord_webhook_url: non_empty("DISCORD_WEBHOOK_URL"),
+            notify_copy_trades: flag("NOTIFY_COPY_TRADES", default.notify_copy_trades),
+            notify_errors: flag("NOTIFY_ERRORS", default.notify_errors),
+            notify_insufficient_balance: flag(
+                "NOTIFY_INSUFFICIENT_BALANCE",
+                default.notify_insufficient_balance,
+            ),
+            notify_connection_loss: flag("NOTIFY_CONNECTION_LOSS", default.notify_connection_loss),
+            min_interval: std::env::var("NOTIFY_MIN_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default.min_interval),
+            max_retries: std::env::var("NOTIFY_MAX_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.max_retries),
+        }
+    }
+
+    fn channels(&self) -> Vec<Channel> {
+        let mut channels = Vec::new();
+        if let (Some(token), Some(chat_id)) = (&self.telegram_bot_token, &self.telegram_chat_id) {
+            channels.push(Channel::Telegram {
+                token: token.clone(),
+                chat_id: chat_id.clone(),
+            });
+        }
+        if let Some(url) = &self.discord_webhook_url {
+            channels.push(Channel::Discord { url: url.clone() });
+        }
+        channels
+    }
+
+    fn kinds(&self) -> Vec<EventKind> {
+        let mut kinds = Vec::new();
+        if self.notify_copy_trades {
+            kinds.push(EventKind::CopyTradeExecution);
+        }
+        if self.notify_errors {
+            kinds.push(EventKind::Error);
+            kinds.push(EventKind::CopyTradingBreaker);
+            kinds.push(EventKind::TradingSwitch);
+        }
+        if self.notify_insufficient_balance {
+            kinds.push(EventKind::InsufficientBalance);
+        }
+        if self.notify_connection_loss {
+            kinds.push(EventKind::ConnectionAlert);
+        }
+        kinds
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Channel {
+    Telegram { token: String, chat_id: String },
+    Discord { url: String },
+}
+
+impl Channel {
+    fn name(&self) -> &'static str {
+        match self {
+            Channel::Telegram { .. } => "telegram",
+            Channel::Discord { .. } => "discord",
+        }
+    }
+}
+
+// Pushes selected events to Telegram and/or Discord. Events are formatted on
+// a reader task and handed to the delivery task through a bounded queue, so a
+// slow or failing chat API only ever drops notifications, never trading
+// events. Returns None when no channel is configured
+pub fn spawn_notifier(
+    config: NotifierConfig,
+    event_system: Arc<EventSystem>,
+) -> Option<JoinHandle<()>> {
+    let channels = config.channels();
+    if channels.is_empty() {
+        return None;
+    }
+    info!(
+        "Notifier delivering to {:?}",
+        channels.iter().map(Channel::name).collect::<Vec<_>>()
+    );
+
+    let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+    let events = event_system.subscribe_filtered(&config.kinds());
+    tokio::spawn(run_reader(events, sender));
+    Some(tokio::spawn(run_delivery(config, channels, receiver)))
+}
+
+async fn run_reader(mut events: FilteredReceiver, sender: mpsc::Sender<String>) {
+    loop {
+        let message = match events.recv().await {
+            Ok(event) => format_event(&event),
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Notifier missed {} events", skipped);
+                None
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        if let Some(message) = message {
+            enqueue(&sender, message);
+        }
+    }
+}
+
+fn enqueue(sender: &mpsc::Sender<String>, message: String) {
+    if sender.try_send(message).is_err() {
+        if let Some(metrics) = metrics() {
+            metrics
+                .notification_failures
+                .with_label_values(&["queue"])
+                .inc();
+        }
+    }
+}
+
+fn format_event(event: &Event) -> Option<String> {
+    match event {
+        Event::CopyTradeExecution(notification) => {
+            let trade = &notification.data;
+            let action = match trade.transaction_type {
+                TransactionType::Buy => "bought",
+                TransactionType::Sell => "sold",
+                _ => return None,
+            };
+            let tracked_wallet = match &trade.tracked_wallet_label {
+                Some(label) => format!("{} ({})", label, trade.tracked_wallet_address),
+                None => trade.tracked_wallet_address.clone(),
+            };
+            let mut message = format!(
+                "💱 Copied {} {} {} for {:.4} SOL on {:?}\nTracked wallet: {}\nhttps://solscan.io/tx/{}",
+                action,
+                trade.amount_token,
+                trade.token_symbol,
+                trade.amount_sol,
+                trade.dex_type,
+                tracked_wallet,
+                trade.signature
+            );
+            if let Some(slippage) = &notification.slippage {
+                message.push_str(&format!("\nSlippage: {:.2}%", slippage.slippage * 100.0));
+            }
+            Some(message)
+        }
+        Event::Error(notification) if notification.data.severity == ErrorSeverity::Error => {
+            Some(format!(
+                "⚠️ {} error ({}): {}",
+                notification.data.source, notification.data.code, notification.data.message
+            ))
+        }
+        Event::InsufficientBalance(notification) => Some(format!(
+            "💸 Skipped {}: balance {:.4} SOL, needs {:.4} SOL",
+            notification.data.token_symbol,
+            notification.data.current_balance_sol,
+            notification.data.required_balance_sol
+        )),
+        Event::ConnectionAlert(notification) => {
+            let alert = &notification.data;
+            Some(match alert.kind {
+                ConnectionAlertKind::Down => {
+                    let mut message = format!(
+                        "🔴 {:?} connection down for {}s",
+                        alert.connection_type, alert.outage_secs
+                    );
+                    if let Some(error) = &alert.error {
+                        message.push_str(&format!("\n{}", error));
+                    }
+                    message
+                }
+                ConnectionAlertKind::Recovered => format!(
+                    "✅ {:?} connection restored after {}s",
+                    alert.connection_type, alert.outage_secs
+                ),
+            })
+        }
+        // Tripping is reported as an error, only the automatic resume needs its own message
+        Event::CopyTradingBreaker(notification)
+            if notification.data.state == CopyTradingBreakerState::Reset =>
+        {
+            Some("▶️ Copy trading resumed after the failure cool-down".to_string())
+        }
+        Event::TradingSwitch(notification) => Some(if notification.data.halted {
+            format!(
+                "⛔ Trading halted: {}",
+                notification
+                    .data
+                    .reason
+                    .as_deref()
+                    .unwrap_or("no reason given")
+            )
+        } else {
+            "▶️ Trading resumed".to_string()
+        }),
+        _ => None,
+    }
+}
+
+async fn run_delivery(
+    config: NotifierConfig,
+    channels: Vec<Channel>,
+    mut receiver: mpsc::Receiver<String>,
+) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default();
+    let mut pending = Vec::new();
+    let mut last_sent: Option<Instant> = None;
+    let mut flush_check = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        tokio::select! {
+            message = receiver.recv() => match message {
+                Some(message) => pending.push(message),
+                None => break,
+            },
+            _ = flush_check.tick() => {}
+        }
+
+        let rate_limited = matches!(last_sent, Some(sent) if sent.elapsed() < config.min_interval);
+        if pending.is_empty() || rate_limited {
+            continue;
+        }
+
+        let text = digest(std::mem::take(&mut pending));
+        for channel in &channels {
+            deliver(&client, channel, &text, config.max_retries).await;
+        }
+        last_sent = Some(Instant::now());
+    }
+}
+
+// A burst collapses into one message listing what happened
+fn digest(mut messages: Vec<String>) -> String {
+    let text = if messages.len() == 1 {
+        messages.remove(0)
+    } else {
+        let total = messages.len();
+        let mut text = format!("{} notifications:", total);
+        for message in messages.iter().take(MAX_DIGEST_LINES) {
+            text.push_str("\n• ");
+            text.push_str(&message.replace('\n', " | "));
+        }
+        if total > MAX_DIGEST_LINES {
+            text.push_str(&format!("\n…and {} more", total - MAX_DIGEST_LINES));
+        }
+        text
+    };
+
+    match text.char_indices().nth(MAX_MESSAGE_LENGTH) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+async fn deliver(client: &reqwest::Client, channel: &Channel, text: &str, max_retries: u32) {
+    let (url, body) = match channel {
+        Channel::Telegram { token, chat_id } => (
+            format!("{}/bot{}/sendMessage", TELEGRAM_API_URL, token),
+            json!({ "chat_id": chat_id, "text": text, "disable_web_page_preview": true }),
+        ),
+        Channel::Discord { url } => (url.clone(), json!({ "content": text })),
+    };
+
+    let mut attempt = 0;
+    loop {
+        let result = client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await;
+
+        // Transport errors, rate limits and server errors are worth another try
+        let error = match result {
+            Ok(response) if response.status().is_success() => {
+                if let Some(metrics) = metrics() {
+                    metrics
+                        .notifications_sent
+                        .with_label_values(&[channel.name()])
+                        .inc();
+                }
+                return;
+            }
+            Ok(response) => {
+                let status = response.status();
+                let retryable =
+                    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+                (format!("HTTP {}", status), retryable)
+            }
+            // The URL holds the bot token or webhook secret, keep it out of the logs
+            Err(e) => (e.without_url().to_string(), true),
+        };
+
+        match error {
+            (error, true) if attempt < max_retries => {
+                attempt += 1;
+                let delay = Duration::from_secs(1 << attempt.min(5));
+                warn!(
+                    "{} notification failed ({}), retry {} of {} in {:?}",
+                    channel.name(),
+                    error,
+                    attempt,
+                    max_retries,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+            }
+            (error, _) => {
+                warn!("{} notification failed: {}", channel.name(), error);
+                if let Some(metrics) = metrics() {
+                    metrics
+                        .notification_failures
+                        .with_label_values(&[channel.name()])
+                        .inc();
+                }
+                return;
+            }
+        }
+    }
+}
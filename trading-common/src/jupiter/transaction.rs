@@ -0,0 +1,12 @@
+use crate::utils::transaction::extract_swap_from_balance_changes;
+use crate::TransactionType;
+use anyhow::Result;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+// Multi-hop routes (e.g. SOL -> USDC -> token) net out to the SOL spent and
+// token received by the signer
+pub fn extract_transaction_details(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<(TransactionType, String, f64, f64, f64)> {
+    extract_swap_from_balance_changes(transaction, "Jupiter")
+}
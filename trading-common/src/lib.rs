@@ -1,22 +1,45 @@
 pub mod constants;
 pub mod database;
 pub mod error;
+pub mod jupiter;
 pub mod models;
+pub mod orca;
 pub mod pumpdotfun;
+pub mod pumpswap;
 pub mod raydium;
+pub mod token_metadata;
 pub mod websocket;
 pub mod utils {
+    pub mod amounts;
+    pub mod chunked_execution;
+    pub mod compute_budget;
+    pub mod confirmation_tracker;
     pub mod copy_trade;
     pub mod data;
     pub mod dex;
+    pub mod health;
+    pub mod kill_switch;
+    pub mod logging;
+    pub mod metrics;
+    pub mod priority_fee;
+    pub mod query_latency;
+    pub mod rpc;
+    pub mod send;
+    pub mod simulation;
+    pub mod spend_guard;
     pub mod transaction;
+    pub mod transaction_fetcher;
+    pub mod transaction_log_writer;
+    pub mod wallet_stats;
 }
 pub mod wallet {
     pub mod server_wallet_manager;
 }
 
 pub mod events {
+    pub mod connection_monitor;
     pub mod event_system;
+    pub mod notifier;
 }
 
 pub use constants::{
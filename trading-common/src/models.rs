@@ -1,8 +1,18 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Instant;
 use uuid::Uuid;
 
+use crate::amounts::{fraction_to_bps, sol_to_lamports};
 use crate::dex::DexType;
+use crate::error::AppError;
+
+pub const MAX_ALLOWED_TOKENS: usize = 100;
+pub const MAX_WALLET_LABEL_LEN: usize = 64;
+pub const MAX_WALLET_NOTES_LEN: usize = 2_000;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum TransactionType {
@@ -29,6 +39,32 @@ pub struct ClientTxInfo {
     pub seller: String,
     pub buyer: String,
     pub dex_type: DexType,
+    #[serde(default)]
+    pub tracked_wallet_id: Option<Uuid>,
+    #[serde(default)]
+    pub tracked_wallet_address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracked_wallet_label: Option<String>,
+    #[serde(default)]
+    pub slot: u64,
+    // Pool or bonding curve the swap went through, None for routed swaps and transfers
+    #[serde(default)]
+    pub pool_address: Option<String>,
+    #[serde(default)]
+    pub fee_lamports: u64,
+    // Fee paid above the base signature fee
+    #[serde(default)]
+    pub priority_fee_lamports: Option<u64>,
+    // Slots the websocket feed was behind the RPC tip when this was received
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot_lag: Option<u64>,
+    // When the signature arrived on the websocket, only meaningful in process
+    #[serde(skip)]
+    pub observed_at: Option<Instant>,
+    // Replayed by a backfill rather than received live. Too old to copy is
+    // still recorded, only the copy itself is skipped
+    #[serde(default)]
+    pub backfilled: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -43,10 +79,45 @@ pub struct TrackedWallet {
     pub user_id: Option<String>,
     pub wallet_address: String,
     pub is_active: bool,
+    // Shown in notifications instead of the address
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+impl TrackedWallet {
+    // Trims label and notes, blank values are stored as None
+    pub fn validate(&mut self) -> Result<(), AppError> {
+        let mut errors = Vec::new();
+
+        for (field, value, max_len) in [
+            ("label", &mut self.label, MAX_WALLET_LABEL_LEN),
+            ("notes", &mut self.notes, MAX_WALLET_NOTES_LEN),
+        ] {
+            *value = value
+                .as_deref()
+                .map(str::trim)
+                .filter(|trimmed| !trimmed.is_empty())
+                .map(str::to_string);
+            if value.as_ref().is_some_and(|v| v.chars().count() > max_len) {
+                errors.push(FieldError::new(
+                    field,
+                    format!("At most {} characters are allowed", max_len),
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::ValidationError(errors))
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CopyTradeSettings {
     pub id: Option<Uuid>,
@@ -62,6 +133,14 @@ pub struct CopyTradeSettings {
     pub allow_additional_buys: bool,
     pub match_sell_percentage: bool,
     pub min_sol_balance: f64,
+    #[serde(default)]
+    pub route_unsupported_venues: bool,
+    // Token transfers of tracked wallets smaller than this are ignored
+    #[serde(default)]
+    pub min_transfer_amount: f64,
+    // Sends copies without simulating them first, trading safety for latency
+    #[serde(default)]
+    pub skip_simulation: bool,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
@@ -81,12 +160,74 @@ impl Default for CopyTradeSettings {
             allow_additional_buys: false,
             match_sell_percentage: false,
             min_sol_balance: 0.01,
+            route_unsupported_venues: false,
+            min_transfer_amount: 0.0,
+            skip_simulation: false,
             created_at: None,
             updated_at: None,
         }
     }
 }
 
+impl CopyTradeSettings {
+    // Trims allowed_tokens entries and checks each is a distinct token address,
+    // so the allowlist compares against what the parser produces
+    pub fn validate(&mut self) -> Result<(), AppError> {
+        let mut errors = Vec::new();
+
+        if let Some(tokens) = self.allowed_tokens.as_mut() {
+            if tokens.len() > MAX_ALLOWED_TOKENS {
+                errors.push(FieldError::new(
+                    "allowed_tokens",
+                    format!("At most {} tokens are allowed", MAX_ALLOWED_TOKENS),
+                ));
+            }
+
+            let mut seen = HashSet::new();
+            for (index, token) in tokens.iter_mut().enumerate() {
+                let field = format!("allowed_tokens[{}]", index);
+                let trimmed = token.trim();
+                match Pubkey::from_str(trimmed) {
+                    Ok(pubkey) => {
+                        *token = pubkey.to_string();
+                        if !seen.insert(pubkey) {
+                            errors.push(FieldError::new(
+                                &field,
+                                format!("Duplicate token {}", token),
+                            ));
+                        }
+                    }
+                    Err(_) => errors.push(FieldError::new(
+                        &field,
+                        format!("Invalid token address: {}", trimmed),
+                    )),
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::ValidationError(errors))
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: String) -> Self {
+        Self {
+            field: field.to_string(),
+            message,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TransactionLog {
     pub id: Uuid,
@@ -100,11 +241,247 @@ pub struct TransactionLog {
     pub timestamp: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub limit: usize,
+    // Opaque cursor from the previous page, None starts at the beginning
+    pub cursor: Option<String>,
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        Self {
+            limit: 100,
+            cursor: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TransactionLogFilter {
+    pub tracked_wallet_id: Option<Uuid>,
+    pub token_address: Option<String>,
+    pub transaction_type: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub page: PageRequest,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeSource {
+    Manual,
+    Copy,
+}
+
+impl TradeSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TradeSource::Manual => "manual",
+            TradeSource::Copy => "copy",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeStatus {
+    Pending,
+    Success,
+    Failed,
+    // The executor decided not to trade, e.g. nothing held to sell
+    Skipped,
+}
+
+impl TradeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TradeStatus::Pending => "pending",
+            TradeStatus::Success => "success",
+            TradeStatus::Failed => "failed",
+            TradeStatus::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TradeExecution {
+    pub id: Uuid,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    pub trade_type: TradeSource,
+    pub dex_type: DexType,
+    // Venue of the copied transaction when the copy was rerouted to another one
+    #[serde(default)]
+    pub signal_dex_type: Option<DexType>,
+    pub transaction_type: TransactionType,
+    pub token_address: String,
+    pub tracked_wallet_id: Option<Uuid>,
+    pub amount_sol: f64,
+    pub amount_token: f64,
+    pub signature: Option<String>,
+    // Every transaction of a chunked trade, signature is the last of them
+    #[serde(default)]
+    pub child_signatures: Vec<String>,
+    // Intermediate route the trade took, None for a direct swap
+    #[serde(default)]
+    pub route: Option<String>,
+    pub status: TradeStatus,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TradeExecution {
+    pub fn pending(
+        trade_type: TradeSource,
+        dex_type: DexType,
+        transaction_type: TransactionType,
+        token_address: &str,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            user_id: None,
+            trade_type,
+            dex_type,
+            signal_dex_type: None,
+            transaction_type,
+            token_address: token_address.to_string(),
+            tracked_wallet_id: None,
+            amount_sol: 0.0,
+            amount_token: 0.0,
+            signature: None,
+            child_signatures: Vec::new(),
+            route: None,
+            status: TradeStatus::Pending,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn succeeded(&mut self, signature: &str, amount_sol: f64, amount_token: f64) {
+        self.signature = Some(signature.to_string());
+        self.amount_sol = amount_sol;
+        self.amount_token = amount_token;
+        self.status = TradeStatus::Success;
+        self.error = None;
+    }
+
+    pub fn failed(&mut self, error: String) {
+        self.status = TradeStatus::Failed;
+        self.error = Some(error);
+    }
+
+    pub fn skipped(&mut self, reason: String) {
+        self.status = TradeStatus::Skipped;
+        self.error = Some(reason);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TradeExecutionFilter {
+    pub tracked_wallet_id: Option<Uuid>,
+    pub token_address: Option<String>,
+    pub status: Option<TradeStatus>,
+    pub trade_type: Option<TradeSource>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub page: PageRequest,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Watchlist {
+    pub id: Option<Uuid>,
+    pub user_id: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WatchlistToken {
+    pub id: Option<Uuid>,
+    pub watchlist_id: Uuid,
+    pub token_address: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WatchlistWithTokens {
+    #[serde(flatten)]
+    pub watchlist: Watchlist,
+    // Embedded by PostgREST under the related table's name
+    #[serde(rename = "watchlist_tokens", default)]
+    pub tokens: Vec<WatchlistToken>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WatchlistRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WatchlistTokenRequest {
+    pub token_address: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventAuditRecord {
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub correlation_id: Option<String>,
+    pub payload: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MonitorCursor {
+    pub wallet_address: String,
+    pub last_signature: String,
+    pub last_slot: u64,
+    pub last_block_time: i64,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// Every notification carries a unique event_id. correlation_id ties together
+// notifications produced for the same source, usually a transaction signature,
+// so consumers can drop duplicates
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CopyTradeNotification {
     pub data: ClientTxInfo,
     #[serde(rename = "type")]
     pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slippage: Option<SlippageReport>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub child_signatures: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnsupportedVenueNotification {
+    pub data: ClientTxInfo,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -112,6 +489,39 @@ pub struct TrackedWalletNotification {
     pub data: ClientTxInfo,
     #[serde(rename = "type")]
     pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum TransactionState {
+    Submitted,
+    Confirmed,
+    Finalized,
+    Failed,
+    Dropped,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransactionStateChange {
+    pub signature: String,
+    pub state: TransactionState,
+    pub previous_state: Option<TransactionState>,
+    pub slot: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransactionStateNotification {
+    pub data: TransactionStateChange,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -119,6 +529,10 @@ pub struct TransactionLoggedNotification {
     pub data: TransactionLog,
     #[serde(rename = "type")]
     pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -126,6 +540,520 @@ pub struct WalletUpdateNotification {
     pub data: serde_json::Value,
     #[serde(rename = "type")]
     pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Position {
+    pub token_address: String,
+    pub amount_token: f64,
+    // Total SOL paid for the tokens still held. Tokens received outside of
+    // trades add to the amount without adding cost
+    pub cost_basis_sol: f64,
+    pub realized_pnl_sol: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unrealized_pnl_sol: Option<f64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Position {
+    pub fn average_entry_price(&self) -> Option<f64> {
+        (self.amount_token > 0.0).then(|| self.cost_basis_sol / self.amount_token)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PortfolioSnapshot {
+    pub sol_balance: f64,
+    pub positions: Vec<Position>,
+    pub realized_pnl_sol: f64,
+    pub unrealized_pnl_sol: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PortfolioNotification {
+    pub data: PortfolioSnapshot,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum WalletStateChangeType {
+    Added,
+    Archived,
+    Unarchived,
+    Deleted,
+    // Label or notes changed, the subscription stays as it is
+    Updated,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WalletStateChange {
+    pub wallet_address: String,
+    pub change_type: WalletStateChangeType,
+    pub wallet: Option<TrackedWallet>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WalletStateNotification {
+    pub data: WalletStateChange,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueueStats {
+    pub queue_len: usize,
+    pub capacity: usize,
+    pub dropped: u64,
+    pub rejected: u64,
+    pub stale: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueueStatsNotification {
+    pub data: QueueStats,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FetcherStats {
+    pub requests: u64,
+    pub cache_hits: u64,
+    pub cache_hit_rate: f64,
+    pub deduplicated: u64,
+    pub batches: u64,
+    pub last_batch_size: u64,
+    pub avg_batch_size: f64,
+    pub rpc_errors: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FetcherStatsNotification {
+    pub data: FetcherStats,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionType {
+    WebSocket,
+    Database,
+}
+
+impl ConnectionType {
+    pub const ALL: [ConnectionType; 2] = [ConnectionType::WebSocket, ConnectionType::Database];
+
+    // Copy trading stops without a critical connection, the others only degrade it
+    pub fn is_critical(&self) -> bool {
+        match self {
+            ConnectionType::WebSocket => true,
+            ConnectionType::Database => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ConnectionState {
+    Connected,
+    Reconnecting,
+    Disconnected,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConnectionStatus {
+    pub connection_type: ConnectionType,
+    pub state: ConnectionState,
+    // Reconnect attempt while the state is Reconnecting, 0 otherwise
+    #[serde(default)]
+    pub attempt: u32,
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot_lag: Option<u64>,
+    pub since: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConnectionStatusNotification {
+    pub data: ConnectionStatus,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ConnectionAlertKind {
+    Down,
+    Recovered,
+}
+
+// Escalation of a connection that stayed down past its threshold, sent once
+// when the threshold is crossed and once when the connection comes back
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConnectionAlert {
+    pub connection_type: ConnectionType,
+    pub kind: ConnectionAlertKind,
+    pub outage_secs: u64,
+    pub down_since: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConnectionAlertNotification {
+    pub data: ConnectionAlert,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum OverallStatus {
+    Healthy,
+    Degraded,
+    Down,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HealthSnapshot {
+    pub status: OverallStatus,
+    // When the overall status last changed
+    pub since: DateTime<Utc>,
+    pub connections: Vec<ConnectionStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HealthSnapshotNotification {
+    pub data: HealthSnapshot,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DatabaseOperationStats {
+    pub operation: String,
+    pub pending: usize,
+    pub spilled: usize,
+    pub flushed: u64,
+    pub dropped: u64,
+    pub failed_batches: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DatabaseOperationNotification {
+    pub data: DatabaseOperationStats,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+// One Supabase request, timed across retries
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DatabaseQuery {
+    pub operation: String,
+    pub table: String,
+    pub duration_ms: u64,
+    pub success: bool,
+    // From the Content-Range header, only known when PostgREST reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DatabaseQueryNotification {
+    pub data: DatabaseQuery,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MonitorHealth {
+    pub rpc_slot: Option<u64>,
+    pub notification_slot: Option<u64>,
+    pub slot_lag: Option<u64>,
+    pub lagging: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MonitorHealthNotification {
+    pub data: MonitorHealth,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WalletStats {
+    pub tracked_wallet_id: Uuid,
+    pub buys_observed: u64,
+    pub sells_observed: u64,
+    pub copied_trades: u64,
+    pub closed_positions: u64,
+    pub winning_positions: u64,
+    pub win_rate: f64,
+    pub realized_pnl_sol: f64,
+    pub open_positions: usize,
+    // Open positions marked at the last price seen for the token
+    pub unrealized_pnl_sol: f64,
+    pub avg_hold_secs: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WalletStatsNotification {
+    pub data: WalletStats,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubscriptionFailure {
+    pub wallet_address: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubscriptionFailureNotification {
+    pub data: SubscriptionFailure,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ControlCommand {
+    PauseCopyTrading,
+    ResumeCopyTrading,
+    PauseAll,
+    ResumeAll,
+    CleanupDust,
+}
+
+// A command queued through the API. The bot applies pending commands in the
+// order they were queued and stamps applied_at so each one runs once
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ControlCommandRecord {
+    pub id: Uuid,
+    pub command: ControlCommand,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+// The kill switch, one row per user. A halt stops every trade in every process
+// until it is explicitly resumed. No row means trading was never halted
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct TradingSwitch {
+    pub halted: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// Emitted when a process adopts a halt or a resume, carrying the reason given
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TradingSwitchNotification {
+    pub data: TradingSwitch,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum CopyTradingBreakerState {
+    Tripped,
+    // The cool-down ran out and copy trading was resumed
+    Reset,
+}
+
+// Copy trading paused by the bot itself after consecutive failed copy trades
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CopyTradingBreaker {
+    pub state: CopyTradingBreakerState,
+    pub consecutive_failures: u32,
+    // None when copy trading stays paused until resumed by hand
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cooldown_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CopyTradingBreakerNotification {
+    pub data: CopyTradingBreaker,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DustCleanupResult {
+    pub token_account: String,
+    pub mint: String,
+    pub amount: f64,
+    pub burned: bool,
+    pub closed: bool,
+    pub signature: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DustCleanupNotification {
+    pub data: Vec<DustCleanupResult>,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InsufficientBalanceInfo {
+    pub token_address: String,
+    pub token_symbol: String,
+    pub current_balance_sol: f64,
+    pub required_balance_sol: f64,
+    pub trade_amount_sol: f64,
+    pub estimated_fees_sol: f64,
+    pub min_sol_balance: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InsufficientBalanceNotification {
+    pub data: InsufficientBalanceInfo,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum ErrorSeverity {
+    // Something was degraded or skipped but trading carried on
+    Warning,
+    #[default]
+    Error,
+}
+
+// What the error was about, so the frontend can link to it
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ErrorContext {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wallet_address: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ErrorInfo {
+    pub source: String,
+    // Machine readable error type, e.g. copy_trade_failed
+    pub code: String,
+    pub message: String,
+    #[serde(default)]
+    pub severity: ErrorSeverity,
+    #[serde(default)]
+    pub context: ErrorContext,
+}
+
+impl ErrorInfo {
+    pub fn new(severity: ErrorSeverity, source: &str, code: &str, message: String) -> Self {
+        Self {
+            source: source.to_string(),
+            code: code.to_string(),
+            message,
+            severity,
+            context: ErrorContext::default(),
+        }
+    }
+
+    pub fn with_context(mut self, context: ErrorContext) -> Self {
+        self.context = context;
+        self
+    }
+}
+
+impl ErrorContext {
+    pub fn for_transaction(tx_info: &ClientTxInfo) -> Self {
+        Self {
+            signature: Some(tx_info.signature.clone()),
+            token_address: Some(tx_info.token_address.clone()),
+            wallet_address: Some(tx_info.tracked_wallet_address.clone())
+                .filter(|address| !address.is_empty()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ErrorNotification {
+    pub data: ErrorInfo,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub event_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
 }
 
 #[derive(Debug)]
@@ -143,6 +1071,21 @@ pub struct BuyRequest {
     pub token_address: String,
     pub sol_quantity: f64,
     pub slippage_tolerance: f64,
+    #[serde(default)]
+    pub skip_simulation: bool,
+    // Pool to trade against instead of discovering one, ignored if it doesn't fit the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_address: Option<String>,
+}
+
+impl BuyRequest {
+    pub fn sol_lamports(&self) -> u64 {
+        sol_to_lamports(self.sol_quantity)
+    }
+
+    pub fn slippage_bps(&self) -> u16 {
+        fraction_to_bps(self.slippage_tolerance)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -153,14 +1096,32 @@ pub struct BuyResponse {
     pub token_quantity: f64,
     pub sol_spent: f64,
     pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub child_signatures: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
 }
 
 //sell request
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SellRequest {
     pub token_address: String,
+    #[serde(default)]
     pub token_quantity: f64,
     pub slippage_tolerance: f64,
+    // Sells the full balance held when the transaction is built, token_quantity is ignored
+    #[serde(default)]
+    pub sell_all: bool,
+    #[serde(default)]
+    pub skip_simulation: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_address: Option<String>,
+}
+
+impl SellRequest {
+    pub fn slippage_bps(&self) -> u16 {
+        fraction_to_bps(self.slippage_tolerance)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -171,13 +1132,128 @@ pub struct SellResponse {
     pub sol_received: f64,
     pub solscan_tx_url: String,
     pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slippage: Option<SlippageReport>,
+}
+
+// Expected output is quoted from pool reserves before sending, realized output
+// is read from the confirmed transaction
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct SlippageReport {
+    pub expected_sol: f64,
+    pub realized_sol: f64,
+    pub slippage: f64,
+}
+
+impl SlippageReport {
+    pub fn new(expected_sol: f64, realized_sol: f64) -> Self {
+        let slippage = if expected_sol > 0.0 {
+            (expected_sol - realized_sol) / expected_sol
+        } else {
+            0.0
+        };
+
+        Self {
+            expected_sol,
+            realized_sol,
+            slippage,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BuyTokenCalculations {
     pub token_out: u64,
+    pub min_token_out: u64,
     pub max_sol_cost: u64,
     pub price_per_token: f64,
     pub max_token_output: f64,
     pub min_token_output: f64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use serde_json::Value;
+
+    // Additive changes only: every field in the fixture must still be emitted
+    // with the same name and value, new fields may appear next to them
+    fn assert_compatible(expected: &Value, actual: &Value, path: &str) {
+        match (expected, actual) {
+            (Value::Object(expected), Value::Object(actual)) => {
+                for (key, value) in expected {
+                    let field = format!("{}.{}", path, key);
+                    match actual.get(key) {
+                        Some(actual) => assert_compatible(value, actual, &field),
+                        None => panic!("{} is no longer serialized", field),
+                    }
+                }
+            }
+            (Value::Array(expected), Value::Array(actual)) => {
+                assert_eq!(expected.len(), actual.len(), "{} changed length", path);
+                for (index, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                    assert_compatible(expected, actual, &format!("{}[{}]", path, index));
+                }
+            }
+            _ => assert_eq!(expected, actual, "{} changed", path),
+        }
+    }
+
+    fn assert_golden<T: Serialize + DeserializeOwned>(fixture: &str) -> T {
+        let expected: Value = serde_json::from_str(fixture).unwrap();
+        let parsed: T = serde_json::from_value(expected.clone()).unwrap();
+        assert_compatible(&expected, &serde_json::to_value(&parsed).unwrap(), "$");
+        parsed
+    }
+
+    #[test]
+    fn copy_trade_notification_matches_golden() {
+        let notification: CopyTradeNotification = assert_golden(include_str!(
+            "../tests/fixtures/notifications/copy_trade.json"
+        ));
+        assert_eq!(notification.data.dex_type, DexType::PumpFun);
+        assert!(notification.slippage.is_some());
+    }
+
+    #[test]
+    fn connection_notifications_match_golden() {
+        assert_golden::<ConnectionStatusNotification>(include_str!(
+            "../tests/fixtures/notifications/connection_status.json"
+        ));
+        assert_golden::<ConnectionAlertNotification>(include_str!(
+            "../tests/fixtures/notifications/connection_alert.json"
+        ));
+    }
+
+    #[test]
+    fn transaction_state_notification_matches_golden() {
+        assert_golden::<TransactionStateNotification>(include_str!(
+            "../tests/fixtures/notifications/transaction_state.json"
+        ));
+    }
+
+    #[test]
+    fn wallet_update_notification_matches_golden() {
+        assert_golden::<WalletUpdateNotification>(include_str!(
+            "../tests/fixtures/notifications/wallet_update.json"
+        ));
+    }
+
+    #[test]
+    fn older_copy_trade_payload_still_parses() {
+        // Written before event ids, wallet attribution and fees were added, and
+        // carrying a field this build doesn't know about
+        let notification: CopyTradeNotification = serde_json::from_str(include_str!(
+            "../tests/fixtures/notifications/copy_trade_legacy.json"
+        ))
+        .unwrap();
+
+        assert_eq!(notification.event_id, Uuid::nil());
+        assert_eq!(notification.correlation_id, None);
+        assert_eq!(notification.data.transaction_type, TransactionType::Sell);
+        assert_eq!(notification.data.tracked_wallet_id, None);
+        assert_eq!(notification.data.slot, 0);
+        assert_eq!(notification.data.priority_fee_lamports, None);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod constants;
+pub mod transaction;
+
+pub use constants::*;
+pub use transaction::*;
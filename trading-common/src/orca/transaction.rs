@@ -0,0 +1,10 @@
+use crate::utils::transaction::extract_swap_from_balance_changes;
+use crate::TransactionType;
+use anyhow::Result;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+pub fn extract_transaction_details(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<(TransactionType, String, f64, f64, f64)> {
+    extract_swap_from_balance_changes(transaction, "Orca Whirlpool")
+}
@@ -4,22 +4,32 @@ use solana_sdk::{
     commitment_config::CommitmentConfig,
     compute_budget::ComputeBudgetInstruction,
     instruction::{AccountMeta, Instruction},
-    message::Message,
     pubkey::Pubkey,
     signature::Keypair,
     signer::Signer,
-    transaction::Transaction,
 };
 use solana_transaction_status::UiTransactionEncoding;
 use std::str::FromStr;
-
-use crate::models::{BuyRequest, BuyResponse};
-use crate::utils::data::confirm_transaction;
+use tracing::{debug, field, info, instrument, warn};
+
+use crate::amounts::{bps_to_fraction, lamports_to_sol, BPS_DENOMINATOR};
+use crate::models::{BuyRequest, BuyResponse, BuyTokenCalculations};
+use crate::token_metadata::get_mint_decimals;
+use crate::utils::compute_budget::compute_budget_config;
+use crate::utils::confirmation_tracker::track_signature;
+use crate::utils::dex::DexType;
+use crate::utils::priority_fee::get_priority_fee;
+use crate::utils::send::{check_requote, confirm_before_expiry, send_config, ConfirmationOutcome};
+use crate::utils::simulation::prepare_transaction_with_expiry;
+use crate::utils::spend_guard::spend_guard;
 
 use super::{
     constants::*,
     types::{PumpFunTokenContainer, TokenAccountOwnerContainer},
-    utils::{derive_trading_accounts, ensure_token_account, get_bonding_curve_data},
+    utils::{
+        derive_trading_accounts, ensure_token_account, get_bonding_curve_data,
+        get_coin_data_with_fallback,
+    },
 };
 
 pub async fn buy(
@@ -27,82 +37,120 @@ pub async fn buy(
     secret_keypair: &impl Signer,
     token_account_container: &TokenAccountOwnerContainer,
     pump_fun_token_container: &PumpFunTokenContainer,
-    sol_quantity: f64,
-    slippage: f64,
-) -> Result<String, AppError> {
+    sol_in_lamports: u64,
+    slippage_bps: u16,
+    simulate: bool,
+) -> Result<(String, BuyTokenCalculations), AppError> {
     let user_address = secret_keypair.pubkey();
 
     // Validate slippage
-    if slippage >= 1.0 {
+    if slippage_bps >= BPS_DENOMINATOR {
         return Err(AppError::BadRequest(
             "Slippage must be less than 100%".to_string(),
         ));
     }
 
-    println!(
+    info!(
         "Initiator {} >> Buy: Token: {} Slippage %: {}",
         user_address,
         token_account_container.mint_address,
-        slippage * 100.0
-    );
-
-    // Get bonding curve data directly from chain
-    let bonding_curve_data =
-        get_bonding_curve_data(rpc_client, &pump_fun_token_container.mint_address).await?;
-
-    // Calculate amounts using bonding curve data
-    let (token_out, sol_in_lamports) = bonding_curve_data.calculate_buy_amount(sol_quantity);
-    let max_sol_cost = (sol_in_lamports as f64 * (1.0 + slippage)) as u64;
-
-    // Calculate min/max outputs for logging
-    let decimals = 9; // Most Solana tokens use 9 decimals
-    let max_token_output = token_out as f64 / 10f64.powi(decimals);
-    let min_token_output = max_token_output * (1.0 - slippage);
-
-    println!(
-        "Token Output >> Min: {:.8}, Max: {:.8}",
-        min_token_output, max_token_output
-    );
-
-    println!(
-        "Sol in (lamports): {}, Token out: {}, Max cost: {}",
-        sol_in_lamports, token_out, max_sol_cost
+        bps_to_fraction(slippage_bps) * 100.0
     );
 
-    // Build and send transaction
-    let (instruction, compute_budget_instructions) = build_buy_instructions(
-        user_address,
-        pump_fun_token_container,
-        token_account_container,
-        token_out,
-        max_sol_cost,
-    )?;
-
-    let signature = send_buy_transaction(
-        rpc_client,
-        secret_keypair,
-        &instruction,
-        &compute_budget_instructions,
-        user_address,
-    )
-    .await?;
-
-    println!("Transaction signature: {}", signature);
-
-    // Confirm transaction with retries
-    match confirm_transaction(rpc_client, &signature, 20, 3).await {
-        Ok(true) => {
-            println!("Buy transaction confirmed successfully!");
-            Ok(signature.to_string())
+    let decimals = get_mint_decimals(rpc_client, &pump_fun_token_container.mint_address)
+        .map_err(|e| AppError::ServerError(format!("Failed to get token decimals: {}", e)))?;
+    let (bonding_curve, _) = derive_trading_accounts(&pump_fun_token_container.mint_address)?;
+    let max_rebuilds = send_config().max_rebuilds;
+    let mut original_min_token_out = None;
+
+    // Reserved once and committed on the first send, rebuilds resend the same spend
+    let mut spend = spend_guard().reserve(lamports_to_sol(sol_in_lamports))?;
+
+    // Everything price dependent is requoted on each attempt, an expired
+    // blockhash means the transaction never landed and can be rebuilt
+    for attempt in 0..=max_rebuilds {
+        // Get bonding curve data directly from chain
+        let bonding_curve_data =
+            get_bonding_curve_data(rpc_client, &pump_fun_token_container.mint_address).await?;
+        let calculations =
+            bonding_curve_data.calculate_buy_tokens(sol_in_lamports, slippage_bps, decimals);
+
+        if calculations.min_token_out == 0 {
+            return Err(AppError::BadRequest(
+                "Buy amount is too small or the bonding curve has no tokens left".to_string(),
+            ));
         }
-        Ok(false) => Err(AppError::ServerError(
-            "Transaction failed during confirmation".to_string(),
-        )),
-        Err(e) => {
-            println!("Error during confirmation: {:?}", e);
-            Err(e)
+        match original_min_token_out {
+            Some(original) => check_requote(original, calculations.token_out)?,
+            None => original_min_token_out = Some(calculations.min_token_out),
+        }
+
+        info!(
+            "Token Output >> Min: {:.8}, Max: {:.8}",
+            calculations.min_token_output, calculations.max_token_output
+        );
+
+        info!(
+            "Token out: {}, Min token out: {}, Max cost: {}",
+            calculations.token_out, calculations.min_token_out, calculations.max_sol_cost
+        );
+
+        // Build and send transaction
+        let unit_limit = compute_budget_config().unit_limit(&DexType::PumpFun);
+        let unit_price = get_priority_fee(rpc_client, &[bonding_curve], unit_limit, UNIT_PRICE);
+        info!(
+            "Compute budget: limit {}, price {} micro-lamports",
+            unit_limit, unit_price
+        );
+
+        // Buying exactly the minimum output lets the program enforce slippage via max_sol_cost
+        let (instruction, compute_budget_instructions) = build_buy_instructions(
+            user_address,
+            pump_fun_token_container,
+            token_account_container,
+            calculations.min_token_out,
+            calculations.max_sol_cost,
+            unit_price,
+            unit_limit,
+        )?;
+
+        let (signature, last_valid_block_height) = send_buy_transaction(
+            rpc_client,
+            secret_keypair,
+            &instruction,
+            &compute_budget_instructions,
+            user_address,
+            simulate,
+        )
+        .await?;
+        spend.commit();
+
+        info!("Transaction signature: {}", signature);
+
+        track_signature(&signature);
+        match confirm_before_expiry(rpc_client, &signature, last_valid_block_height).await? {
+            ConfirmationOutcome::Confirmed => {
+                info!("Buy transaction confirmed successfully!");
+                return Ok((signature.to_string(), calculations));
+            }
+            ConfirmationOutcome::Failed => {
+                return Err(AppError::ServerError(
+                    "Transaction failed during confirmation".to_string(),
+                ))
+            }
+            ConfirmationOutcome::Expired => warn!(
+                "Buy {} expired without landing, rebuilding ({}/{})",
+                signature,
+                attempt + 1,
+                max_rebuilds
+            ),
         }
     }
+
+    Err(AppError::ServerError(format!(
+        "Buy transaction expired {} times without landing",
+        max_rebuilds + 1
+    )))
 }
 
 fn build_buy_instructions(
@@ -111,6 +159,8 @@ fn build_buy_instructions(
     token_account_container: &TokenAccountOwnerContainer,
     token_out: u64,
     max_sol_cost: u64,
+    unit_price: u64,
+    unit_limit: u32,
 ) -> Result<(Instruction, Vec<Instruction>), AppError> {
     // Build instruction data
     let mut data = Vec::with_capacity(24);
@@ -143,8 +193,8 @@ fn build_buy_instructions(
     // Create instructions
     let instruction = Instruction::new_with_bytes(PUMP_FUN_PROGRAM_ID, &data, accounts);
     let compute_budget_instructions = vec![
-        ComputeBudgetInstruction::set_compute_unit_price(UNIT_PRICE),
-        ComputeBudgetInstruction::set_compute_unit_limit(UNIT_BUDGET),
+        ComputeBudgetInstruction::set_compute_unit_price(unit_price),
+        ComputeBudgetInstruction::set_compute_unit_limit(unit_limit),
     ];
 
     Ok((instruction, compute_budget_instructions))
@@ -156,17 +206,19 @@ async fn send_buy_transaction(
     instruction: &Instruction,
     compute_budget_instructions: &[Instruction],
     user_address: Pubkey,
-) -> Result<solana_sdk::signature::Signature, AppError> {
-    let recent_blockhash = rpc_client.get_latest_blockhash()?;
-
+    simulate: bool,
+) -> Result<(solana_sdk::signature::Signature, u64), AppError> {
     let mut instructions = Vec::with_capacity(compute_budget_instructions.len() + 1);
     instructions.extend_from_slice(compute_budget_instructions);
     instructions.push(instruction.clone());
 
-    let message =
-        Message::new_with_blockhash(&instructions, Some(&user_address), &recent_blockhash);
-
-    let transaction = Transaction::new(&[secret_keypair], message, recent_blockhash);
+    let (transaction, last_valid_block_height) = prepare_transaction_with_expiry(
+        rpc_client,
+        instructions,
+        &user_address,
+        &[secret_keypair],
+        simulate,
+    )?;
 
     const CONFIG: RpcSendTransactionConfig = RpcSendTransactionConfig {
         skip_preflight: false,
@@ -178,24 +230,37 @@ async fn send_buy_transaction(
 
     rpc_client
         .send_transaction_with_config(&transaction, CONFIG)
+        .map(|signature| (signature, last_valid_block_height))
         .map_err(|e| AppError::RequestError(format!("Failed to send transaction: {}", e)))
 }
 
+#[instrument(
+    name = "trade",
+    skip_all,
+    fields(side = "buy", dex = "pump_fun", token = %request.token_address, signature = field::Empty)
+)]
 pub async fn process_buy_request(
     rpc_client: &RpcClient,
     server_keypair: &Keypair,
     request: BuyRequest,
 ) -> Result<BuyResponse, AppError> {
-    println!("Processing buy request");
+    info!("Processing buy request");
     let token_address = Pubkey::from_str(&request.token_address)
         .map_err(|e| AppError::BadRequest(format!("Invalid token address: {}", e)))?;
 
-    println!("Token address: {:?}", token_address);
+    debug!("Token address: {:?}", token_address);
+
+    let coin_data = get_coin_data_with_fallback(rpc_client, &token_address).await?;
+    if coin_data.complete {
+        return Err(AppError::BadRequest(
+            "Bonding curve is complete, token has migrated".to_string(),
+        ));
+    }
 
     // Create containers
     let pump_fun_token_container = PumpFunTokenContainer {
         mint_address: token_address,
-        pump_fun_coin_data: None,
+        pump_fun_coin_data: Some(coin_data),
         program_account_info: None,
     };
 
@@ -214,28 +279,25 @@ pub async fn process_buy_request(
     };
 
     // Execute buy
-    let signature = buy(
+    let (signature, calculations) = buy(
         rpc_client,
         server_keypair,
         &token_account_container,
         &pump_fun_token_container,
-        request.sol_quantity,
-        request.slippage_tolerance,
+        request.sol_lamports(),
+        request.slippage_bps(),
+        !request.skip_simulation,
     )
     .await?;
 
-    // Get bonding curve data for calculations
-    let bonding_curve_data = get_bonding_curve_data(rpc_client, &token_address).await?;
-
-    let (token_out, _) = bonding_curve_data.calculate_buy_amount(request.sol_quantity);
-    let adjusted_token_output = token_out as f64 / 1e9; // Convert to human readable
-
     Ok(BuyResponse {
         success: true,
         signature: signature.to_string(),
         solscan_tx_url: format!("https://solscan.io/tx/{}", signature),
-        token_quantity: adjusted_token_output,
+        token_quantity: calculations.min_token_output,
         sol_spent: request.sol_quantity,
         error: None,
+        child_signatures: Vec::new(),
+        route: None,
     })
 }
@@ -4,24 +4,33 @@ use solana_sdk::{
     commitment_config::CommitmentConfig,
     compute_budget::ComputeBudgetInstruction,
     instruction::{AccountMeta, Instruction},
-    message::Message,
     pubkey::Pubkey,
     signature::Keypair,
     signer::Signer,
-    transaction::Transaction,
 };
 use solana_transaction_status::UiTransactionEncoding;
 use std::str::FromStr;
+use tracing::{debug, field, info, instrument, warn};
 
 use crate::{
-    data::{confirm_transaction, get_token_balance},
+    amounts::min_with_slippage,
+    compute_budget::compute_budget_config,
+    confirmation_tracker::track_signature,
+    data::resolve_sell_amount,
+    dex::DexType,
     models::{SellRequest, SellResponse},
+    priority_fee::get_priority_fee,
+    send::{check_requote, confirm_before_expiry, send_config, ConfirmationOutcome},
+    simulation::prepare_transaction_with_expiry,
 };
 
 use super::{
     constants::*,
     types::{PumpFunTokenContainer, TokenAccountOwnerContainer},
-    utils::{derive_trading_accounts, ensure_token_account, get_bonding_curve_data},
+    utils::{
+        derive_trading_accounts, ensure_token_account, get_bonding_curve_data,
+        get_coin_data_with_fallback,
+    },
 };
 
 pub async fn sell(
@@ -29,89 +38,109 @@ pub async fn sell(
     secret_keypair: &impl Signer,
     token_account_container: &TokenAccountOwnerContainer,
     pump_fun_token_container: &PumpFunTokenContainer,
-    token_quantity: f64,
-    slippage: f64,
+    token_amount: u64,
+    slippage_bps: u16,
+    simulate: bool,
 ) -> Result<String, AppError> {
     let user_address = secret_keypair.pubkey();
 
-    println!(
+    info!(
         "Selling token with Pump.fun DEX with keypair: {}",
         secret_keypair.pubkey()
     );
 
-    // Get token holdings
-    let token_holdings = rpc_client
-        .get_token_account_balance(&token_account_container.token_account_address.unwrap())?;
-
-    println!("Token account balance retrieved: {}", token_holdings.amount);
-
-    let token_balance = token_holdings.amount.parse::<u64>().unwrap();
-    let token_decimals = token_holdings.decimals;
-
-    println!("Token balance: {} (smallest unit)", token_balance);
-    println!("Token decimals: {}", token_decimals);
-
-    // Get bonding curve data from chain
-    let bonding_curve_data =
-        get_bonding_curve_data(rpc_client, &pump_fun_token_container.mint_address).await?;
-
-    println!(
-        "Token Reserves for {}",
-        token_account_container.mint_address
-    );
-    println!(
-        "Virtual token reserves: {}",
-        bonding_curve_data.virtual_token_reserves
-    );
-    println!(
-        "Virtual sol reserves: {}",
-        bonding_curve_data.virtual_sol_reserves
-    );
-
-    // Calculate sell amounts
-    let (token_amount, expected_sol_output) =
-        bonding_curve_data.calculate_sell_amount(token_quantity, token_decimals);
-    let min_sol_output = (expected_sol_output as f64 * (1.0 - slippage)) as u64;
-
-    println!(
-        "Expected SOL output: {} SOL",
-        expected_sol_output as f64 / LAMPORTS_PER_SOL as f64
-    );
-    println!(
-        "Minimum SOL output with slippage: {} SOL",
-        min_sol_output as f64 / LAMPORTS_PER_SOL as f64
-    );
-
-    let (instruction, compute_budget_instructions) = build_sell_instructions(
-        user_address,
-        pump_fun_token_container,
-        token_account_container,
-        token_amount,
-        min_sol_output,
-    )?;
-
-    let signature = send_sell_transaction(
-        rpc_client,
-        secret_keypair,
-        &instruction,
-        &compute_budget_instructions,
-        user_address,
-    )
-    .await?;
-
-    match confirm_transaction(rpc_client, &signature, 20, 3).await {
-        Ok(true) => {
-            println!("Transaction confirmed successfully!");
-            Ok(signature.to_string())
+    info!("Token amount to sell: {} (smallest unit)", token_amount);
+
+    let (bonding_curve, _) = derive_trading_accounts(&pump_fun_token_container.mint_address)?;
+    let max_rebuilds = send_config().max_rebuilds;
+    let mut original_min_sol_output = None;
+
+    for attempt in 0..=max_rebuilds {
+        // Get bonding curve data from chain
+        let bonding_curve_data =
+            get_bonding_curve_data(rpc_client, &pump_fun_token_container.mint_address).await?;
+
+        info!(
+            "Token Reserves for {}",
+            token_account_container.mint_address
+        );
+        info!(
+            "Virtual token reserves: {}",
+            bonding_curve_data.virtual_token_reserves
+        );
+        info!(
+            "Virtual sol reserves: {}",
+            bonding_curve_data.virtual_sol_reserves
+        );
+
+        // Calculate sell amounts
+        let expected_sol_output = bonding_curve_data.calculate_sol_output(token_amount);
+        let min_sol_output = min_with_slippage(expected_sol_output, slippage_bps);
+        match original_min_sol_output {
+            Some(original) => check_requote(original, expected_sol_output)?,
+            None => original_min_sol_output = Some(min_sol_output),
         }
-        Ok(false) => Err(AppError::ServerError(
-            "Transaction failed during confirmation".to_string(),
-        )),
-        Err(e) => {
-            println!("Error during confirmation: {:?}", e);
-            Err(e)
+
+        info!(
+            "Expected SOL output: {} SOL",
+            expected_sol_output as f64 / LAMPORTS_PER_SOL as f64
+        );
+        info!(
+            "Minimum SOL output with slippage: {} SOL",
+            min_sol_output as f64 / LAMPORTS_PER_SOL as f64
+        );
+
+        let unit_limit = compute_budget_config().unit_limit(&DexType::PumpFun);
+        let unit_price = get_priority_fee(rpc_client, &[bonding_curve], unit_limit, UNIT_PRICE);
+        info!(
+            "Compute budget: limit {}, price {} micro-lamports",
+            unit_limit, unit_price
+        );
+
+        let (instruction, compute_budget_instructions) = build_sell_instructions(
+            user_address,
+            pump_fun_token_container,
+            token_account_container,
+            token_amount,
+            min_sol_output,
+            unit_price,
+            unit_limit,
+        )?;
+
+        let (signature, last_valid_block_height) = send_sell_transaction(
+            rpc_client,
+            secret_keypair,
+            &instruction,
+            &compute_budget_instructions,
+            user_address,
+            simulate,
+        )
+        .await?;
+
+        track_signature(&signature);
+        match confirm_before_expiry(rpc_client, &signature, last_valid_block_height).await? {
+            ConfirmationOutcome::Confirmed => {
+                info!("Transaction confirmed successfully!");
+                return Ok(signature.to_string());
+            }
+            ConfirmationOutcome::Failed => {
+                return Err(AppError::ServerError(
+                    "Transaction failed during confirmation".to_string(),
+                ))
+            }
+            ConfirmationOutcome::Expired => warn!(
+                "Sell {} expired without landing, rebuilding ({}/{})",
+                signature,
+                attempt + 1,
+                max_rebuilds
+            ),
         }
     }
+
+    Err(AppError::ServerError(format!(
+        "Sell transaction expired {} times without landing",
+        max_rebuilds + 1
+    )))
 }
 
 fn build_sell_instructions(
@@ -120,6 +149,8 @@ fn build_sell_instructions(
     token_account_container: &TokenAccountOwnerContainer,
     token_amount: u64,
     min_sol_output: u64,
+    unit_price: u64,
+    unit_limit: u32,
 ) -> Result<(Instruction, Vec<Instruction>), AppError> {
     let mut data = Vec::with_capacity(24);
     data.extend_from_slice(&SELL_DISCRIMINATOR);
@@ -150,8 +181,8 @@ fn build_sell_instructions(
 
     let instruction = Instruction::new_with_bytes(PUMP_FUN_PROGRAM_ID, &data, accounts);
     let compute_budget_instructions = vec![
-        ComputeBudgetInstruction::set_compute_unit_price(UNIT_PRICE),
-        ComputeBudgetInstruction::set_compute_unit_limit(UNIT_BUDGET),
+        ComputeBudgetInstruction::set_compute_unit_price(unit_price),
+        ComputeBudgetInstruction::set_compute_unit_limit(unit_limit),
     ];
 
     Ok((instruction, compute_budget_instructions))
@@ -163,17 +194,19 @@ async fn send_sell_transaction(
     instruction: &Instruction,
     compute_budget_instructions: &[Instruction],
     user_address: Pubkey,
-) -> Result<solana_sdk::signature::Signature, AppError> {
-    let recent_blockhash = rpc_client.get_latest_blockhash()?;
-
+    simulate: bool,
+) -> Result<(solana_sdk::signature::Signature, u64), AppError> {
     let mut instructions = Vec::with_capacity(compute_budget_instructions.len() + 1);
     instructions.extend_from_slice(compute_budget_instructions);
     instructions.push(instruction.clone());
 
-    let message =
-        Message::new_with_blockhash(&instructions, Some(&user_address), &recent_blockhash);
-
-    let transaction = Transaction::new(&[secret_keypair], message, recent_blockhash);
+    let (transaction, last_valid_block_height) = prepare_transaction_with_expiry(
+        rpc_client,
+        instructions,
+        &user_address,
+        &[secret_keypair],
+        simulate,
+    )?;
 
     const CONFIG: RpcSendTransactionConfig = RpcSendTransactionConfig {
         skip_preflight: false,
@@ -185,23 +218,36 @@ async fn send_sell_transaction(
 
     rpc_client
         .send_transaction_with_config(&transaction, CONFIG)
+        .map(|signature| (signature, last_valid_block_height))
         .map_err(|e| AppError::RequestError(format!("Failed to send transaction: {}", e)))
 }
 
+#[instrument(
+    name = "trade",
+    skip_all,
+    fields(side = "sell", dex = "pump_fun", token = %request.token_address, signature = field::Empty)
+)]
 pub async fn process_sell_request(
     rpc_client: &RpcClient,
     server_keypair: &Keypair,
     request: SellRequest,
 ) -> Result<SellResponse, AppError> {
-    println!("Processing sell request: {:?}", request);
+    debug!("Processing sell request: {:?}", request);
 
     let token_address = Pubkey::from_str(&request.token_address)
         .map_err(|e| AppError::BadRequest(format!("Invalid token address: {}", e)))?;
 
+    let coin_data = get_coin_data_with_fallback(rpc_client, &token_address).await?;
+    if coin_data.complete {
+        return Err(AppError::BadRequest(
+            "Bonding curve is complete, token has migrated".to_string(),
+        ));
+    }
+
     // Create containers
     let pump_fun_token_container = PumpFunTokenContainer {
         mint_address: token_address,
-        pump_fun_coin_data: None,
+        pump_fun_coin_data: Some(coin_data),
         program_account_info: None,
     };
 
@@ -220,36 +266,32 @@ pub async fn process_sell_request(
     };
 
     // Verify balance
-    let token_balance = get_token_balance(rpc_client, &token_account).await?;
-    if token_balance < request.token_quantity {
-        return Err(AppError::BadRequest(
-            "Insufficient token balance".to_string(),
-        ));
-    }
+    let sell_amount = resolve_sell_amount(rpc_client, &token_account, &request)?;
 
     // Get bonding curve data for price calculation
     let bonding_curve_data = get_bonding_curve_data(rpc_client, &token_address).await?;
-    let (_, expected_sol_output) = bonding_curve_data.calculate_sell_amount(
-        request.token_quantity,
-        9, // Need to get decimals from chain
-    );
+    let expected_sol_output = bonding_curve_data.calculate_sol_output(sell_amount.amount);
 
     let signature = sell(
         rpc_client,
         server_keypair,
         &token_account_container,
         &pump_fun_token_container,
-        request.token_quantity,
-        request.slippage_tolerance,
+        sell_amount.amount,
+        request.slippage_bps(),
+        !request.skip_simulation,
     )
     .await?;
 
     Ok(SellResponse {
         success: true,
         signature: signature.clone(),
-        token_quantity: request.token_quantity,
+        token_quantity: sell_amount.ui_amount,
         sol_received: expected_sol_output as f64 / LAMPORTS_PER_SOL as f64,
         solscan_tx_url: format!("https://solscan.io/tx/{}", signature),
         error: None,
+        slippage: None,
+        child_signatures: Vec::new(),
+        route: None,
     })
 }
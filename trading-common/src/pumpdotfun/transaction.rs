@@ -1,3 +1,6 @@
+use crate::constants::WSOL;
+use crate::data::token_balance_change;
+use crate::utils::transaction::get_transaction_signer;
 use crate::TransactionType;
 use anyhow::Result;
 use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
@@ -34,20 +37,21 @@ pub fn extract_transaction_details(
         .as_ref()
         .unwrap_or(&empty_token_balances);
 
-    let token_address = post_balances
-        .first()
-        .map(|b| b.mint.clone())
+    // Use the signer's token account, the curve's account moves by the same amount
+    let signer = get_transaction_signer(transaction);
+    let trader_balance = post_balances
+        .iter()
+        .chain(pre_balances.iter())
+        .find(|b| {
+            b.mint != WSOL
+                && Option::<String>::from(b.owner.clone()).as_deref() == Some(signer.as_str())
+        })
+        .or_else(|| post_balances.first())
         .ok_or_else(|| anyhow::anyhow!("No token balance information"))?;
 
-    let pre_amount = pre_balances
-        .first()
-        .and_then(|b| b.ui_token_amount.ui_amount)
-        .unwrap_or(0.0);
-    let post_amount = post_balances
-        .first()
-        .and_then(|b| b.ui_token_amount.ui_amount)
-        .unwrap_or(0.0);
-    let amount_token = (post_amount - pre_amount).abs();
+    let token_address = trader_balance.mint.clone();
+    let amount_token =
+        token_balance_change(pre_balances, post_balances, trader_balance.account_index).abs();
 
     let pre_sol = meta.pre_balances.first().copied().unwrap_or(0);
     let post_sol = meta.post_balances.first().copied().unwrap_or(0);
@@ -68,28 +72,49 @@ pub fn extract_transaction_details(
     ))
 }
 
-pub fn extract_accounts(
-    transaction: &EncodedConfirmedTransactionWithStatusMeta,
-    transaction_type: &TransactionType,
-) -> Result<(String, String)> {
-    match &transaction.transaction.transaction {
-        solana_transaction_status::EncodedTransaction::Json(tx) => {
-            let account_keys = crate::data::get_account_keys_from_message(&tx.message);
-
-            let (seller, buyer) = match transaction_type {
-                TransactionType::Sell => (
-                    account_keys.first().cloned().unwrap_or_default(),
-                    account_keys.get(1).cloned().unwrap_or_default(),
-                ),
-                TransactionType::Buy => (
-                    account_keys.get(1).cloned().unwrap_or_default(),
-                    account_keys.first().cloned().unwrap_or_default(),
-                ),
-                _ => (String::new(), String::new()),
-            };
-
-            Ok((seller, buyer))
-        }
-        _ => Ok((String::new(), String::new())),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::transaction::extract_swap_accounts;
+
+    fn buy_transaction() -> EncodedConfirmedTransactionWithStatusMeta {
+        serde_json::from_str(include_str!("../../tests/fixtures/pumpdotfun_buy_v0.json")).unwrap()
+    }
+
+    #[test]
+    fn parses_a_buy_through_lookup_tables() {
+        let (transaction_type, token_address, amount_token, amount_sol, price_per_token) =
+            extract_transaction_details(&buy_transaction()).unwrap();
+
+        assert_eq!(transaction_type, TransactionType::Buy);
+        assert_eq!(token_address, "Mint1111111111111111111111111111111111pump");
+        assert_eq!(amount_token, 35000.5);
+        assert_eq!(amount_sol, 0.10114428);
+        assert_eq!(price_per_token, 0.10114428 / 35000.5);
+    }
+
+    #[test]
+    fn buyer_is_the_signer_and_seller_the_curve() {
+        let (seller, buyer) = extract_swap_accounts(&buy_transaction(), &TransactionType::Buy);
+
+        assert_eq!(seller, "BondingCurve111111111111111111111111111111");
+        assert_eq!(buyer, "Trader1111111111111111111111111111111111111");
+    }
+
+    #[test]
+    fn logs_without_an_instruction_are_unknown() {
+        let mut transaction = buy_transaction();
+        transaction.transaction.meta.as_mut().unwrap().log_messages = Some(Vec::new()).into();
+
+        let (transaction_type, ..) = extract_transaction_details(&transaction).unwrap();
+        assert_eq!(transaction_type, TransactionType::Unknown);
+    }
+
+    #[test]
+    fn missing_metadata_is_an_error() {
+        let mut transaction = buy_transaction();
+        transaction.transaction.meta = None;
+
+        assert!(extract_transaction_details(&transaction).is_err());
     }
 }
@@ -3,7 +3,9 @@ use serde::{Deserialize, Serialize};
 use solana_sdk::pubkey::Pubkey;
 use thiserror::Error;
 
-use super::LAMPORTS_PER_SOL;
+use super::{FEE_BASIS_POINTS, LAMPORTS_PER_SOL};
+use crate::amounts::{max_with_slippage, min_with_slippage, raw_to_ui, ui_to_raw};
+use crate::models::BuyTokenCalculations;
 
 #[derive(Debug, Clone)]
 pub struct TokenMetadata {
@@ -33,22 +35,71 @@ impl BondingCurveData {
             / (self.virtual_token_reserves as f64 * LAMPORTS_PER_SOL as f64)
     }
 
-    pub fn calculate_buy_amount(&self, sol_quantity: f64) -> (u64, u64) {
-        let sol_in_lamports = (sol_quantity * LAMPORTS_PER_SOL as f64) as u64;
-        let token_out = ((sol_in_lamports as f64 * self.virtual_token_reserves as f64)
-            / self.virtual_sol_reserves as f64) as u64;
-        (token_out, sol_in_lamports)
+    pub fn calculate_buy_amount(&self, sol_in_lamports: u64) -> (u64, u64) {
+        let virtual_sol_reserves = self.virtual_sol_reserves.max(0) as u128;
+        let token_out = if virtual_sol_reserves == 0 {
+            0
+        } else {
+            sol_in_lamports as u128 * self.virtual_token_reserves.max(0) as u128
+                / virtual_sol_reserves
+        };
+        (token_out.min(u64::MAX as u128) as u64, sol_in_lamports)
+    }
+
+    // Exact constant product quote for spending sol_in_lamports including the pump.fun fee
+    pub fn calculate_buy_tokens(
+        &self,
+        sol_in_lamports: u64,
+        slippage_bps: u16,
+        decimals: u8,
+    ) -> BuyTokenCalculations {
+        // The fee is charged on top of the curve cost, so only part of the budget buys tokens
+        let sol_for_curve = sol_in_lamports as u128 * 10_000 / (10_000 + FEE_BASIS_POINTS as u128);
+        let virtual_token_reserves = self.virtual_token_reserves.max(0) as u128;
+        let virtual_sol_reserves = self.virtual_sol_reserves.max(0) as u128;
+
+        let token_out = if virtual_sol_reserves + sol_for_curve == 0 {
+            0
+        } else {
+            virtual_token_reserves * sol_for_curve / (virtual_sol_reserves + sol_for_curve)
+        };
+        // Near completion the curve can't hand out more than its real reserves
+        let token_out = token_out.min(self.real_token_reserves.max(0) as u128) as u64;
+        let min_token_out = min_with_slippage(token_out, slippage_bps);
+
+        BuyTokenCalculations {
+            token_out,
+            min_token_out,
+            max_sol_cost: sol_in_lamports,
+            price_per_token: self.get_price(),
+            max_token_output: raw_to_ui(token_out, decimals),
+            min_token_output: raw_to_ui(min_token_out, decimals),
+        }
     }
 
     pub fn calculate_sell_amount(&self, token_quantity: f64, decimals: u8) -> (u64, u64) {
-        let token_amount = (token_quantity * 10f64.powi(decimals as i32)) as u64;
-        let expected_sol_output = ((token_amount as f64 * self.virtual_sol_reserves as f64)
-            / self.virtual_token_reserves as f64) as u64;
-        (token_amount, expected_sol_output)
+        let token_amount = ui_to_raw(token_quantity, decimals);
+        (token_amount, self.calculate_sol_output(token_amount))
+    }
+
+    pub fn calculate_sol_output(&self, token_amount: u64) -> u64 {
+        let virtual_token_reserves = self.virtual_token_reserves.max(0) as u128;
+        if virtual_token_reserves == 0 {
+            return 0;
+        }
+        let sol_out = token_amount as u128 * self.virtual_sol_reserves.max(0) as u128
+            / virtual_token_reserves;
+        sol_out.min(u64::MAX as u128) as u64
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoinDataSource {
+    Api,
+    OnChain,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PumpFunCoinData {
     pub mint: String,
     pub name: String,
@@ -115,26 +166,138 @@ impl PumpFunCalcResult {
     pub fn new(
         virtual_token_reserves: i64,
         virtual_sol_reserves: i64,
-        sol_quantity: f64,
-        slippage: f64,
-        _decimals: u8,
+        sol_in_lamports: u64,
+        slippage_bps: u16,
+        decimals: u8,
     ) -> Self {
-        let vtokenr = virtual_token_reserves as f64;
-        let vsolr = virtual_sol_reserves as f64;
-
-        let sol_in_lamports = sol_quantity * LAMPORTS_PER_SOL as f64;
-        let token_out = ((sol_in_lamports * vtokenr) / vsolr) as u64;
+        let vtokenr = virtual_token_reserves.max(0) as u128;
+        let vsolr = virtual_sol_reserves.max(0) as u128;
 
-        // Convert token_out to human readable (divide by 10^6)
-        let max_token_output = token_out as f64 / 1_000_000.0; // 6 decimals
-        let min_token_output = max_token_output * (1.0 - slippage);
+        let token_out = if vsolr == 0 {
+            0
+        } else {
+            (sol_in_lamports as u128 * vtokenr / vsolr).min(u64::MAX as u128) as u64
+        };
 
         Self {
             token_out,
-            max_sol_cost: (sol_in_lamports * (1.0 + slippage)) as u64,
-            price_per_token: vsolr / (vtokenr * LAMPORTS_PER_SOL as f64),
-            adjusted_max_token_output: max_token_output,
-            adjusted_min_token_output: min_token_output,
+            max_sol_cost: max_with_slippage(sol_in_lamports, slippage_bps),
+            price_per_token: virtual_sol_reserves as f64
+                / (virtual_token_reserves as f64 * LAMPORTS_PER_SOL as f64),
+            adjusted_max_token_output: raw_to_ui(token_out, decimals),
+            adjusted_min_token_output: raw_to_ui(
+                min_with_slippage(token_out, slippage_bps),
+                decimals,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A freshly launched pump.fun curve
+    fn fresh_curve() -> BondingCurveData {
+        BondingCurveData {
+            padding: [0; 8],
+            virtual_token_reserves: 1_073_000_000_000_000,
+            virtual_sol_reserves: 30_000_000_000,
+            real_token_reserves: 793_100_000_000_000,
+            real_sol_reserves: 0,
+            token_total_supply: 1_000_000_000_000_000,
+            complete: false,
+        }
+    }
+
+    #[test]
+    fn buy_quote_takes_the_fee_out_of_the_budget() {
+        let calculations = fresh_curve().calculate_buy_tokens(1_000_000_000, 100, 6);
+
+        // 1 SOL buys 990_099_009 lamports of curve after the 1% fee
+        assert_eq!(calculations.token_out, 34_281_150_129_545);
+        assert_eq!(calculations.min_token_out, 33_938_338_628_249);
+        assert_eq!(calculations.max_sol_cost, 1_000_000_000);
+        assert_eq!(calculations.max_token_output, 34_281_150.129545);
+    }
+
+    #[test]
+    fn buy_quote_slippage_edges() {
+        let curve = fresh_curve();
+
+        let exact = curve.calculate_buy_tokens(1_000_000_000, 0, 6);
+        assert_eq!(exact.min_token_out, exact.token_out);
+
+        let unbounded = curve.calculate_buy_tokens(1_000_000_000, 10_000, 6);
+        assert_eq!(unbounded.min_token_out, 0);
+    }
+
+    #[test]
+    fn buy_quote_is_capped_by_real_reserves() {
+        let mut curve = fresh_curve();
+        curve.real_token_reserves = 1_000;
+
+        let calculations = curve.calculate_buy_tokens(u64::MAX, 0, 6);
+        assert_eq!(calculations.token_out, 1_000);
+    }
+
+    #[test]
+    fn buy_quote_on_empty_curve_is_zero() {
+        let mut curve = fresh_curve();
+        curve.virtual_token_reserves = 0;
+        curve.virtual_sol_reserves = 0;
+
+        assert_eq!(curve.calculate_buy_tokens(0, 100, 6).token_out, 0);
+        assert_eq!(
+            curve.calculate_buy_tokens(1_000_000_000, 100, 6).token_out,
+            0
+        );
+        assert_eq!(curve.calculate_sol_output(1_000), 0);
+    }
+
+    // Settlement as the pump.fun program computes it: the fee is added on top of
+    // the curve cost and the buyer receives what the invariant gives up, rounded
+    // in the curve's favour
+    fn program_fill(curve: &BondingCurveData, sol_in_lamports: u64) -> u64 {
+        let virtual_sol_reserves = curve.virtual_sol_reserves as u128;
+        let virtual_token_reserves = curve.virtual_token_reserves as u128;
+        let sol_for_curve = sol_in_lamports as u128 * 10_000 / (10_000 + FEE_BASIS_POINTS as u128);
+        let remaining = virtual_sol_reserves * virtual_token_reserves
+            / (virtual_sol_reserves + sol_for_curve)
+            + 1;
+        (virtual_token_reserves - remaining) as u64
+    }
+
+    #[test]
+    fn buy_quote_matches_program_fills() {
+        // Fresh, half way and close to completion
+        let curves = [
+            (30_000_000_000, 1_073_000_000_000_000),
+            (45_000_000_000, 715_000_000_000_000),
+            (80_000_000_000, 402_375_000_000_000),
+        ];
+        for (virtual_sol_reserves, virtual_token_reserves) in curves {
+            let curve = BondingCurveData {
+                virtual_sol_reserves,
+                virtual_token_reserves,
+                real_token_reserves: virtual_token_reserves - 279_900_000_000_000,
+                ..fresh_curve()
+            };
+            for sol_in_lamports in [10_000_000, 100_000_000, 2_500_000_000, 10_000_000_000] {
+                let quoted = curve.calculate_buy_tokens(sol_in_lamports, 0, 6).token_out as f64;
+                let filled = program_fill(&curve, sol_in_lamports) as f64;
+
+                // Within 0.01%, the difference is integer rounding
+                assert!(
+                    (quoted - filled).abs() / filled < 0.0001,
+                    "curve {} / {} buying with {}: quoted {}, filled {}",
+                    virtual_sol_reserves,
+                    virtual_token_reserves,
+                    sol_in_lamports,
+                    quoted,
+                    filled
+                );
+            }
         }
     }
 }
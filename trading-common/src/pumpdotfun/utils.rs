@@ -1,15 +1,19 @@
 use anyhow::Result;
-use borsh::BorshDeserialize;
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer, transaction::Transaction};
 use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
 use std::str::FromStr;
+use std::time::Duration;
+use tracing::{debug, info, warn};
 
 use super::{
-    types::{PumpFunCoinData, PumpFunTokenContainer},
-    BondingCurveData, BONDING_CURVE_MARGIN_OF_ERROR, PUMP_FUN_PROGRAM_ID,
+    types::{CoinDataSource, PumpFunCoinData, PumpFunTokenContainer},
+    BondingCurveData, BONDING_CURVE_MARGIN_OF_ERROR, COIN_API_TIMEOUT_SECS, PUMP_FUN_PROGRAM_ID,
+};
+use crate::{
+    data::{get_metadata_account, get_transaction_account_keys, unpack_metadata_account},
+    error::AppError,
 };
-use crate::{data::get_account_keys_from_message, error::AppError};
 
 pub async fn get_bonding_curve_data(
     rpc_client: &RpcClient,
@@ -17,7 +21,7 @@ pub async fn get_bonding_curve_data(
 ) -> Result<BondingCurveData> {
     let (bonding_curve, _) = derive_bonding_curve_address(mint);
     let account_data = rpc_client.get_account_data(&bonding_curve)?;
-    BondingCurveData::try_from_slice(&account_data).map_err(|e| e.into())
+    decode_bonding_curve_data(&account_data)
 }
 
 pub fn derive_bonding_curve_address(mint: &Pubkey) -> (Pubkey, u8) {
@@ -44,7 +48,7 @@ pub async fn get_bonding_curve_info(
     let bonding_curve_pubkey = Pubkey::from_str(&coin_data.bonding_curve)?;
     let account_data = rpc_client.get_account_data(&bonding_curve_pubkey)?;
 
-    let bonding_curve_data = BondingCurveData::try_from_slice(&account_data)?;
+    let bonding_curve_data = decode_bonding_curve_data(&account_data)?;
 
     // Compare with API values and check threshold
     let within_threshold = (bonding_curve_data.virtual_sol_reserves as f64
@@ -55,7 +59,7 @@ pub async fn get_bonding_curve_info(
             < coin_data.virtual_token_reserves as f64);
 
     if !within_threshold {
-        println!("Warning: Chain values differ significantly from API values");
+        info!("Warning: Chain values differ significantly from API values");
     }
 
     Ok((
@@ -66,7 +70,7 @@ pub async fn get_bonding_curve_info(
 
 pub async fn get_coin_data(token_address: &Pubkey) -> Result<PumpFunCoinData, AppError> {
     let url = format!("https://frontend-api.pump.fun/coins/{}", token_address);
-    println!("url: {:?}", url);
+    debug!("url: {:?}", url);
     let mut response = surf::get(url)
         .header(
             "User-Agent",
@@ -94,22 +98,97 @@ pub async fn get_coin_data(token_address: &Pubkey) -> Result<PumpFunCoinData, Ap
     Ok(pump_fun_coin_data)
 }
 
-pub fn decode_bonding_curve_data(data: &[u8]) -> Result<(i64, i64)> {
-    if data.len() < 24 {
+// Newer curve accounts carry trailing fields (e.g. the creator), so only the
+// known prefix is decoded
+pub fn decode_bonding_curve_data(data: &[u8]) -> Result<BondingCurveData> {
+    if data.len() < 49 {
         return Err(anyhow::anyhow!(
             "Insufficient data to decode bonding curve info"
         ));
     }
 
-    let virtual_token_reserves = i64::from_le_bytes(data[8..16].try_into()?);
-    let virtual_sol_reserves = i64::from_le_bytes(data[16..24].try_into()?);
+    let read_i64 = |offset: usize| -> Result<i64> {
+        Ok(i64::from_le_bytes(data[offset..offset + 8].try_into()?))
+    };
+
+    Ok(BondingCurveData {
+        padding: data[0..8].try_into()?,
+        virtual_token_reserves: read_i64(8)?,
+        virtual_sol_reserves: read_i64(16)?,
+        real_token_reserves: read_i64(24)?,
+        real_sol_reserves: read_i64(32)?,
+        token_total_supply: read_i64(40)?,
+        complete: data[48] != 0,
+    })
+}
+
+// Builds the coin data needed for trading purely from chain state
+pub async fn get_coin_data_from_chain(
+    rpc_client: &RpcClient,
+    mint: &Pubkey,
+) -> Result<PumpFunCoinData> {
+    let (bonding_curve, associated_bonding_curve) = derive_trading_accounts(mint)?;
+    let account_data = rpc_client.get_account_data(&bonding_curve)?;
+    let curve = decode_bonding_curve_data(&account_data)?;
 
-    println!(
-        "Raw decoded values: token_reserves={}, sol_reserves={}",
-        virtual_token_reserves, virtual_sol_reserves
-    );
+    let (name, symbol, metadata_uri) = match rpc_client
+        .get_account_data(&get_metadata_account(mint))
+        .map_err(anyhow::Error::from)
+        .and_then(|data| unpack_metadata_account(&data))
+    {
+        Ok(metadata) => (metadata.name, metadata.symbol, metadata.uri),
+        Err(_) => (String::new(), String::new(), String::new()),
+    };
 
-    Ok((virtual_token_reserves, virtual_sol_reserves))
+    Ok(PumpFunCoinData {
+        mint: mint.to_string(),
+        name,
+        symbol,
+        metadata_uri,
+        bonding_curve: bonding_curve.to_string(),
+        associated_bonding_curve: associated_bonding_curve.to_string(),
+        complete: curve.complete,
+        virtual_sol_reserves: curve.virtual_sol_reserves,
+        virtual_token_reserves: curve.virtual_token_reserves,
+        total_supply: curve.token_total_supply,
+        ..Default::default()
+    })
+}
+
+// Prefers the pump.fun API and falls back to chain state on error or timeout
+pub async fn get_coin_data_with_fallback(
+    rpc_client: &RpcClient,
+    mint: &Pubkey,
+) -> Result<PumpFunCoinData, AppError> {
+    let api_result = tokio::time::timeout(
+        Duration::from_secs(COIN_API_TIMEOUT_SECS),
+        get_coin_data(mint),
+    )
+    .await;
+
+    let (coin_data, source) = match api_result {
+        Ok(Ok(coin_data)) => (coin_data, CoinDataSource::Api),
+        Ok(Err(e)) => {
+            warn!(
+                "pump.fun API failed for {}: {}, using on-chain data",
+                mint, e
+            );
+            (
+                get_coin_data_from_chain(rpc_client, mint).await?,
+                CoinDataSource::OnChain,
+            )
+        }
+        Err(_) => {
+            warn!("pump.fun API timed out for {}, using on-chain data", mint);
+            (
+                get_coin_data_from_chain(rpc_client, mint).await?,
+                CoinDataSource::OnChain,
+            )
+        }
+    };
+
+    info!("Coin data for {} loaded from {:?}", mint, source);
+    Ok(coin_data)
 }
 
 pub async fn ensure_token_account(
@@ -148,14 +227,55 @@ pub async fn ensure_token_account(
 pub fn get_transaction_accounts(
     transaction: &EncodedConfirmedTransactionWithStatusMeta,
 ) -> Option<(String, String)> {
-    match &transaction.transaction.transaction {
-        solana_transaction_status::EncodedTransaction::Json(tx) => {
-            let account_keys = get_account_keys_from_message(&tx.message);
-            Some((
-                account_keys.first().cloned().unwrap_or_default(),
-                account_keys.get(1).cloned().unwrap_or_default(),
-            ))
-        }
-        _ => None,
+    let account_keys = get_transaction_account_keys(transaction);
+    if account_keys.is_empty() {
+        return None;
+    }
+
+    Some((
+        account_keys.first().cloned().unwrap_or_default(),
+        account_keys.get(1).cloned().unwrap_or_default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve_account(trailing: &[u8]) -> Vec<u8> {
+        let mut data = vec![0xAA; 8];
+        data.extend_from_slice(&1_073_000_000_000_000i64.to_le_bytes());
+        data.extend_from_slice(&30_000_000_000i64.to_le_bytes());
+        data.extend_from_slice(&793_100_000_000_000i64.to_le_bytes());
+        data.extend_from_slice(&5i64.to_le_bytes());
+        data.extend_from_slice(&1_000_000_000_000_000i64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(trailing);
+        data
+    }
+
+    #[test]
+    fn decodes_bonding_curve_at_known_offsets() {
+        let curve = decode_bonding_curve_data(&curve_account(&[])).unwrap();
+
+        assert_eq!(curve.padding, [0xAA; 8]);
+        assert_eq!(curve.virtual_token_reserves, 1_073_000_000_000_000);
+        assert_eq!(curve.virtual_sol_reserves, 30_000_000_000);
+        assert_eq!(curve.real_token_reserves, 793_100_000_000_000);
+        assert_eq!(curve.real_sol_reserves, 5);
+        assert_eq!(curve.token_total_supply, 1_000_000_000_000_000);
+        assert!(curve.complete);
+    }
+
+    #[test]
+    fn ignores_trailing_curve_fields() {
+        let curve = decode_bonding_curve_data(&curve_account(&[7; 32])).unwrap();
+        assert_eq!(curve.token_total_supply, 1_000_000_000_000_000);
+    }
+
+    #[test]
+    fn rejects_truncated_curve_account() {
+        let data = curve_account(&[]);
+        assert!(decode_bonding_curve_data(&data[..48]).is_err());
     }
 }
@@ -0,0 +1,144 @@
+use super::constants::*;
+use super::utils::*;
+use crate::{
+    amounts::max_with_slippage,
+    compute_budget::compute_budget_config,
+    confirmation_tracker::track_signature,
+    data::confirm_transaction,
+    dex::DexType,
+    error::AppError,
+    models::{BuyRequest, BuyResponse},
+    priority_fee::get_priority_fee,
+    simulation::prepare_transaction,
+    spend_guard::spend_guard,
+};
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction, pubkey::Pubkey,
+    signature::Keypair, signer::Signer, system_instruction,
+};
+use solana_transaction_status::UiTransactionEncoding;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use spl_token::instruction as token_instruction;
+use std::str::FromStr;
+use tracing::{debug, field, info, instrument};
+
+#[instrument(
+    name = "trade",
+    skip_all,
+    fields(side = "buy", dex = "pump_swap", token = %request.token_address, signature = field::Empty)
+)]
+pub async fn process_buy_request(
+    rpc_client: &RpcClient,
+    server_keypair: &Keypair,
+    request: &BuyRequest,
+) -> Result<BuyResponse, AppError> {
+    debug!("Processing PumpSwap buy request: {:?}", request);
+
+    let token_mint = Pubkey::from_str(&request.token_address)
+        .map_err(|_| AppError::BadRequest("Invalid token address".to_string()))?;
+
+    let (pool, pool_state) =
+        find_preferred_pool(rpc_client, &token_mint, request.pool_address.as_deref())
+            .await?
+            .ok_or_else(|| {
+                AppError::BadRequest(format!("No PumpSwap pool found for {}", token_mint))
+            })?;
+    let global_config = get_global_config(rpc_client).await?;
+    let reserves = get_pool_reserves(rpc_client, &pool_state).await?;
+
+    let quote_amount_in = request.sol_lamports();
+    let expected_base_out =
+        reserves.calculate_buy_amount(quote_amount_in, global_config.total_fee_basis_points());
+    let max_quote_amount_in = max_with_slippage(quote_amount_in, request.slippage_bps());
+
+    info!(
+        "Swap parameters: pool={}, quote_in={}, expected_base_out={}, max_quote_in={}",
+        pool, quote_amount_in, expected_base_out, max_quote_amount_in
+    );
+
+    let user = server_keypair.pubkey();
+    let wsol_account =
+        spl_associated_token_account::get_associated_token_address(&user, &WSOL_MINT);
+
+    let unit_limit = compute_budget_config().unit_limit(&DexType::PumpSwap);
+    let unit_price = get_priority_fee(rpc_client, &[pool], unit_limit, UNIT_PRICE);
+    info!(
+        "Compute budget: limit {}, price {} micro-lamports",
+        unit_limit, unit_price
+    );
+    let mut instructions = vec![
+        ComputeBudgetInstruction::set_compute_unit_price(unit_price),
+        ComputeBudgetInstruction::set_compute_unit_limit(unit_limit),
+        create_associated_token_account_idempotent(&user, &user, &token_mint, &spl_token::id()),
+        create_associated_token_account_idempotent(&user, &user, &WSOL_MINT, &spl_token::id()),
+        // Wrap enough SOL to cover the slippage ceiling, the rest comes back on close
+        system_instruction::transfer(&user, &wsol_account, max_quote_amount_in),
+        token_instruction::sync_native(&spl_token::id(), &wsol_account)?,
+    ];
+
+    instructions.push(build_swap_instruction(
+        BUY_DISCRIMINATOR,
+        &user,
+        &pool,
+        &pool_state,
+        &global_config,
+        expected_base_out,
+        max_quote_amount_in,
+    ));
+
+    instructions.push(token_instruction::close_account(
+        &spl_token::id(),
+        &wsol_account,
+        &user,
+        &user,
+        &[],
+    )?);
+
+    let mut spend = spend_guard().reserve(request.sol_quantity)?;
+    let transaction = prepare_transaction(
+        rpc_client,
+        instructions,
+        &user,
+        &[server_keypair],
+        !request.skip_simulation,
+    )?;
+
+    info!("Sending transaction...");
+    let signature = rpc_client.send_transaction_with_config(
+        &transaction,
+        RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentConfig::confirmed().commitment),
+            encoding: Some(UiTransactionEncoding::Base64),
+            max_retries: Some(3),
+            min_context_slot: None,
+        },
+    )?;
+    spend.commit();
+
+    info!("Transaction sent, signature: {}", signature);
+
+    track_signature(&signature);
+    match confirm_transaction(rpc_client, &signature, 20, 3).await {
+        Ok(true) => {
+            let decimals = rpc_client
+                .get_token_account_balance(&pool_state.pool_base_token_account)?
+                .decimals;
+
+            Ok(BuyResponse {
+                success: true,
+                signature: signature.to_string(),
+                token_quantity: expected_base_out as f64 / 10f64.powi(decimals as i32),
+                sol_spent: request.sol_quantity,
+                solscan_tx_url: format!("https://solscan.io/tx/{}", signature),
+                error: None,
+                child_signatures: Vec::new(),
+                route: None,
+            })
+        }
+        _ => Err(AppError::ServerError(
+            "Transaction failed during confirmation".to_string(),
+        )),
+    }
+}
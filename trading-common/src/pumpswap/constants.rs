@@ -0,0 +1,23 @@
+use solana_sdk::pubkey::Pubkey;
+
+pub const UNIT_PRICE: u64 = 1_000_000;
+pub const UNIT_BUDGET: u32 = 200_000;
+pub const FEE_BASIS_POINTS_DENOMINATOR: u64 = 10_000;
+
+// Program IDs and account addresses
+pub const PUMP_SWAP_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA");
+pub const GLOBAL_CONFIG: Pubkey =
+    solana_sdk::pubkey!("ADyA8hdefvWN2dbGGWFotbzWxrAvLW83WG6QCVXvJKqw");
+pub const EVENT_AUTHORITY: Pubkey =
+    solana_sdk::pubkey!("GS4CU59F31iL7aR2Q8zVS8DRrcRnXX1yjQ66TqNVQnaR");
+pub const WSOL_MINT: Pubkey = solana_sdk::pubkey!("So11111111111111111111111111111111111111112");
+
+// PDA seeds
+pub const POOL_SEED: &[u8] = b"pool";
+pub const POOL_AUTHORITY_SEED: &[u8] = b"pool-authority";
+pub const CREATOR_VAULT_SEED: &[u8] = b"creator_vault";
+
+// Instruction discriminators
+pub const BUY_DISCRIMINATOR: [u8; 8] = [102, 6, 61, 18, 1, 218, 235, 234];
+pub const SELL_DISCRIMINATOR: [u8; 8] = [51, 230, 133, 164, 1, 127, 131, 173];
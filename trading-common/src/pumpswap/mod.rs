@@ -0,0 +1,13 @@
+pub mod buy;
+pub mod constants;
+pub mod sell;
+pub mod transaction;
+pub mod types;
+pub mod utils;
+
+pub use buy::*;
+pub use constants::*;
+pub use sell::*;
+pub use transaction::*;
+pub use types::*;
+pub use utils::*;
@@ -0,0 +1,128 @@
+use super::constants::*;
+use super::utils::*;
+use crate::{
+    amounts::min_with_slippage,
+    compute_budget::compute_budget_config,
+    confirmation_tracker::track_signature,
+    data::{confirm_transaction, resolve_sell_amount},
+    dex::DexType,
+    error::AppError,
+    models::{SellRequest, SellResponse},
+    priority_fee::get_priority_fee,
+    pumpdotfun::constants::LAMPORTS_PER_SOL,
+    simulation::prepare_transaction,
+};
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction, pubkey::Pubkey,
+    signature::Keypair, signer::Signer,
+};
+use solana_transaction_status::UiTransactionEncoding;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use spl_token::instruction as token_instruction;
+use std::str::FromStr;
+use tracing::{debug, field, info, instrument};
+
+#[instrument(
+    name = "trade",
+    skip_all,
+    fields(side = "sell", dex = "pump_swap", token = %request.token_address, signature = field::Empty)
+)]
+pub async fn process_sell_request(
+    rpc_client: &RpcClient,
+    server_keypair: &Keypair,
+    request: &SellRequest,
+) -> Result<SellResponse, AppError> {
+    debug!("Processing PumpSwap sell request: {:?}", request);
+
+    let token_mint = Pubkey::from_str(&request.token_address)
+        .map_err(|_| AppError::BadRequest("Invalid token address".to_string()))?;
+
+    let (pool, pool_state) =
+        find_preferred_pool(rpc_client, &token_mint, request.pool_address.as_deref())
+            .await?
+            .ok_or_else(|| {
+                AppError::BadRequest(format!("No PumpSwap pool found for {}", token_mint))
+            })?;
+    let global_config = get_global_config(rpc_client).await?;
+    let reserves = get_pool_reserves(rpc_client, &pool_state).await?;
+
+    let user = server_keypair.pubkey();
+    let token_account =
+        spl_associated_token_account::get_associated_token_address(&user, &token_mint);
+    let sell_amount = resolve_sell_amount(rpc_client, &token_account, request)?;
+
+    let base_amount_in = sell_amount.amount;
+    let expected_quote_out =
+        reserves.calculate_sell_amount(base_amount_in, global_config.total_fee_basis_points());
+    let min_quote_amount_out = min_with_slippage(expected_quote_out, request.slippage_bps());
+
+    info!(
+        "Sell calculation: pool={}, base_in={}, expected_quote_out={}, min_quote_out={}",
+        pool, base_amount_in, expected_quote_out, min_quote_amount_out
+    );
+
+    let wsol_account =
+        spl_associated_token_account::get_associated_token_address(&user, &WSOL_MINT);
+
+    let unit_limit = compute_budget_config().unit_limit(&DexType::PumpSwap);
+    let unit_price = get_priority_fee(rpc_client, &[pool], unit_limit, UNIT_PRICE);
+    info!(
+        "Compute budget: limit {}, price {} micro-lamports",
+        unit_limit, unit_price
+    );
+    let instructions = vec![
+        ComputeBudgetInstruction::set_compute_unit_price(unit_price),
+        ComputeBudgetInstruction::set_compute_unit_limit(unit_limit),
+        create_associated_token_account_idempotent(&user, &user, &WSOL_MINT, &spl_token::id()),
+        build_swap_instruction(
+            SELL_DISCRIMINATOR,
+            &user,
+            &pool,
+            &pool_state,
+            &global_config,
+            base_amount_in,
+            min_quote_amount_out,
+        ),
+        token_instruction::close_account(&spl_token::id(), &wsol_account, &user, &user, &[])?,
+    ];
+
+    let transaction = prepare_transaction(
+        rpc_client,
+        instructions,
+        &user,
+        &[server_keypair],
+        !request.skip_simulation,
+    )?;
+
+    let signature = rpc_client.send_transaction_with_config(
+        &transaction,
+        RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentConfig::confirmed().commitment),
+            encoding: Some(UiTransactionEncoding::Base64),
+            max_retries: Some(3),
+            min_context_slot: None,
+        },
+    )?;
+
+    info!("Transaction sent: {}", signature);
+
+    track_signature(&signature);
+    match confirm_transaction(rpc_client, &signature, 20, 3).await {
+        Ok(true) => Ok(SellResponse {
+            success: true,
+            signature: signature.to_string(),
+            token_quantity: sell_amount.ui_amount,
+            sol_received: expected_quote_out as f64 / LAMPORTS_PER_SOL as f64,
+            solscan_tx_url: format!("https://solscan.io/tx/{}", signature),
+            error: None,
+            slippage: None,
+            child_signatures: Vec::new(),
+            route: None,
+        }),
+        _ => Err(AppError::ServerError(
+            "Transaction failed during confirmation".to_string(),
+        )),
+    }
+}
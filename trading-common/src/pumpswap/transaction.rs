@@ -0,0 +1,63 @@
+use crate::utils::transaction::extract_swap_from_balance_changes;
+use crate::TransactionType;
+use anyhow::Result;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+// PumpSwap pools are quoted in WSOL, so the signer's balance changes give the swap
+pub fn extract_transaction_details(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<(TransactionType, String, f64, f64, f64)> {
+    extract_swap_from_balance_changes(transaction, "PumpSwap")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::transaction::extract_swap_accounts;
+    use solana_transaction_status::option_serializer::OptionSerializer;
+
+    fn buy_transaction() -> EncodedConfirmedTransactionWithStatusMeta {
+        serde_json::from_str(include_str!("../../tests/fixtures/pumpswap_buy.json")).unwrap()
+    }
+
+    #[test]
+    fn parses_a_buy_paid_in_sol_and_wsol() {
+        let (transaction_type, token_address, amount_token, amount_sol, price_per_token) =
+            extract_transaction_details(&buy_transaction()).unwrap();
+
+        // 0.5 SOL from the wallet on top of the fee plus 0.5 already wrapped
+        assert_eq!(transaction_type, TransactionType::Buy);
+        assert_eq!(
+            token_address,
+            "PumpSwapMint11111111111111111111111111111pump"
+        );
+        assert_eq!(amount_token, 20000.0);
+        assert_eq!(amount_sol, 1.0);
+        assert_eq!(price_per_token, 1.0 / 20000.0);
+    }
+
+    #[test]
+    fn buyer_is_the_signer_and_seller_the_pool() {
+        let (seller, buyer) = extract_swap_accounts(&buy_transaction(), &TransactionType::Buy);
+
+        assert_eq!(seller, "PumpSwapPoo1111111111111111111111111111111");
+        assert_eq!(buyer, "Trader1111111111111111111111111111111111111");
+    }
+
+    #[test]
+    fn token_to_token_swaps_are_unknown() {
+        let mut transaction = buy_transaction();
+        let meta = transaction.transaction.meta.as_mut().unwrap();
+        // No SOL left the wallet beyond the fee and no WSOL moved
+        meta.post_balances[0] = meta.pre_balances[0] - meta.fee;
+        if let OptionSerializer::Some(balances) = &mut meta.pre_token_balances {
+            balances.retain(|balance| balance.account_index != 1);
+        }
+        if let OptionSerializer::Some(balances) = &mut meta.post_token_balances {
+            balances.retain(|balance| balance.account_index != 1);
+        }
+
+        let (transaction_type, ..) = extract_transaction_details(&transaction).unwrap();
+        assert_eq!(transaction_type, TransactionType::Unknown);
+    }
+}
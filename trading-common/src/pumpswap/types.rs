@@ -0,0 +1,126 @@
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+
+use super::constants::FEE_BASIS_POINTS_DENOMINATOR;
+
+#[derive(Debug, Clone)]
+pub struct PoolState {
+    pub pool_bump: u8,
+    pub index: u16,
+    pub creator: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub lp_mint: Pubkey,
+    pub pool_base_token_account: Pubkey,
+    pub pool_quote_token_account: Pubkey,
+    pub lp_supply: u64,
+    pub coin_creator: Pubkey,
+}
+
+impl PoolState {
+    // Anchor account: 8 byte discriminator followed by the fields in order.
+    // coin_creator was appended later, older pools don't have it
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < 211 {
+            return Err(anyhow::anyhow!("Insufficient data to decode PumpSwap pool"));
+        }
+
+        Ok(Self {
+            pool_bump: data[8],
+            index: u16::from_le_bytes(data[9..11].try_into()?),
+            creator: read_pubkey(data, 11)?,
+            base_mint: read_pubkey(data, 43)?,
+            quote_mint: read_pubkey(data, 75)?,
+            lp_mint: read_pubkey(data, 107)?,
+            pool_base_token_account: read_pubkey(data, 139)?,
+            pool_quote_token_account: read_pubkey(data, 171)?,
+            lp_supply: u64::from_le_bytes(data[203..211].try_into()?),
+            coin_creator: if data.len() >= 243 {
+                read_pubkey(data, 211)?
+            } else {
+                Pubkey::default()
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GlobalConfig {
+    pub admin: Pubkey,
+    pub lp_fee_basis_points: u64,
+    pub protocol_fee_basis_points: u64,
+    pub disable_flags: u8,
+    pub protocol_fee_recipients: Vec<Pubkey>,
+    pub coin_creator_fee_basis_points: u64,
+}
+
+impl GlobalConfig {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < 313 {
+            return Err(anyhow::anyhow!(
+                "Insufficient data to decode PumpSwap global config"
+            ));
+        }
+
+        let protocol_fee_recipients = (0..8)
+            .map(|i| read_pubkey(data, 57 + i * 32))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            admin: read_pubkey(data, 8)?,
+            lp_fee_basis_points: u64::from_le_bytes(data[40..48].try_into()?),
+            protocol_fee_basis_points: u64::from_le_bytes(data[48..56].try_into()?),
+            disable_flags: data[56],
+            protocol_fee_recipients,
+            coin_creator_fee_basis_points: if data.len() >= 321 {
+                u64::from_le_bytes(data[313..321].try_into()?)
+            } else {
+                0
+            },
+        })
+    }
+
+    pub fn total_fee_basis_points(&self) -> u64 {
+        self.lp_fee_basis_points
+            + self.protocol_fee_basis_points
+            + self.coin_creator_fee_basis_points
+    }
+
+    pub fn protocol_fee_recipient(&self) -> Pubkey {
+        self.protocol_fee_recipients
+            .iter()
+            .find(|recipient| **recipient != Pubkey::default())
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolReserves {
+    pub base_reserve: u64,
+    pub quote_reserve: u64,
+}
+
+impl PoolReserves {
+    // Buy fees are charged on top of the quote amount going into the pool
+    pub fn calculate_buy_amount(&self, quote_in: u64, fee_basis_points: u64) -> u64 {
+        let effective_quote_in = quote_in as u128 * FEE_BASIS_POINTS_DENOMINATOR as u128
+            / (FEE_BASIS_POINTS_DENOMINATOR + fee_basis_points) as u128;
+        let base_out = self.base_reserve as u128 * effective_quote_in
+            / (self.quote_reserve as u128 + effective_quote_in);
+        base_out as u64
+    }
+
+    // Sell fees are taken from the quote amount coming out of the pool
+    pub fn calculate_sell_amount(&self, base_in: u64, fee_basis_points: u64) -> u64 {
+        let quote_out = self.quote_reserve as u128 * base_in as u128
+            / (self.base_reserve as u128 + base_in as u128);
+        let fee = quote_out * fee_basis_points as u128 / FEE_BASIS_POINTS_DENOMINATOR as u128;
+        (quote_out - fee) as u64
+    }
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    let bytes: [u8; 32] = data[offset..offset + 32].try_into()?;
+    Ok(Pubkey::new_from_array(bytes))
+}
@@ -0,0 +1,160 @@
+use crate::error::AppError;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use super::{
+    constants::*,
+    types::{GlobalConfig, PoolReserves, PoolState},
+};
+use crate::pumpdotfun::constants::PUMP_FUN_PROGRAM_ID;
+
+// Pools created by pump.fun migrations are owned by a PDA of the bonding curve program
+pub fn derive_canonical_pool_address(mint: &Pubkey) -> Pubkey {
+    let (pool_authority, _) =
+        Pubkey::find_program_address(&[POOL_AUTHORITY_SEED, mint.as_ref()], &PUMP_FUN_PROGRAM_ID);
+    let (pool, _) = Pubkey::find_program_address(
+        &[
+            POOL_SEED,
+            &0u16.to_le_bytes(),
+            pool_authority.as_ref(),
+            mint.as_ref(),
+            WSOL_MINT.as_ref(),
+        ],
+        &PUMP_SWAP_PROGRAM_ID,
+    );
+    pool
+}
+
+pub fn derive_coin_creator_vault(pool_state: &PoolState) -> (Pubkey, Pubkey) {
+    let (vault_authority, _) = Pubkey::find_program_address(
+        &[CREATOR_VAULT_SEED, pool_state.coin_creator.as_ref()],
+        &PUMP_SWAP_PROGRAM_ID,
+    );
+    let vault_ata = spl_associated_token_account::get_associated_token_address(
+        &vault_authority,
+        &pool_state.quote_mint,
+    );
+    (vault_ata, vault_authority)
+}
+
+pub async fn get_pool_state(rpc_client: &RpcClient, pool: &Pubkey) -> Result<PoolState, AppError> {
+    let data = rpc_client.get_account_data(pool)?;
+    Ok(PoolState::decode(&data)?)
+}
+
+pub async fn get_global_config(rpc_client: &RpcClient) -> Result<GlobalConfig, AppError> {
+    let data = rpc_client.get_account_data(&GLOBAL_CONFIG)?;
+    Ok(GlobalConfig::decode(&data)?)
+}
+
+// Returns the PumpSwap pool for a mint, if the token has migrated to one
+pub async fn find_pool_for_mint(
+    rpc_client: &RpcClient,
+    mint: &Pubkey,
+) -> Result<Option<(Pubkey, PoolState)>, AppError> {
+    let pool = derive_canonical_pool_address(mint);
+    match rpc_client.get_account_data(&pool) {
+        Ok(data) => Ok(Some((pool, PoolState::decode(&data)?))),
+        Err(_) => Ok(None),
+    }
+}
+
+// The pool a copied trade went through when it pairs the mint with SOL, the
+// canonical pool otherwise
+pub async fn find_preferred_pool(
+    rpc_client: &RpcClient,
+    mint: &Pubkey,
+    preferred: Option<&str>,
+) -> Result<Option<(Pubkey, PoolState)>, AppError> {
+    if let Some(pool) = preferred.and_then(|pool| Pubkey::from_str(pool).ok()) {
+        match get_pool_state(rpc_client, &pool).await {
+            Ok(pool_state)
+                if pool_state.base_mint == *mint && pool_state.quote_mint == WSOL_MINT =>
+            {
+                return Ok(Some((pool, pool_state)))
+            }
+            Ok(_) => info!(
+                "Pool {} can't be used for {}, using the canonical pool",
+                pool, mint
+            ),
+            Err(e) => warn!("Failed to load pool {}: {}", pool, e),
+        }
+    }
+    find_pool_for_mint(rpc_client, mint).await
+}
+
+pub async fn get_pool_reserves(
+    rpc_client: &RpcClient,
+    pool_state: &PoolState,
+) -> Result<PoolReserves, AppError> {
+    let base = rpc_client.get_token_account_balance(&pool_state.pool_base_token_account)?;
+    let quote = rpc_client.get_token_account_balance(&pool_state.pool_quote_token_account)?;
+
+    Ok(PoolReserves {
+        base_reserve: base.amount.parse().map_err(|e| {
+            AppError::TokenAccountError(format!("Invalid base reserve amount: {}", e))
+        })?,
+        quote_reserve: quote.amount.parse().map_err(|e| {
+            AppError::TokenAccountError(format!("Invalid quote reserve amount: {}", e))
+        })?,
+    })
+}
+
+// Buy and sell share the same account list
+pub fn build_swap_instruction(
+    discriminator: [u8; 8],
+    user: &Pubkey,
+    pool: &Pubkey,
+    pool_state: &PoolState,
+    global_config: &GlobalConfig,
+    base_amount: u64,
+    quote_amount: u64,
+) -> Instruction {
+    let mut data = Vec::with_capacity(24);
+    data.extend_from_slice(&discriminator);
+    data.extend_from_slice(&base_amount.to_le_bytes());
+    data.extend_from_slice(&quote_amount.to_le_bytes());
+
+    let user_base_token_account =
+        spl_associated_token_account::get_associated_token_address(user, &pool_state.base_mint);
+    let user_quote_token_account =
+        spl_associated_token_account::get_associated_token_address(user, &pool_state.quote_mint);
+    let protocol_fee_recipient = global_config.protocol_fee_recipient();
+    let protocol_fee_recipient_token_account =
+        spl_associated_token_account::get_associated_token_address(
+            &protocol_fee_recipient,
+            &pool_state.quote_mint,
+        );
+    let (coin_creator_vault_ata, coin_creator_vault_authority) =
+        derive_coin_creator_vault(pool_state);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new(*user, true),
+        AccountMeta::new_readonly(GLOBAL_CONFIG, false),
+        AccountMeta::new_readonly(pool_state.base_mint, false),
+        AccountMeta::new_readonly(pool_state.quote_mint, false),
+        AccountMeta::new(user_base_token_account, false),
+        AccountMeta::new(user_quote_token_account, false),
+        AccountMeta::new(pool_state.pool_base_token_account, false),
+        AccountMeta::new(pool_state.pool_quote_token_account, false),
+        AccountMeta::new_readonly(protocol_fee_recipient, false),
+        AccountMeta::new(protocol_fee_recipient_token_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(EVENT_AUTHORITY, false),
+        AccountMeta::new_readonly(PUMP_SWAP_PROGRAM_ID, false),
+        AccountMeta::new(coin_creator_vault_ata, false),
+        AccountMeta::new_readonly(coin_creator_vault_authority, false),
+    ];
+
+    Instruction::new_with_bytes(PUMP_SWAP_PROGRAM_ID, &data, accounts)
+}
@@ -1,162 +1,285 @@
 use super::constants::*;
-use super::types::PoolKeys;
+use super::layouts::RaydiumPoolType;
 use crate::{
-    data::confirm_transaction,
+    compute_budget::compute_budget_config,
+    confirmation_tracker::track_signature,
+    dex::DexType,
     error::AppError,
     extract_transaction_details,
     models::{BuyRequest, BuyResponse},
+    priority_fee::get_priority_fee,
     raydium::{
-        constants::{COMPUTE_BUDGET_PRICE, COMPUTE_BUDGET_UNITS, LAMPORTS_PER_SOL},
+        constants::COMPUTE_BUDGET_PRICE,
+        route::process_usdc_route_buy_request,
         utils::{
-            create_swap_instruction, create_wsol_account_instructions, get_pool_info, get_pool_keys,
+            close_wsol_account_instruction, create_cpmm_swap_instruction, create_swap_instruction,
+            create_wsol_account_instructions, detect_pool_type, find_usdc_pool, get_amm_pool_keys,
+            get_cpmm_pool_state, quote_swap_from_reserves, resolve_preferred_pool_id,
         },
     },
+    send::{check_requote, confirm_before_expiry, send_config, ConfirmationOutcome},
+    simulation::prepare_transaction_with_expiry,
+    spend_guard::spend_guard,
 };
 use solana_client::{rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
 use solana_sdk::{
-    commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction,
-    message::Message, pubkey::Pubkey, signature::Keypair, signer::Signer, transaction::Transaction,
+    commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction, pubkey::Pubkey,
+    signature::Keypair, signer::Signer,
 };
 use solana_transaction_status::UiTransactionEncoding;
-use spl_token::instruction as token_instruction;
 use std::str::FromStr;
+use tracing::{debug, field, info, instrument, warn};
 
+#[instrument(
+    name = "trade",
+    skip_all,
+    fields(side = "buy", dex = "raydium", token = %request.token_address, signature = field::Empty)
+)]
 pub async fn process_buy_request(
     rpc_client: &RpcClient,
     server_keypair: &Keypair,
     request: &BuyRequest,
 ) -> Result<BuyResponse, AppError> {
-    println!("Processing Raydium buy request: {:?}", request);
+    debug!("Processing Raydium buy request: {:?}", request);
 
-    // Get pool info first to verify pool exists
-    let pool_info = get_pool_info(&request.token_address).await?;
-    println!("Found pool info: {}", pool_info.id);
+    // Resolve the pool first to verify it exists, tokens without a SOL pool go through USDC
+    let pool_id = match resolve_preferred_pool_id(
+        rpc_client,
+        &request.token_address,
+        request.pool_address.as_deref(),
+    )
+    .await
+    {
+        Ok(pool_id) => pool_id,
+        Err(e) => match find_usdc_pool(rpc_client, &request.token_address).await? {
+            Some(usdc_pool_id) => {
+                return process_usdc_route_buy_request(
+                    rpc_client,
+                    server_keypair,
+                    request,
+                    &usdc_pool_id,
+                )
+                .await
+            }
+            None => return Err(e),
+        },
+    };
+    info!("Found pool: {}", pool_id);
 
-    // Get complete pool keys
-    let pool_keys = get_pool_keys(&pool_info.id).await?;
-    let pool_keys = PoolKeys::from(pool_keys);
-    println!("Pool keys fetched successfully for pool {}", pool_info.id);
+    let pool_type = detect_pool_type(rpc_client, &pool_id)?;
+    info!("Pool {} is a {:?} pool", pool_id, pool_type);
 
     // Validate token accounts and amounts
     let token_mint = Pubkey::from_str(&request.token_address)
         .map_err(|_| AppError::BadRequest("Invalid token address".to_string()))?;
 
-    let amount_in = (request.sol_quantity * LAMPORTS_PER_SOL as f64) as u64;
-    let minimum_out = ((amount_in as f64) * (1.0 - request.slippage_tolerance)) as u64;
+    let amount_in = request.sol_lamports();
 
-    println!(
-        "Swap parameters: amount_in={}, minimum_out={}, slippage={}",
-        amount_in, minimum_out, request.slippage_tolerance
-    );
+    let (cpmm_pool_state, amm_pool_keys) = match pool_type {
+        RaydiumPoolType::AmmV4 => {
+            let pool_keys = get_amm_pool_keys(rpc_client, &pool_id).await?;
+            debug!("Pool keys fetched successfully for pool {}", pool_id);
+            (None, Some(pool_keys))
+        }
+        RaydiumPoolType::Cpmm => (Some(get_cpmm_pool_state(rpc_client, &pool_id)?), None),
+        RaydiumPoolType::Clmm => {
+            return Err(AppError::BadRequest(format!(
+                "Swaps on CLMM pool {} are not supported",
+                pool_id
+            )))
+        }
+    };
 
-    // Create temporary WSOL account
-    let (wsol_keypair, wsol_instructions) =
-        create_wsol_account_instructions(rpc_client, server_keypair, amount_in).await?;
+    let wsol_mint = Pubkey::from_str(WSOL)?;
 
-    // Get or create token account
-    let token_account = spl_associated_token_account::get_associated_token_address(
+    // Get or create token account. Token-2022 mints on CPMM pools need their own token program
+    let token_program = match &cpmm_pool_state {
+        Some(pool_state) if pool_state.token_0_mint == token_mint => pool_state.token_0_program,
+        Some(pool_state) => pool_state.token_1_program,
+        None => spl_token::id(),
+    };
+    let token_account = spl_associated_token_account::get_associated_token_address_with_program_id(
         &server_keypair.pubkey(),
         &token_mint,
+        &token_program,
     );
 
-    // Check if token account exists, if not add creation instruction
-    let mut instructions = vec![
-        ComputeBudgetInstruction::set_compute_unit_price(COMPUTE_BUDGET_PRICE),
-        ComputeBudgetInstruction::set_compute_unit_limit(COMPUTE_BUDGET_UNITS),
-    ];
-
-    // Add WSOL account instructions
-    instructions.extend(wsol_instructions);
-
-    // Add token account creation if needed
-    if rpc_client.get_account(&token_account).is_err() {
-        println!("Creating new associated token account");
-        instructions.push(
-            spl_associated_token_account::instruction::create_associated_token_account(
-                &server_keypair.pubkey(),
-                &server_keypair.pubkey(),
-                &token_mint,
-                &spl_token::id(),
-            ),
+    let max_rebuilds = send_config().max_rebuilds;
+    let mut original_minimum_out = None;
+    let mut confirmed_signature = None;
+
+    // Reserved once and committed on the first send, rebuilds resend the same spend
+    let mut spend = spend_guard().reserve(request.sol_quantity)?;
+
+    // An expired blockhash means the swap never landed, it is requoted and rebuilt
+    for attempt in 0..=max_rebuilds {
+        // Min-out comes from the pool's live reserves
+        let quote = quote_swap_from_reserves(
+            rpc_client,
+            &pool_id,
+            pool_type,
+            &wsol_mint,
+            amount_in,
+            request.slippage_bps(),
+        )?;
+        let minimum_out = quote.minimum_out;
+        match original_minimum_out {
+            Some(original) => check_requote(original, quote.expected_out)?,
+            None => original_minimum_out = Some(minimum_out),
+        }
+
+        info!(
+            "Swap parameters: amount_in={}, expected_out={}, minimum_out={}, slippage_bps={}",
+            amount_in,
+            quote.expected_out,
+            minimum_out,
+            request.slippage_bps()
+        );
+
+        // Create temporary WSOL account
+        let (wsol_keypair, wsol_instructions) =
+            create_wsol_account_instructions(rpc_client, server_keypair, amount_in).await?;
+
+        // Check if token account exists, if not add creation instruction
+        let unit_limit = compute_budget_config().unit_limit(&DexType::Raydium);
+        let unit_price = get_priority_fee(rpc_client, &[pool_id], unit_limit, COMPUTE_BUDGET_PRICE);
+        info!(
+            "Compute budget: limit {}, price {} micro-lamports",
+            unit_limit, unit_price
         );
+        let mut instructions = vec![
+            ComputeBudgetInstruction::set_compute_unit_price(unit_price),
+            ComputeBudgetInstruction::set_compute_unit_limit(unit_limit),
+        ];
+
+        // Add WSOL account instructions
+        instructions.extend(wsol_instructions);
+
+        // Add token account creation if needed
+        if rpc_client.get_account(&token_account).is_err() {
+            info!("Creating new associated token account");
+            instructions.push(
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &server_keypair.pubkey(),
+                    &server_keypair.pubkey(),
+                    &token_mint,
+                    &token_program,
+                ),
+            );
+        }
+
+        // Create swap instruction
+        let swap_ix = match (&cpmm_pool_state, &amm_pool_keys) {
+            (Some(pool_state), _) => create_cpmm_swap_instruction(
+                &pool_id,
+                pool_state,
+                &wsol_mint,
+                amount_in,
+                minimum_out,
+                wsol_keypair.pubkey(),
+                token_account,
+                server_keypair,
+            )?,
+            (None, Some(pool_keys)) => create_swap_instruction(
+                pool_keys,
+                amount_in,
+                minimum_out,
+                wsol_keypair.pubkey(),
+                token_account,
+                server_keypair,
+            )?,
+            (None, None) => {
+                return Err(AppError::ServerError(format!(
+                    "No swap accounts resolved for pool {}",
+                    pool_id
+                )))
+            }
+        };
+        instructions.push(swap_ix);
+
+        // Add close WSOL account instruction
+        instructions.push(close_wsol_account_instruction(
+            &wsol_keypair.pubkey(),
+            &server_keypair.pubkey(),
+        )?);
+
+        // Execute transaction
+        let (transaction, last_valid_block_height) = prepare_transaction_with_expiry(
+            rpc_client,
+            instructions,
+            &server_keypair.pubkey(),
+            &[server_keypair, &wsol_keypair],
+            !request.skip_simulation,
+        )?;
+
+        info!("Sending transaction...");
+        let signature = rpc_client.send_transaction_with_config(
+            &transaction,
+            RpcSendTransactionConfig {
+                skip_preflight: true,
+                preflight_commitment: Some(CommitmentConfig::confirmed().commitment),
+                encoding: Some(UiTransactionEncoding::Base64),
+                max_retries: Some(3),
+                min_context_slot: None,
+            },
+        )?;
+        spend.commit();
+
+        info!("Transaction sent, signature: {}", signature);
+
+        // Wait for confirmation
+        track_signature(&signature);
+        match confirm_before_expiry(rpc_client, &signature, last_valid_block_height).await? {
+            ConfirmationOutcome::Confirmed => {
+                confirmed_signature = Some(signature);
+                break;
+            }
+            ConfirmationOutcome::Failed => {
+                return Err(AppError::ServerError(
+                    "Transaction failed during confirmation".to_string(),
+                ))
+            }
+            ConfirmationOutcome::Expired => warn!(
+                "Buy {} expired without landing, rebuilding ({}/{})",
+                signature,
+                attempt + 1,
+                max_rebuilds
+            ),
+        }
     }
 
-    // Create swap instruction
-    let swap_ix = create_swap_instruction(
-        &pool_keys,
-        amount_in,
-        minimum_out,
-        wsol_keypair.pubkey(),
-        token_account,
-        server_keypair,
-    )?;
-    instructions.push(swap_ix);
+    let signature = confirmed_signature.ok_or_else(|| {
+        AppError::ServerError(format!(
+            "Buy transaction expired {} times without landing",
+            max_rebuilds + 1
+        ))
+    })?;
+    info!("Transaction confirmed successfully");
 
-    // Add close WSOL account instruction
-    let close_wsol_ix = token_instruction::close_account(
-        &Pubkey::from_str(TOKEN_PROGRAM_ID)?,
-        &wsol_keypair.pubkey(),
-        &server_keypair.pubkey(),
-        &server_keypair.pubkey(),
-        &[],
-    )?;
-    instructions.push(close_wsol_ix);
-
-    // Execute transaction
-    let recent_blockhash = rpc_client.get_latest_blockhash()?;
-    let message = Message::new_with_blockhash(
-        &instructions,
-        Some(&server_keypair.pubkey()),
-        &recent_blockhash,
-    );
-    let transaction = Transaction::new(&[server_keypair, &wsol_keypair], message, recent_blockhash);
-
-    println!("Sending transaction...");
-    let signature = rpc_client.send_transaction_with_config(
-        &transaction,
-        RpcSendTransactionConfig {
-            skip_preflight: true,
-            preflight_commitment: Some(CommitmentConfig::confirmed().commitment),
-            encoding: Some(UiTransactionEncoding::Base64),
-            max_retries: Some(3),
-            min_context_slot: None,
+    // Get the transaction data to extract exact token amount received
+    let tx_data = rpc_client.get_transaction_with_config(
+        &signature,
+        solana_client::rpc_config::RpcTransactionConfig {
+            encoding: Some(solana_transaction_status::UiTransactionEncoding::Json),
+            commitment: Some(solana_sdk::commitment_config::CommitmentConfig::confirmed()),
+            max_supported_transaction_version: Some(0),
         },
     )?;
 
-    println!("Transaction sent, signature: {}", signature);
-
-    // Wait for confirmation
-    match confirm_transaction(rpc_client, &signature, 20, 3).await {
-        Ok(true) => {
-            println!("Transaction confirmed successfully");
-
-            // Get the transaction data to extract exact token amount received
-            let tx_data = rpc_client.get_transaction_with_config(
-                &signature,
-                solana_client::rpc_config::RpcTransactionConfig {
-                    encoding: Some(solana_transaction_status::UiTransactionEncoding::Json),
-                    commitment: Some(solana_sdk::commitment_config::CommitmentConfig::confirmed()),
-                    max_supported_transaction_version: Some(0),
-                },
-            )?;
-
-            println!("Transaction data: {:?}", tx_data);
-
-            // Extract token amount from transaction data
-            let (_, _, amount_token, _, _) = extract_transaction_details(&tx_data)?;
-            println!("Tokens received from swap: {}", amount_token);
-
-            Ok(BuyResponse {
-                success: true,
-                signature: signature.to_string(),
-                token_quantity: amount_token,
-                sol_spent: request.sol_quantity,
-                solscan_tx_url: format!("https://solscan.io/tx/{}", signature),
-                error: None,
-            })
-        }
-        _ => Err(AppError::ServerError(
-            "Transaction failed during confirmation".to_string(),
-        )),
-    }
+    debug!("Transaction data: {:?}", tx_data);
+
+    // Extract token amount from transaction data
+    let (_, _, amount_token, _, _) = extract_transaction_details(&tx_data)?;
+    info!("Tokens received from swap: {}", amount_token);
+
+    Ok(BuyResponse {
+        success: true,
+        signature: signature.to_string(),
+        token_quantity: amount_token,
+        sol_spent: request.sol_quantity,
+        solscan_tx_url: format!("https://solscan.io/tx/{}", signature),
+        error: None,
+        child_signatures: Vec::new(),
+        route: None,
+    })
 }
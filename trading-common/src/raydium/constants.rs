@@ -12,6 +12,9 @@ pub const RAY_AUTHORITY_V4: Pubkey =
 pub const OPEN_BOOK_PROGRAM: Pubkey =
     solana_sdk::pubkey!("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX");
 pub const SOL: &str = "So11111111111111111111111111111111111111112";
+pub const USDC: Pubkey = solana_sdk::pubkey!("EPjFWdd5AufqSSqeM2qjxEPQ7WnWkEXkZzKRYCjvZkWj");
+pub const SOL_USDC_AMM_POOL: Pubkey =
+    solana_sdk::pubkey!("58oQChx4yWmvKdwLLZzBi4ChoCc2fqCUWBkwMihLYQo2");
 
 pub const SOL_DECIMALS: u8 = 9;
 pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
@@ -20,3 +23,12 @@ pub const ACCOUNT_FLAGS_LAYOUT_SIZE: usize = 8;
 pub const TOKEN_ACCOUNT_LAYOUT_SIZE: usize = 165;
 pub const MARKET_STATE_LAYOUT_V3_SIZE: usize = 388;
 pub const LIQUIDITY_STATE_LAYOUT_V4_SIZE: usize = 752;
+
+pub const RAY_CPMM_PROGRAM_ID: &str = "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C";
+pub const RAY_CLMM_PROGRAM_ID: &str = "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK";
+pub const RAY_CPMM: Pubkey = solana_sdk::pubkey!("CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C");
+pub const RAY_CLMM: Pubkey = solana_sdk::pubkey!("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK");
+pub const RAY_CPMM_AUTHORITY: Pubkey =
+    solana_sdk::pubkey!("GpMZbSM2GgvTKHJirzeGfMFoaZ8UR2X7F4v8vHTvxFbL");
+pub const CPMM_SWAP_BASE_INPUT_DISCRIMINATOR: [u8; 8] = [143, 190, 90, 218, 196, 30, 51, 222];
+pub const CPMM_FEE_RATE_DENOMINATOR: u64 = 1_000_000;
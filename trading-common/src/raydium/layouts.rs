@@ -0,0 +1,509 @@
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+
+use super::constants::{
+    CPMM_FEE_RATE_DENOMINATOR, LIQUIDITY_STATE_LAYOUT_V4_SIZE, MARKET_STATE_LAYOUT_V3_SIZE,
+    RAY_CLMM, RAY_CPMM, RAY_V4,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RaydiumPoolType {
+    AmmV4,
+    Cpmm,
+    Clmm,
+}
+
+impl RaydiumPoolType {
+    // Pools are identified by the program that owns the pool account
+    pub fn from_owner(owner: &Pubkey) -> Option<Self> {
+        if *owner == RAY_V4 {
+            Some(Self::AmmV4)
+        } else if *owner == RAY_CPMM {
+            Some(Self::Cpmm)
+        } else if *owner == RAY_CLMM {
+            Some(Self::Clmm)
+        } else {
+            None
+        }
+    }
+}
+
+// Anchor account, fields follow the 8 byte discriminator
+#[derive(Debug, Clone)]
+pub struct CpmmPoolState {
+    pub amm_config: Pubkey,
+    pub pool_creator: Pubkey,
+    pub token_0_vault: Pubkey,
+    pub token_1_vault: Pubkey,
+    pub lp_mint: Pubkey,
+    pub token_0_mint: Pubkey,
+    pub token_1_mint: Pubkey,
+    pub token_0_program: Pubkey,
+    pub token_1_program: Pubkey,
+    pub observation_key: Pubkey,
+    pub auth_bump: u8,
+    pub status: u8,
+    pub lp_mint_decimals: u8,
+    pub mint_0_decimals: u8,
+    pub mint_1_decimals: u8,
+    pub lp_supply: u64,
+    pub protocol_fees_token_0: u64,
+    pub protocol_fees_token_1: u64,
+    pub fund_fees_token_0: u64,
+    pub fund_fees_token_1: u64,
+    pub open_time: u64,
+}
+
+impl CpmmPoolState {
+    pub const LEN: usize = 381;
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < Self::LEN {
+            return Err(anyhow::anyhow!("Data length too short for CPMM pool state"));
+        }
+
+        Ok(Self {
+            amm_config: read_pubkey(data, 8)?,
+            pool_creator: read_pubkey(data, 40)?,
+            token_0_vault: read_pubkey(data, 72)?,
+            token_1_vault: read_pubkey(data, 104)?,
+            lp_mint: read_pubkey(data, 136)?,
+            token_0_mint: read_pubkey(data, 168)?,
+            token_1_mint: read_pubkey(data, 200)?,
+            token_0_program: read_pubkey(data, 232)?,
+            token_1_program: read_pubkey(data, 264)?,
+            observation_key: read_pubkey(data, 296)?,
+            auth_bump: data[328],
+            status: data[329],
+            lp_mint_decimals: data[330],
+            mint_0_decimals: data[331],
+            mint_1_decimals: data[332],
+            lp_supply: read_u64(data, 333)?,
+            protocol_fees_token_0: read_u64(data, 341)?,
+            protocol_fees_token_1: read_u64(data, 349)?,
+            fund_fees_token_0: read_u64(data, 357)?,
+            fund_fees_token_1: read_u64(data, 365)?,
+            open_time: read_u64(data, 373)?,
+        })
+    }
+
+    // Vaults also hold uncollected protocol and fund fees, which aren't tradable liquidity
+    pub fn reserves(&self, vault_0_amount: u64, vault_1_amount: u64) -> (u64, u64) {
+        (
+            vault_0_amount
+                .saturating_sub(self.protocol_fees_token_0)
+                .saturating_sub(self.fund_fees_token_0),
+            vault_1_amount
+                .saturating_sub(self.protocol_fees_token_1)
+                .saturating_sub(self.fund_fees_token_1),
+        )
+    }
+
+    // Returns (vault, mint, token program) for the input and output side of a swap
+    pub fn swap_sides(
+        &self,
+        input_mint: &Pubkey,
+    ) -> Result<((Pubkey, Pubkey, Pubkey), (Pubkey, Pubkey, Pubkey))> {
+        let side_0 = (self.token_0_vault, self.token_0_mint, self.token_0_program);
+        let side_1 = (self.token_1_vault, self.token_1_mint, self.token_1_program);
+
+        if *input_mint == self.token_0_mint {
+            Ok((side_0, side_1))
+        } else if *input_mint == self.token_1_mint {
+            Ok((side_1, side_0))
+        } else {
+            Err(anyhow::anyhow!(
+                "Mint {} is not part of this pool",
+                input_mint
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CpmmAmmConfig {
+    pub bump: u8,
+    pub disable_create_pool: bool,
+    pub index: u16,
+    pub trade_fee_rate: u64,
+    pub protocol_fee_rate: u64,
+    pub fund_fee_rate: u64,
+}
+
+impl CpmmAmmConfig {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < 36 {
+            return Err(anyhow::anyhow!("Data length too short for CPMM amm config"));
+        }
+
+        Ok(Self {
+            bump: data[8],
+            disable_create_pool: data[9] != 0,
+            index: u16::from_le_bytes(data[10..12].try_into()?),
+            trade_fee_rate: read_u64(data, 12)?,
+            protocol_fee_rate: read_u64(data, 20)?,
+            fund_fee_rate: read_u64(data, 28)?,
+        })
+    }
+
+    // Constant product output for an exact input, trade fee taken from the input
+    pub fn calculate_amount_out(&self, amount_in: u64, reserve_in: u64, reserve_out: u64) -> u64 {
+        let fee = (amount_in as u128 * self.trade_fee_rate as u128)
+            .div_ceil(CPMM_FEE_RATE_DENOMINATOR as u128);
+        let amount_in_after_fee = amount_in as u128 - fee;
+        let amount_out =
+            reserve_out as u128 * amount_in_after_fee / (reserve_in as u128 + amount_in_after_fee);
+        amount_out as u64
+    }
+}
+
+// Only the leading fields of the CLMM pool are decoded; tick and reward state
+// isn't needed to locate vaults or read the current price
+#[derive(Debug, Clone)]
+pub struct ClmmPoolState {
+    pub bump: u8,
+    pub amm_config: Pubkey,
+    pub owner: Pubkey,
+    pub token_mint_0: Pubkey,
+    pub token_mint_1: Pubkey,
+    pub token_vault_0: Pubkey,
+    pub token_vault_1: Pubkey,
+    pub observation_key: Pubkey,
+    pub mint_decimals_0: u8,
+    pub mint_decimals_1: u8,
+    pub tick_spacing: u16,
+    pub liquidity: u128,
+    pub sqrt_price_x64: u128,
+    pub tick_current: i32,
+}
+
+impl ClmmPoolState {
+    pub const MIN_LEN: usize = 273;
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < Self::MIN_LEN {
+            return Err(anyhow::anyhow!("Data length too short for CLMM pool state"));
+        }
+
+        Ok(Self {
+            bump: data[8],
+            amm_config: read_pubkey(data, 9)?,
+            owner: read_pubkey(data, 41)?,
+            token_mint_0: read_pubkey(data, 73)?,
+            token_mint_1: read_pubkey(data, 105)?,
+            token_vault_0: read_pubkey(data, 137)?,
+            token_vault_1: read_pubkey(data, 169)?,
+            observation_key: read_pubkey(data, 201)?,
+            mint_decimals_0: data[233],
+            mint_decimals_1: data[234],
+            tick_spacing: u16::from_le_bytes(data[235..237].try_into()?),
+            liquidity: u128::from_le_bytes(data[237..253].try_into()?),
+            sqrt_price_x64: u128::from_le_bytes(data[253..269].try_into()?),
+            tick_current: i32::from_le_bytes(data[269..273].try_into()?),
+        })
+    }
+
+    // Price of token 0 denominated in token 1
+    pub fn get_price(&self) -> f64 {
+        let sqrt_price = self.sqrt_price_x64 as f64 / 2f64.powi(64);
+        sqrt_price
+            * sqrt_price
+            * 10f64.powi(self.mint_decimals_0 as i32 - self.mint_decimals_1 as i32)
+    }
+}
+
+// Account keys of the AMM v4 liquidity state, the numeric state before them isn't needed
+#[derive(Debug, Clone)]
+pub struct AmmV4PoolState {
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub lp_mint: Pubkey,
+    pub open_orders: Pubkey,
+    pub market_id: Pubkey,
+    pub market_program_id: Pubkey,
+    pub target_orders: Pubkey,
+}
+
+impl AmmV4PoolState {
+    pub const BASE_VAULT_OFFSET: usize = 336;
+    pub const BASE_MINT_OFFSET: usize = 400;
+    pub const QUOTE_MINT_OFFSET: usize = 432;
+    // Trade fee of 25 basis points
+    pub const FEE_NUMERATOR: u64 = 25;
+    pub const FEE_DENOMINATOR: u64 = 10_000;
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < LIQUIDITY_STATE_LAYOUT_V4_SIZE {
+            return Err(anyhow::anyhow!(
+                "Data length too short for AMM v4 pool state"
+            ));
+        }
+
+        Ok(Self {
+            base_vault: read_pubkey(data, Self::BASE_VAULT_OFFSET)?,
+            quote_vault: read_pubkey(data, Self::BASE_VAULT_OFFSET + 32)?,
+            base_mint: read_pubkey(data, Self::BASE_MINT_OFFSET)?,
+            quote_mint: read_pubkey(data, Self::QUOTE_MINT_OFFSET)?,
+            lp_mint: read_pubkey(data, 464)?,
+            open_orders: read_pubkey(data, 496)?,
+            market_id: read_pubkey(data, 528)?,
+            market_program_id: read_pubkey(data, 560)?,
+            target_orders: read_pubkey(data, 592)?,
+        })
+    }
+
+    pub fn calculate_amount_out(amount_in: u64, reserve_in: u64, reserve_out: u64) -> u64 {
+        let amount_in_after_fee = amount_in as u128
+            * (Self::FEE_DENOMINATOR - Self::FEE_NUMERATOR) as u128
+            / Self::FEE_DENOMINATOR as u128;
+        (reserve_out as u128 * amount_in_after_fee / (reserve_in as u128 + amount_in_after_fee))
+            as u64
+    }
+}
+
+// OpenBook / Serum v3 market, starts with the 5 byte "serum" padding
+#[derive(Debug, Clone)]
+pub struct MarketStateV3 {
+    pub vault_signer_nonce: u64,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub event_queue: Pubkey,
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+}
+
+impl MarketStateV3 {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < MARKET_STATE_LAYOUT_V3_SIZE {
+            return Err(anyhow::anyhow!("Data length too short for market state"));
+        }
+
+        Ok(Self {
+            vault_signer_nonce: read_u64(data, 45)?,
+            base_vault: read_pubkey(data, 117)?,
+            quote_vault: read_pubkey(data, 165)?,
+            event_queue: read_pubkey(data, 253)?,
+            bids: read_pubkey(data, 285)?,
+            asks: read_pubkey(data, 317)?,
+        })
+    }
+
+    pub fn authority(&self, market_id: &Pubkey, market_program_id: &Pubkey) -> Result<Pubkey> {
+        Pubkey::create_program_address(
+            &[market_id.as_ref(), &self.vault_signer_nonce.to_le_bytes()],
+            market_program_id,
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to derive market authority: {}", e))
+    }
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    let bytes: [u8; 32] = data[offset..offset + 32].try_into()?;
+    Ok(Pubkey::new_from_array(bytes))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(data[offset..offset + 8].try_into()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(seed: u8) -> Pubkey {
+        Pubkey::new_from_array([seed; 32])
+    }
+
+    fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    #[test]
+    fn pool_type_from_owner() {
+        assert_eq!(
+            RaydiumPoolType::from_owner(&RAY_V4),
+            Some(RaydiumPoolType::AmmV4)
+        );
+        assert_eq!(
+            RaydiumPoolType::from_owner(&RAY_CPMM),
+            Some(RaydiumPoolType::Cpmm)
+        );
+        assert_eq!(
+            RaydiumPoolType::from_owner(&RAY_CLMM),
+            Some(RaydiumPoolType::Clmm)
+        );
+        assert_eq!(RaydiumPoolType::from_owner(&key(1)), None);
+    }
+
+    #[test]
+    fn decodes_cpmm_pool_at_known_offsets() {
+        let mut data = vec![0u8; CpmmPoolState::LEN];
+        for (index, offset) in (8..=296).step_by(32).enumerate() {
+            put(&mut data, offset, key(index as u8 + 1).as_ref());
+        }
+        put(&mut data, 328, &[254, 1, 9, 6, 9]);
+        for (index, offset) in (333..=373).step_by(8).enumerate() {
+            put(&mut data, offset, &(index as u64 + 100).to_le_bytes());
+        }
+
+        let pool = CpmmPoolState::decode(&data).unwrap();
+        assert_eq!(pool.amm_config, key(1));
+        assert_eq!(pool.token_0_vault, key(3));
+        assert_eq!(pool.token_1_vault, key(4));
+        assert_eq!(pool.token_0_mint, key(6));
+        assert_eq!(pool.token_1_mint, key(7));
+        assert_eq!(pool.token_0_program, key(8));
+        assert_eq!(pool.token_1_program, key(9));
+        assert_eq!(pool.observation_key, key(10));
+        assert_eq!(pool.auth_bump, 254);
+        assert_eq!(pool.mint_0_decimals, 6);
+        assert_eq!(pool.mint_1_decimals, 9);
+        assert_eq!(pool.lp_supply, 100);
+        assert_eq!(pool.protocol_fees_token_0, 101);
+        assert_eq!(pool.fund_fees_token_1, 104);
+        assert_eq!(pool.open_time, 105);
+
+        assert!(CpmmPoolState::decode(&data[..CpmmPoolState::LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn cpmm_reserves_exclude_uncollected_fees() {
+        let mut data = vec![0u8; CpmmPoolState::LEN];
+        put(&mut data, 341, &10u64.to_le_bytes());
+        put(&mut data, 349, &20u64.to_le_bytes());
+        put(&mut data, 357, &1u64.to_le_bytes());
+        put(&mut data, 365, &2u64.to_le_bytes());
+        let pool = CpmmPoolState::decode(&data).unwrap();
+
+        assert_eq!(pool.reserves(1_000, 1_000), (989, 978));
+        assert_eq!(pool.reserves(5, 5), (0, 0));
+    }
+
+    #[test]
+    fn cpmm_swap_sides_follow_the_input_mint() {
+        let mut data = vec![0u8; CpmmPoolState::LEN];
+        put(&mut data, 72, key(3).as_ref());
+        put(&mut data, 104, key(4).as_ref());
+        put(&mut data, 168, key(6).as_ref());
+        put(&mut data, 200, key(7).as_ref());
+        let pool = CpmmPoolState::decode(&data).unwrap();
+
+        let (input, output) = pool.swap_sides(&key(7)).unwrap();
+        assert_eq!((input.0, input.1), (key(4), key(7)));
+        assert_eq!((output.0, output.1), (key(3), key(6)));
+        assert!(pool.swap_sides(&key(42)).is_err());
+    }
+
+    #[test]
+    fn decodes_cpmm_amm_config_and_quotes() {
+        let mut data = vec![0u8; 36];
+        put(&mut data, 8, &[255, 1]);
+        put(&mut data, 10, &3u16.to_le_bytes());
+        put(&mut data, 12, &2_500u64.to_le_bytes());
+        put(&mut data, 20, &120_000u64.to_le_bytes());
+        put(&mut data, 28, &40_000u64.to_le_bytes());
+
+        let config = CpmmAmmConfig::decode(&data).unwrap();
+        assert_eq!(config.bump, 255);
+        assert!(config.disable_create_pool);
+        assert_eq!(config.index, 3);
+        assert_eq!(config.trade_fee_rate, 2_500);
+        assert_eq!(config.protocol_fee_rate, 120_000);
+        assert_eq!(config.fund_fee_rate, 40_000);
+
+        assert_eq!(
+            config.calculate_amount_out(1_000_000, 1_000_000_000, 1_000_000_000),
+            996_505
+        );
+        // The fee is rounded up, a 1 unit input pays it all
+        assert_eq!(config.calculate_amount_out(1, 1_000, 1_000), 0);
+        assert!(CpmmAmmConfig::decode(&data[..35]).is_err());
+    }
+
+    #[test]
+    fn decodes_clmm_pool_at_known_offsets() {
+        let mut data = vec![0u8; ClmmPoolState::MIN_LEN];
+        data[8] = 250;
+        for (index, offset) in (9..=201).step_by(32).enumerate() {
+            put(&mut data, offset, key(index as u8 + 1).as_ref());
+        }
+        put(&mut data, 233, &[9, 6]);
+        put(&mut data, 235, &60u16.to_le_bytes());
+        put(&mut data, 237, &5_000u128.to_le_bytes());
+        put(&mut data, 253, &(1u128 << 64).to_le_bytes());
+        put(&mut data, 269, &(-42i32).to_le_bytes());
+
+        let pool = ClmmPoolState::decode(&data).unwrap();
+        assert_eq!(pool.bump, 250);
+        assert_eq!(pool.amm_config, key(1));
+        assert_eq!(pool.token_mint_0, key(3));
+        assert_eq!(pool.token_mint_1, key(4));
+        assert_eq!(pool.token_vault_0, key(5));
+        assert_eq!(pool.token_vault_1, key(6));
+        assert_eq!(pool.observation_key, key(7));
+        assert_eq!(pool.tick_spacing, 60);
+        assert_eq!(pool.liquidity, 5_000);
+        assert_eq!(pool.tick_current, -42);
+        // sqrt price of 1.0 with 9 and 6 decimals
+        assert!((pool.get_price() - 1_000.0).abs() < 1e-9);
+
+        assert!(ClmmPoolState::decode(&data[..ClmmPoolState::MIN_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn decodes_amm_v4_pool_at_known_offsets() {
+        let mut data = vec![0u8; LIQUIDITY_STATE_LAYOUT_V4_SIZE];
+        for (index, offset) in (AmmV4PoolState::BASE_VAULT_OFFSET..=592)
+            .step_by(32)
+            .enumerate()
+        {
+            put(&mut data, offset, key(index as u8 + 1).as_ref());
+        }
+
+        let pool = AmmV4PoolState::decode(&data).unwrap();
+        assert_eq!(pool.base_vault, key(1));
+        assert_eq!(pool.quote_vault, key(2));
+        assert_eq!(pool.base_mint, key(3));
+        assert_eq!(pool.quote_mint, key(4));
+        assert_eq!(pool.lp_mint, key(5));
+        assert_eq!(pool.open_orders, key(6));
+        assert_eq!(pool.market_id, key(7));
+        assert_eq!(pool.market_program_id, key(8));
+        assert_eq!(pool.target_orders, key(9));
+
+        assert!(AmmV4PoolState::decode(&data[..LIQUIDITY_STATE_LAYOUT_V4_SIZE - 1]).is_err());
+    }
+
+    #[test]
+    fn amm_v4_quote_takes_the_fee_from_the_input() {
+        assert_eq!(
+            AmmV4PoolState::calculate_amount_out(1_000_000, 1_000_000_000, 1_000_000_000),
+            996_505
+        );
+        assert_eq!(AmmV4PoolState::calculate_amount_out(0, 1_000, 1_000), 0);
+        assert_eq!(AmmV4PoolState::calculate_amount_out(1_000, 0, 0), 0);
+    }
+
+    #[test]
+    fn decodes_market_state_at_known_offsets() {
+        let mut data = vec![0u8; MARKET_STATE_LAYOUT_V3_SIZE];
+        put(&mut data, 45, &7u64.to_le_bytes());
+        put(&mut data, 117, key(1).as_ref());
+        put(&mut data, 165, key(2).as_ref());
+        put(&mut data, 253, key(3).as_ref());
+        put(&mut data, 285, key(4).as_ref());
+        put(&mut data, 317, key(5).as_ref());
+
+        let market = MarketStateV3::decode(&data).unwrap();
+        assert_eq!(market.vault_signer_nonce, 7);
+        assert_eq!(market.base_vault, key(1));
+        assert_eq!(market.quote_vault, key(2));
+        assert_eq!(market.event_queue, key(3));
+        assert_eq!(market.bids, key(4));
+        assert_eq!(market.asks, key(5));
+
+        assert!(MarketStateV3::decode(&data[..MARKET_STATE_LAYOUT_V3_SIZE - 1]).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 pub mod buy;
 pub mod constants;
+pub mod layouts;
+pub mod route;
 pub mod sell;
 pub mod transaction;
 pub mod types;
@@ -7,6 +9,8 @@ pub mod utils;
 
 pub use buy::*;
 pub use constants::*;
+pub use layouts::*;
+pub use route::*;
 pub use sell::*;
 pub use transaction::*;
 pub use types::*;
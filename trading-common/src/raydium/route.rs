@@ -0,0 +1,420 @@
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction, message::Message, packet::PACKET_DATA_SIZE, pubkey::Pubkey,
+    signature::Keypair, signer::Signer,
+};
+use solana_transaction_status::UiTransactionEncoding;
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use std::str::FromStr;
+use tracing::{field, info, instrument};
+
+use super::constants::*;
+use super::layouts::RaydiumPoolType;
+use super::types::PoolKeys;
+use super::utils::{
+    close_wsol_account_instruction, create_swap_instruction, create_wsol_account_instructions,
+    get_amm_pool_keys, quote_swap_from_reserves,
+};
+use crate::{
+    amounts::two_leg_slippage_bps,
+    compute_budget::compute_budget_config,
+    confirmation_tracker::track_signature,
+    data::{confirm_transaction, resolve_sell_amount},
+    dex::DexType,
+    error::AppError,
+    models::{BuyRequest, BuyResponse, SellRequest, SellResponse},
+    priority_fee::get_priority_fee,
+    simulation::prepare_transaction,
+    spend_guard::{spend_guard, SpendReservation},
+};
+
+// Recorded on the trade execution row of every trade that went through USDC
+pub const USDC_ROUTE: &str = "usdc";
+
+struct RouteLeg {
+    pool_keys: PoolKeys,
+    input_mint: Pubkey,
+    amount_in: u64,
+    minimum_out: u64,
+}
+
+struct RouteResult {
+    signatures: Vec<String>,
+    minimum_out: u64,
+}
+
+// Tokens that only trade against USDC are reached with SOL -> USDC on the
+// SOL/USDC pool followed by USDC -> token
+#[instrument(
+    name = "trade",
+    skip_all,
+    fields(side = "buy", dex = "raydium_usdc_route", token = %request.token_address, signature = field::Empty)
+)]
+pub async fn process_usdc_route_buy_request(
+    rpc_client: &RpcClient,
+    server_keypair: &Keypair,
+    request: &BuyRequest,
+    token_pool_id: &Pubkey,
+) -> Result<BuyResponse, AppError> {
+    info!(
+        "Routing buy of {} through USDC pool {}",
+        request.token_address, token_pool_id
+    );
+
+    let token_mint = Pubkey::from_str(&request.token_address)?;
+    let owner = server_keypair.pubkey();
+    let amount_in = request.sol_lamports();
+    let token_account = get_associated_token_address(&owner, &token_mint);
+    let balance_before = token_ui_balance(rpc_client, &token_account);
+
+    let mut spend = spend_guard().reserve(request.sol_quantity)?;
+    let result = execute_usdc_route(
+        rpc_client,
+        server_keypair,
+        &SOL_USDC_AMM_POOL,
+        token_pool_id,
+        &Pubkey::from_str(WSOL)?,
+        &token_mint,
+        amount_in,
+        request.slippage_bps(),
+        !request.skip_simulation,
+        Some(&mut spend),
+    )
+    .await?;
+
+    let token_quantity = token_ui_balance(rpc_client, &token_account) - balance_before;
+    let signature = result.signatures.last().cloned().unwrap_or_default();
+
+    Ok(BuyResponse {
+        success: true,
+        solscan_tx_url: format!("https://solscan.io/tx/{}", signature),
+        signature,
+        token_quantity,
+        sol_spent: request.sol_quantity,
+        error: None,
+        child_signatures: result.signatures,
+        route: Some(USDC_ROUTE.to_string()),
+    })
+}
+
+#[instrument(
+    name = "trade",
+    skip_all,
+    fields(side = "sell", dex = "raydium_usdc_route", token = %request.token_address, signature = field::Empty)
+)]
+pub async fn process_usdc_route_sell_request(
+    rpc_client: &RpcClient,
+    server_keypair: &Keypair,
+    request: &SellRequest,
+    token_pool_id: &Pubkey,
+) -> Result<SellResponse, AppError> {
+    info!(
+        "Routing sell of {} through USDC pool {}",
+        request.token_address, token_pool_id
+    );
+
+    let token_mint = Pubkey::from_str(&request.token_address)?;
+    let owner = server_keypair.pubkey();
+    let token_account = get_associated_token_address(&owner, &token_mint);
+    let sell_amount = resolve_sell_amount(rpc_client, &token_account, request)?;
+    let sol_before = rpc_client.get_balance(&owner).ok();
+
+    let result = execute_usdc_route(
+        rpc_client,
+        server_keypair,
+        token_pool_id,
+        &SOL_USDC_AMM_POOL,
+        &token_mint,
+        &Pubkey::from_str(WSOL)?,
+        sell_amount.amount,
+        request.slippage_bps(),
+        !request.skip_simulation,
+        None,
+    )
+    .await?;
+
+    // The WSOL account is closed at the end of the route, so the wallet's SOL
+    // balance change is what was received net of fees. The quoted minimum is
+    // only used when a balance read fails
+    let lamports_received = match (sol_before, rpc_client.get_balance(&owner).ok()) {
+        (Some(before), Some(after)) => after.saturating_sub(before),
+        _ => result.minimum_out,
+    };
+    let signature = result.signatures.last().cloned().unwrap_or_default();
+
+    Ok(SellResponse {
+        success: true,
+        solscan_tx_url: format!("https://solscan.io/tx/{}", signature),
+        signature,
+        token_quantity: sell_amount.ui_amount,
+        sol_received: lamports_received as f64 / LAMPORTS_PER_SOL as f64,
+        error: None,
+        slippage: None,
+        child_signatures: result.signatures,
+        route: Some(USDC_ROUTE.to_string()),
+    })
+}
+
+// Slippage is split evenly across both legs so the route as a whole stays
+// within the requested tolerance. A buy's spend reservation is committed as
+// soon as the first transaction is sent
+#[allow(clippy::too_many_arguments)]
+async fn execute_usdc_route(
+    rpc_client: &RpcClient,
+    server_keypair: &Keypair,
+    first_pool_id: &Pubkey,
+    second_pool_id: &Pubkey,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount_in: u64,
+    slippage_bps: u16,
+    simulate: bool,
+    mut spend: Option<&mut SpendReservation<'_>>,
+) -> Result<RouteResult, AppError> {
+    let leg_slippage_bps = two_leg_slippage_bps(slippage_bps);
+
+    let first_pool = get_amm_pool_keys(rpc_client, first_pool_id).await?;
+    let first_min_out = quote_leg(
+        rpc_client,
+        &first_pool,
+        input_mint,
+        amount_in,
+        leg_slippage_bps,
+    )?;
+    let second_pool = get_amm_pool_keys(rpc_client, second_pool_id).await?;
+    // The second leg spends exactly what the first leg is guaranteed to deliver
+    let second_min_out = quote_leg(
+        rpc_client,
+        &second_pool,
+        &USDC,
+        first_min_out,
+        leg_slippage_bps,
+    )?;
+
+    info!(
+        "USDC route: {} in -> min {} USDC -> min {} out (slippage per leg {} bps)",
+        amount_in, first_min_out, second_min_out, leg_slippage_bps
+    );
+
+    let first_leg = RouteLeg {
+        pool_keys: first_pool,
+        input_mint: *input_mint,
+        amount_in,
+        minimum_out: first_min_out,
+    };
+    let second_leg = RouteLeg {
+        pool_keys: second_pool,
+        input_mint: USDC,
+        amount_in: first_min_out,
+        minimum_out: second_min_out,
+    };
+
+    let (instructions, wsol_keypair) = build_route_instructions(
+        rpc_client,
+        server_keypair,
+        &[&first_leg, &second_leg],
+        output_mint,
+    )
+    .await?;
+
+    // Two AMM v4 swaps carry a lot of accounts, a legacy transaction can't always hold both
+    let mut signatures = Vec::new();
+    if fits_in_single_transaction(&instructions, &server_keypair.pubkey()) {
+        info!("USDC route fits in a single transaction");
+        signatures.push(
+            send_route_transaction(
+                rpc_client,
+                server_keypair,
+                instructions,
+                wsol_keypair,
+                simulate,
+                spend,
+            )
+            .await?,
+        );
+    } else {
+        info!("USDC route split into two transactions");
+        for leg in [&first_leg, &second_leg] {
+            let (instructions, wsol_keypair) =
+                build_route_instructions(rpc_client, server_keypair, &[leg], output_mint).await?;
+            signatures.push(
+                send_route_transaction(
+                    rpc_client,
+                    server_keypair,
+                    instructions,
+                    wsol_keypair,
+                    simulate,
+                    spend.as_deref_mut(),
+                )
+                .await?,
+            );
+        }
+    }
+
+    info!("USDC route signatures: {:?}", signatures);
+
+    Ok(RouteResult {
+        signatures,
+        minimum_out: second_min_out,
+    })
+}
+
+fn quote_leg(
+    rpc_client: &RpcClient,
+    pool_keys: &PoolKeys,
+    input_mint: &Pubkey,
+    amount_in: u64,
+    slippage_bps: u16,
+) -> Result<u64, AppError> {
+    let quote = quote_swap_from_reserves(
+        rpc_client,
+        &pool_keys.id,
+        RaydiumPoolType::AmmV4,
+        input_mint,
+        amount_in,
+        slippage_bps,
+    )?;
+    Ok(quote.minimum_out)
+}
+
+// SOL legs go through a temporary WSOL account, every other mint uses the ATA
+async fn build_route_instructions(
+    rpc_client: &RpcClient,
+    server_keypair: &Keypair,
+    legs: &[&RouteLeg],
+    output_mint: &Pubkey,
+) -> Result<(Vec<Instruction>, Option<Keypair>), AppError> {
+    let owner = server_keypair.pubkey();
+    let wsol_mint = Pubkey::from_str(WSOL)?;
+
+    let unit_limit = compute_budget_config().unit_limit(&DexType::Raydium) * legs.len() as u32;
+    let pools = legs.iter().map(|leg| leg.pool_keys.id).collect::<Vec<_>>();
+    let unit_price = get_priority_fee(rpc_client, &pools, unit_limit, COMPUTE_BUDGET_PRICE);
+    info!(
+        "Compute budget: limit {}, price {} micro-lamports",
+        unit_limit, unit_price
+    );
+
+    let mut instructions = vec![
+        ComputeBudgetInstruction::set_compute_unit_price(unit_price),
+        ComputeBudgetInstruction::set_compute_unit_limit(unit_limit),
+    ];
+    let mut wsol_keypair = None;
+
+    let touches_sol = legs.iter().any(|leg| leg.input_mint == wsol_mint)
+        || legs.last().map(|leg| leg_output_mint(leg)) == Some(wsol_mint);
+    if touches_sol {
+        let sol_in = legs
+            .iter()
+            .find(|leg| leg.input_mint == wsol_mint)
+            .map(|leg| leg.amount_in)
+            .unwrap_or(0);
+        let (keypair, wsol_instructions) =
+            create_wsol_account_instructions(rpc_client, server_keypair, sol_in).await?;
+        instructions.extend(wsol_instructions);
+        wsol_keypair = Some(keypair);
+    }
+    let wsol_account = wsol_keypair.as_ref().map(|keypair| keypair.pubkey());
+
+    let account_for = |mint: &Pubkey| match wsol_account {
+        Some(wsol_account) if *mint == wsol_mint => wsol_account,
+        _ => get_associated_token_address(&owner, mint),
+    };
+
+    for mint in [&USDC, output_mint] {
+        if *mint != wsol_mint {
+            instructions.push(create_associated_token_account_idempotent(
+                &owner,
+                &owner,
+                mint,
+                &spl_token::id(),
+            ));
+        }
+    }
+
+    for leg in legs {
+        instructions.push(create_swap_instruction(
+            &leg.pool_keys,
+            leg.amount_in,
+            leg.minimum_out,
+            account_for(&leg.input_mint),
+            account_for(&leg_output_mint(leg)),
+            server_keypair,
+        )?);
+    }
+
+    if let Some(wsol_account) = wsol_account {
+        instructions.push(close_wsol_account_instruction(&wsol_account, &owner)?);
+    }
+
+    Ok((instructions, wsol_keypair))
+}
+
+fn leg_output_mint(leg: &RouteLeg) -> Pubkey {
+    if leg.pool_keys.base_mint == leg.input_mint {
+        leg.pool_keys.quote_mint
+    } else {
+        leg.pool_keys.base_mint
+    }
+}
+
+fn fits_in_single_transaction(instructions: &[Instruction], payer: &Pubkey) -> bool {
+    let message = Message::new(instructions, Some(payer));
+    let size = 1 + message.header.num_required_signatures as usize * 64 + message.serialize().len();
+    size <= PACKET_DATA_SIZE
+}
+
+async fn send_route_transaction(
+    rpc_client: &RpcClient,
+    server_keypair: &Keypair,
+    instructions: Vec<Instruction>,
+    wsol_keypair: Option<Keypair>,
+    simulate: bool,
+    spend: Option<&mut SpendReservation<'_>>,
+) -> Result<String, AppError> {
+    let mut signers = vec![server_keypair];
+    signers.extend(wsol_keypair.as_ref());
+
+    let transaction = prepare_transaction(
+        rpc_client,
+        instructions,
+        &server_keypair.pubkey(),
+        &signers,
+        simulate,
+    )?;
+
+    let signature = rpc_client.send_transaction_with_config(
+        &transaction,
+        RpcSendTransactionConfig {
+            skip_preflight: true,
+            preflight_commitment: Some(CommitmentConfig::confirmed().commitment),
+            encoding: Some(UiTransactionEncoding::Base64),
+            max_retries: Some(3),
+            min_context_slot: None,
+        },
+    )?;
+    info!("Route transaction sent: {}", signature);
+    if let Some(spend) = spend {
+        spend.commit();
+    }
+
+    track_signature(&signature);
+    match confirm_transaction(rpc_client, &signature, 20, 3).await {
+        Ok(true) => Ok(signature.to_string()),
+        _ => Err(AppError::ServerError(format!(
+            "Route transaction {} failed during confirmation",
+            signature
+        ))),
+    }
+}
+
+fn token_ui_balance(rpc_client: &RpcClient, token_account: &Pubkey) -> f64 {
+    rpc_client
+        .get_token_account_balance(token_account)
+        .ok()
+        .and_then(|balance| balance.ui_amount)
+        .unwrap_or(0.0)
+}
@@ -1,145 +1,269 @@
 use std::str::FromStr;
+use tracing::{debug, field, info, instrument, warn};
 
 use crate::{
+    compute_budget::compute_budget_config,
+    confirmation_tracker::track_signature,
     create_wsol_account_instructions,
-    data::confirm_transaction,
+    data::resolve_sell_amount,
+    dex::DexType,
     error::AppError,
-    models::{SellRequest, SellResponse},
+    models::{SellRequest, SellResponse, SlippageReport},
+    priority_fee::get_priority_fee,
+    send::{check_requote, confirm_before_expiry, send_config, ConfirmationOutcome},
+    simulation::prepare_transaction_with_expiry,
 };
 
 use super::constants::*;
-use super::types::PoolKeys;
+use super::layouts::RaydiumPoolType;
+use super::route::process_usdc_route_sell_request;
+use super::transaction::extract_transaction_details;
 use super::utils::*;
-use solana_client::{rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcSendTransactionConfig, RpcTransactionConfig},
+};
 use solana_sdk::{
-    commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction,
-    message::Message, pubkey::Pubkey, signature::Keypair, signer::Signer, transaction::Transaction,
+    commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction, pubkey::Pubkey,
+    signature::Keypair, signer::Signer,
 };
 use solana_transaction_status::UiTransactionEncoding;
-use spl_token::instruction as token_instruction;
 
+#[instrument(
+    name = "trade",
+    skip_all,
+    fields(side = "sell", dex = "raydium", token = %request.token_address, signature = field::Empty)
+)]
 pub async fn process_sell_request(
     rpc_client: &RpcClient,
     server_keypair: &Keypair,
     request: &SellRequest,
 ) -> Result<SellResponse, AppError> {
-    println!("Processing Raydium sell request: {:?}", request);
-
-    // Get pool info and market data
-    let pool_info = get_pool_info(&request.token_address).await?;
-    let pool_keys = get_pool_keys(&pool_info.id).await?;
-    let pool_keys = PoolKeys::from(pool_keys);
-
-    // Get token decimals from the pool info
-    let token_decimals = if pool_info.mint_a.address == request.token_address {
-        pool_info.mint_a.decimals
-    } else {
-        pool_info.mint_b.decimals
-    };
-    println!("Token decimals: {}", token_decimals);
-
-    // Calculate amounts using correct decimals
-    let amount_in = (request.token_quantity * 10f64.powi(token_decimals)) as u64;
-    let expected_sol_output = pool_info.price * request.token_quantity;
-    let minimum_out = ((expected_sol_output * (1.0 - request.slippage_tolerance))
-        * LAMPORTS_PER_SOL as f64) as u64;
-
-    println!(
-        "Sell calculation:\n\
-         Amount in (raw): {}\n\
-         Expected SOL out: {} SOL\n\
-         Minimum SOL out: {} SOL",
-        amount_in,
-        expected_sol_output,
-        minimum_out as f64 / LAMPORTS_PER_SOL as f64
-    );
+    debug!("Processing Raydium sell request: {:?}", request);
 
-    // Create WSOL account
-    let (wsol_keypair, wsol_instructions) = create_wsol_account_instructions(
+    // Resolve the pool and its market data
+    let pool_id = match resolve_preferred_pool_id(
         rpc_client,
-        server_keypair,
-        0, // No initial SOL for selling
+        &request.token_address,
+        request.pool_address.as_deref(),
     )
-    .await?;
+    .await
+    {
+        Ok(pool_id) => pool_id,
+        Err(e) => match find_usdc_pool(rpc_client, &request.token_address).await? {
+            Some(usdc_pool_id) => {
+                return process_usdc_route_sell_request(
+                    rpc_client,
+                    server_keypair,
+                    request,
+                    &usdc_pool_id,
+                )
+                .await
+            }
+            None => return Err(e),
+        },
+    };
+    let pool_type = detect_pool_type(rpc_client, &pool_id)?;
+    info!("Pool {} is a {:?} pool", pool_id, pool_type);
+
+    let token_mint = Pubkey::from_str(&request.token_address)?;
 
-    // Build transaction
-    let mut instructions = vec![
-        ComputeBudgetInstruction::set_compute_unit_price(COMPUTE_BUDGET_PRICE),
-        ComputeBudgetInstruction::set_compute_unit_limit(COMPUTE_BUDGET_UNITS),
-    ];
-    instructions.extend(wsol_instructions);
+    let (cpmm_pool_state, amm_pool_keys) = match pool_type {
+        RaydiumPoolType::AmmV4 => (None, Some(get_amm_pool_keys(rpc_client, &pool_id).await?)),
+        RaydiumPoolType::Cpmm => (Some(get_cpmm_pool_state(rpc_client, &pool_id)?), None),
+        RaydiumPoolType::Clmm => {
+            return Err(AppError::BadRequest(format!(
+                "Swaps on CLMM pool {} are not supported",
+                pool_id
+            )))
+        }
+    };
 
     // Get token account
-    let token_mint = Pubkey::from_str(&request.token_address)?;
-    let token_account = spl_associated_token_account::get_associated_token_address(
+    let token_program = match &cpmm_pool_state {
+        Some(pool_state) if pool_state.token_0_mint == token_mint => pool_state.token_0_program,
+        Some(pool_state) => pool_state.token_1_program,
+        None => spl_token::id(),
+    };
+    let token_account = spl_associated_token_account::get_associated_token_address_with_program_id(
         &server_keypair.pubkey(),
         &token_mint,
+        &token_program,
     );
 
-    // Check balance
-    let balance = get_token_balance(rpc_client, token_account).await?;
-    if balance < request.token_quantity {
-        return Err(AppError::BadRequest(format!(
-            "Insufficient token balance. Have {} but tried to sell {}",
-            balance, request.token_quantity
-        )));
+    // Check balance, decimals come from the token account
+    let sell_amount = resolve_sell_amount(rpc_client, &token_account, request)?;
+    let amount_in = sell_amount.amount;
+
+    let max_rebuilds = send_config().max_rebuilds;
+    let mut original_minimum_out = None;
+    let mut confirmed = None;
+
+    // An expired blockhash means the swap never landed, it is requoted and rebuilt
+    for attempt in 0..=max_rebuilds {
+        // Min-out comes from the pool's live reserves for the exact amount sold
+        let quote = quote_swap_from_reserves(
+            rpc_client,
+            &pool_id,
+            pool_type,
+            &token_mint,
+            amount_in,
+            request.slippage_bps(),
+        )?;
+        let expected_sol_output = quote.expected_out as f64 / LAMPORTS_PER_SOL as f64;
+        let minimum_out = quote.minimum_out;
+        match original_minimum_out {
+            Some(original) => check_requote(original, quote.expected_out)?,
+            None => original_minimum_out = Some(minimum_out),
+        }
+
+        info!(
+            "Sell calculation:\n\
+             Amount in (raw): {}\n\
+             Expected SOL out: {} SOL\n\
+             Minimum SOL out: {} SOL",
+            amount_in,
+            expected_sol_output,
+            minimum_out as f64 / LAMPORTS_PER_SOL as f64
+        );
+
+        // Create WSOL account
+        let (wsol_keypair, wsol_instructions) = create_wsol_account_instructions(
+            rpc_client,
+            server_keypair,
+            0, // No initial SOL for selling
+        )
+        .await?;
+
+        // Build transaction
+        let unit_limit = compute_budget_config().unit_limit(&DexType::Raydium);
+        let unit_price = get_priority_fee(rpc_client, &[pool_id], unit_limit, COMPUTE_BUDGET_PRICE);
+        info!(
+            "Compute budget: limit {}, price {} micro-lamports",
+            unit_limit, unit_price
+        );
+        let mut instructions = vec![
+            ComputeBudgetInstruction::set_compute_unit_price(unit_price),
+            ComputeBudgetInstruction::set_compute_unit_limit(unit_limit),
+        ];
+        instructions.extend(wsol_instructions);
+
+        // Create swap instruction
+        let swap_ix = match (&cpmm_pool_state, &amm_pool_keys) {
+            (Some(pool_state), _) => create_cpmm_swap_instruction(
+                &pool_id,
+                pool_state,
+                &token_mint,
+                amount_in,
+                minimum_out,
+                token_account,
+                wsol_keypair.pubkey(),
+                server_keypair,
+            )?,
+            (None, Some(pool_keys)) => create_swap_instruction(
+                pool_keys,
+                amount_in,
+                minimum_out,
+                token_account,
+                wsol_keypair.pubkey(),
+                server_keypair,
+            )?,
+            (None, None) => {
+                return Err(AppError::ServerError(format!(
+                    "No swap accounts resolved for pool {}",
+                    pool_id
+                )))
+            }
+        };
+        instructions.push(swap_ix);
+
+        // Add close WSOL account instruction
+        instructions.push(close_wsol_account_instruction(
+            &wsol_keypair.pubkey(),
+            &server_keypair.pubkey(),
+        )?);
+
+        // Execute transaction
+        let (transaction, last_valid_block_height) = prepare_transaction_with_expiry(
+            rpc_client,
+            instructions,
+            &server_keypair.pubkey(),
+            &[server_keypair, &wsol_keypair],
+            !request.skip_simulation,
+        )?;
+
+        // Send and confirm
+        let signature = rpc_client.send_transaction_with_config(
+            &transaction,
+            RpcSendTransactionConfig {
+                skip_preflight: true,
+                preflight_commitment: Some(CommitmentConfig::confirmed().commitment),
+                encoding: Some(UiTransactionEncoding::Base64),
+                max_retries: Some(3),
+                min_context_slot: None,
+            },
+        )?;
+
+        info!("Transaction sent: {}", signature);
+
+        track_signature(&signature);
+        match confirm_before_expiry(rpc_client, &signature, last_valid_block_height).await? {
+            ConfirmationOutcome::Confirmed => {
+                confirmed = Some((signature, expected_sol_output));
+                break;
+            }
+            ConfirmationOutcome::Failed => {
+                return Err(AppError::ServerError(
+                    "Transaction failed during confirmation".to_string(),
+                ))
+            }
+            ConfirmationOutcome::Expired => warn!(
+                "Sell {} expired without landing, rebuilding ({}/{})",
+                signature,
+                attempt + 1,
+                max_rebuilds
+            ),
+        }
     }
 
-    // Create swap instruction
-    let swap_ix = create_swap_instruction(
-        &pool_keys,
-        amount_in,
-        minimum_out,
-        token_account,
-        wsol_keypair.pubkey(),
-        server_keypair,
-    )?;
-    instructions.push(swap_ix);
-
-    // Add close WSOL account instruction
-    let close_wsol_ix = token_instruction::close_account(
-        &Pubkey::from_str(TOKEN_PROGRAM_ID)?,
-        &wsol_keypair.pubkey(),
-        &server_keypair.pubkey(),
-        &server_keypair.pubkey(),
-        &[],
-    )?;
-    instructions.push(close_wsol_ix);
-
-    // Execute transaction
-    let recent_blockhash = rpc_client.get_latest_blockhash()?;
-    let message = Message::new_with_blockhash(
-        &instructions,
-        Some(&server_keypair.pubkey()),
-        &recent_blockhash,
+    let (signature, expected_sol_output) = confirmed.ok_or_else(|| {
+        AppError::ServerError(format!(
+            "Sell transaction expired {} times without landing",
+            max_rebuilds + 1
+        ))
+    })?;
+
+    // Realized output comes from the confirmed transaction's balance changes
+    let realized_sol_output = rpc_client
+        .get_transaction_with_config(
+            &signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Json),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .ok()
+        .and_then(|tx_data| extract_transaction_details(&tx_data).ok())
+        .map(|(_, _, _, amount_sol, _)| amount_sol)
+        .unwrap_or(expected_sol_output);
+    let slippage = SlippageReport::new(expected_sol_output, realized_sol_output);
+    info!(
+        "Sell slippage: expected {} SOL, realized {} SOL ({:.2}%)",
+        slippage.expected_sol,
+        slippage.realized_sol,
+        slippage.slippage * 100.0
     );
-    let transaction = Transaction::new(&[server_keypair, &wsol_keypair], message, recent_blockhash);
-
-    // Send and confirm
-    let signature = rpc_client.send_transaction_with_config(
-        &transaction,
-        RpcSendTransactionConfig {
-            skip_preflight: true,
-            preflight_commitment: Some(CommitmentConfig::confirmed().commitment),
-            encoding: Some(UiTransactionEncoding::Base64),
-            max_retries: Some(3),
-            min_context_slot: None,
-        },
-    )?;
-
-    println!("Transaction sent: {}", signature);
-
-    match confirm_transaction(rpc_client, &signature, 20, 3).await {
-        Ok(true) => Ok(SellResponse {
-            success: true,
-            signature: signature.to_string(),
-            token_quantity: request.token_quantity,
-            sol_received: expected_sol_output,
-            solscan_tx_url: format!("https://solscan.io/tx/{}", signature),
-            error: None,
-        }),
-        _ => Err(AppError::ServerError(
-            "Transaction failed during confirmation".to_string(),
-        )),
-    }
+
+    Ok(SellResponse {
+        success: true,
+        signature: signature.to_string(),
+        token_quantity: sell_amount.ui_amount,
+        sol_received: realized_sol_output,
+        solscan_tx_url: format!("https://solscan.io/tx/{}", signature),
+        error: None,
+        slippage: Some(slippage),
+        child_signatures: Vec::new(),
+        route: None,
+    })
 }
@@ -1,7 +1,10 @@
 use super::constants::WSOL;
+use crate::data::token_balance_change;
+use crate::utils::transaction::get_transaction_signer;
 use crate::TransactionType;
 use anyhow::Result;
 use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use tracing::info;
 
 pub fn extract_transaction_details(
     transaction: &EncodedConfirmedTransactionWithStatusMeta,
@@ -15,11 +18,11 @@ pub fn extract_transaction_details(
     let empty_logs = Vec::new();
     let logs = meta.log_messages.as_ref().unwrap_or(&empty_logs);
 
-    println!("Analyzing Raydium transaction logs...");
+    info!("Analyzing Raydium transaction logs...");
 
     // Find ray_log entry
     let ray_log = logs.iter().find(|log| log.contains("ray_log:"));
-    println!("Found ray_log: {:?}", ray_log);
+    info!("Found ray_log: {:?}", ray_log);
 
     let empty_token_balances = Vec::new();
     let pre_balances = meta
@@ -31,36 +34,30 @@ pub fn extract_transaction_details(
         .as_ref()
         .unwrap_or(&empty_token_balances);
 
-    // Find the non-WSOL token
-    let token_balance = pre_balances
+    // Find the signer's non-WSOL token account, falling back to any non-WSOL token
+    let signer = get_transaction_signer(transaction);
+    let token_balance = post_balances
         .iter()
-        .find(|balance| balance.mint != WSOL)
+        .chain(pre_balances.iter())
+        .find(|balance| {
+            balance.mint != WSOL
+                && Option::<String>::from(balance.owner.clone()).as_deref() == Some(signer.as_str())
+        })
+        .or_else(|| pre_balances.iter().find(|balance| balance.mint != WSOL))
         .ok_or_else(|| anyhow::anyhow!("Could not find token balance"))?;
 
     let token_address = token_balance.mint.clone();
 
-    // Calculate token amount change for the non-WSOL token
-    let pre_amount = pre_balances
-        .iter()
-        .find(|b| b.mint == token_address)
-        .and_then(|b| b.ui_token_amount.ui_amount)
-        .unwrap_or(0.0);
-
-    let post_amount = post_balances
-        .iter()
-        .find(|b| b.mint == token_address)
-        .and_then(|b| b.ui_token_amount.ui_amount)
-        .unwrap_or(0.0);
-
     // If token amount increases, it's a buy. If it decreases, it's a sell
-    let token_amount_change = post_amount - pre_amount;
-    println!("Token amount change: {}", token_amount_change);
+    let token_amount_change =
+        token_balance_change(pre_balances, post_balances, token_balance.account_index);
+    info!("Token amount change: {}", token_amount_change);
 
     let transaction_type = if token_amount_change > 0.0 {
-        println!("Detected Raydium BUY (token balance increased)");
+        info!("Detected Raydium BUY (token balance increased)");
         TransactionType::Buy
     } else {
-        println!("Detected Raydium SELL (token balance decreased)");
+        info!("Detected Raydium SELL (token balance decreased)");
         TransactionType::Sell
     };
 
@@ -77,12 +74,12 @@ pub fn extract_transaction_details(
         0.0
     };
 
-    println!("Transaction summary:");
-    println!("  Token: {}", token_address);
-    println!("  Type: {:?}", transaction_type);
-    println!("  Amount token: {}", amount_token);
-    println!("  Amount SOL: {}", amount_sol);
-    println!("  Price per token: {}", price_per_token);
+    info!("Transaction summary:");
+    info!("  Token: {}", token_address);
+    info!("  Type: {:?}", transaction_type);
+    info!("  Amount token: {}", amount_token);
+    info!("  Amount SOL: {}", amount_sol);
+    info!("  Price per token: {}", price_per_token);
 
     Ok((
         transaction_type,
@@ -93,29 +90,45 @@ pub fn extract_transaction_details(
     ))
 }
 
-pub fn extract_accounts(
-    transaction: &EncodedConfirmedTransactionWithStatusMeta,
-    transaction_type: &TransactionType,
-) -> Result<(String, String)> {
-    match &transaction.transaction.transaction {
-        solana_transaction_status::EncodedTransaction::Json(tx) => {
-            let account_keys = crate::data::get_account_keys_from_message(&tx.message);
-
-            // For Raydium, the account order might need verification
-            let (seller, buyer) = match transaction_type {
-                TransactionType::Sell => (
-                    account_keys.first().cloned().unwrap_or_default(),
-                    account_keys.get(1).cloned().unwrap_or_default(),
-                ),
-                TransactionType::Buy => (
-                    account_keys.get(1).cloned().unwrap_or_default(),
-                    account_keys.first().cloned().unwrap_or_default(),
-                ),
-                _ => (String::new(), String::new()),
-            };
-
-            Ok((seller, buyer))
-        }
-        _ => Ok((String::new(), String::new())),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::transaction::extract_swap_accounts;
+
+    fn sell_transaction() -> EncodedConfirmedTransactionWithStatusMeta {
+        serde_json::from_str(include_str!("../../tests/fixtures/raydium_sell.json")).unwrap()
+    }
+
+    #[test]
+    fn parses_a_sell_from_the_signer_balance() {
+        let (transaction_type, token_address, amount_token, amount_sol, price_per_token) =
+            extract_transaction_details(&sell_transaction()).unwrap();
+
+        assert_eq!(transaction_type, TransactionType::Sell);
+        assert_eq!(
+            token_address,
+            "RaydiumMint111111111111111111111111111111111"
+        );
+        assert_eq!(amount_token, 1000.0);
+        assert_eq!(amount_sol, 0.49999);
+        assert_eq!(price_per_token, 0.49999 / 1000.0);
+    }
+
+    #[test]
+    fn seller_is_the_signer_and_buyer_the_pool_authority() {
+        let (seller, buyer) = extract_swap_accounts(&sell_transaction(), &TransactionType::Sell);
+
+        assert_eq!(seller, "Trader1111111111111111111111111111111111111");
+        assert_eq!(buyer, "5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1");
+    }
+
+    #[test]
+    fn transaction_without_token_balances_is_an_error() {
+        let mut transaction = sell_transaction();
+        let meta = transaction.transaction.meta.as_mut().unwrap();
+        meta.pre_token_balances = Some(Vec::new()).into();
+        meta.post_token_balances = Some(Vec::new()).into();
+
+        assert!(extract_transaction_details(&transaction).is_err());
     }
 }
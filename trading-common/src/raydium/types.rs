@@ -129,7 +129,7 @@ pub struct RaydiumPool {
     pub market_event_queue: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PoolKeys {
     pub id: Pubkey,
     pub base_mint: Pubkey,
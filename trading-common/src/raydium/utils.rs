@@ -1,26 +1,43 @@
 use anyhow::Result;
-use solana_client::rpc_client::RpcClient;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_program::program_pack::Pack;
 use solana_sdk::{
+    commitment_config::CommitmentConfig,
     instruction::{AccountMeta, Instruction},
-    message::Message,
     pubkey::Pubkey,
     signature::Keypair,
     signer::Signer,
-    transaction::Transaction,
 };
-use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
 
-use crate::{error::AppError, TransactionType};
+use crate::{amounts::min_with_slippage, error::AppError};
 
 use super::{
+    layouts::{AmmV4PoolState, CpmmAmmConfig, CpmmPoolState, MarketStateV3, RaydiumPoolType},
     types::{
         PoolKeys, RaydiumApiResponse, RaydiumPoolInfo, RaydiumPoolKeyInfo, RaydiumPoolKeyResponse,
     },
-    AmmV4, COMPUTE_BUDGET_PRICE, COMPUTE_BUDGET_UNITS, OPEN_BOOK_PROGRAM, RAY_AUTHORITY_V4, RAY_V4,
-    TOKEN_PROGRAM_ID, WSOL,
+    AmmV4, CPMM_SWAP_BASE_INPUT_DISCRIMINATOR, LIQUIDITY_STATE_LAYOUT_V4_SIZE, OPEN_BOOK_PROGRAM,
+    RAY_AUTHORITY_V4, RAY_CPMM, RAY_CPMM_AUTHORITY, RAY_V4, TOKEN_PROGRAM_ID, USDC, WSOL,
 };
 
+const POOL_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+const POOL_CACHE_TTL: Duration = Duration::from_secs(600);
+
+// Keyed by (mint, paired mint)
+static DISCOVERED_POOLS: Lazy<RwLock<HashMap<(Pubkey, Pubkey), (Pubkey, Instant)>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
 // Core functionality
 pub async fn get_pool_info(token_mint: &str) -> Result<RaydiumPoolInfo, AppError> {
     let url = format!(
@@ -103,16 +120,365 @@ pub async fn get_pool_keys(pool_id: &str) -> Result<RaydiumPoolKeyInfo, AppError
     let _ = Pubkey::from_str(&pool_info.vault.A)?;
     let _ = Pubkey::from_str(&pool_info.vault.B)?;
 
-    println!("Pool keys validated for pool {}", pool_id);
-    println!("Market ID: {}", pool_info.market_id);
-    println!("Market Authority: {}", pool_info.market_authority);
-    println!("OpenOrders: {}", pool_info.open_orders);
-    println!("Base Vault: {}", pool_info.vault.A);
-    println!("Quote Vault: {}", pool_info.vault.B);
+    info!("Pool keys validated for pool {}", pool_id);
+    info!("Market ID: {}", pool_info.market_id);
+    info!("Market Authority: {}", pool_info.market_authority);
+    info!("OpenOrders: {}", pool_info.open_orders);
+    info!("Base Vault: {}", pool_info.vault.A);
+    info!("Quote Vault: {}", pool_info.vault.B);
 
     Ok(api_response.data[0].clone())
 }
 
+// Prefers the Raydium API and falls back to scanning the chain, which sees new
+// listings before the API indexes them
+pub async fn resolve_pool_id(rpc_client: &RpcClient, token_mint: &str) -> Result<Pubkey, AppError> {
+    match get_pool_info(token_mint).await {
+        Ok(pool_info) => Ok(Pubkey::from_str(&pool_info.id)?),
+        Err(e) => {
+            warn!("Raydium API pool lookup failed for {}: {}", token_mint, e);
+            let mint = Pubkey::from_str(token_mint)?;
+            discover_amm_pool(rpc_client, &mint).await?.ok_or_else(|| {
+                AppError::BadRequest(format!("No pool found for token {}", token_mint))
+            })
+        }
+    }
+}
+
+// The pool a copied trade went through when it pairs the token with SOL on a
+// program we can swap against, otherwise the usual lookup
+pub async fn resolve_preferred_pool_id(
+    rpc_client: &RpcClient,
+    token_mint: &str,
+    preferred: Option<&str>,
+) -> Result<Pubkey, AppError> {
+    if let Some(pool_id) = preferred.and_then(|pool| Pubkey::from_str(pool).ok()) {
+        match pool_pairs_with_sol(rpc_client, &pool_id, token_mint) {
+            Ok(true) => return Ok(pool_id),
+            Ok(false) => info!(
+                "Pool {} can't be used for {}, looking up another",
+                pool_id, token_mint
+            ),
+            Err(e) => warn!("Failed to check pool {}: {}", pool_id, e),
+        }
+    }
+    resolve_pool_id(rpc_client, token_mint).await
+}
+
+fn pool_pairs_with_sol(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+    token_mint: &str,
+) -> Result<bool, AppError> {
+    let mint = Pubkey::from_str(token_mint)?;
+    let wsol = Pubkey::from_str(WSOL)?;
+    let (mint_a, mint_b) = match detect_pool_type(rpc_client, pool_id)? {
+        RaydiumPoolType::AmmV4 => {
+            let pool_state = AmmV4PoolState::decode(&rpc_client.get_account_data(pool_id)?)?;
+            (pool_state.base_mint, pool_state.quote_mint)
+        }
+        RaydiumPoolType::Cpmm => {
+            let pool_state = get_cpmm_pool_state(rpc_client, pool_id)?;
+            (pool_state.token_0_mint, pool_state.token_1_mint)
+        }
+        RaydiumPoolType::Clmm => return Ok(false),
+    };
+    Ok((mint_a, mint_b) == (mint, wsol) || (mint_a, mint_b) == (wsol, mint))
+}
+
+pub async fn find_usdc_pool(
+    rpc_client: &RpcClient,
+    token_mint: &str,
+) -> Result<Option<Pubkey>, AppError> {
+    let mint = Pubkey::from_str(token_mint)?;
+    discover_amm_pool_for_pair(rpc_client, &mint, &USDC).await
+}
+
+// Finds the AMM v4 pool pairing the mint with WSOL
+pub async fn discover_amm_pool(
+    rpc_client: &RpcClient,
+    mint: &Pubkey,
+) -> Result<Option<Pubkey>, AppError> {
+    discover_amm_pool_for_pair(rpc_client, mint, &Pubkey::from_str(WSOL)?).await
+}
+
+// When several pools exist for the pair the one with the deepest paired-side
+// vault wins
+pub async fn discover_amm_pool_for_pair(
+    rpc_client: &RpcClient,
+    mint: &Pubkey,
+    pair_mint: &Pubkey,
+) -> Result<Option<Pubkey>, AppError> {
+    if let Some((pool_id, discovered_at)) = DISCOVERED_POOLS.read().get(&(*mint, *pair_mint)) {
+        if discovered_at.elapsed() < POOL_CACHE_TTL {
+            return Ok(Some(*pool_id));
+        }
+    }
+
+    let mut candidates = Vec::new();
+    for (base_mint, quote_mint) in [(*mint, *pair_mint), (*pair_mint, *mint)] {
+        match scan_amm_pools(rpc_client, &base_mint, &quote_mint, pair_mint) {
+            Ok(pools) => candidates.extend(pools),
+            Err(e) => warn!("AMM pool scan failed for {}: {}", mint, e),
+        }
+    }
+
+    let pool_id = candidates
+        .into_iter()
+        .map(|(pool_id, pair_vault)| {
+            let pair_balance = rpc_client
+                .get_token_account_balance(&pair_vault)
+                .ok()
+                .and_then(|balance| balance.amount.parse::<u64>().ok())
+                .unwrap_or(0);
+            (pool_id, pair_balance)
+        })
+        .max_by_key(|(_, pair_balance)| *pair_balance)
+        .map(|(pool_id, _)| pool_id);
+
+    if let Some(pool_id) = pool_id {
+        info!(
+            "Discovered AMM pool {} for {}/{} on-chain",
+            pool_id, mint, pair_mint
+        );
+        DISCOVERED_POOLS
+            .write()
+            .insert((*mint, *pair_mint), (pool_id, Instant::now()));
+    }
+
+    Ok(pool_id)
+}
+
+// Returns (pool, paired-side vault) pairs. The scan gets its own client so a
+// slow getProgramAccounts is cut off at POOL_DISCOVERY_TIMEOUT
+fn scan_amm_pools(
+    rpc_client: &RpcClient,
+    base_mint: &Pubkey,
+    quote_mint: &Pubkey,
+    pair_mint: &Pubkey,
+) -> Result<Vec<(Pubkey, Pubkey)>> {
+    let scan_client = RpcClient::new_with_timeout(rpc_client.url(), POOL_DISCOVERY_TIMEOUT);
+    let accounts = scan_client
+        .get_program_accounts_with_config(&RAY_V4, amm_pool_scan_config(base_mint, quote_mint))
+        .map_err(|e| anyhow::anyhow!("Pool scan request failed: {}", e))?;
+
+    Ok(accounts
+        .into_iter()
+        .filter_map(|(pool_id, account)| {
+            paired_vault(&account.data, quote_mint == pair_mint).map(|vault| (pool_id, vault))
+        })
+        .collect())
+}
+
+// Only the two vaults are returned, the base vault followed by the quote vault
+fn amm_pool_scan_config(base_mint: &Pubkey, quote_mint: &Pubkey) -> RpcProgramAccountsConfig {
+    RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize(LIQUIDITY_STATE_LAYOUT_V4_SIZE as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                AmmV4PoolState::BASE_MINT_OFFSET,
+                base_mint.as_ref(),
+            )),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                AmmV4PoolState::QUOTE_MINT_OFFSET,
+                quote_mint.as_ref(),
+            )),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig {
+                offset: AmmV4PoolState::BASE_VAULT_OFFSET,
+                length: 64,
+            }),
+            commitment: Some(CommitmentConfig::confirmed()),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    }
+}
+
+fn paired_vault(vaults: &[u8], pair_is_quote: bool) -> Option<Pubkey> {
+    let offset = if pair_is_quote { 32 } else { 0 };
+    let vault: [u8; 32] = vaults.get(offset..offset + 32)?.try_into().ok()?;
+    Some(Pubkey::new_from_array(vault))
+}
+
+// Builds AMM v4 pool keys from the pool and its OpenBook market account
+pub fn get_pool_keys_from_chain(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+) -> Result<PoolKeys, AppError> {
+    let pool_state = AmmV4PoolState::decode(&rpc_client.get_account_data(pool_id)?)?;
+    let market = MarketStateV3::decode(&rpc_client.get_account_data(&pool_state.market_id)?)?;
+    let market_authority =
+        market.authority(&pool_state.market_id, &pool_state.market_program_id)?;
+
+    Ok(PoolKeys {
+        id: *pool_id,
+        base_mint: pool_state.base_mint,
+        quote_mint: pool_state.quote_mint,
+        base_vault: pool_state.base_vault,
+        quote_vault: pool_state.quote_vault,
+        open_orders: pool_state.open_orders,
+        target_orders: pool_state.target_orders,
+        market_id: pool_state.market_id,
+        market_base_vault: market.base_vault,
+        market_quote_vault: market.quote_vault,
+        market_authority,
+        bids: market.bids,
+        asks: market.asks,
+        event_queue: market.event_queue,
+    })
+}
+
+pub async fn get_amm_pool_keys(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+) -> Result<PoolKeys, AppError> {
+    match get_pool_keys(&pool_id.to_string()).await {
+        Ok(pool_keys) => Ok(PoolKeys::from(pool_keys)),
+        Err(e) => {
+            warn!(
+                "Raydium API pool keys failed for {}: {}, reading chain",
+                pool_id, e
+            );
+            get_pool_keys_from_chain(rpc_client, pool_id)
+        }
+    }
+}
+
+pub fn get_amm_v4_reserves(
+    rpc_client: &RpcClient,
+    pool_keys: &PoolKeys,
+) -> Result<(u64, u64), AppError> {
+    Ok((
+        get_vault_amount(rpc_client, &pool_keys.base_vault)?,
+        get_vault_amount(rpc_client, &pool_keys.quote_vault)?,
+    ))
+}
+
+fn get_vault_amount(rpc_client: &RpcClient, vault: &Pubkey) -> Result<u64, AppError> {
+    rpc_client
+        .get_token_account_balance(vault)?
+        .amount
+        .parse()
+        .map_err(|e| AppError::TokenAccountError(format!("Invalid vault amount: {}", e)))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SwapQuote {
+    pub expected_out: u64,
+    pub minimum_out: u64,
+}
+
+// Quotes an exact-input swap from the pool's live vault balances with fees
+// applied, rather than from a previously observed price
+pub fn quote_swap_from_reserves(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+    pool_type: RaydiumPoolType,
+    input_mint: &Pubkey,
+    amount_in: u64,
+    slippage_bps: u16,
+) -> Result<SwapQuote, AppError> {
+    let (expected_out, _, _) =
+        quote_with_reserves(rpc_client, pool_id, pool_type, input_mint, amount_in)?;
+
+    Ok(SwapQuote {
+        expected_out,
+        minimum_out: min_with_slippage(expected_out, slippage_bps),
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PriceImpact {
+    pub expected_out: u64,
+    // Output per unit of input, in raw token units
+    pub spot_price: f64,
+    pub execution_price: f64,
+    // Includes the pool fee
+    pub price_impact: f64,
+    pub reserve_in: u64,
+    pub reserve_out: u64,
+}
+
+// Works for both directions, a sell is simply the token as input_mint
+pub fn estimate_price_impact(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+    pool_type: RaydiumPoolType,
+    input_mint: &Pubkey,
+    amount_in: u64,
+) -> Result<PriceImpact, AppError> {
+    let (expected_out, reserve_in, reserve_out) =
+        quote_with_reserves(rpc_client, pool_id, pool_type, input_mint, amount_in)?;
+
+    if reserve_in == 0 || amount_in == 0 {
+        return Err(AppError::BadRequest(format!(
+            "Cannot estimate price impact on pool {} without reserves or input",
+            pool_id
+        )));
+    }
+
+    let spot_price = reserve_out as f64 / reserve_in as f64;
+    let execution_price = expected_out as f64 / amount_in as f64;
+
+    Ok(PriceImpact {
+        expected_out,
+        spot_price,
+        execution_price,
+        price_impact: 1.0 - execution_price / spot_price,
+        reserve_in,
+        reserve_out,
+    })
+}
+
+// Returns (expected_out, reserve_in, reserve_out)
+fn quote_with_reserves(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+    pool_type: RaydiumPoolType,
+    input_mint: &Pubkey,
+    amount_in: u64,
+) -> Result<(u64, u64, u64), AppError> {
+    match pool_type {
+        RaydiumPoolType::AmmV4 => {
+            let pool_state = AmmV4PoolState::decode(&rpc_client.get_account_data(pool_id)?)?;
+            let base_reserve = get_vault_amount(rpc_client, &pool_state.base_vault)?;
+            let quote_reserve = get_vault_amount(rpc_client, &pool_state.quote_vault)?;
+            let (reserve_in, reserve_out) = if pool_state.base_mint == *input_mint {
+                (base_reserve, quote_reserve)
+            } else {
+                (quote_reserve, base_reserve)
+            };
+            Ok((
+                AmmV4PoolState::calculate_amount_out(amount_in, reserve_in, reserve_out),
+                reserve_in,
+                reserve_out,
+            ))
+        }
+        RaydiumPoolType::Cpmm => {
+            let pool_state = get_cpmm_pool_state(rpc_client, pool_id)?;
+            let amm_config = get_cpmm_amm_config(rpc_client, &pool_state)?;
+            let (reserve_0, reserve_1) = get_cpmm_reserves(rpc_client, &pool_state)?;
+            let (reserve_in, reserve_out) = if pool_state.token_0_mint == *input_mint {
+                (reserve_0, reserve_1)
+            } else {
+                (reserve_1, reserve_0)
+            };
+            Ok((
+                amm_config.calculate_amount_out(amount_in, reserve_in, reserve_out),
+                reserve_in,
+                reserve_out,
+            ))
+        }
+        RaydiumPoolType::Clmm => Err(AppError::BadRequest(format!(
+            "Quotes on CLMM pool {} are not supported",
+            pool_id
+        ))),
+    }
+}
+
 pub fn create_swap_instruction(
     pool_keys: &PoolKeys,
     amount_in: u64,
@@ -155,105 +521,136 @@ pub fn create_swap_instruction(
     Ok(Instruction::new_with_bytes(RAY_V4, &data, accounts))
 }
 
+pub fn detect_pool_type(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+) -> Result<RaydiumPoolType, AppError> {
+    let account = rpc_client.get_account(pool_id)?;
+    RaydiumPoolType::from_owner(&account.owner).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Pool {} is owned by unsupported program {}",
+            pool_id, account.owner
+        ))
+    })
+}
+
+pub fn get_cpmm_pool_state(
+    rpc_client: &RpcClient,
+    pool_id: &Pubkey,
+) -> Result<CpmmPoolState, AppError> {
+    let data = rpc_client.get_account_data(pool_id)?;
+    Ok(CpmmPoolState::decode(&data)?)
+}
+
+pub fn get_cpmm_amm_config(
+    rpc_client: &RpcClient,
+    pool_state: &CpmmPoolState,
+) -> Result<CpmmAmmConfig, AppError> {
+    let data = rpc_client.get_account_data(&pool_state.amm_config)?;
+    Ok(CpmmAmmConfig::decode(&data)?)
+}
+
+pub fn get_cpmm_reserves(
+    rpc_client: &RpcClient,
+    pool_state: &CpmmPoolState,
+) -> Result<(u64, u64), AppError> {
+    let vault_0_amount = get_vault_amount(rpc_client, &pool_state.token_0_vault)?;
+    let vault_1_amount = get_vault_amount(rpc_client, &pool_state.token_1_vault)?;
+    Ok(pool_state.reserves(vault_0_amount, vault_1_amount))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_cpmm_swap_instruction(
+    pool_id: &Pubkey,
+    pool_state: &CpmmPoolState,
+    input_mint: &Pubkey,
+    amount_in: u64,
+    minimum_out: u64,
+    token_account_in: Pubkey,
+    token_account_out: Pubkey,
+    owner: &Keypair,
+) -> Result<Instruction, AppError> {
+    let ((input_vault, input_mint, input_program), (output_vault, output_mint, output_program)) =
+        pool_state.swap_sides(input_mint)?;
+
+    let accounts = vec![
+        AccountMeta::new_readonly(owner.pubkey(), true),
+        AccountMeta::new_readonly(RAY_CPMM_AUTHORITY, false),
+        AccountMeta::new_readonly(pool_state.amm_config, false),
+        AccountMeta::new(*pool_id, false),
+        AccountMeta::new(token_account_in, false),
+        AccountMeta::new(token_account_out, false),
+        AccountMeta::new(input_vault, false),
+        AccountMeta::new(output_vault, false),
+        AccountMeta::new_readonly(input_program, false),
+        AccountMeta::new_readonly(output_program, false),
+        AccountMeta::new_readonly(input_mint, false),
+        AccountMeta::new_readonly(output_mint, false),
+        AccountMeta::new(pool_state.observation_key, false),
+    ];
+
+    let mut data = Vec::with_capacity(24);
+    data.extend_from_slice(&CPMM_SWAP_BASE_INPUT_DISCRIMINATOR);
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_out.to_le_bytes());
+
+    Ok(Instruction::new_with_bytes(RAY_CPMM, &data, accounts))
+}
+
 // Utility functions for WSOL handling
+// Swaps wrap SOL in a fresh account instead of the wallet's WSOL ATA, so a stale
+// ATA balance can never change how much SOL a buy spends. The account holds
+// exactly `amount` lamports on top of rent
 pub async fn create_wsol_account_instructions(
     rpc_client: &RpcClient,
     owner: &Keypair,
     amount: u64,
 ) -> Result<(Keypair, Vec<Instruction>)> {
     let wsol_account = Keypair::new();
-    let rent = rpc_client.get_minimum_balance_for_rent_exemption(165)?;
-    let total_amount = rent + amount;
+    let rent = rpc_client.get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
+    let instructions =
+        wsol_account_instructions(&owner.pubkey(), &wsol_account.pubkey(), rent, amount)?;
+
+    Ok((wsol_account, instructions))
+}
 
+fn wsol_account_instructions(
+    owner: &Pubkey,
+    wsol_account: &Pubkey,
+    rent: u64,
+    amount: u64,
+) -> Result<Vec<Instruction>> {
     let create_account_ix = solana_sdk::system_instruction::create_account(
-        &owner.pubkey(),
-        &wsol_account.pubkey(),
-        total_amount,
-        165,
+        owner,
+        wsol_account,
+        rent + amount,
+        spl_token::state::Account::LEN as u64,
         &Pubkey::from_str(TOKEN_PROGRAM_ID)?,
     );
 
     let init_account_ix = spl_token::instruction::initialize_account(
         &Pubkey::from_str(TOKEN_PROGRAM_ID)?,
-        &wsol_account.pubkey(),
+        wsol_account,
         &Pubkey::from_str(WSOL)?,
-        &owner.pubkey(),
+        owner,
     )?;
 
-    Ok((wsol_account, vec![create_account_ix, init_account_ix]))
+    Ok(vec![create_account_ix, init_account_ix])
 }
 
-// Helper function for price calculation
-pub async fn calculate_price_impact(
-    rpc_client: &RpcClient,
-    pool_keys: &PoolKeys,
-    amount_in: u64,
-    is_base_to_quote: bool,
-) -> Result<f64> {
-    let base_balance = rpc_client.get_token_account_balance(&pool_keys.base_vault)?;
-    let quote_balance = rpc_client.get_token_account_balance(&pool_keys.quote_vault)?;
-
-    let base_amount = base_balance.ui_amount.unwrap_or(0.0);
-    let quote_amount = quote_balance.ui_amount.unwrap_or(0.0);
-
-    let current_price = quote_amount / base_amount;
-    let k = base_amount * quote_amount;
-
-    let new_base_amount = if is_base_to_quote {
-        base_amount + (amount_in as f64 / 10f64.powi(base_balance.decimals as i32))
-    } else {
-        base_amount
-    };
-
-    let new_quote_amount = k / new_base_amount;
-    let new_price = new_quote_amount / new_base_amount;
-
-    Ok(((new_price - current_price) / current_price).abs())
-}
-
-// Main swap function that builds and sends the transaction
-pub async fn execute_swap(
-    rpc_client: &RpcClient,
-    owner: &Keypair,
-    pool_keys: &PoolKeys,
-    amount_in: u64,
-    minimum_out: u64,
-    source_token: Pubkey,
-    destination_token: Pubkey,
-) -> Result<String> {
-    // Set compute budget
-    let compute_budget_ix =
-        solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(
-            COMPUTE_BUDGET_UNITS,
-        );
-    let priority_fee_ix =
-        solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(
-            COMPUTE_BUDGET_PRICE,
-        );
-
-    // Create swap instruction
-    let swap_ix = create_swap_instruction(
-        pool_keys,
-        amount_in,
-        minimum_out,
-        source_token,
-        destination_token,
+// Must be the last instruction of the swap so residual SOL and the rent go back
+// to the owner in the same transaction
+pub fn close_wsol_account_instruction(
+    wsol_account: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Instruction> {
+    Ok(spl_token::instruction::close_account(
+        &Pubkey::from_str(TOKEN_PROGRAM_ID)?,
+        wsol_account,
         owner,
-    )?;
-
-    // Build transaction
-    let recent_blockhash = rpc_client.get_latest_blockhash()?;
-    let message = Message::new(
-        &[compute_budget_ix, priority_fee_ix, swap_ix],
-        Some(&owner.pubkey()),
-    );
-    let mut transaction = Transaction::new_unsigned(message);
-    transaction.sign(&[owner], recent_blockhash);
-
-    // Send and confirm transaction
-    let signature = rpc_client.send_and_confirm_transaction_with_spinner(&transaction)?;
-
-    Ok(signature.to_string())
+        owner,
+        &[],
+    )?)
 }
 
 pub async fn get_token_balance(
@@ -267,33 +664,6 @@ pub async fn get_token_balance(
     Ok(account_balance.ui_amount.unwrap_or(0.0))
 }
 
-pub fn extract_accounts(
-    transaction: &EncodedConfirmedTransactionWithStatusMeta,
-    transaction_type: &TransactionType,
-) -> Result<(String, String)> {
-    match &transaction.transaction.transaction {
-        solana_transaction_status::EncodedTransaction::Json(tx) => {
-            let account_keys = crate::data::get_account_keys_from_message(&tx.message);
-
-            // For Raydium, the account order might need verification
-            let (seller, buyer) = match transaction_type {
-                TransactionType::Sell => (
-                    account_keys.first().cloned().unwrap_or_default(),
-                    account_keys.get(1).cloned().unwrap_or_default(),
-                ),
-                TransactionType::Buy => (
-                    account_keys.get(1).cloned().unwrap_or_default(),
-                    account_keys.first().cloned().unwrap_or_default(),
-                ),
-                _ => (String::new(), String::new()),
-            };
-
-            Ok((seller, buyer))
-        }
-        _ => Ok((String::new(), String::new())),
-    }
-}
-
 use std::mem;
 
 pub fn parse_pool_state(data: &[u8]) -> Result<AmmV4> {
@@ -310,3 +680,124 @@ pub fn parse_pool_state(data: &[u8]) -> Result<AmmV4> {
 
     Ok(*amm)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spl_token::instruction::TokenInstruction;
+
+    #[test]
+    fn wsol_account_is_funded_with_exactly_the_amount_plus_rent() {
+        let owner = Pubkey::new_unique();
+        let wsol_account = Pubkey::new_unique();
+
+        let instructions =
+            wsol_account_instructions(&owner, &wsol_account, 2_039_280, 500_000_000).unwrap();
+
+        assert_eq!(
+            instructions[0],
+            solana_sdk::system_instruction::create_account(
+                &owner,
+                &wsol_account,
+                502_039_280,
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            )
+        );
+        assert_eq!(instructions[1].program_id, spl_token::id());
+        assert!(matches!(
+            TokenInstruction::unpack(&instructions[1].data).unwrap(),
+            TokenInstruction::InitializeAccount
+        ));
+        assert_eq!(instructions[1].accounts[0].pubkey, wsol_account);
+        assert_eq!(
+            instructions[1].accounts[1].pubkey,
+            Pubkey::from_str(WSOL).unwrap()
+        );
+        assert_eq!(instructions[1].accounts[2].pubkey, owner);
+    }
+
+    #[test]
+    fn closing_the_wsol_account_returns_everything_to_the_owner() {
+        let owner = Pubkey::new_unique();
+        let wsol_account = Pubkey::new_unique();
+
+        let instruction = close_wsol_account_instruction(&wsol_account, &owner).unwrap();
+
+        assert_eq!(instruction.program_id, spl_token::id());
+        assert!(matches!(
+            TokenInstruction::unpack(&instruction.data).unwrap(),
+            TokenInstruction::CloseAccount
+        ));
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|meta| (meta.pubkey, meta.is_writable, meta.is_signer))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            accounts,
+            vec![
+                (wsol_account, true, false),
+                (owner, true, false),
+                (owner, false, true),
+            ]
+        );
+    }
+
+    fn pool_state_with_mints(base_mint: &Pubkey, quote_mint: &Pubkey) -> Vec<u8> {
+        let mut data = vec![0u8; LIQUIDITY_STATE_LAYOUT_V4_SIZE];
+        data[AmmV4PoolState::BASE_MINT_OFFSET..AmmV4PoolState::BASE_MINT_OFFSET + 32]
+            .copy_from_slice(base_mint.as_ref());
+        data[AmmV4PoolState::QUOTE_MINT_OFFSET..AmmV4PoolState::QUOTE_MINT_OFFSET + 32]
+            .copy_from_slice(quote_mint.as_ref());
+        data
+    }
+
+    fn filters_match(config: &RpcProgramAccountsConfig, data: &[u8]) -> bool {
+        config
+            .filters
+            .as_ref()
+            .unwrap()
+            .iter()
+            .all(|filter| match filter {
+                RpcFilterType::DataSize(size) => data.len() as u64 == *size,
+                RpcFilterType::Memcmp(memcmp) => memcmp.bytes_match(data),
+                _ => false,
+            })
+    }
+
+    #[test]
+    fn pool_scan_matches_only_the_requested_base_and_quote() {
+        let mint = Pubkey::new_unique();
+        let wsol = Pubkey::from_str(WSOL).unwrap();
+        let config = amm_pool_scan_config(&mint, &wsol);
+
+        assert!(filters_match(&config, &pool_state_with_mints(&mint, &wsol)));
+        assert!(!filters_match(
+            &config,
+            &pool_state_with_mints(&wsol, &mint)
+        ));
+        assert!(!filters_match(
+            &config,
+            &pool_state_with_mints(&mint, &Pubkey::new_unique())
+        ));
+        assert_eq!(
+            config.account_config.data_slice,
+            Some(UiDataSliceConfig {
+                offset: AmmV4PoolState::BASE_VAULT_OFFSET,
+                length: 64,
+            })
+        );
+    }
+
+    #[test]
+    fn paired_vault_follows_the_side_of_the_pair() {
+        let base_vault = Pubkey::new_unique();
+        let quote_vault = Pubkey::new_unique();
+        let vaults = [base_vault.to_bytes(), quote_vault.to_bytes()].concat();
+
+        assert_eq!(paired_vault(&vaults, true), Some(quote_vault));
+        assert_eq!(paired_vault(&vaults, false), Some(base_vault));
+        assert_eq!(paired_vault(&vaults[..40], true), None);
+    }
+}
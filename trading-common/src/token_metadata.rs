@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_program::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use spl_token::state::Mint;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::pumpdotfun::utils::get_coin_data;
+use crate::utils::data::{
+    get_metadata_account, unpack_metadata_account, TokenMetadata as MetaplexMetadata,
+};
+
+const METADATA_CACHE_TTL: Duration = Duration::from_secs(3600);
+const PUMP_FUN_API_TIMEOUT: Duration = Duration::from_secs(3);
+
+static METADATA_CACHE: Lazy<RwLock<HashMap<Pubkey, (TokenMetadata, Instant)>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataSource {
+    PumpFun,
+    Metaplex,
+    Mint,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub mint: String,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub image_uri: String,
+    pub decimals: u8,
+    pub source: MetadataSource,
+}
+
+// Resolves metadata from the cache, then the pump.fun API, then the Metaplex
+// metadata account, and finally the mint account alone
+pub async fn get_token_metadata(rpc_client: &RpcClient, mint: &Pubkey) -> Result<TokenMetadata> {
+    if let Some((metadata, cached_at)) = METADATA_CACHE.read().get(mint) {
+        if cached_at.elapsed() < METADATA_CACHE_TTL {
+            return Ok(metadata.clone());
+        }
+    }
+
+    // Decimals always come from the mint so Token-2022 mints are handled too
+    let decimals = get_mint_decimals(rpc_client, mint)?;
+
+    let metadata = match tokio::time::timeout(PUMP_FUN_API_TIMEOUT, get_coin_data(mint)).await {
+        Ok(Ok(coin_data)) => TokenMetadata {
+            mint: mint.to_string(),
+            name: coin_data.name,
+            symbol: coin_data.symbol,
+            uri: coin_data.metadata_uri,
+            image_uri: coin_data.image_uri,
+            decimals,
+            source: MetadataSource::PumpFun,
+        },
+        pump_fun_result => {
+            if let Ok(Err(e)) = pump_fun_result {
+                warn!("pump.fun metadata lookup failed for {}: {}", mint, e);
+            }
+
+            match get_metaplex_metadata(rpc_client, mint) {
+                Ok(metaplex) => TokenMetadata {
+                    mint: mint.to_string(),
+                    name: metaplex.name,
+                    symbol: metaplex.symbol,
+                    image_uri: metaplex.uri.clone(),
+                    uri: metaplex.uri,
+                    decimals,
+                    source: MetadataSource::Metaplex,
+                },
+                Err(e) => {
+                    warn!("Metaplex metadata lookup failed for {}: {}", mint, e);
+                    mint_only_metadata(mint, decimals)
+                }
+            }
+        }
+    };
+
+    METADATA_CACHE
+        .write()
+        .insert(*mint, (metadata.clone(), Instant::now()));
+
+    Ok(metadata)
+}
+
+pub fn get_mint_decimals(rpc_client: &RpcClient, mint: &Pubkey) -> Result<u8> {
+    let data = rpc_client
+        .get_account_data(mint)
+        .context("Failed to fetch mint account")?;
+
+    if data.len() < Mint::LEN {
+        return Err(anyhow!("Account {} is not a mint", mint));
+    }
+
+    let mint_account = Mint::unpack_from_slice(&data[..Mint::LEN])
+        .context("Failed to unpack mint account data")?;
+    Ok(mint_account.decimals)
+}
+
+fn get_metaplex_metadata(rpc_client: &RpcClient, mint: &Pubkey) -> Result<MetaplexMetadata> {
+    let data = rpc_client
+        .get_account_data(&get_metadata_account(mint))
+        .context("Failed to fetch metadata account data")?;
+    unpack_metadata_account(&data)
+}
+
+fn mint_only_metadata(mint: &Pubkey, decimals: u8) -> TokenMetadata {
+    let address = mint.to_string();
+    let name = format!("{}...{}", &address[..4], &address[address.len() - 4..]);
+
+    TokenMetadata {
+        mint: address.clone(),
+        symbol: address[..4].to_string(),
+        name,
+        uri: String::new(),
+        image_uri: String::new(),
+        decimals,
+        source: MetadataSource::Mint,
+    }
+}
@@ -0,0 +1,130 @@
+// Amounts are f64 at the JSON boundary only. Everything that feeds an
+// instruction works in lamports, raw token units and basis points so
+// slippage bounds match what the programs compute
+
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+pub fn sol_to_lamports(sol: f64) -> u64 {
+    // Rounded rather than truncated, 0.3 SOL must be 300_000_000 lamports
+    (sol * LAMPORTS_PER_SOL as f64).round() as u64
+}
+
+pub fn lamports_to_sol(lamports: u64) -> f64 {
+    lamports as f64 / LAMPORTS_PER_SOL as f64
+}
+
+pub fn ui_to_raw(amount: f64, decimals: u8) -> u64 {
+    (amount * 10f64.powi(decimals as i32)).round() as u64
+}
+
+pub fn raw_to_ui(amount: u64, decimals: u8) -> f64 {
+    amount as f64 / 10f64.powi(decimals as i32)
+}
+
+// A slippage fraction such as 0.05 as basis points, clamped to 0..=100%
+pub fn fraction_to_bps(fraction: f64) -> u16 {
+    (fraction * BPS_DENOMINATOR as f64)
+        .round()
+        .clamp(0.0, BPS_DENOMINATOR as f64) as u16
+}
+
+pub fn bps_to_fraction(bps: u16) -> f64 {
+    bps as f64 / BPS_DENOMINATOR as f64
+}
+
+// Lowest acceptable output, rounded down so a remainder never makes the
+// on-chain check stricter than requested
+pub fn min_with_slippage(amount: u64, slippage_bps: u16) -> u64 {
+    let kept = (BPS_DENOMINATOR - slippage_bps.min(BPS_DENOMINATOR)) as u128;
+    (amount as u128 * kept / BPS_DENOMINATOR as u128) as u64
+}
+
+// Highest acceptable input, rounded up for the same reason
+pub fn max_with_slippage(amount: u64, slippage_bps: u16) -> u64 {
+    let allowed = BPS_DENOMINATOR as u128 + slippage_bps as u128;
+    let max = (amount as u128 * allowed).div_ceil(BPS_DENOMINATOR as u128);
+    max.min(u64::MAX as u128) as u64
+}
+
+// Splits a total slippage across legs that compound, (1 - leg)^2 = 1 - total.
+// The leg is rounded down so the route as a whole stays within the total
+pub fn two_leg_slippage_bps(total_bps: u16) -> u16 {
+    let kept = (BPS_DENOMINATOR - total_bps.min(BPS_DENOMINATOR)) as f64;
+    let leg_kept = (kept * BPS_DENOMINATOR as f64).sqrt().ceil() as u16;
+    BPS_DENOMINATOR - leg_kept.min(BPS_DENOMINATOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sol_to_lamports_rounds_instead_of_truncating() {
+        assert_eq!(sol_to_lamports(0.3), 300_000_000);
+        assert_eq!(sol_to_lamports(0.1 + 0.2), 300_000_000);
+        assert_eq!(sol_to_lamports(0.000_000_001), 1);
+        assert_eq!(sol_to_lamports(0.0), 0);
+        assert_eq!(
+            lamports_to_sol(sol_to_lamports(1.234_567_891)),
+            1.234_567_891
+        );
+    }
+
+    #[test]
+    fn ui_and_raw_amounts_round_trip() {
+        assert_eq!(ui_to_raw(1.5, 6), 1_500_000);
+        assert_eq!(ui_to_raw(0.1 + 0.2, 9), 300_000_000);
+        assert_eq!(ui_to_raw(42.0, 0), 42);
+        assert_eq!(raw_to_ui(1_500_000, 6), 1.5);
+    }
+
+    #[test]
+    fn fraction_to_bps_clamps_to_the_valid_range() {
+        assert_eq!(fraction_to_bps(0.05), 500);
+        assert_eq!(fraction_to_bps(0.0), 0);
+        assert_eq!(fraction_to_bps(1.0), BPS_DENOMINATOR);
+        assert_eq!(fraction_to_bps(-0.1), 0);
+        assert_eq!(fraction_to_bps(1.5), BPS_DENOMINATOR);
+        assert_eq!(bps_to_fraction(250), 0.025);
+    }
+
+    #[test]
+    fn min_with_slippage_rounds_down() {
+        assert_eq!(min_with_slippage(1_000, 0), 1_000);
+        assert_eq!(min_with_slippage(999, 100), 989);
+        assert_eq!(min_with_slippage(1_000, BPS_DENOMINATOR), 0);
+        // Anything over 100% is treated as 100%
+        assert_eq!(min_with_slippage(1_000, u16::MAX), 0);
+        assert_eq!(min_with_slippage(u64::MAX, 0), u64::MAX);
+    }
+
+    #[test]
+    fn max_with_slippage_rounds_up_and_saturates() {
+        assert_eq!(max_with_slippage(1_000, 0), 1_000);
+        assert_eq!(max_with_slippage(999, 100), 1_009);
+        assert_eq!(max_with_slippage(1_000, BPS_DENOMINATOR), 2_000);
+        assert_eq!(max_with_slippage(u64::MAX, 0), u64::MAX);
+        assert_eq!(max_with_slippage(u64::MAX, u16::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn two_leg_slippage_stays_within_the_total() {
+        assert_eq!(two_leg_slippage_bps(0), 0);
+        assert_eq!(two_leg_slippage_bps(100), 50);
+        assert_eq!(two_leg_slippage_bps(BPS_DENOMINATOR), BPS_DENOMINATOR);
+        assert_eq!(two_leg_slippage_bps(u16::MAX), BPS_DENOMINATOR);
+
+        let denominator = BPS_DENOMINATOR as u64;
+        for total in 0..=BPS_DENOMINATOR {
+            let leg_kept = (BPS_DENOMINATOR - two_leg_slippage_bps(total)) as u64;
+            let total_kept = (BPS_DENOMINATOR - total) as u64;
+            assert!(
+                leg_kept * leg_kept >= total_kept * denominator,
+                "two legs at {} bps exceed a total of {} bps",
+                two_leg_slippage_bps(total),
+                total
+            );
+        }
+    }
+}
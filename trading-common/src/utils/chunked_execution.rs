@@ -0,0 +1,425 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::amounts::{lamports_to_sol, raw_to_ui};
+use crate::dex::DexType;
+use crate::error::AppError;
+use crate::models::{BuyRequest, BuyResponse};
+use crate::spend_guard::spend_guard;
+use crate::token_metadata::get_mint_decimals;
+use crate::{pumpdotfun, pumpswap, raydium};
+
+#[derive(Debug, Clone)]
+pub struct ChunkingConfig {
+    // Buys moving the reserves by more than this are split, 0 disables chunking
+    pub price_impact_threshold: f64,
+    pub max_chunks: u32,
+    pub chunk_delay: Duration,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            price_impact_threshold: 0.05,
+            max_chunks: 5,
+            chunk_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl ChunkingConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            price_impact_threshold: std::env::var("CHUNK_PRICE_IMPACT_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.price_impact_threshold),
+            max_chunks: std::env::var("CHUNK_MAX_CHUNKS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.max_chunks),
+            chunk_delay: std::env::var("CHUNK_DELAY_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(default.chunk_delay),
+        }
+    }
+}
+
+static CHUNKING_CONFIG: Lazy<ChunkingConfig> = Lazy::new(ChunkingConfig::from_env);
+
+pub fn chunking_config() -> &'static ChunkingConfig {
+    &CHUNKING_CONFIG
+}
+
+// Reserves of the pool a buy spends into, in raw units of SOL and the token
+#[derive(Debug, Clone, Copy)]
+pub struct BuyReserves {
+    pub sol_reserve: u64,
+    pub token_reserve: u64,
+    pub token_decimals: u8,
+}
+
+impl BuyReserves {
+    pub fn spot_price_sol(&self) -> f64 {
+        lamports_to_sol(self.sol_reserve) / raw_to_ui(self.token_reserve, self.token_decimals)
+    }
+}
+
+// Constant product, fees aside: spending a into reserves x moves the price by
+// a / (x + a), the same figure as the loss against spot on the swap itself
+pub fn reserve_price_impact(amount_in: u64, reserve_in: u64) -> f64 {
+    if amount_in == 0 {
+        return 0.0;
+    }
+    amount_in as f64 / (reserve_in as f64 + amount_in as f64)
+}
+
+// Splits a buy of A into n swaps that each move the price by the same factor.
+// Swapping a_k out of reserves x_k leaves x_{k+1} = x_k + a_k, so equal impact
+// means x_{k+1} = g * x_k and after n swaps x_n = x_0 * g^n = x_0 + A, giving
+//
+//   g   = (1 + A / x_0)^(1 / n)
+//   a_k = x_0 * g^k * (g - 1)
+//
+// with a per-swap impact of 1 - 1 / g. The smallest n keeping that within I is
+// n = ceil(ln(1 + A / x_0) / -ln(1 - I)), capped at max_chunks. Chunks grow
+// geometrically because each one starts from deeper reserves than the last.
+// Without other flow in between the total cost equals a single swap, the gain
+// is a bounded impact per transaction and the option to stop part way
+pub fn plan_chunks(amount_in: u64, reserve_in: u64, max_impact: f64, max_chunks: u32) -> Vec<u64> {
+    if amount_in == 0 || reserve_in == 0 || max_impact <= 0.0 || max_impact >= 1.0 {
+        return vec![amount_in];
+    }
+
+    let x0 = reserve_in as f64;
+    let growth = 1.0 + amount_in as f64 / x0;
+    let chunks = (growth.ln() / -(1.0 - max_impact).ln()).ceil() as u32;
+    let chunks = chunks.clamp(1, max_chunks.max(1));
+    if chunks == 1 {
+        return vec![amount_in];
+    }
+
+    let g = growth.powf(1.0 / chunks as f64);
+    let mut sizes = (0..chunks - 1)
+        .map(|k| (x0 * g.powi(k as i32) * (g - 1.0)) as u64)
+        .collect::<Vec<_>>();
+    // The last chunk takes whatever rounding left over
+    let planned: u64 = sizes.iter().sum();
+    sizes.push(amount_in.saturating_sub(planned));
+    sizes
+}
+
+pub async fn fetch_buy_reserves(
+    rpc_client: &RpcClient,
+    token_address: &str,
+    pool_address: Option<&str>,
+    dex_type: &DexType,
+) -> Result<Option<BuyReserves>> {
+    let mint = Pubkey::from_str(token_address)?;
+
+    let (sol_reserve, token_reserve) = match dex_type {
+        DexType::PumpFun => {
+            let curve = pumpdotfun::get_bonding_curve_data(rpc_client, &mint).await?;
+            (
+                curve.virtual_sol_reserves.max(0) as u64,
+                curve.virtual_token_reserves.max(0) as u64,
+            )
+        }
+        DexType::Raydium | DexType::Jupiter => {
+            let pool_id =
+                raydium::resolve_preferred_pool_id(rpc_client, token_address, pool_address).await?;
+            let pool_type = raydium::detect_pool_type(rpc_client, &pool_id)?;
+            let impact = raydium::estimate_price_impact(
+                rpc_client,
+                &pool_id,
+                pool_type,
+                &Pubkey::from_str(raydium::WSOL)?,
+                1,
+            )?;
+            (impact.reserve_in, impact.reserve_out)
+        }
+        _ => return Ok(None),
+    };
+
+    if sol_reserve == 0 || token_reserve == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(BuyReserves {
+        sol_reserve,
+        token_reserve,
+        token_decimals: get_mint_decimals(rpc_client, &mint)?,
+    }))
+}
+
+pub async fn process_single_buy_request(
+    rpc_client: &RpcClient,
+    server_keypair: &Keypair,
+    request: &BuyRequest,
+    dex_type: &DexType,
+) -> Result<BuyResponse, AppError> {
+    match dex_type {
+        DexType::PumpFun => {
+            pumpdotfun::process_buy_request(rpc_client, server_keypair, request.clone()).await
+        }
+        DexType::Raydium | DexType::Jupiter => {
+            raydium::process_buy_request(rpc_client, server_keypair, request).await
+        }
+        DexType::PumpSwap => {
+            pumpswap::process_buy_request(rpc_client, server_keypair, request).await
+        }
+        DexType::OrcaWhirlpool | DexType::Unknown => Err(AppError::BadRequest(format!(
+            "Unsupported venue {:?}, cannot execute buy",
+            dex_type
+        ))),
+    }
+}
+
+// Buys whose price impact is above the threshold are sent as several smaller
+// swaps. The whole order shares one slippage budget: once the average price
+// paid so far is worse than a single swap's average by more than the
+// slippage tolerance, the remaining chunks are dropped. Fees come out of
+// that budget too
+pub async fn process_chunked_buy_request(
+    rpc_client: &RpcClient,
+    server_keypair: &Keypair,
+    request: &BuyRequest,
+    dex_type: &DexType,
+) -> Result<BuyResponse, AppError> {
+    let config = chunking_config();
+    if config.price_impact_threshold <= 0.0 {
+        return process_single_buy_request(rpc_client, server_keypair, request, dex_type).await;
+    }
+
+    let reserves = match fetch_buy_reserves(
+        rpc_client,
+        &request.token_address,
+        request.pool_address.as_deref(),
+        dex_type,
+    )
+    .await
+    {
+        Ok(Some(reserves)) => reserves,
+        Ok(None) => {
+            return process_single_buy_request(rpc_client, server_keypair, request, dex_type).await
+        }
+        // The single buy will surface a missing pool or curve, don't block on the estimate
+        Err(e) => {
+            warn!(
+                "Reserve lookup for {} failed, not chunking: {}",
+                request.token_address, e
+            );
+            return process_single_buy_request(rpc_client, server_keypair, request, dex_type).await;
+        }
+    };
+
+    let amount_in = request.sol_lamports();
+    let price_impact = reserve_price_impact(amount_in, reserves.sol_reserve);
+    let chunks = plan_chunks(
+        amount_in,
+        reserves.sol_reserve,
+        config.price_impact_threshold,
+        config.max_chunks,
+    );
+    if price_impact <= config.price_impact_threshold || chunks.len() < 2 {
+        return process_single_buy_request(rpc_client, server_keypair, request, dex_type).await;
+    }
+
+    // Each chunk is authorized on its own, the order as a whole must fit too
+    spend_guard().check(request.sol_quantity)?;
+
+    // A single swap of the whole amount pays spot * (x + A) / x on average
+    let single_swap_price = reserves.spot_price_sol()
+        * (reserves.sol_reserve as f64 + amount_in as f64)
+        / reserves.sol_reserve as f64;
+    let max_average_price = single_swap_price * (1.0 + request.slippage_tolerance);
+    info!(
+        "Price impact {:.2}% for {} SOL of {} exceeds {:.2}%, splitting into {} chunks: {:?}",
+        price_impact * 100.0,
+        request.sol_quantity,
+        request.token_address,
+        config.price_impact_threshold * 100.0,
+        chunks.len(),
+        chunks
+    );
+
+    let mut response = BuyResponse {
+        success: false,
+        signature: String::new(),
+        solscan_tx_url: String::new(),
+        token_quantity: 0.0,
+        sol_spent: 0.0,
+        error: None,
+        child_signatures: Vec::new(),
+        route: None,
+    };
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        if index > 0 {
+            tokio::time::sleep(config.chunk_delay).await;
+        }
+
+        let chunk_request = BuyRequest {
+            sol_quantity: lamports_to_sol(*chunk),
+            ..request.clone()
+        };
+        match process_single_buy_request(rpc_client, server_keypair, &chunk_request, dex_type).await
+        {
+            Ok(chunk_response) if chunk_response.success => {
+                info!(
+                    "Chunk {}/{} bought {} for {} SOL: {}",
+                    index + 1,
+                    chunks.len(),
+                    chunk_response.token_quantity,
+                    chunk_response.sol_spent,
+                    chunk_response.signature
+                );
+                response.sol_spent += chunk_response.sol_spent;
+                response.token_quantity += chunk_response.token_quantity;
+                // A routed chunk already lists every transaction it sent
+                if chunk_response.child_signatures.is_empty() {
+                    response.child_signatures.push(chunk_response.signature);
+                } else {
+                    response
+                        .child_signatures
+                        .extend(chunk_response.child_signatures);
+                }
+                response.route = response.route.or(chunk_response.route);
+            }
+            Ok(chunk_response) if index > 0 => {
+                response.error = Some(format!(
+                    "Stopped after {} of {} chunks: {}",
+                    index,
+                    chunks.len(),
+                    chunk_response.error.unwrap_or_default()
+                ));
+                break;
+            }
+            Ok(chunk_response) => return Ok(chunk_response),
+            Err(e) if index > 0 => {
+                response.error = Some(format!(
+                    "Stopped after {} of {} chunks: {}",
+                    index,
+                    chunks.len(),
+                    e
+                ));
+                break;
+            }
+            Err(e) => return Err(e),
+        }
+
+        let remaining = chunks.len() - index - 1;
+        if remaining > 0 && response.token_quantity > 0.0 {
+            let average_price = response.sol_spent / response.token_quantity;
+            if average_price > max_average_price {
+                response.error = Some(format!(
+                    "Average price {:.12} SOL exceeded the budget of {:.12} SOL, skipped {} remaining chunks",
+                    average_price, max_average_price, remaining
+                ));
+                break;
+            }
+        }
+    }
+
+    if let Some(error) = &response.error {
+        info!("Chunked buy of {}: {}", request.token_address, error);
+    }
+
+    response.success = !response.child_signatures.is_empty();
+    response.signature = response
+        .child_signatures
+        .last()
+        .cloned()
+        .unwrap_or_default();
+    response.solscan_tx_url = format!("https://solscan.io/tx/{}", response.signature);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Impact of every chunk against the reserves left by the ones before it
+    fn chunk_impacts(chunks: &[u64], reserve_in: u64) -> Vec<f64> {
+        let mut reserve = reserve_in;
+        chunks
+            .iter()
+            .map(|chunk| {
+                let impact = reserve_price_impact(*chunk, reserve);
+                reserve += chunk;
+                impact
+            })
+            .collect()
+    }
+
+    #[test]
+    fn impact_is_the_share_of_the_deepened_reserve() {
+        assert_eq!(reserve_price_impact(0, 1_000), 0.0);
+        assert_eq!(reserve_price_impact(1_000, 1_000), 0.5);
+        assert_eq!(reserve_price_impact(1_000, 0), 1.0);
+    }
+
+    #[test]
+    fn chunks_add_up_and_stay_within_the_threshold() {
+        let reserve_in = 30_000_000_000;
+        for amount_in in [1_000_000_000, 5_000_000_000, 20_000_000_000, 90_000_000_000] {
+            let chunks = plan_chunks(amount_in, reserve_in, 0.05, 50);
+
+            assert_eq!(chunks.iter().sum::<u64>(), amount_in);
+            for impact in chunk_impacts(&chunks, reserve_in) {
+                // Rounding to whole lamports moves the last chunk by a few units
+                assert!(impact <= 0.05 + 1e-9, "{} over the threshold", impact);
+            }
+        }
+    }
+
+    #[test]
+    fn uses_the_fewest_chunks_that_fit_the_threshold() {
+        // A 1 SOL buy into 30 SOL moves the price by 1/31, one swap is enough
+        assert_eq!(plan_chunks(1_000_000_000, 30_000_000_000, 0.05, 5).len(), 1);
+
+        // Doubling the reserves needs ln 2 / -ln 0.95 = 13.5, so 14 chunks
+        let chunks = plan_chunks(30_000_000_000, 30_000_000_000, 0.05, 50);
+        assert_eq!(chunks.len(), 14);
+        // Later chunks start from deeper reserves, so they are larger
+        assert!(chunks.windows(2).all(|pair| pair[0] <= pair[1] + 1));
+    }
+
+    #[test]
+    fn chunk_count_is_capped() {
+        let chunks = plan_chunks(30_000_000_000, 30_000_000_000, 0.05, 5);
+
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(chunks.iter().sum::<u64>(), 30_000_000_000);
+        // Capped chunks exceed the threshold but still share the impact evenly
+        let impacts = chunk_impacts(&chunks, 30_000_000_000);
+        let expected = 1.0 - 2f64.powf(-1.0 / 5.0);
+        assert!(impacts
+            .iter()
+            .all(|impact| (impact - expected).abs() < 1e-6));
+
+        // Zero is treated as one chunk
+        assert_eq!(
+            plan_chunks(30_000_000_000, 30_000_000_000, 0.05, 0).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn degenerate_inputs_are_a_single_swap() {
+        assert_eq!(plan_chunks(0, 30_000_000_000, 0.05, 5), vec![0]);
+        assert_eq!(plan_chunks(1_000, 0, 0.05, 5), vec![1_000]);
+        assert_eq!(plan_chunks(1_000, 1_000, 0.0, 5), vec![1_000]);
+        assert_eq!(plan_chunks(1_000, 1_000, 1.0, 5), vec![1_000]);
+        assert_eq!(plan_chunks(1_000, 1_000, 1.5, 5), vec![1_000]);
+    }
+}
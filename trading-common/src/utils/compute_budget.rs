@@ -0,0 +1,74 @@
+use once_cell::sync::Lazy;
+
+use crate::dex::DexType;
+use crate::{pumpdotfun, pumpswap, raydium};
+
+#[derive(Debug, Clone)]
+pub struct ComputeBudgetConfig {
+    pub pump_fun_unit_limit: u32,
+    pub pump_swap_unit_limit: u32,
+    pub raydium_unit_limit: u32,
+    // Multiplier applied to simulated units, the real send can take a slightly different path
+    pub simulation_margin: f64,
+    pub max_unit_limit: u32,
+}
+
+impl Default for ComputeBudgetConfig {
+    fn default() -> Self {
+        Self {
+            pump_fun_unit_limit: pumpdotfun::UNIT_BUDGET,
+            pump_swap_unit_limit: pumpswap::UNIT_BUDGET,
+            raydium_unit_limit: raydium::COMPUTE_BUDGET_UNITS,
+            simulation_margin: 1.2,
+            max_unit_limit: 1_400_000,
+        }
+    }
+}
+
+impl ComputeBudgetConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let env_or = |key: &str, fallback: u32| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(fallback)
+        };
+
+        Self {
+            pump_fun_unit_limit: env_or("COMPUTE_UNIT_LIMIT_PUMP_FUN", default.pump_fun_unit_limit),
+            pump_swap_unit_limit: env_or(
+                "COMPUTE_UNIT_LIMIT_PUMP_SWAP",
+                default.pump_swap_unit_limit,
+            ),
+            raydium_unit_limit: env_or("COMPUTE_UNIT_LIMIT_RAYDIUM", default.raydium_unit_limit),
+            simulation_margin: std::env::var("COMPUTE_UNIT_SIMULATION_MARGIN")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.simulation_margin),
+            max_unit_limit: default.max_unit_limit,
+        }
+    }
+
+    // Jupiter and Orca copies are executed through Raydium
+    pub fn unit_limit(&self, dex_type: &DexType) -> u32 {
+        match dex_type {
+            DexType::PumpFun => self.pump_fun_unit_limit,
+            DexType::PumpSwap => self.pump_swap_unit_limit,
+            DexType::Raydium | DexType::Jupiter | DexType::OrcaWhirlpool | DexType::Unknown => {
+                self.raydium_unit_limit
+            }
+        }
+    }
+
+    pub fn simulated_unit_limit(&self, units_consumed: u64) -> u32 {
+        ((units_consumed as f64 * self.simulation_margin) as u64).min(self.max_unit_limit as u64)
+            as u32
+    }
+}
+
+static COMPUTE_BUDGET_CONFIG: Lazy<ComputeBudgetConfig> = Lazy::new(ComputeBudgetConfig::from_env);
+
+pub fn compute_budget_config() -> &'static ComputeBudgetConfig {
+    &COMPUTE_BUDGET_CONFIG
+}
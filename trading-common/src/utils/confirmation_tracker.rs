@@ -0,0 +1,445 @@
+use once_cell::sync::OnceCell;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{field, info, warn, Span};
+use uuid::Uuid;
+
+use crate::database::SupabaseClient;
+use crate::event_system::EventSystem;
+use crate::metrics::record_sent;
+use crate::models::{
+    ErrorContext, ErrorInfo, ErrorSeverity, TradeStatus, TransactionState, TransactionStateChange,
+    TransactionStateNotification,
+};
+
+// getSignatureStatuses accepts at most this many signatures per call
+const MAX_SIGNATURES_PER_REQUEST: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct ConfirmationTrackerConfig {
+    pub initial_poll_interval: Duration,
+    pub max_poll_interval: Duration,
+    // A blockhash expires after ~150 blocks, a signature still unknown after
+    // this long can no longer land
+    pub drop_after: Duration,
+    pub max_tracked: usize,
+}
+
+impl Default for ConfirmationTrackerConfig {
+    fn default() -> Self {
+        Self {
+            initial_poll_interval: Duration::from_secs(1),
+            max_poll_interval: Duration::from_secs(10),
+            drop_after: Duration::from_secs(90),
+            max_tracked: 1_000,
+        }
+    }
+}
+
+impl ConfirmationTrackerConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            initial_poll_interval: std::env::var("CONFIRMATION_POLL_INTERVAL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(default.initial_poll_interval),
+            drop_after: std::env::var("CONFIRMATION_DROP_AFTER_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default.drop_after),
+            ..default
+        }
+    }
+}
+
+static CONFIRMATION_TRACKER: OnceCell<ConfirmationTracker> = OnceCell::new();
+
+// Called by the executors right after a transaction is sent. Nothing is
+// tracked until a tracker has been installed
+pub fn track_signature(signature: &Signature) {
+    // Tags the executor's trade span, a rebuild overwrites it with the new signature
+    Span::current().record("signature", field::display(signature));
+    record_sent();
+    if let Some(tracker) = CONFIRMATION_TRACKER.get() {
+        tracker.track(*signature);
+    }
+}
+
+#[derive(Clone)]
+pub struct ConfirmationTracker {
+    sender: mpsc::UnboundedSender<Signature>,
+}
+
+impl ConfirmationTracker {
+    pub fn spawn(
+        config: ConfirmationTrackerConfig,
+        rpc_client: Arc<RpcClient>,
+        event_system: Arc<EventSystem>,
+        supabase_client: Option<Arc<SupabaseClient>>,
+    ) -> (Self, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let worker = TrackerWorker {
+            config,
+            rpc_client,
+            event_system,
+            supabase_client,
+            pending: HashMap::new(),
+        };
+        let handle = tokio::spawn(worker.run(receiver));
+        (Self { sender }, handle)
+    }
+
+    // Makes this tracker the one track_signature reports to. Only the first
+    // install wins
+    pub fn install(self) -> bool {
+        CONFIRMATION_TRACKER.set(self).is_ok()
+    }
+
+    pub fn track(&self, signature: Signature) {
+        if self.sender.send(signature).is_err() {
+            warn!("Confirmation tracker stopped, not tracking {}", signature);
+        }
+    }
+}
+
+struct TrackedSignature {
+    state: TransactionState,
+    submitted_at: Instant,
+    next_check: Instant,
+    interval: Duration,
+}
+
+// An unknown signature only counts as dropped while it was never seen, a
+// confirmed one that stops being returned is still waiting to finalize
+fn next_state(
+    tracked: &TrackedSignature,
+    status: Option<&TransactionStatus>,
+    drop_after: Duration,
+) -> Option<(TransactionState, Option<String>)> {
+    let state = match status {
+        Some(status) => {
+            if let Some(err) = &status.err {
+                return Some((TransactionState::Failed, Some(err.to_string())));
+            }
+            match status.confirmation_status {
+                Some(TransactionConfirmationStatus::Finalized) => TransactionState::Finalized,
+                Some(TransactionConfirmationStatus::Confirmed) => TransactionState::Confirmed,
+                _ => return None,
+            }
+        }
+        None if tracked.state == TransactionState::Submitted
+            && tracked.submitted_at.elapsed() > drop_after =>
+        {
+            return Some((
+                TransactionState::Dropped,
+                Some("Transaction was not seen before its blockhash expired".to_string()),
+            ));
+        }
+        None => return None,
+    };
+
+    (state != tracked.state).then_some((state, None))
+}
+
+struct TrackerWorker {
+    config: ConfirmationTrackerConfig,
+    rpc_client: Arc<RpcClient>,
+    event_system: Arc<EventSystem>,
+    supabase_client: Option<Arc<SupabaseClient>>,
+    pending: HashMap<Signature, TrackedSignature>,
+}
+
+impl TrackerWorker {
+    async fn run(mut self, mut receiver: mpsc::UnboundedReceiver<Signature>) {
+        let mut poll_interval = tokio::time::interval(self.config.initial_poll_interval);
+        loop {
+            tokio::select! {
+                signature = receiver.recv() => {
+                    match signature {
+                        Some(signature) => self.add(signature).await,
+                        None => break,
+                    }
+                }
+                _ = poll_interval.tick() => {
+                    self.poll().await;
+                }
+            }
+        }
+    }
+
+    async fn add(&mut self, signature: Signature) {
+        if self.pending.contains_key(&signature) {
+            return;
+        }
+        if self.pending.len() >= self.config.max_tracked {
+            info!(
+                "Tracking {} signatures already, not tracking {}",
+                self.pending.len(),
+                signature
+            );
+            return;
+        }
+
+        let now = Instant::now();
+        self.pending.insert(
+            signature,
+            TrackedSignature {
+                state: TransactionState::Submitted,
+                submitted_at: now,
+                next_check: now + self.config.initial_poll_interval,
+                interval: self.config.initial_poll_interval,
+            },
+        );
+        self.emit(&signature, TransactionState::Submitted, None, None, None)
+            .await;
+    }
+
+    async fn poll(&mut self) {
+        let now = Instant::now();
+        let due = self
+            .pending
+            .iter()
+            .filter(|(_, tracked)| tracked.next_check <= now)
+            .map(|(signature, _)| *signature)
+            .take(MAX_SIGNATURES_PER_REQUEST)
+            .collect::<Vec<_>>();
+        if due.is_empty() {
+            return;
+        }
+
+        let statuses = match self.rpc_client.get_signature_statuses(&due) {
+            Ok(response) => response.value,
+            Err(e) => {
+                warn!("Failed to fetch signature statuses: {}", e);
+                return;
+            }
+        };
+
+        for (signature, status) in due.iter().zip(statuses) {
+            let next_state = match self.pending.get(signature) {
+                Some(tracked) => next_state(tracked, status.as_ref(), self.config.drop_after),
+                None => continue,
+            };
+
+            match next_state {
+                Some((state, error)) => {
+                    let slot = status.as_ref().map(|status| status.slot);
+                    self.transition(signature, state, slot, error).await;
+                }
+                None => {
+                    if let Some(tracked) = self.pending.get_mut(signature) {
+                        tracked.interval =
+                            (tracked.interval * 2).min(self.config.max_poll_interval);
+                        tracked.next_check = now + tracked.interval;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn transition(
+        &mut self,
+        signature: &Signature,
+        state: TransactionState,
+        slot: Option<u64>,
+        error: Option<String>,
+    ) {
+        let previous_state = match self.pending.get_mut(signature) {
+            Some(tracked) => {
+                let previous_state = tracked.state;
+                tracked.state = state;
+                tracked.next_check = Instant::now() + tracked.interval;
+                previous_state
+            }
+            None => return,
+        };
+
+        info!(
+            "Transaction {} {:?} -> {:?}",
+            signature, previous_state, state
+        );
+
+        // Confirmed transactions are followed until finalized
+        if matches!(
+            state,
+            TransactionState::Finalized | TransactionState::Failed | TransactionState::Dropped
+        ) {
+            self.pending.remove(signature);
+        }
+
+        let trade_status = match state {
+            TransactionState::Confirmed => Some(TradeStatus::Success),
+            TransactionState::Finalized if previous_state != TransactionState::Confirmed => {
+                Some(TradeStatus::Success)
+            }
+            TransactionState::Failed | TransactionState::Dropped => Some(TradeStatus::Failed),
+            _ => None,
+        };
+        if let (Some(trade_status), Some(supabase_client)) = (trade_status, &self.supabase_client) {
+            if let Err(e) = supabase_client
+                .update_trade_execution_by_signature(
+                    &signature.to_string(),
+                    trade_status,
+                    error.as_deref(),
+                )
+                .await
+            {
+                warn!("Failed to update trade execution {}: {}", signature, e);
+                self.event_system.report_error(
+                    ErrorInfo::new(
+                        ErrorSeverity::Warning,
+                        "confirmation_tracker",
+                        "database_write_failed",
+                        format!("Failed to update trade execution: {}", e),
+                    )
+                    .with_context(ErrorContext {
+                        signature: Some(signature.to_string()),
+                        ..Default::default()
+                    }),
+                    Some(signature.to_string()),
+                );
+            }
+        }
+
+        self.emit(signature, state, Some(previous_state), slot, error)
+            .await;
+    }
+
+    async fn emit(
+        &self,
+        signature: &Signature,
+        state: TransactionState,
+        previous_state: Option<TransactionState>,
+        slot: Option<u64>,
+        error: Option<String>,
+    ) {
+        self.event_system
+            .handle_transaction_state(TransactionStateNotification {
+                data: TransactionStateChange {
+                    signature: signature.to_string(),
+                    state,
+                    previous_state,
+                    slot,
+                    error,
+                },
+                type_: "transaction_state".to_string(),
+                event_id: Uuid::new_v4(),
+                correlation_id: Some(signature.to_string()),
+            })
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::transaction::TransactionError;
+
+    const DROP_AFTER: Duration = Duration::from_secs(90);
+
+    fn tracked(state: TransactionState, age: Duration) -> TrackedSignature {
+        let now = Instant::now();
+        TrackedSignature {
+            state,
+            submitted_at: now - age,
+            next_check: now,
+            interval: Duration::from_secs(1),
+        }
+    }
+
+    fn status(
+        confirmation_status: Option<TransactionConfirmationStatus>,
+        err: Option<TransactionError>,
+    ) -> TransactionStatus {
+        TransactionStatus {
+            slot: 42,
+            confirmations: None,
+            status: err.clone().map_or(Ok(()), Err),
+            err,
+            confirmation_status,
+        }
+    }
+
+    #[test]
+    fn transaction_error_fails_the_signature() {
+        let status = status(
+            Some(TransactionConfirmationStatus::Confirmed),
+            Some(TransactionError::AccountNotFound),
+        );
+        let (state, error) = next_state(
+            &tracked(TransactionState::Submitted, Duration::ZERO),
+            Some(&status),
+            DROP_AFTER,
+        )
+        .unwrap();
+
+        assert_eq!(state, TransactionState::Failed);
+        assert!(error.is_some());
+    }
+
+    #[test]
+    fn confirmed_signature_moves_on_to_finalized() {
+        let confirmed = status(Some(TransactionConfirmationStatus::Confirmed), None);
+        let finalized = status(Some(TransactionConfirmationStatus::Finalized), None);
+
+        assert_eq!(
+            next_state(
+                &tracked(TransactionState::Submitted, Duration::ZERO),
+                Some(&confirmed),
+                DROP_AFTER
+            ),
+            Some((TransactionState::Confirmed, None))
+        );
+        // Seeing the same status again is not a transition
+        assert_eq!(
+            next_state(
+                &tracked(TransactionState::Confirmed, Duration::ZERO),
+                Some(&confirmed),
+                DROP_AFTER
+            ),
+            None
+        );
+        assert_eq!(
+            next_state(
+                &tracked(TransactionState::Confirmed, Duration::ZERO),
+                Some(&finalized),
+                DROP_AFTER
+            ),
+            Some((TransactionState::Finalized, None))
+        );
+    }
+
+    #[test]
+    fn unseen_signature_is_dropped_after_the_timeout() {
+        let young = tracked(TransactionState::Submitted, Duration::from_secs(10));
+        assert_eq!(next_state(&young, None, DROP_AFTER), None);
+
+        let old = tracked(
+            TransactionState::Submitted,
+            DROP_AFTER + Duration::from_secs(1),
+        );
+        let (state, error) = next_state(&old, None, DROP_AFTER).unwrap();
+        assert_eq!(state, TransactionState::Dropped);
+        assert!(error.is_some());
+    }
+
+    #[test]
+    fn confirmed_signature_is_never_dropped() {
+        let confirmed = tracked(
+            TransactionState::Confirmed,
+            DROP_AFTER + Duration::from_secs(60),
+        );
+
+        assert_eq!(next_state(&confirmed, None, DROP_AFTER), None);
+    }
+}
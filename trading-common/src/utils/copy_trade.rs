@@ -1,27 +1,86 @@
 use anyhow::Result;
 use solana_client::rpc_client::RpcClient;
-use solana_sdk::transaction::Transaction;
 use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
 use std::str::FromStr;
 use std::sync::Arc;
+use tracing::{info, warn};
 
+use crate::amounts::{lamports_to_sol, sol_to_lamports};
+use crate::chunked_execution::process_chunked_buy_request;
+use crate::compute_budget::compute_budget_config;
+use crate::constants::{BASE_TRANSACTION_FEE_LAMPORTS, TOKEN_ACCOUNT_RENT_LAMPORTS};
 use crate::dex::DexType;
-use crate::models::SellRequest;
+use crate::kill_switch::kill_switch;
+use crate::metrics::record_skip;
+use crate::models::{InsufficientBalanceInfo, SellRequest, SlippageReport};
+use crate::priority_fee::priority_fee_config;
 use crate::pumpdotfun;
+use crate::pumpswap;
 use crate::raydium;
 use crate::utils::data::get_token_balance;
 use crate::wallet::server_wallet_manager::ServerWalletManager;
 use crate::{models::BuyRequest, ClientTxInfo, CopyTradeSettings, TransactionType};
+
+// Rough upper bound of what a buy costs on top of the trade amount itself. The
+// priority fee is whatever the estimator may charge at most, not a typical fee
+pub fn estimate_buy_fees_lamports(dex_type: &DexType, needs_token_account: bool) -> u64 {
+    let (signatures, temporary_accounts) = match dex_type {
+        DexType::PumpFun => (1, 0),
+        // Raydium buys fund a temporary WSOL account that is closed in the same transaction.
+        // Jupiter and Orca copies are executed through Raydium
+        DexType::Raydium | DexType::Jupiter | DexType::OrcaWhirlpool => (2, 1),
+        // PumpSwap buys wrap SOL into the WSOL ATA and close it afterwards
+        DexType::PumpSwap => (1, 1),
+        DexType::Unknown => (1, 0),
+    };
+    let priority_fee = match dex_type {
+        DexType::Unknown => 0,
+        _ => priority_fee_config()
+            .max_priority_fee_lamports(compute_budget_config().unit_limit(dex_type)),
+    };
+
+    let token_account_rent = if needs_token_account {
+        TOKEN_ACCOUNT_RENT_LAMPORTS
+    } else {
+        0
+    };
+
+    BASE_TRANSACTION_FEE_LAMPORTS * signatures
+        + priority_fee
+        + TOKEN_ACCOUNT_RENT_LAMPORTS * temporary_accounts
+        + token_account_rent
+}
+
+pub fn has_sufficient_balance(
+    balance_sol: f64,
+    trade_amount_sol: f64,
+    estimated_fees_lamports: u64,
+    min_sol_balance: f64,
+) -> bool {
+    let required = sol_to_lamports(trade_amount_sol)
+        + estimated_fees_lamports
+        + sol_to_lamports(min_sol_balance);
+    sol_to_lamports(balance_sol) >= required
+}
+
 pub async fn should_copy_trade(
     tx_info: &ClientTxInfo,
     settings: &CopyTradeSettings,
     server_wallet_manager: &Arc<tokio::sync::Mutex<ServerWalletManager>>,
 ) -> Result<bool> {
+    // A halt is a skip rather than a failed trade, so it never trips the failure breaker
+    if let Err(e) = kill_switch().check() {
+        info!("Skipping copy trade of {}: {}", tx_info.token_address, e);
+        record_skip("trading_halted");
+        return Ok(false);
+    }
+
     // Token allowlist check
     if settings.use_allowed_tokens_list {
         if let Some(allowed_tokens) = &settings.allowed_tokens {
             if !allowed_tokens.contains(&tx_info.token_address) {
-                println!("Token not in allowed list: {}", tx_info.token_address);
+                info!("Token not in allowed list: {}", tx_info.token_address);
+                record_skip("token_not_allowed");
                 return Ok(false);
             }
         }
@@ -35,157 +94,491 @@ pub async fn should_copy_trade(
             let current_positions = manager.get_tokens().len();
 
             if current_positions >= settings.max_open_positions as usize {
-                println!(
+                info!(
                     "Maximum open positions reached: Current {} of {}",
                     current_positions, settings.max_open_positions
                 );
+                record_skip("max_open_positions");
                 return Ok(false);
             }
 
             if !settings.allow_additional_buys {
                 // Check if we already hold this token
                 if manager.get_tokens().contains_key(&tx_info.token_address) {
-                    println!("Additional buys not allowed and token already held");
+                    info!("Additional buys not allowed and token already held");
+                    record_skip("already_held");
                     return Ok(false);
                 }
             }
+
+            // Fetch a fresh balance so earlier trades in this session are accounted for
+            let current_balance = manager.get_sol_balance().await?;
+            let needs_token_account = !manager.get_tokens().contains_key(&tx_info.token_address);
+            let estimated_fees = estimate_buy_fees_lamports(&tx_info.dex_type, needs_token_account);
+
+            if !has_sufficient_balance(
+                current_balance,
+                settings.trade_amount_sol,
+                estimated_fees,
+                settings.min_sol_balance,
+            ) {
+                let estimated_fees_sol = lamports_to_sol(estimated_fees);
+                let required_balance_sol =
+                    settings.trade_amount_sol + estimated_fees_sol + settings.min_sol_balance;
+                warn!(
+                    "Insufficient balance for copy trade: have {} SOL, need {} SOL",
+                    current_balance, required_balance_sol
+                );
+
+                manager.emit_insufficient_balance(InsufficientBalanceInfo {
+                    token_address: tx_info.token_address.clone(),
+                    token_symbol: tx_info.token_symbol.clone(),
+                    current_balance_sol: current_balance,
+                    required_balance_sol,
+                    trade_amount_sol: settings.trade_amount_sol,
+                    estimated_fees_sol,
+                    min_sol_balance: settings.min_sol_balance,
+                });
+                record_skip("insufficient_balance");
+                return Ok(false);
+            }
         }
         TransactionType::Sell => {
             // Sell-specific validation goes here
         }
-        _ => return Ok(false),
+        _ => {
+            record_skip("unsupported_transaction_type");
+            return Ok(false);
+        }
     }
 
     Ok(true)
 }
 
+// Large copies into thin Raydium pools can move the price by more than the
+// slippage we accept, in which case the copy is skipped. Other venues pass
+pub async fn within_price_impact(
+    rpc_client: &Arc<RpcClient>,
+    tx_info: &ClientTxInfo,
+    settings: &CopyTradeSettings,
+    venue: &DexType,
+) -> bool {
+    if *venue != DexType::Raydium || tx_info.transaction_type != TransactionType::Buy {
+        return true;
+    }
+
+    let impact = async {
+        let pool_id = raydium::resolve_pool_id(rpc_client, &tx_info.token_address).await?;
+        let pool_type = raydium::detect_pool_type(rpc_client, &pool_id)?;
+        raydium::estimate_price_impact(
+            rpc_client,
+            &pool_id,
+            pool_type,
+            &Pubkey::from_str(raydium::WSOL)?,
+            sol_to_lamports(settings.trade_amount_sol),
+        )
+    }
+    .await;
+
+    match impact {
+        Ok(impact) if impact.price_impact > settings.max_slippage => {
+            warn!(
+                "Price impact {:.2}% for {} SOL on {} exceeds max slippage {:.2}%, skipping copy",
+                impact.price_impact * 100.0,
+                settings.trade_amount_sol,
+                tx_info.token_address,
+                settings.max_slippage * 100.0
+            );
+            record_skip("price_impact");
+            false
+        }
+        Ok(impact) => {
+            info!(
+                "Estimated price impact: {:.2}%",
+                impact.price_impact * 100.0
+            );
+            true
+        }
+        // The trade itself will surface a missing pool, don't block on the estimate
+        Err(e) => {
+            warn!(
+                "Price impact estimate failed for {}: {}",
+                tx_info.token_address, e
+            );
+            true
+        }
+    }
+}
+
+// Where a copy goes based on the mint's bonding curve, for signals whose venue
+// may not match where the token trades now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CurveRoute {
+    BondingCurve,
+    Graduated,
+    Amm,
+}
+
+// curve_complete is None when the mint has no readable bonding curve. A pump.fun
+// signal stays on the curve unless it's known to be complete, an AMM signal without
+// a pool only falls back to the curve while it's still active
+fn route_by_curve_state(signal: &DexType, curve_complete: Option<bool>) -> CurveRoute {
+    match (signal, curve_complete) {
+        (DexType::PumpFun, Some(true)) => CurveRoute::Graduated,
+        (DexType::PumpFun, _) => CurveRoute::BondingCurve,
+        (_, Some(false)) => CurveRoute::BondingCurve,
+        (_, _) => CurveRoute::Amm,
+    }
+}
+
+// Graduated tokens trade on PumpSwap when a pool exists there, otherwise Raydium
+fn graduated_venue(has_pumpswap_pool: bool) -> DexType {
+    if has_pumpswap_pool {
+        DexType::PumpSwap
+    } else {
+        DexType::Raydium
+    }
+}
+
+async fn curve_complete(rpc_client: &RpcClient, mint: &Pubkey) -> Option<bool> {
+    pumpdotfun::get_bonding_curve_data(rpc_client, mint)
+        .await
+        .ok()
+        .map(|curve| curve.complete)
+}
+
+// Picks the venue a copy is executed on. Venues we can't trade on directly are
+// either routed through pump.fun / Raydium for the same mint or skipped
+pub async fn resolve_execution_venue(
+    rpc_client: &Arc<RpcClient>,
+    tx_info: &ClientTxInfo,
+    settings: &CopyTradeSettings,
+) -> Option<DexType> {
+    match tx_info.dex_type {
+        DexType::PumpFun => {
+            let mint = Pubkey::from_str(&tx_info.token_address).ok()?;
+            let complete = curve_complete(rpc_client, &mint).await;
+            match route_by_curve_state(&DexType::PumpFun, complete) {
+                CurveRoute::Graduated | CurveRoute::Amm => {
+                    let has_pumpswap_pool = matches!(
+                        pumpswap::find_pool_for_mint(rpc_client, &mint).await,
+                        Ok(Some(_))
+                    );
+                    let venue = graduated_venue(has_pumpswap_pool);
+                    info!(
+                        "Bonding curve for {} is complete, re-routing copy to {:?}",
+                        tx_info.token_address, venue
+                    );
+                    Some(venue)
+                }
+                CurveRoute::BondingCurve => Some(DexType::PumpFun),
+            }
+        }
+        DexType::PumpSwap => Some(DexType::PumpSwap),
+        DexType::Raydium | DexType::Jupiter => {
+            if raydium::resolve_pool_id(rpc_client, &tx_info.token_address)
+                .await
+                .is_ok()
+            {
+                return Some(DexType::Raydium);
+            }
+
+            // No pool yet, the token may still be on its bonding curve
+            let mint = Pubkey::from_str(&tx_info.token_address).ok()?;
+            let complete = curve_complete(rpc_client, &mint).await;
+            match route_by_curve_state(&tx_info.dex_type, complete) {
+                CurveRoute::BondingCurve => {
+                    info!(
+                        "No Raydium pool for {}, re-routing copy to the bonding curve",
+                        tx_info.token_address
+                    );
+                    Some(DexType::PumpFun)
+                }
+                CurveRoute::Graduated | CurveRoute::Amm => Some(DexType::Raydium),
+            }
+        }
+        DexType::OrcaWhirlpool if settings.route_unsupported_venues => {
+            let mint = Pubkey::from_str(&tx_info.token_address).ok()?;
+            let complete = curve_complete(rpc_client, &mint).await;
+            match route_by_curve_state(&DexType::OrcaWhirlpool, complete) {
+                CurveRoute::BondingCurve => Some(DexType::PumpFun),
+                CurveRoute::Graduated | CurveRoute::Amm => Some(DexType::Raydium),
+            }
+        }
+        DexType::OrcaWhirlpool | DexType::Unknown => None,
+    }
+}
+
+// Signature and amounts of the copy that was sent, no signature means nothing was traded
+#[derive(Debug, Clone, Default)]
+pub struct CopyTradeResult {
+    pub signature: Option<String>,
+    pub amount_sol: f64,
+    pub amount_token: f64,
+    pub slippage: Option<SlippageReport>,
+    pub child_signatures: Vec<String>,
+    pub route: Option<String>,
+}
+
+impl CopyTradeResult {
+    fn executed(&mut self, signature: &str, amount_sol: f64, amount_token: f64) {
+        self.signature = Some(signature.to_string());
+        self.amount_sol = amount_sol;
+        self.amount_token = amount_token;
+    }
+}
+
 pub async fn execute_copy_trade(
     rpc_client: &Arc<RpcClient>,
     server_keypair: &Keypair,
     tx_info: &ClientTxInfo,
     settings: &CopyTradeSettings,
     dex_type: DexType,
-) -> Result<()> {
+) -> Result<CopyTradeResult> {
+    let mut result = CopyTradeResult::default();
+
+    // Trade against the same pool as the tracked wallet, unless the venue was rerouted
+    let pool_address = if dex_type == tx_info.dex_type {
+        tx_info.pool_address.clone()
+    } else {
+        None
+    };
+
     match tx_info.transaction_type {
         TransactionType::Buy => {
             let request = BuyRequest {
                 token_address: tx_info.token_address.clone(),
                 sol_quantity: settings.trade_amount_sol,
                 slippage_tolerance: settings.max_slippage,
+                skip_simulation: settings.skip_simulation,
+                pool_address,
             };
 
-            match dex_type {
-                DexType::PumpFun => {
-                    println!("Executing Pump.fun buy");
-                    let response =
-                        pumpdotfun::process_buy_request(rpc_client, server_keypair, request)
-                            .await?;
-                    if response.success {
-                        println!("Pump.fun copy trade buy executed: {}", response.signature);
-                    }
-                }
-                DexType::Raydium => {
-                    println!("Executing Raydium buy");
-                    let response =
-                        raydium::process_buy_request(rpc_client, server_keypair, &request).await?;
-                    if response.success {
-                        println!("Raydium copy trade buy executed: {}", response.signature);
-                    }
-                }
-                DexType::Unknown => {
-                    println!("Unknown DEX type, cannot execute buy");
-                    return Ok(());
-                }
+            if matches!(dex_type, DexType::OrcaWhirlpool | DexType::Unknown) {
+                warn!("Unsupported venue {:?}, cannot execute buy", dex_type);
+                return Ok(result);
+            }
+
+            info!("Executing {:?} buy", dex_type);
+            let response =
+                process_chunked_buy_request(rpc_client, server_keypair, &request, &dex_type)
+                    .await?;
+            if response.success {
+                info!(
+                    "{:?} copy trade buy executed: {}",
+                    dex_type, response.signature
+                );
+                result.executed(
+                    &response.signature,
+                    response.sol_spent,
+                    response.token_quantity,
+                );
+                result.child_signatures = response.child_signatures;
+                result.route = response.route;
             }
         }
         TransactionType::Sell => {
-            println!("Preparing to execute copy trade sell");
+            info!("Preparing to execute copy trade sell");
             let token_mint = Pubkey::from_str(&tx_info.token_address)?;
 
-            // Create token account if needed
             let token_account = spl_associated_token_account::get_associated_token_address(
                 &server_keypair.pubkey(),
                 &token_mint,
             );
 
-            // Create ATA if it doesn't exist
+            // Without a token account there is nothing to sell
             if rpc_client.get_account(&token_account).is_err() {
-                println!("Creating token account for {}", tx_info.token_symbol);
-                let create_ata_ix =
-                    spl_associated_token_account::instruction::create_associated_token_account(
-                        &server_keypair.pubkey(),
-                        &server_keypair.pubkey(),
-                        &token_mint,
-                        &spl_token::id(),
-                    );
-
-                let recent_blockhash = rpc_client.get_latest_blockhash()?;
-                let create_ata_tx = Transaction::new_signed_with_payer(
-                    &[create_ata_ix],
-                    Some(&server_keypair.pubkey()),
-                    &[server_keypair],
-                    recent_blockhash,
-                );
-
-                rpc_client.send_and_confirm_transaction(&create_ata_tx)?;
-                println!("Token account created successfully");
+                info!("No {} token account, nothing to sell", tx_info.token_symbol);
+                return Ok(result);
             }
 
-            println!("Using token account: {}", token_account);
+            info!("Using token account: {}", token_account);
             let token_balance = get_token_balance(rpc_client, &token_account).await?;
-            println!(
+            info!(
                 "Found token balance to sell: {} {}",
                 token_balance, tx_info.token_symbol
             );
-            println!("Using max slippage: {}%", settings.max_slippage * 100.0);
+            info!("Using max slippage: {}%", settings.max_slippage * 100.0);
 
             if token_balance > 0.0 {
+                // Copy trades always exit the full position
                 let request = SellRequest {
                     token_address: tx_info.token_address.clone(),
                     token_quantity: token_balance,
                     slippage_tolerance: settings.max_slippage,
+                    sell_all: true,
+                    skip_simulation: settings.skip_simulation,
+                    pool_address,
                 };
 
                 match dex_type {
                     DexType::PumpFun => {
-                        println!("Executing Pump.fun sell");
+                        info!("Executing Pump.fun sell");
                         let response =
                             pumpdotfun::process_sell_request(rpc_client, server_keypair, request)
                                 .await?;
                         if response.success {
-                            println!("Pump.fun copy trade sell executed: {}", response.signature);
-                            println!(
+                            info!("Pump.fun copy trade sell executed: {}", response.signature);
+                            info!(
                                 "  Amount sold: {} {}",
                                 response.token_quantity, tx_info.token_symbol
                             );
-                            println!("  SOL received: {} SOL", response.sol_received);
+                            info!("  SOL received: {} SOL", response.sol_received);
+                            result.executed(
+                                &response.signature,
+                                response.sol_received,
+                                response.token_quantity,
+                            );
                         }
                     }
-                    DexType::Raydium => {
-                        println!("Executing Raydium sell");
+                    DexType::Raydium | DexType::Jupiter => {
+                        info!("Executing Raydium sell");
                         let response =
                             raydium::process_sell_request(rpc_client, server_keypair, &request)
                                 .await?;
                         if response.success {
-                            println!("Raydium copy trade sell executed: {}", response.signature);
-                            println!(
+                            info!("Raydium copy trade sell executed: {}", response.signature);
+                            info!(
                                 "  Amount sold: {} {}",
                                 response.token_quantity, tx_info.token_symbol
                             );
-                            println!("  SOL received: {} SOL", response.sol_received);
+                            info!("  SOL received: {} SOL", response.sol_received);
+                            result.executed(
+                                &response.signature,
+                                response.sol_received,
+                                response.token_quantity,
+                            );
+                            result.child_signatures = response.child_signatures;
+                            result.route = response.route;
                         }
+                        result.slippage = response.slippage;
                     }
-                    DexType::Unknown => {
-                        println!("Unknown DEX type, cannot execute sell");
-                        return Ok(());
+                    DexType::PumpSwap => {
+                        info!("Executing PumpSwap sell");
+                        let response =
+                            pumpswap::process_sell_request(rpc_client, server_keypair, &request)
+                                .await?;
+                        if response.success {
+                            info!("PumpSwap copy trade sell executed: {}", response.signature);
+                            info!(
+                                "  Amount sold: {} {}",
+                                response.token_quantity, tx_info.token_symbol
+                            );
+                            info!("  SOL received: {} SOL", response.sol_received);
+                            result.executed(
+                                &response.signature,
+                                response.sol_received,
+                                response.token_quantity,
+                            );
+                        }
+                    }
+                    DexType::OrcaWhirlpool | DexType::Unknown => {
+                        warn!("Unsupported venue {:?}, cannot execute sell", dex_type);
+                        return Ok(result);
                     }
                 }
             } else {
-                println!("No tokens to sell");
+                info!("No tokens to sell");
             }
         }
         _ => {}
     }
 
-    Ok(())
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balance_must_cover_trade_fees_and_reserve() {
+        let fees = 10_000;
+        // 0.5 SOL trade + 0.01 SOL reserve + 10_000 lamports of fees
+        let required = lamports_to_sol(500_000_000 + 10_000_000 + fees);
+
+        assert!(has_sufficient_balance(required, 0.5, fees, 0.01));
+        assert!(!has_sufficient_balance(
+            required - lamports_to_sol(1),
+            0.5,
+            fees,
+            0.01
+        ));
+    }
+
+    #[test]
+    fn balance_check_is_exact_in_lamports() {
+        // 0.1 + 0.2 is not 0.3 in f64, the comparison must not care
+        assert!(has_sufficient_balance(0.3, 0.1 + 0.2, 0, 0.0));
+        assert!(has_sufficient_balance(0.0, 0.0, 0, 0.0));
+        assert!(!has_sufficient_balance(0.0, 0.0, 1, 0.0));
+    }
+
+    #[test]
+    fn buy_fee_estimate_includes_token_account_rent() {
+        for dex_type in [
+            DexType::PumpFun,
+            DexType::PumpSwap,
+            DexType::Raydium,
+            DexType::Jupiter,
+            DexType::OrcaWhirlpool,
+            DexType::Unknown,
+        ] {
+            assert_eq!(
+                estimate_buy_fees_lamports(&dex_type, true)
+                    - estimate_buy_fees_lamports(&dex_type, false),
+                TOKEN_ACCOUNT_RENT_LAMPORTS
+            );
+        }
+        assert_eq!(
+            estimate_buy_fees_lamports(&DexType::Unknown, false),
+            BASE_TRANSACTION_FEE_LAMPORTS
+        );
+    }
+
+    #[test]
+    fn temporary_wsol_accounts_are_counted() {
+        assert!(
+            estimate_buy_fees_lamports(&DexType::Raydium, false)
+                >= 2 * BASE_TRANSACTION_FEE_LAMPORTS + TOKEN_ACCOUNT_RENT_LAMPORTS
+        );
+        assert!(
+            estimate_buy_fees_lamports(&DexType::PumpSwap, false)
+                >= BASE_TRANSACTION_FEE_LAMPORTS + TOKEN_ACCOUNT_RENT_LAMPORTS
+        );
+    }
+
+    #[test]
+    fn pump_fun_signal_stays_on_an_active_or_unreadable_curve() {
+        assert_eq!(
+            route_by_curve_state(&DexType::PumpFun, Some(false)),
+            CurveRoute::BondingCurve
+        );
+        assert_eq!(
+            route_by_curve_state(&DexType::PumpFun, None),
+            CurveRoute::BondingCurve
+        );
+    }
+
+    #[test]
+    fn pump_fun_signal_for_a_complete_curve_is_rerouted() {
+        assert_eq!(
+            route_by_curve_state(&DexType::PumpFun, Some(true)),
+            CurveRoute::Graduated
+        );
+        assert_eq!(graduated_venue(true), DexType::PumpSwap);
+        assert_eq!(graduated_venue(false), DexType::Raydium);
+    }
+
+    #[test]
+    fn amm_signal_without_a_pool_falls_back_to_an_active_curve() {
+        for signal in [DexType::Raydium, DexType::Jupiter, DexType::OrcaWhirlpool] {
+            assert_eq!(
+                route_by_curve_state(&signal, Some(false)),
+                CurveRoute::BondingCurve
+            );
+            assert_eq!(route_by_curve_state(&signal, Some(true)), CurveRoute::Amm);
+            assert_eq!(route_by_curve_state(&signal, None), CurveRoute::Amm);
+        }
+    }
 }
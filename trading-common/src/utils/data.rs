@@ -1,4 +1,6 @@
 use crate::error::AppError;
+use crate::metrics::record_confirmed;
+use crate::models::SellRequest;
 use anyhow::{anyhow, Context, Result};
 use once_cell::sync::Lazy;
 use serde_json::Value;
@@ -8,14 +10,19 @@ use solana_program::program_pack::Pack;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Keypair;
 use solana_sdk::signature::Signature;
-use solana_transaction_status::UiTransactionEncoding;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiTransactionEncoding,
+    UiTransactionTokenBalance,
+};
 use solana_transaction_status::{UiMessage, UiParsedMessage};
 use spl_token::state::Mint;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use surf::{Client, Url};
 use tokio::time::sleep;
+use tracing::{debug, info, warn};
 
 static HTTP_CLIENT: Lazy<Client> = Lazy::new(Client::new);
 
@@ -156,6 +163,54 @@ pub async fn get_token_balance(rpc_client: &RpcClient, token_account: &Pubkey) -
         .ok_or_else(|| anyhow!("Failed to get token balance"))
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct SellAmount {
+    pub amount: u64,
+    pub ui_amount: f64,
+    pub decimals: u8,
+}
+
+// Reads the balance at build time so sell-all requests sell exactly what's held
+pub fn resolve_sell_amount(
+    rpc_client: &RpcClient,
+    token_account: &Pubkey,
+    request: &SellRequest,
+) -> Result<SellAmount, AppError> {
+    let balance = rpc_client.get_token_account_balance(token_account)?;
+    let held = balance
+        .amount
+        .parse::<u64>()
+        .map_err(|e| AppError::TokenAccountError(format!("Invalid token balance: {}", e)))?;
+    let decimals = balance.decimals;
+
+    if held == 0 {
+        return Err(AppError::InsufficientBalanceError(format!(
+            "No {} balance to sell",
+            request.token_address
+        )));
+    }
+
+    let amount = if request.sell_all {
+        held
+    } else {
+        (request.token_quantity * 10f64.powi(decimals as i32)) as u64
+    };
+
+    if amount > held {
+        return Err(AppError::InsufficientBalanceError(format!(
+            "Have {} but tried to sell {}",
+            format_token_amount(held, decimals),
+            request.token_quantity
+        )));
+    }
+
+    Ok(SellAmount {
+        amount,
+        ui_amount: format_token_amount(amount, decimals),
+        decimals,
+    })
+}
+
 pub async fn get_metadata(rpc_client: &Arc<RpcClient>, mint: &Pubkey) -> Result<TokenMetadata> {
     let metadata_account = get_metadata_account(mint);
     let account_info = rpc_client
@@ -166,12 +221,12 @@ pub async fn get_metadata(rpc_client: &Arc<RpcClient>, mint: &Pubkey) -> Result<
 }
 
 pub async fn fetch_extended_metadata(uri: &str) -> Result<Value> {
-    println!("Fetching extended metadata from {}", uri);
+    info!("Fetching extended metadata from {}", uri);
     if uri.starts_with("ipfs://") || uri.contains("/ipfs/") {
-        println!("Fetching IPFS metadata from {}", uri);
+        info!("Fetching IPFS metadata from {}", uri);
         fetch_ipfs_metadata(uri).await
     } else {
-        println!("Fetching HTTP metadata from {}", uri);
+        info!("Fetching HTTP metadata from {}", uri);
         fetch_http_metadata(uri).await
     }
 }
@@ -186,7 +241,7 @@ async fn fetch_ipfs_metadata(uri: &str) -> Result<Value> {
 
     for gateway in IPFS_GATEWAYS {
         let full_uri = format!("{}{}", gateway, cid);
-        println!("Trying IPFS gateway: {}", full_uri);
+        info!("Trying IPFS gateway: {}", full_uri);
     }
 
     Err(anyhow!("Failed to fetch IPFS metadata from all gateways"))
@@ -198,13 +253,13 @@ async fn fetch_http_metadata(initial_uri: &str) -> Result<Value> {
     const MAX_REDIRECTS: u8 = 5;
 
     loop {
-        println!("Fetching HTTP metadata from {}", uri);
+        info!("Fetching HTTP metadata from {}", uri);
         let mut response = HTTP_CLIENT
             .get(&uri)
             .await
             .map_err(|e| anyhow!("Failed to fetch metadata from {}: {}", uri, e))?;
 
-        println!("Response: {:?}", response);
+        info!("Response: {:?}", response);
 
         if response.status().is_redirection() {
             if redirect_count >= MAX_REDIRECTS {
@@ -221,7 +276,7 @@ async fn fetch_http_metadata(initial_uri: &str) -> Result<Value> {
             let current_url = Url::parse(&uri)?;
             let new_url = current_url.join(&new_location)?;
 
-            println!("Following redirect to: {}", new_url);
+            info!("Following redirect to: {}", new_url);
             uri = new_url.to_string();
             redirect_count += 1;
             continue;
@@ -232,7 +287,7 @@ async fn fetch_http_metadata(initial_uri: &str) -> Result<Value> {
             .await
             .map_err(|e| anyhow!("Failed to parse metadata JSON from {}: {}", uri, e))?;
 
-        println!("JSON: {:?}", json);
+        info!("JSON: {:?}", json);
         return Ok(json);
     }
 }
@@ -244,6 +299,7 @@ pub async fn confirm_transaction(
     retry_interval: u64,
 ) -> Result<bool, AppError> {
     let mut retries = 0;
+    let started = Instant::now();
 
     while retries < max_retries {
         match rpc_client.get_transaction_with_config(
@@ -257,21 +313,22 @@ pub async fn confirm_transaction(
             Ok(confirmed_tx) => {
                 if let Some(meta) = confirmed_tx.transaction.meta {
                     if meta.err.is_none() {
-                        println!("Transaction confirmed... try count: {}", retries + 1);
+                        debug!("Transaction confirmed... try count: {}", retries + 1);
+                        record_confirmed(started.elapsed());
                         return Ok(true);
                     }
-                    println!("Transaction failed.");
+                    warn!("Transaction failed.");
                     return Ok(false);
                 }
             }
             Err(e) => {
-                println!(
+                debug!(
                     "Awaiting confirmation... try count: {}/{}",
                     retries + 1,
                     max_retries
                 );
                 if e.to_string().contains("Transaction version") {
-                    println!("Transaction failed.");
+                    warn!("Transaction failed.");
                     return Ok(false);
                 }
             }
@@ -281,7 +338,7 @@ pub async fn confirm_transaction(
         tokio::time::sleep(Duration::from_secs(retry_interval)).await;
     }
 
-    println!("Max retries reached. Transaction confirmation failed.");
+    warn!("Max retries reached. Transaction confirmation failed.");
     Ok(false)
 }
 
@@ -293,7 +350,7 @@ pub async fn sleeper(
 ) -> u32 {
     *retry_count += 1;
 
-    println!("Retry {} of {} for {}", retry_count, max_retries, signature);
+    warn!("Retry {} of {} for {}", retry_count, max_retries, signature);
 
     sleep(Duration::from_secs(retry_interval)).await;
 
@@ -318,3 +375,134 @@ pub fn get_account_keys_from_message(message: &UiMessage) -> Vec<String> {
         UiMessage::Raw(raw_message) => raw_message.account_keys.clone(),
     }
 }
+
+// Account keys in the order token balance indexes refer to. Parsed messages already
+// list lookup table addresses, raw v0 messages only carry the static keys and the
+// loaded writable then readonly addresses follow them
+pub fn get_transaction_account_keys(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Vec<String> {
+    let message = match &transaction.transaction.transaction {
+        EncodedTransaction::Json(tx) => &tx.message,
+        _ => return Vec::new(),
+    };
+
+    let mut account_keys = get_account_keys_from_message(message);
+    if let UiMessage::Raw(_) = message {
+        if let Some(OptionSerializer::Some(loaded)) = transaction
+            .transaction
+            .meta
+            .as_ref()
+            .map(|meta| &meta.loaded_addresses)
+        {
+            account_keys.extend(loaded.writable.iter().cloned());
+            account_keys.extend(loaded.readonly.iter().cloned());
+        }
+    }
+
+    account_keys
+}
+
+// Pre and post balance lists only contain token accounts that existed at that
+// point, so they're matched by account index rather than position
+pub fn token_balance_change(
+    pre_balances: &[UiTransactionTokenBalance],
+    post_balances: &[UiTransactionTokenBalance],
+    account_index: u8,
+) -> f64 {
+    let amount = |balances: &[UiTransactionTokenBalance]| {
+        balances
+            .iter()
+            .find(|b| b.account_index == account_index)
+            .and_then(|b| b.ui_token_amount.ui_amount)
+            .unwrap_or(0.0)
+    };
+
+    amount(post_balances) - amount(pre_balances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRADER_TOKEN_ACCOUNT: u8 = 1;
+    const CURVE_TOKEN_ACCOUNT: u8 = 5;
+
+    fn v0_transaction() -> EncodedConfirmedTransactionWithStatusMeta {
+        serde_json::from_str(include_str!("../../tests/fixtures/pumpdotfun_buy_v0.json")).unwrap()
+    }
+
+    fn token_balances(
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    ) -> (
+        Vec<UiTransactionTokenBalance>,
+        Vec<UiTransactionTokenBalance>,
+    ) {
+        let meta = transaction.transaction.meta.as_ref().unwrap();
+        (
+            Option::<Vec<_>>::from(meta.pre_token_balances.clone()).unwrap(),
+            Option::<Vec<_>>::from(meta.post_token_balances.clone()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn raw_v0_keys_are_followed_by_loaded_writable_then_readonly() {
+        let keys = get_transaction_account_keys(&v0_transaction());
+
+        assert_eq!(
+            keys,
+            vec![
+                "Trader1111111111111111111111111111111111111",
+                "TraderTokenAccount1111111111111111111111111",
+                "ComputeBudget111111111111111111111111111111",
+                "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P",
+                "BondingCurve111111111111111111111111111111",
+                "CurveTokenAccount11111111111111111111111111",
+                "Mint1111111111111111111111111111111111pump",
+            ]
+        );
+    }
+
+    #[test]
+    fn keys_without_loaded_addresses_are_the_static_keys() {
+        let mut transaction = v0_transaction();
+        transaction
+            .transaction
+            .meta
+            .as_mut()
+            .unwrap()
+            .loaded_addresses = OptionSerializer::None;
+
+        assert_eq!(get_transaction_account_keys(&transaction).len(), 4);
+    }
+
+    #[test]
+    fn binary_encoded_transactions_have_no_keys() {
+        let mut transaction = v0_transaction();
+        transaction.transaction.transaction = EncodedTransaction::LegacyBinary(String::new());
+
+        assert!(get_transaction_account_keys(&transaction).is_empty());
+    }
+
+    #[test]
+    fn balance_change_matches_by_account_index() {
+        let (pre, post) = token_balances(&v0_transaction());
+
+        // The curve account is second in the post list but first in the pre list
+        assert_eq!(
+            token_balance_change(&pre, &post, CURVE_TOKEN_ACCOUNT),
+            -35000.5
+        );
+    }
+
+    #[test]
+    fn balance_change_treats_a_missing_pre_balance_as_zero() {
+        let (pre, post) = token_balances(&v0_transaction());
+
+        assert_eq!(
+            token_balance_change(&pre, &post, TRADER_TOKEN_ACCOUNT),
+            35000.5
+        );
+        assert_eq!(token_balance_change(&pre, &post, 2), 0.0);
+    }
+}
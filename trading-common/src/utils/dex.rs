@@ -1,16 +1,26 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use tracing::info;
 
 use crate::{
+    jupiter, orca,
     pumpdotfun::{self},
-    raydium, TransactionType,
+    pumpswap, raydium, TransactionType,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum DexType {
     PumpFun,
     Raydium,
+    // Variants added after the frontend shipped keep explicit names so the
+    // serialized values never change
+    #[serde(rename = "Jupiter")]
+    Jupiter,
+    #[serde(rename = "OrcaWhirlpool")]
+    OrcaWhirlpool,
+    #[serde(rename = "PumpSwap")]
+    PumpSwap,
     Unknown,
 }
 
@@ -30,27 +40,56 @@ impl DexTransaction {
             let empty_logs = Vec::new();
             let logs = meta.log_messages.as_ref().unwrap_or(&empty_logs);
 
-            println!("Checking DEX type...");
+            info!("Checking DEX type...");
+
+            // Jupiter routes invoke the underlying AMMs, so check it first
+            let is_jupiter = logs
+                .iter()
+                .any(|log| log.contains(jupiter::constants::JUPITER_V6_PROGRAM_ID));
+            if is_jupiter {
+                info!("Detected Jupiter transaction");
+                return DexType::Jupiter;
+            }
+
+            // Migrated pump.fun tokens trade on the PumpSwap AMM
+            let is_pump_swap = logs
+                .iter()
+                .any(|log| log.contains(&pumpswap::constants::PUMP_SWAP_PROGRAM_ID.to_string()));
+            if is_pump_swap {
+                info!("Detected PumpSwap transaction");
+                return DexType::PumpSwap;
+            }
 
             // Check for Pump.fun signatures
             let is_pump_fun = logs
                 .iter()
                 .any(|log| log.contains(&pumpdotfun::constants::PUMP_FUN_PROGRAM_ID.to_string()));
             if is_pump_fun {
-                println!("Detected Pump.fun transaction");
+                info!("Detected Pump.fun transaction");
                 return DexType::PumpFun;
             }
 
             // Check for Raydium signatures
-            let is_raydium = logs
-                .iter()
-                .any(|log| log.contains(&raydium::constants::RAY_V4_PROGRAM_ID.to_string()));
+            let is_raydium = logs.iter().any(|log| {
+                log.contains(raydium::constants::RAY_V4_PROGRAM_ID)
+                    || log.contains(raydium::constants::RAY_CPMM_PROGRAM_ID)
+                    || log.contains(raydium::constants::RAY_CLMM_PROGRAM_ID)
+            });
             if is_raydium {
-                println!("Detected Raydium transaction");
+                info!("Detected Raydium transaction");
                 return DexType::Raydium;
             }
 
-            println!("No matching DEX found in transaction");
+            // Check for Orca Whirlpool signatures
+            let is_orca = logs
+                .iter()
+                .any(|log| log.contains(orca::constants::WHIRLPOOL_PROGRAM_ID));
+            if is_orca {
+                info!("Detected Orca Whirlpool transaction");
+                return DexType::OrcaWhirlpool;
+            }
+
+            info!("No matching DEX found in transaction");
         }
         DexType::Unknown
     }
@@ -71,6 +110,15 @@ impl DexTransaction {
                         DexType::Raydium => {
                             raydium::transaction::extract_transaction_details(transaction)?
                         }
+                        DexType::Jupiter => {
+                            jupiter::transaction::extract_transaction_details(transaction)?
+                        }
+                        DexType::OrcaWhirlpool => {
+                            orca::transaction::extract_transaction_details(transaction)?
+                        }
+                        DexType::PumpSwap => {
+                            pumpswap::transaction::extract_transaction_details(transaction)?
+                        }
                         DexType::Unknown => unreachable!(),
                     };
 
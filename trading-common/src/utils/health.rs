@@ -0,0 +1,191 @@
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::connection_monitor::ConnectionMonitor;
+use crate::models::{ConnectionState, ConnectionType};
+
+#[derive(Debug, Clone)]
+pub struct ReadinessConfig {
+    // A task that hasn't beaten for this long is considered stuck
+    pub max_heartbeat_age: Duration,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            max_heartbeat_age: Duration::from_secs(300),
+        }
+    }
+}
+
+impl ReadinessConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            max_heartbeat_age: std::env::var("HEALTH_MAX_HEARTBEAT_AGE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default.max_heartbeat_age),
+        }
+    }
+}
+
+// Long running loops beat on every iteration so a hung task shows up even
+// while its connections still look fine
+pub struct Heartbeat {
+    last: Mutex<Instant>,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self {
+            last: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn beat(&self) {
+        *self.last.lock() = Instant::now();
+    }
+
+    pub fn age(&self) -> Duration {
+        self.last.lock().elapsed()
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionHealth {
+    pub connection_type: ConnectionType,
+    pub state: Option<ConnectionState>,
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HeartbeatHealth {
+    pub name: String,
+    pub age_secs: f64,
+    pub max_age_secs: f64,
+    pub healthy: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub connections: Vec<ConnectionHealth>,
+    pub heartbeats: Vec<HeartbeatHealth>,
+    // State worth seeing from outside, such as whether copy trading is paused.
+    // A paused bot is still ready, flags never affect the verdict
+    pub flags: BTreeMap<String, bool>,
+}
+
+// Aggregates ConnectionMonitor state and task heartbeats into one verdict.
+// Every connection the monitor knows about must be connected. Connections
+// listed as required must also have reported at least once, others such as
+// the database only report when something goes wrong
+pub struct ReadinessCheck {
+    config: ReadinessConfig,
+    connection_monitor: Arc<ConnectionMonitor>,
+    required: Vec<ConnectionType>,
+    heartbeats: Vec<(String, Arc<Heartbeat>)>,
+    flags: Vec<(String, Arc<AtomicBool>)>,
+}
+
+impl ReadinessCheck {
+    pub fn new(config: ReadinessConfig, connection_monitor: Arc<ConnectionMonitor>) -> Self {
+        Self {
+            config,
+            connection_monitor,
+            required: Vec::new(),
+            heartbeats: Vec::new(),
+            flags: Vec::new(),
+        }
+    }
+
+    pub fn require_connection(mut self, connection_type: ConnectionType) -> Self {
+        self.required.push(connection_type);
+        self
+    }
+
+    pub fn with_heartbeat(mut self, name: &str, heartbeat: Arc<Heartbeat>) -> Self {
+        self.heartbeats.push((name.to_string(), heartbeat));
+        self
+    }
+
+    pub fn with_flag(mut self, name: &str, flag: Arc<AtomicBool>) -> Self {
+        self.flags.push((name.to_string(), flag));
+        self
+    }
+
+    pub fn report(&self) -> ReadinessReport {
+        let mut connections = self
+            .connection_monitor
+            .snapshot()
+            .into_iter()
+            .map(|status| ConnectionHealth {
+                connection_type: status.connection_type,
+                state: Some(status.state),
+                healthy: status.state == ConnectionState::Connected,
+                since: Some(status.since),
+                error: status.error,
+            })
+            .collect::<Vec<_>>();
+
+        for connection_type in &self.required {
+            if !connections
+                .iter()
+                .any(|connection| connection.connection_type == *connection_type)
+            {
+                connections.push(ConnectionHealth {
+                    connection_type: *connection_type,
+                    state: None,
+                    healthy: false,
+                    since: None,
+                    error: Some("No status reported yet".to_string()),
+                });
+            }
+        }
+
+        let max_age = self.config.max_heartbeat_age;
+        let heartbeats = self
+            .heartbeats
+            .iter()
+            .map(|(name, heartbeat)| {
+                let age = heartbeat.age();
+                HeartbeatHealth {
+                    name: name.clone(),
+                    age_secs: age.as_secs_f64(),
+                    max_age_secs: max_age.as_secs_f64(),
+                    healthy: age <= max_age,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        ReadinessReport {
+            ready: connections.iter().all(|connection| connection.healthy)
+                && heartbeats.iter().all(|heartbeat| heartbeat.healthy),
+            connections,
+            heartbeats,
+            flags: self
+                .flags
+                .iter()
+                .map(|(name, flag)| (name.clone(), flag.load(Ordering::SeqCst)))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::database::SupabaseClient;
+use crate::error::AppError;
+use crate::event_system::{Event, EventSystem};
+use crate::models::{TradingSwitch, TradingSwitchNotification};
+
+// Halts every trade, copy and manual, in every process sharing the database.
+// The switch is stored in the trading_switch table, so a process started while
+// trading is halted comes up halted. Until the table has been read once the
+// state is unknown and trading is refused
+pub struct KillSwitch {
+    state: RwLock<Option<TradingSwitch>>,
+    halted: Arc<AtomicBool>,
+    event_system: OnceCell<Arc<EventSystem>>,
+}
+
+static KILL_SWITCH: Lazy<KillSwitch> = Lazy::new(KillSwitch::new);
+
+pub fn kill_switch() -> &'static KillSwitch {
+    &KILL_SWITCH
+}
+
+impl KillSwitch {
+    pub fn new() -> Self {
+        Self {
+            state: RwLock::new(None),
+            halted: Arc::new(AtomicBool::new(true)),
+            event_system: OnceCell::new(),
+        }
+    }
+
+    // Halts and resumes are only acknowledged once an event system is attached
+    pub fn set_event_system(&self, event_system: Arc<EventSystem>) {
+        let _ = self.event_system.set(event_system);
+    }
+
+    // Every executor calls this right before signing
+    pub fn check(&self) -> Result<(), AppError> {
+        match &*self.state.read() {
+            None => Err(AppError::TradingHalted(
+                "The trading switch has not been loaded yet".to_string(),
+            )),
+            Some(switch) if switch.halted => Err(AppError::TradingHalted(
+                switch
+                    .reason
+                    .clone()
+                    .unwrap_or_else(|| "No reason given".to_string()),
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted.load(Ordering::SeqCst)
+    }
+
+    // For readiness reports, true until the switch has been loaded
+    pub fn halted_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.halted)
+    }
+
+    // Adopts the stored switch. Returns true when this halted or resumed trading
+    pub fn apply(&self, switch: TradingSwitch) -> bool {
+        let previous = self.state.write().replace(switch.clone());
+        self.halted.store(switch.halted, Ordering::SeqCst);
+
+        // Loading a running switch at startup changes nothing worth reporting
+        let changed = match &previous {
+            Some(previous) => previous.halted != switch.halted,
+            None => switch.halted,
+        };
+        if changed {
+            self.acknowledge(switch);
+        }
+        changed
+    }
+
+    // Reads the stored switch. When that fails the last known state stays in effect
+    pub async fn sync(&self, supabase_client: &SupabaseClient) -> Result<bool, AppError> {
+        let switch = supabase_client.get_trading_switch().await?;
+        Ok(self.apply(switch))
+    }
+
+    fn acknowledge(&self, switch: TradingSwitch) {
+        if switch.halted {
+            warn!(
+                "Trading halted: {}",
+                switch.reason.as_deref().unwrap_or("no reason given")
+            );
+        } else {
+            info!("Trading resumed");
+        }
+
+        if let Some(event_system) = self.event_system.get() {
+            event_system.emit(Event::TradingSwitch(TradingSwitchNotification {
+                data: switch,
+                type_: "trading_switch".to_string(),
+                event_id: Uuid::new_v4(),
+                correlation_id: None,
+            }));
+        }
+    }
+}
+
+impl Default for KillSwitch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn switch(halted: bool, reason: Option<&str>) -> TradingSwitch {
+        TradingSwitch {
+            halted,
+            reason: reason.map(str::to_string),
+            updated_at: None,
+        }
+    }
+
+    fn acknowledgments(events: &mut tokio::sync::broadcast::Receiver<Event>) -> Vec<TradingSwitch> {
+        let mut switches = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let Event::TradingSwitch(notification) = event {
+                switches.push(notification.data);
+            }
+        }
+        switches
+    }
+
+    #[test]
+    fn refuses_trading_until_loaded() {
+        let kill_switch = KillSwitch::new();
+
+        assert!(kill_switch.is_halted());
+        assert!(matches!(
+            kill_switch.check(),
+            Err(AppError::TradingHalted(_))
+        ));
+
+        kill_switch.apply(TradingSwitch::default());
+        assert!(!kill_switch.is_halted());
+        assert!(kill_switch.check().is_ok());
+    }
+
+    #[test]
+    fn halt_carries_its_reason() {
+        let kill_switch = KillSwitch::new();
+        kill_switch.apply(switch(true, Some("bad fills on every venue")));
+
+        match kill_switch.check() {
+            Err(AppError::TradingHalted(reason)) => assert_eq!(reason, "bad fills on every venue"),
+            other => panic!("expected a halt, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn acknowledges_halts_and_resumes_but_not_a_running_startup() {
+        let event_system = Arc::new(EventSystem::new());
+        let mut events = event_system.subscribe();
+        let kill_switch = KillSwitch::new();
+        kill_switch.set_event_system(event_system);
+
+        assert!(!kill_switch.apply(TradingSwitch::default()));
+        assert!(kill_switch.apply(switch(true, Some("manual halt"))));
+        // Polling the same state again is not a new halt
+        assert!(!kill_switch.apply(switch(true, Some("manual halt"))));
+        assert!(kill_switch.apply(switch(false, None)));
+
+        assert_eq!(
+            acknowledgments(&mut events),
+            vec![switch(true, Some("manual halt")), switch(false, None)]
+        );
+    }
+
+    #[test]
+    fn starting_halted_is_acknowledged() {
+        let event_system = Arc::new(EventSystem::new());
+        let mut events = event_system.subscribe();
+        let kill_switch = KillSwitch::new();
+        kill_switch.set_event_system(event_system);
+
+        assert!(kill_switch.apply(switch(true, None)));
+        assert_eq!(acknowledgments(&mut events), vec![switch(true, None)]);
+    }
+}
@@ -0,0 +1,41 @@
+use tracing_subscriber::EnvFilter;
+
+// Levels come from RUST_LOG, e.g. "info,trading_common::raydium=debug".
+// LOG_FORMAT=json switches to one JSON object per line for log shippers
+pub fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    let result = match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().with_current_span(true).try_init(),
+        _ => builder.try_init(),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to initialize tracing: {}", e);
+    }
+}
+
+// RPC providers put API keys in the path or query string and some URLs carry
+// credentials, only the scheme and host are safe to log
+pub fn redact_url(url: &str) -> String {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, url),
+    };
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let authority = &rest[..end];
+    let host = authority
+        .rsplit_once('@')
+        .map(|(_, host)| host)
+        .unwrap_or(authority);
+    let redacted = if end < rest.len() || host.len() < authority.len() {
+        "/***"
+    } else {
+        ""
+    };
+
+    match scheme {
+        Some(scheme) => format!("{}://{}{}", scheme, host, redacted),
+        None => format!("{}{}", host, redacted),
+    }
+}
@@ -0,0 +1,246 @@
+use once_cell::sync::OnceCell;
+use prometheus::{
+    Encoder, GaugeVec, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts, Registry, TextEncoder,
+};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::models::{ConnectionState, ConnectionType};
+
+const CONNECTION_STATES: [ConnectionState; 3] = [
+    ConnectionState::Connected,
+    ConnectionState::Reconnecting,
+    ConnectionState::Disconnected,
+];
+
+static METRICS: OnceCell<Arc<Metrics>> = OnceCell::new();
+
+tokio::task_local! {
+    // When the tracked transaction behind the current copy trade was observed
+    static SIGNAL_AT: Instant;
+}
+
+pub struct Metrics {
+    registry: Registry,
+    pub transactions_observed: IntCounter,
+    pub transactions_parsed: IntCounter,
+    pub transactions_skipped: IntCounterVec,
+    pub copy_trades_attempted: IntCounterVec,
+    pub copy_trades_succeeded: IntCounterVec,
+    pub copy_trades_failed: IntCounterVec,
+    pub signal_to_send_seconds: Histogram,
+    pub send_to_confirm_seconds: Histogram,
+    pub message_queue_depth: IntGauge,
+    pub tracked_wallets: IntGauge,
+    pub connection_status: IntGaugeVec,
+    pub notifications_sent: IntCounterVec,
+    pub notification_failures: IntCounterVec,
+    pub database_queries: IntCounterVec,
+    pub database_query_latency_seconds: GaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some("trading_bot".to_string()), None)?;
+
+        let metrics = Self {
+            transactions_observed: IntCounter::new(
+                "transactions_observed_total",
+                "Tracked wallet transactions seen on the websocket",
+            )?,
+            transactions_parsed: IntCounter::new(
+                "transactions_parsed_total",
+                "Tracked wallet transactions parsed into a trade or transfer",
+            )?,
+            transactions_skipped: IntCounterVec::new(
+                Opts::new(
+                    "transactions_skipped_total",
+                    "Transactions that were not copied, by reason",
+                ),
+                &["reason"],
+            )?,
+            copy_trades_attempted: IntCounterVec::new(
+                Opts::new(
+                    "copy_trades_attempted_total",
+                    "Copy trades sent to an executor",
+                ),
+                &["dex"],
+            )?,
+            copy_trades_succeeded: IntCounterVec::new(
+                Opts::new("copy_trades_succeeded_total", "Copy trades that executed"),
+                &["dex"],
+            )?,
+            copy_trades_failed: IntCounterVec::new(
+                Opts::new("copy_trades_failed_total", "Copy trades that failed"),
+                &["dex"],
+            )?,
+            signal_to_send_seconds: Histogram::with_opts(
+                HistogramOpts::new(
+                    "signal_to_send_seconds",
+                    "Time from observing a tracked transaction to sending the copy",
+                )
+                .buckets(vec![0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0]),
+            )?,
+            send_to_confirm_seconds: Histogram::with_opts(
+                HistogramOpts::new(
+                    "send_to_confirm_seconds",
+                    "Time from sending a transaction to its confirmation",
+                )
+                .buckets(vec![0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0]),
+            )?,
+            message_queue_depth: IntGauge::new(
+                "message_queue_depth",
+                "Transactions waiting in the message queue",
+            )?,
+            tracked_wallets: IntGauge::new("tracked_wallets", "Active tracked wallets")?,
+            connection_status: IntGaugeVec::new(
+                Opts::new(
+                    "connection_status",
+                    "1 for the current state of each external connection, 0 otherwise",
+                ),
+                &["connection", "state"],
+            )?,
+            notifications_sent: IntCounterVec::new(
+                Opts::new(
+                    "notifications_sent_total",
+                    "Notifications delivered, by channel",
+                ),
+                &["channel"],
+            )?,
+            notification_failures: IntCounterVec::new(
+                Opts::new(
+                    "notification_failures_total",
+                    "Notifications that could not be delivered, by channel",
+                ),
+                &["channel"],
+            )?,
+            database_queries: IntCounterVec::new(
+                Opts::new(
+                    "database_queries_total",
+                    "Supabase requests, by table, operation and outcome",
+                ),
+                &["table", "operation", "outcome"],
+            )?,
+            database_query_latency_seconds: GaugeVec::new(
+                Opts::new(
+                    "database_query_latency_seconds",
+                    "Rolling latency quantiles of successful Supabase requests, by table",
+                ),
+                &["table", "quantile"],
+            )?,
+            registry,
+        };
+
+        metrics
+            .registry
+            .register(Box::new(metrics.transactions_observed.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.transactions_parsed.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.transactions_skipped.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.copy_trades_attempted.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.copy_trades_succeeded.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.copy_trades_failed.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.signal_to_send_seconds.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.send_to_confirm_seconds.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.message_queue_depth.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.tracked_wallets.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.connection_status.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.notifications_sent.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.notification_failures.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.database_queries.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.database_query_latency_seconds.clone()))?;
+
+        Ok(metrics)
+    }
+
+    // Makes these the metrics the helpers below record to. Only the first
+    // install wins
+    pub fn install(self: Arc<Self>) -> bool {
+        METRICS.set(self).is_ok()
+    }
+
+    // Prometheus text exposition format
+    pub fn encode(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+// Nothing is recorded until metrics have been installed
+pub fn metrics() -> Option<&'static Arc<Metrics>> {
+    METRICS.get()
+}
+
+pub fn record_skip(reason: &str) {
+    if let Some(metrics) = metrics() {
+        metrics
+            .transactions_skipped
+            .with_label_values(&[reason])
+            .inc();
+    }
+}
+
+pub fn record_connection_state(connection_type: ConnectionType, state: ConnectionState) {
+    if let Some(metrics) = metrics() {
+        let connection = format!("{:?}", connection_type);
+        for candidate in CONNECTION_STATES {
+            metrics
+                .connection_status
+                .with_label_values(&[&connection, &format!("{:?}", candidate)])
+                .set((candidate == state) as i64);
+        }
+    }
+}
+
+// Sends made inside the future are timed against the observation of the signal
+pub async fn with_signal_time<F: Future>(signal_at: Instant, future: F) -> F::Output {
+    SIGNAL_AT.scope(signal_at, future).await
+}
+
+pub fn record_sent() {
+    if let Some(metrics) = metrics() {
+        if let Ok(signal_at) = SIGNAL_AT.try_with(|signal_at| *signal_at) {
+            metrics
+                .signal_to_send_seconds
+                .observe(signal_at.elapsed().as_secs_f64());
+        }
+    }
+}
+
+pub fn record_confirmed(elapsed: Duration) {
+    if let Some(metrics) = metrics() {
+        metrics
+            .send_to_confirm_seconds
+            .observe(elapsed.as_secs_f64());
+    }
+}
@@ -0,0 +1,171 @@
+use once_cell::sync::Lazy;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeePercentile {
+    P50,
+    P75,
+    P90,
+}
+
+impl FeePercentile {
+    fn value(&self) -> usize {
+        match self {
+            FeePercentile::P50 => 50,
+            FeePercentile::P75 => 75,
+            FeePercentile::P90 => 90,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PriorityFeeConfig {
+    pub percentile: FeePercentile,
+    // Upper bound on the total priority fee of a transaction
+    pub max_fee_lamports: u64,
+    // Skips sampling entirely, in micro-lamports per compute unit
+    pub static_unit_price: Option<u64>,
+}
+
+impl Default for PriorityFeeConfig {
+    fn default() -> Self {
+        Self {
+            percentile: FeePercentile::P75,
+            max_fee_lamports: 5_000_000,
+            static_unit_price: None,
+        }
+    }
+}
+
+impl PriorityFeeConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        let percentile = match std::env::var("PRIORITY_FEE_PERCENTILE").as_deref() {
+            Ok("50") | Ok("p50") => FeePercentile::P50,
+            Ok("75") | Ok("p75") => FeePercentile::P75,
+            Ok("90") | Ok("p90") => FeePercentile::P90,
+            _ => default.percentile,
+        };
+
+        Self {
+            percentile,
+            max_fee_lamports: std::env::var("PRIORITY_FEE_MAX_LAMPORTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.max_fee_lamports),
+            static_unit_price: std::env::var("PRIORITY_FEE_STATIC_MICRO_LAMPORTS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+        }
+    }
+}
+
+impl PriorityFeeConfig {
+    // Most a transaction with this compute unit limit pays in priority fees, in
+    // lamports. Sampled prices are capped at max_fee_lamports, a static one isn't
+    pub fn max_priority_fee_lamports(&self, compute_unit_limit: u32) -> u64 {
+        if compute_unit_limit == 0 {
+            return 0;
+        }
+        match self.static_unit_price {
+            Some(unit_price) => unit_price.saturating_mul(compute_unit_limit as u64) / 1_000_000,
+            None => self.max_fee_lamports,
+        }
+    }
+}
+
+static PRIORITY_FEE_CONFIG: Lazy<PriorityFeeConfig> = Lazy::new(PriorityFeeConfig::from_env);
+
+pub fn priority_fee_config() -> &'static PriorityFeeConfig {
+    &PRIORITY_FEE_CONFIG
+}
+
+// Returns the compute unit price in micro-lamports for a transaction touching
+// the given writable accounts. Falls back to the caller's default on RPC errors
+pub fn get_priority_fee(
+    rpc_client: &RpcClient,
+    accounts: &[Pubkey],
+    compute_unit_limit: u32,
+    fallback_unit_price: u64,
+) -> u64 {
+    estimate_priority_fee(
+        rpc_client,
+        accounts,
+        compute_unit_limit,
+        fallback_unit_price,
+        &PRIORITY_FEE_CONFIG,
+    )
+}
+
+pub fn estimate_priority_fee(
+    rpc_client: &RpcClient,
+    accounts: &[Pubkey],
+    compute_unit_limit: u32,
+    fallback_unit_price: u64,
+    config: &PriorityFeeConfig,
+) -> u64 {
+    if let Some(unit_price) = config.static_unit_price {
+        info!("Using static priority fee: {} micro-lamports", unit_price);
+        return unit_price;
+    }
+
+    let unit_price = match rpc_client.get_recent_prioritization_fees(accounts) {
+        Ok(samples) if !samples.is_empty() => {
+            let mut fees: Vec<u64> = samples.iter().map(|s| s.prioritization_fee).collect();
+            fees.sort_unstable();
+            let index = (fees.len() - 1) * config.percentile.value() / 100;
+            fees[index]
+        }
+        Ok(_) => fallback_unit_price,
+        Err(e) => {
+            warn!("Failed to fetch recent prioritization fees: {}", e);
+            fallback_unit_price
+        }
+    };
+
+    let max_unit_price = if compute_unit_limit > 0 {
+        config.max_fee_lamports.saturating_mul(1_000_000) / compute_unit_limit as u64
+    } else {
+        unit_price
+    };
+    let unit_price = unit_price.min(max_unit_price);
+
+    info!(
+        "Priority fee: {} micro-lamports ({:?}, cap {} lamports)",
+        unit_price, config.percentile, config.max_fee_lamports
+    );
+
+    unit_price
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampled_fees_are_bounded_by_the_cap() {
+        let config = PriorityFeeConfig {
+            max_fee_lamports: 2_000_000,
+            ..PriorityFeeConfig::default()
+        };
+
+        assert_eq!(config.max_priority_fee_lamports(200_000), 2_000_000);
+        assert_eq!(config.max_priority_fee_lamports(1_400_000), 2_000_000);
+        assert_eq!(config.max_priority_fee_lamports(0), 0);
+    }
+
+    #[test]
+    fn static_price_is_charged_per_unit() {
+        let config = PriorityFeeConfig {
+            max_fee_lamports: 1,
+            static_unit_price: Some(500_000),
+            ..PriorityFeeConfig::default()
+        };
+
+        // 500_000 micro-lamports for 200_000 units, the cap doesn't apply
+        assert_eq!(config.max_priority_fee_lamports(200_000), 100_000);
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::event_system::{Event, EventKind, EventSystem};
+use crate::metrics::metrics;
+
+const QUANTILES: [(f64, &str); 2] = [(0.5, "0.5"), (0.95, "0.95")];
+
+#[derive(Debug, Clone)]
+pub struct QueryLatencyConfig {
+    // Most recent successful queries per table the quantiles are taken over
+    pub window: usize,
+}
+
+impl Default for QueryLatencyConfig {
+    fn default() -> Self {
+        Self { window: 500 }
+    }
+}
+
+impl QueryLatencyConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            window: std::env::var("DB_LATENCY_WINDOW")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .filter(|window| *window > 0)
+                .unwrap_or(default.window),
+        }
+    }
+}
+
+// Keeps rolling p50/p95 per table from DatabaseQuery events and publishes them
+// as gauges. Failed queries are left out so timeouts don't skew the latency
+pub fn spawn_query_latency_aggregator(
+    config: QueryLatencyConfig,
+    event_system: Arc<EventSystem>,
+) -> JoinHandle<()> {
+    let mut events = event_system.subscribe_filtered(&[EventKind::DatabaseQuery]);
+
+    tokio::spawn(async move {
+        let mut windows: HashMap<String, VecDeque<f64>> = HashMap::new();
+
+        loop {
+            let query = match events.recv().await {
+                Ok(Event::DatabaseQuery(notification)) => notification.data,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Query latency aggregator missed {} events", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            if !query.success {
+                continue;
+            }
+
+            let window = windows.entry(query.table.clone()).or_default();
+            if window.len() >= config.window {
+                window.pop_front();
+            }
+            window.push_back(query.duration_ms as f64 / 1000.0);
+
+            if let Some(metrics) = metrics() {
+                let mut sorted = window.iter().copied().collect::<Vec<_>>();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                for (quantile, label) in QUANTILES {
+                    metrics
+                        .database_query_latency_seconds
+                        .with_label_values(&[&query.table, label])
+                        .set(percentile(&sorted, quantile));
+                }
+            }
+        }
+    })
+}
+
+// Nearest rank on an already sorted, non-empty slice
+fn percentile(sorted: &[f64], quantile: f64) -> f64 {
+    let rank = (quantile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
@@ -0,0 +1,148 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    http_sender::HttpSender,
+    rpc_client::{RpcClient, RpcClientConfig},
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::warn;
+
+use crate::logging::redact_url;
+
+// Counters are halved once an endpoint has seen this many requests so a
+// recovered endpoint isn't penalised forever
+const ERROR_RATE_WINDOW: u64 = 200;
+
+struct RpcEndpoint {
+    url: String,
+    sender: HttpSender,
+    requests: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl RpcEndpoint {
+    fn new(url: String) -> Self {
+        Self {
+            sender: HttpSender::new(url.clone()),
+            url,
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    fn error_rate(&self) -> f64 {
+        let requests = self.requests.load(Ordering::Relaxed);
+        if requests == 0 {
+            return 0.0;
+        }
+        self.errors.load(Ordering::Relaxed) as f64 / requests as f64
+    }
+
+    fn record_request(&self) {
+        let requests = self.requests.fetch_add(1, Ordering::Relaxed) + 1;
+        if requests >= ERROR_RATE_WINDOW {
+            self.requests.store(requests / 2, Ordering::Relaxed);
+            self.errors
+                .store(self.errors.load(Ordering::Relaxed) / 2, Ordering::Relaxed);
+        }
+    }
+
+    fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Sends each request to the healthiest endpoint, moving on to the next one
+// when the request fails at the transport level
+pub struct FailoverRpcSender {
+    endpoints: Vec<RpcEndpoint>,
+}
+
+impl FailoverRpcSender {
+    pub fn new(urls: Vec<String>) -> Self {
+        Self {
+            endpoints: urls.into_iter().map(RpcEndpoint::new).collect(),
+        }
+    }
+
+    fn ordered_endpoints(&self) -> Vec<&RpcEndpoint> {
+        let mut endpoints: Vec<&RpcEndpoint> = self.endpoints.iter().collect();
+        endpoints.sort_by(|a, b| a.error_rate().total_cmp(&b.error_rate()));
+        endpoints
+    }
+}
+
+#[async_trait]
+impl RpcSender for FailoverRpcSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let mut last_error = None;
+
+        for endpoint in self.ordered_endpoints() {
+            endpoint.record_request();
+            match endpoint.sender.send(request, params.clone()).await {
+                Ok(value) => return Ok(value),
+                Err(e) if is_transport_error(&e) => {
+                    endpoint.record_error();
+                    warn!(
+                        "RPC endpoint {} failed for {}: {}",
+                        redact_url(&endpoint.url),
+                        request,
+                        e
+                    );
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            ClientErrorKind::Custom("No RPC endpoints configured".to_string()).into()
+        }))
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.endpoints
+            .iter()
+            .map(|endpoint| endpoint.sender.get_transport_stats())
+            .fold(RpcTransportStats::default(), |mut total, stats| {
+                total.request_count += stats.request_count;
+                total.elapsed_time += stats.elapsed_time;
+                total.rate_limited_time += stats.rate_limited_time;
+                total
+            })
+    }
+
+    fn url(&self) -> String {
+        self.ordered_endpoints()
+            .first()
+            .map(|endpoint| endpoint.url.clone())
+            .unwrap_or_default()
+    }
+}
+
+fn is_transport_error(error: &ClientError) -> bool {
+    matches!(
+        error.kind(),
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) | ClientErrorKind::Middleware(_)
+    )
+}
+
+pub fn parse_endpoint_urls(urls: &str) -> Vec<String> {
+    urls.split(',')
+        .map(|url| url.trim())
+        .filter(|url| !url.is_empty())
+        .map(|url| url.to_string())
+        .collect()
+}
+
+// Accepts a single URL or a comma-separated list of RPC endpoints
+pub fn create_rpc_client(urls: &str) -> RpcClient {
+    RpcClient::new_sender(
+        FailoverRpcSender::new(parse_endpoint_urls(urls)),
+        RpcClientConfig::with_commitment(CommitmentConfig::default()),
+    )
+}
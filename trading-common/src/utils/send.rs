@@ -0,0 +1,125 @@
+use once_cell::sync::Lazy;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::error::AppError;
+use crate::metrics::record_confirmed;
+
+#[derive(Debug, Clone)]
+pub struct SendConfig {
+    // Times an expired transaction is rebuilt and sent again
+    pub max_rebuilds: u32,
+    pub confirmation_poll_interval: Duration,
+    // Upper bound on a single confirmation wait when the RPC can't report block height
+    pub confirmation_timeout: Duration,
+}
+
+impl Default for SendConfig {
+    fn default() -> Self {
+        Self {
+            max_rebuilds: 2,
+            confirmation_poll_interval: Duration::from_millis(500),
+            confirmation_timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+impl SendConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            max_rebuilds: std::env::var("TRANSACTION_MAX_REBUILDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.max_rebuilds),
+            confirmation_poll_interval: std::env::var("TRANSACTION_CONFIRM_POLL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(default.confirmation_poll_interval),
+            ..default
+        }
+    }
+}
+
+static SEND_CONFIG: Lazy<SendConfig> = Lazy::new(SendConfig::from_env);
+
+pub fn send_config() -> &'static SendConfig {
+    &SEND_CONFIG
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmationOutcome {
+    Confirmed,
+    Failed,
+    // The blockhash expired without the transaction landing, it is safe to rebuild
+    Expired,
+}
+
+// Polls the signature until it lands or the chain moves past the blockhash's
+// last valid block height. Block height is read before the status so a
+// transaction landing in the final block is not reported as expired
+pub async fn confirm_before_expiry(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+    last_valid_block_height: u64,
+) -> Result<ConfirmationOutcome, AppError> {
+    let config = send_config();
+    let started = Instant::now();
+    let deadline = started + config.confirmation_timeout;
+
+    while Instant::now() < deadline {
+        let block_height = rpc_client
+            .get_block_height_with_commitment(CommitmentConfig::confirmed())
+            .ok();
+
+        match rpc_client.get_signature_statuses(&[*signature]) {
+            Ok(response) => match response.value.into_iter().next().flatten() {
+                Some(status) if status.err.is_some() => {
+                    warn!("Transaction {} failed: {:?}", signature, status.err);
+                    return Ok(ConfirmationOutcome::Failed);
+                }
+                Some(status) if status.satisfies_commitment(CommitmentConfig::confirmed()) => {
+                    info!("Transaction {} confirmed", signature);
+                    record_confirmed(started.elapsed());
+                    return Ok(ConfirmationOutcome::Confirmed);
+                }
+                Some(_) => {}
+                None => {
+                    if block_height.is_some_and(|height| height > last_valid_block_height) {
+                        warn!(
+                            "Blockhash for {} expired at block height {}",
+                            signature, last_valid_block_height
+                        );
+                        return Ok(ConfirmationOutcome::Expired);
+                    }
+                }
+            },
+            Err(e) => warn!("Failed to fetch status of {}: {}", signature, e),
+        }
+
+        tokio::time::sleep(config.confirmation_poll_interval).await;
+    }
+
+    // Without a known expiry the transaction may still land, so it must not be rebuilt
+    Err(AppError::ServerError(format!(
+        "Timed out confirming transaction {}",
+        signature
+    )))
+}
+
+// A rebuild requotes against live reserves. Its expected output must still
+// clear the minimum from the first quote, otherwise the retry is chasing a
+// price that has already run past the requested slippage
+pub fn check_requote(original_minimum_out: u64, expected_out: u64) -> Result<(), AppError> {
+    if expected_out < original_minimum_out {
+        return Err(AppError::SlippageExceeded(format!(
+            "Price moved while rebuilding: expected {} now, first quote allowed at least {}",
+            expected_out, original_minimum_out
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, compute_budget, compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction, message::Message, pubkey::Pubkey, signer::Signers,
+    transaction::Transaction,
+};
+use solana_transaction_status::UiTransactionEncoding;
+use tracing::{info, warn};
+
+use crate::error::AppError;
+use crate::utils::compute_budget::compute_budget_config;
+use crate::utils::kill_switch::kill_switch;
+
+const SET_COMPUTE_UNIT_LIMIT_TAG: u8 = 2;
+
+// Returns the units consumed, or the decoded failure reason
+pub fn simulate_transaction(
+    rpc_client: &RpcClient,
+    transaction: &Transaction,
+) -> Result<Option<u64>, AppError> {
+    let response = rpc_client.simulate_transaction_with_config(
+        transaction,
+        RpcSimulateTransactionConfig {
+            sig_verify: false,
+            commitment: Some(CommitmentConfig::confirmed()),
+            encoding: Some(UiTransactionEncoding::Base64),
+            ..RpcSimulateTransactionConfig::default()
+        },
+    )?;
+
+    let result = response.value;
+    let logs = result.logs.unwrap_or_default();
+
+    if let Some(err) = result.err {
+        let error = decode_simulation_failure(&err.to_string(), &logs);
+        warn!("Simulation failed: {}", error);
+        return Err(error);
+    }
+
+    info!(
+        "Simulation succeeded, units consumed: {:?}",
+        result.units_consumed
+    );
+    Ok(result.units_consumed)
+}
+
+// Maps common pump.fun / Raydium / token program failures to specific errors
+pub fn decode_simulation_failure(error: &str, logs: &[String]) -> AppError {
+    let haystack = logs
+        .iter()
+        .map(|log| log.to_lowercase())
+        .chain(std::iter::once(error.to_lowercase()))
+        .collect::<Vec<_>>();
+    let contains = |patterns: &[&str]| {
+        haystack
+            .iter()
+            .any(|line| patterns.iter().any(|pattern| line.contains(pattern)))
+    };
+
+    let reason = logs
+        .iter()
+        .rev()
+        .find(|log| log.contains("Error") || log.contains("failed"))
+        .cloned()
+        .unwrap_or_else(|| error.to_string());
+
+    if contains(&[
+        "toomuchsolrequired",
+        "toolittlesolreceived",
+        "exceededslippage",
+        "exceeds desired slippage",
+        "slippage",
+    ]) {
+        AppError::SlippageExceeded(reason)
+    } else if contains(&[
+        "insufficient funds",
+        "insufficient lamports",
+        "insufficientfunds",
+    ]) {
+        AppError::InsufficientBalanceError(reason)
+    } else if contains(&[
+        "accountnotinitialized",
+        "uninitializedaccount",
+        "account not initialized",
+        "accountnotfound",
+    ]) {
+        AppError::AccountNotInitialized(reason)
+    } else {
+        AppError::SimulationFailed(reason)
+    }
+}
+
+pub fn set_compute_unit_limit(instructions: &mut [Instruction], limit: u32) {
+    for instruction in instructions.iter_mut() {
+        if instruction.program_id == compute_budget::id()
+            && instruction.data.first() == Some(&SET_COMPUTE_UNIT_LIMIT_TAG)
+        {
+            *instruction = ComputeBudgetInstruction::set_compute_unit_limit(limit);
+        }
+    }
+}
+
+// Signs the instructions and, when simulating, resizes the compute unit limit
+// to what the simulation consumed before signing the final transaction
+pub fn prepare_transaction<T: Signers + ?Sized>(
+    rpc_client: &RpcClient,
+    instructions: Vec<Instruction>,
+    payer: &Pubkey,
+    signers: &T,
+    simulate: bool,
+) -> Result<Transaction, AppError> {
+    prepare_transaction_with_expiry(rpc_client, instructions, payer, signers, simulate)
+        .map(|(transaction, _)| transaction)
+}
+
+// Also returns the last block height the transaction's blockhash is valid for.
+// The final transaction is signed with a blockhash fetched after simulation.
+// Nothing is signed while the kill switch is halted
+pub fn prepare_transaction_with_expiry<T: Signers + ?Sized>(
+    rpc_client: &RpcClient,
+    mut instructions: Vec<Instruction>,
+    payer: &Pubkey,
+    signers: &T,
+    simulate: bool,
+) -> Result<(Transaction, u64), AppError> {
+    let build = |instructions: &[Instruction]| -> Result<(Transaction, u64), AppError> {
+        kill_switch().check()?;
+        let (recent_blockhash, last_valid_block_height) =
+            rpc_client.get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())?;
+        let message = Message::new_with_blockhash(instructions, Some(payer), &recent_blockhash);
+        Ok((
+            Transaction::new(signers, message, recent_blockhash),
+            last_valid_block_height,
+        ))
+    };
+
+    if !simulate {
+        return build(&instructions);
+    }
+
+    let (transaction, _) = build(&instructions)?;
+    if let Some(units_consumed) = simulate_transaction(rpc_client, &transaction)? {
+        let limit = compute_budget_config().simulated_unit_limit(units_consumed);
+        set_compute_unit_limit(&mut instructions, limit);
+        info!(
+            "Compute unit limit set to {} from {} simulated units",
+            limit, units_consumed
+        );
+    }
+    build(&instructions)
+}
@@ -0,0 +1,355 @@
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::error::AppError;
+use crate::event_system::{Event, EventSystem};
+use crate::models::{ControlCommand, ErrorInfo, ErrorSeverity};
+
+#[derive(Debug, Clone)]
+pub struct SpendGuardConfig {
+    // None disables the limit
+    pub max_trade_sol: Option<f64>,
+    pub max_hourly_sol: Option<f64>,
+    // A violation blocks further buys in this process and pauses copy trading
+    // until resumed with ResumeCopyTrading, ResumeAll or the API's resume route
+    pub pause_on_violation: bool,
+    // The rolling window is kept here so a restart doesn't reset it. Every
+    // process has its own file and its own hourly limit, None keeps the window
+    // in memory only
+    pub state_path: Option<PathBuf>,
+}
+
+impl Default for SpendGuardConfig {
+    fn default() -> Self {
+        Self {
+            max_trade_sol: None,
+            max_hourly_sol: None,
+            pause_on_violation: false,
+            state_path: None,
+        }
+    }
+}
+
+impl SpendGuardConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let limit = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|limit| *limit > 0.0)
+        };
+
+        Self {
+            max_trade_sol: limit("SPEND_GUARD_MAX_TRADE_SOL"),
+            max_hourly_sol: limit("SPEND_GUARD_MAX_HOURLY_SOL"),
+            pause_on_violation: std::env::var("SPEND_GUARD_PAUSE_ON_VIOLATION")
+                .map(|v| v == "true")
+                .unwrap_or(default.pause_on_violation),
+            // Named after the binary so the API and the bot never share a file
+            state_path: match std::env::var("SPEND_GUARD_STATE_DIR") {
+                Ok(dir) if !dir.is_empty() => {
+                    Some(PathBuf::from(dir).join(format!("spend_guard_{}.json", process_name())))
+                }
+                _ => default.state_path,
+            },
+        }
+    }
+}
+
+fn process_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|path| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| format!("pid_{}", std::process::id()))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SpendRecord {
+    at: DateTime<Utc>,
+    amount_sol: f64,
+}
+
+// Spend that has been sent plus spend reserved by transactions still being
+// built or sent. Only sent spend is persisted
+#[derive(Default)]
+struct SpendWindow {
+    records: VecDeque<SpendRecord>,
+    reserved: HashMap<u64, f64>,
+    next_reservation: u64,
+}
+
+impl SpendWindow {
+    fn total(&self) -> f64 {
+        self.records
+            .iter()
+            .map(|record| record.amount_sol)
+            .chain(self.reserved.values().copied())
+            .sum()
+    }
+}
+
+// Last line of defence against runaway spending. Every buy executor asks it
+// before signing, so no caller can get a transaction out without passing it
+pub struct SpendGuard {
+    config: SpendGuardConfig,
+    window: Mutex<SpendWindow>,
+    paused: AtomicBool,
+    event_system: OnceCell<Arc<EventSystem>>,
+}
+
+static SPEND_GUARD: Lazy<SpendGuard> = Lazy::new(|| SpendGuard::new(SpendGuardConfig::from_env()));
+
+pub fn spend_guard() -> &'static SpendGuard {
+    &SPEND_GUARD
+}
+
+impl SpendGuard {
+    pub fn new(config: SpendGuardConfig) -> Self {
+        if config.state_path.is_none() && config.max_hourly_sol.is_some() {
+            warn!("SPEND_GUARD_STATE_DIR is not set, the hourly spend resets on restart");
+        }
+        let records = config
+            .state_path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str::<VecDeque<SpendRecord>>(&contents).ok())
+            .unwrap_or_default();
+
+        Self {
+            config,
+            window: Mutex::new(SpendWindow {
+                records,
+                ..Default::default()
+            }),
+            paused: AtomicBool::new(false),
+            event_system: OnceCell::new(),
+        }
+    }
+
+    // Violations are only reported once an event system is attached
+    pub fn set_event_system(&self, event_system: Arc<EventSystem>) {
+        let _ = self.event_system.set(event_system);
+    }
+
+    // Checks an amount without recording it, for orders sent as several transactions
+    pub fn check(&self, amount_sol: f64) -> Result<(), AppError> {
+        let mut window = self.window.lock();
+        self.evaluate(&mut window, amount_sol)
+    }
+
+    // Checks and holds the amount in one step, so concurrent buys can't both
+    // squeeze under the hourly limit. The reservation is committed once the
+    // transaction is sent, and released if it is dropped before that
+    pub fn reserve(&self, amount_sol: f64) -> Result<SpendReservation<'_>, AppError> {
+        let mut window = self.window.lock();
+        self.evaluate(&mut window, amount_sol)?;
+
+        let id = window.next_reservation;
+        window.next_reservation += 1;
+        window.reserved.insert(id, amount_sol);
+        Ok(SpendReservation {
+            guard: self,
+            id,
+            committed: false,
+        })
+    }
+
+    // Includes reserved spend that hasn't been sent yet
+    pub fn spent_last_hour(&self) -> f64 {
+        let mut window = self.window.lock();
+        Self::prune(&mut window);
+        window.total()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn resume(&self) {
+        if self.paused.swap(false, Ordering::SeqCst) {
+            info!("Spend guard resumed");
+        }
+    }
+
+    fn evaluate(&self, window: &mut SpendWindow, amount_sol: f64) -> Result<(), AppError> {
+        if self.is_paused() {
+            return Err(AppError::SpendLimitExceeded(
+                "Buying is paused after a spend limit violation".to_string(),
+            ));
+        }
+
+        if let Some(max_trade_sol) = self.config.max_trade_sol {
+            if amount_sol > max_trade_sol {
+                return Err(self.violation(format!(
+                    "Trade of {} SOL exceeds the per trade limit of {} SOL",
+                    amount_sol, max_trade_sol
+                )));
+            }
+        }
+
+        if let Some(max_hourly_sol) = self.config.max_hourly_sol {
+            Self::prune(window);
+            let spent = window.total();
+            if spent + amount_sol > max_hourly_sol {
+                return Err(self.violation(format!(
+                    "Trade of {} SOL would bring the last hour's spend to {} SOL, over the limit of {} SOL",
+                    amount_sol,
+                    spent + amount_sol,
+                    max_hourly_sol
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn prune(window: &mut SpendWindow) {
+        let cutoff = Utc::now() - ChronoDuration::hours(1);
+        while window
+            .records
+            .front()
+            .is_some_and(|record| record.at < cutoff)
+        {
+            window.records.pop_front();
+        }
+    }
+
+    fn commit(&self, id: u64) {
+        let mut window = self.window.lock();
+        if let Some(amount_sol) = window.reserved.remove(&id) {
+            window.records.push_back(SpendRecord {
+                at: Utc::now(),
+                amount_sol,
+            });
+            self.persist(&window.records);
+        }
+    }
+
+    fn release(&self, id: u64) {
+        self.window.lock().reserved.remove(&id);
+    }
+
+    fn violation(&self, message: String) -> AppError {
+        warn!("Spend guard blocked a trade: {}", message);
+        let pause = self.config.pause_on_violation && !self.paused.swap(true, Ordering::SeqCst);
+
+        if let Some(event_system) = self.event_system.get() {
+            event_system.report_error(
+                ErrorInfo::new(
+                    ErrorSeverity::Error,
+                    "spend_guard",
+                    "spend_limit_exceeded",
+                    message.clone(),
+                ),
+                None,
+            );
+            if pause {
+                event_system.emit(Event::ControlCommand(ControlCommand::PauseCopyTrading));
+            }
+        }
+        if pause {
+            info!("Spend guard paused buying and copy trading");
+        }
+
+        AppError::SpendLimitExceeded(message)
+    }
+
+    fn persist(&self, window: &VecDeque<SpendRecord>) {
+        let path = match &self.config.state_path {
+            Some(path) => path,
+            None => return,
+        };
+        // Written to a temporary file and renamed so a crash never leaves half a file
+        let temp_path = path.with_extension("json.tmp");
+        let result = serde_json::to_string(window)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| std::fs::write(&temp_path, contents).map_err(|e| e.to_string()))
+            .and_then(|_| std::fs::rename(&temp_path, path).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!("Failed to persist spend guard state to {:?}: {}", path, e);
+        }
+    }
+}
+
+// Spend held against the limits while a buy is built and sent. Dropping it
+// without committing, for example when an error is returned with ?, releases it
+#[must_use = "an uncommitted reservation is released when dropped"]
+pub struct SpendReservation<'a> {
+    guard: &'a SpendGuard,
+    id: u64,
+    committed: bool,
+}
+
+impl SpendReservation<'_> {
+    // Records the spend once its transaction has been sent. Rebuilds of the
+    // same buy may call this again, only the first call records anything
+    pub fn commit(&mut self) {
+        if !self.committed {
+            self.committed = true;
+            self.guard.commit(self.id);
+        }
+    }
+}
+
+impl Drop for SpendReservation<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.guard.release(self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(max_hourly_sol: f64) -> SpendGuard {
+        SpendGuard::new(SpendGuardConfig {
+            max_trade_sol: None,
+            max_hourly_sol: Some(max_hourly_sol),
+            pause_on_violation: false,
+            state_path: None,
+        })
+    }
+
+    #[test]
+    fn reservation_counts_against_the_hourly_limit() {
+        let guard = guard(1.0);
+        let _first = guard.reserve(0.6).unwrap();
+
+        assert!(guard.reserve(0.6).is_err());
+        assert!((guard.spent_last_hour() - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn dropped_reservation_is_released() {
+        let guard = guard(1.0);
+        drop(guard.reserve(0.6).unwrap());
+
+        assert_eq!(guard.spent_last_hour(), 0.0);
+        assert!(guard.reserve(0.6).is_ok());
+    }
+
+    #[test]
+    fn committed_reservation_is_recorded_once() {
+        let guard = guard(1.0);
+        let mut spend = guard.reserve(0.6).unwrap();
+        spend.commit();
+        spend.commit();
+        drop(spend);
+
+        assert!((guard.spent_last_hour() - 0.6).abs() < 1e-9);
+        assert!(guard.reserve(0.6).is_err());
+    }
+}
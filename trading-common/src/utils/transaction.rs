@@ -1,71 +1,103 @@
 use anyhow::Result;
 use serde_json::Value;
-use solana_client::{rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
+use solana_client::rpc_client::RpcClient;
 use solana_sdk::{pubkey::Pubkey, signature::Signature};
-use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiInnerInstructions,
+    UiInstruction, UiMessage, UiParsedInstruction, UiTransactionTokenBalance,
+};
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::{sync::Arc, time::Duration};
+use tracing::{debug, error, info, warn};
 
 use crate::pumpdotfun;
 use crate::raydium;
-use crate::{data::get_metadata, ClientTxInfo, TransactionType};
+use crate::{
+    constants::WSOL,
+    data::{get_transaction_account_keys, token_balance_change},
+    token_metadata::get_token_metadata,
+    ClientTxInfo, TrackedWallet, TransactionType,
+};
 
 use super::dex::{DexTransaction, DexType};
+use super::transaction_fetcher::TransactionFetcher;
+
+// Ignore dust left behind by intermediate hops of a route
+const MIN_BALANCE_CHANGE: f64 = 1e-9;
+
+// Charged per signature, anything above it in the fee is priority fee
+const BASE_FEE_PER_SIGNATURE: u64 = 5_000;
 
 pub async fn process_websocket_message(
     text: &str,
     rpc_client: &Arc<RpcClient>,
+    transaction_fetcher: &Arc<TransactionFetcher>,
+    tracked_wallets: &[TrackedWallet],
 ) -> Result<Option<ClientTxInfo>> {
-    println!("Processing websocket message");
+    info!("Processing websocket message");
+    match extract_log_signature(text)? {
+        Some(signature) => {
+            process_signature(&signature, rpc_client, transaction_fetcher, tracked_wallets).await
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn extract_log_signature(text: &str) -> Result<Option<String>> {
     let value: Value = serde_json::from_str(text)?;
-    println!("Raw message: {}", text);
+    debug!("Raw message: {}", text);
 
     // Check for error in json_data
     if value.get("error").is_some() {
-        println!("Received error message from RPC");
+        error!("Received error message from RPC");
         return Ok(None);
     }
 
     let result = match value.get("params").and_then(|p| p.get("result")) {
         Some(r) => r,
         None => {
-            println!("No result in message (subscription confirmation)");
+            info!("No result in message (subscription confirmation)");
             return Ok(None);
         }
     };
 
-    let signature = match result
+    match result
         .get("value")
         .and_then(|v| v.get("signature"))
         .and_then(|s| s.as_str())
     {
-        Some(s) => s.to_string(),
+        Some(s) => Ok(Some(s.to_string())),
         None => {
-            println!("No signature found");
-            return Ok(None);
+            info!("No signature found");
+            Ok(None)
         }
-    };
-
-    // Configure transaction fetch
-    let config = RpcTransactionConfig {
-        encoding: Some(UiTransactionEncoding::JsonParsed),
-        commitment: Some(solana_sdk::commitment_config::CommitmentConfig::confirmed()),
-        max_supported_transaction_version: Some(0),
-    };
+    }
+}
 
-    // Fetch full transaction with retries
-    let signature_obj = Signature::from_str(&signature)?;
+pub async fn process_signature(
+    signature: &str,
+    rpc_client: &Arc<RpcClient>,
+    transaction_fetcher: &Arc<TransactionFetcher>,
+    tracked_wallets: &[TrackedWallet],
+) -> Result<Option<ClientTxInfo>> {
+    // Fetch full transaction with retries, it may not be available right after the log
+    Signature::from_str(signature)?;
     let mut retries = 20;
     let mut transaction_data = None;
 
     while retries > 0 {
-        match rpc_client.get_transaction_with_config(&signature_obj, config) {
-            Ok(data) => {
+        match transaction_fetcher.fetch(signature).await {
+            Ok(Some(data)) => {
                 transaction_data = Some(data);
                 break;
             }
-            Err(e) => {
-                println!(
+            result => {
+                let e = result
+                    .err()
+                    .map(|e| e.to_string())
+                    .unwrap_or_else(|| "transaction not available yet".to_string());
+                error!(
                     "Error fetching transaction {} (retry {}): {}",
                     signature,
                     21 - retries,
@@ -82,13 +114,325 @@ pub async fn process_websocket_message(
     let transaction_data = match transaction_data {
         Some(data) => data,
         None => {
-            println!("Failed to fetch transaction data after retries");
+            warn!("Failed to fetch transaction data after retries");
             return Ok(None);
         }
     };
 
-    // Process the transaction data to create ClientTxInfo
-    create_client_tx_info(&transaction_data, &signature, rpc_client).await
+    // Process the transaction data to create ClientTxInfo, falling back to plain
+    // token transfers for transactions that don't touch a DEX
+    let mut tx_info = match create_client_tx_info(&transaction_data, signature, rpc_client).await? {
+        Some(tx_info) => tx_info,
+        None => {
+            match create_transfer_tx_info(&transaction_data, signature, rpc_client, tracked_wallets)
+                .await?
+            {
+                Some(tx_info) => tx_info,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    // Attribute the transaction to the tracked wallet that signed it
+    let signer = get_transaction_signer(&transaction_data);
+    let tracked_wallet = [
+        signer.as_str(),
+        tx_info.buyer.as_str(),
+        tx_info.seller.as_str(),
+    ]
+    .iter()
+    .filter(|address| !address.is_empty())
+    .find_map(|address| {
+        tracked_wallets
+            .iter()
+            .find(|wallet| wallet.wallet_address == *address)
+    });
+
+    if let Some(wallet) = tracked_wallet {
+        tx_info.tracked_wallet_id = wallet.id;
+        tx_info.tracked_wallet_address = wallet.wallet_address.clone();
+        tx_info.tracked_wallet_label = wallet.label.clone();
+    }
+
+    Ok(Some(tx_info))
+}
+
+pub fn get_transaction_signer(transaction: &EncodedConfirmedTransactionWithStatusMeta) -> String {
+    get_transaction_account_keys(transaction)
+        .first()
+        .cloned()
+        .unwrap_or_default()
+}
+
+// Seller and buyer of a swap signed by the trader. The counterparty is the owner of
+// the account that moved the traded mint in the opposite direction (pool or curve)
+pub fn extract_swap_accounts(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    transaction_type: &TransactionType,
+) -> (String, String) {
+    let trader = get_transaction_signer(transaction);
+    let counterparty = find_swap_counterparty(transaction, &trader).unwrap_or_default();
+
+    match transaction_type {
+        TransactionType::Sell => (trader, counterparty),
+        TransactionType::Buy => (counterparty, trader),
+        _ => (String::new(), String::new()),
+    }
+}
+
+fn find_swap_counterparty(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    trader: &str,
+) -> Option<String> {
+    let meta = transaction.transaction.meta.as_ref()?;
+
+    let empty_token_balances = Vec::new();
+    let pre_balances = meta
+        .pre_token_balances
+        .as_ref()
+        .unwrap_or(&empty_token_balances);
+    let post_balances = meta
+        .post_token_balances
+        .as_ref()
+        .unwrap_or(&empty_token_balances);
+
+    let trader_balance = post_balances
+        .iter()
+        .chain(pre_balances.iter())
+        .find(|b| b.mint != WSOL && is_owned_by(b, trader))?;
+    let trader_change =
+        token_balance_change(pre_balances, post_balances, trader_balance.account_index);
+
+    post_balances
+        .iter()
+        .chain(pre_balances.iter())
+        .filter(|b| b.mint == trader_balance.mint && !is_owned_by(b, trader))
+        .map(|b| {
+            (
+                b,
+                token_balance_change(pre_balances, post_balances, b.account_index),
+            )
+        })
+        .filter(|(_, change)| change.signum() != trader_change.signum())
+        .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+        .and_then(|(b, _)| Option::<String>::from(b.owner.clone()))
+}
+
+// Derives a SOL <-> token swap from the signer's balance changes, for venues
+// whose instructions we don't decode
+pub fn extract_swap_from_balance_changes(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    venue: &str,
+) -> Result<(TransactionType, String, f64, f64, f64)> {
+    let meta = transaction
+        .transaction
+        .meta
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No transaction metadata"))?;
+
+    let signer = get_transaction_signer(transaction);
+    if signer.is_empty() {
+        return Err(anyhow::anyhow!("Could not determine transaction signer"));
+    }
+
+    info!("Analyzing {} transaction for signer {}", venue, signer);
+
+    let empty_token_balances = Vec::new();
+    let pre_balances = meta
+        .pre_token_balances
+        .as_ref()
+        .unwrap_or(&empty_token_balances);
+    let post_balances = meta
+        .post_token_balances
+        .as_ref()
+        .unwrap_or(&empty_token_balances);
+
+    // Net token change per mint for accounts owned by the signer. Intermediate
+    // hops of a multi-hop route (e.g. SOL -> USDC -> token) net out to zero
+    let mut deltas: HashMap<String, f64> = HashMap::new();
+    for balance in post_balances.iter().filter(|b| is_owned_by(b, &signer)) {
+        *deltas.entry(balance.mint.clone()).or_default() +=
+            balance.ui_token_amount.ui_amount.unwrap_or(0.0);
+    }
+    for balance in pre_balances.iter().filter(|b| is_owned_by(b, &signer)) {
+        *deltas.entry(balance.mint.clone()).or_default() -=
+            balance.ui_token_amount.ui_amount.unwrap_or(0.0);
+    }
+
+    // SOL spent or received includes both native SOL and WSOL, excluding the fee
+    let pre_sol = meta.pre_balances.first().copied().unwrap_or(0) as i64;
+    let post_sol = meta.post_balances.first().copied().unwrap_or(0) as i64;
+    let native_sol_change = (post_sol - pre_sol + meta.fee as i64) as f64 / 1e9;
+    let sol_change = native_sol_change + deltas.remove(WSOL).unwrap_or(0.0);
+
+    let token_change = if sol_change < -MIN_BALANCE_CHANGE {
+        deltas
+            .into_iter()
+            .filter(|(_, change)| *change > MIN_BALANCE_CHANGE)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    } else if sol_change > MIN_BALANCE_CHANGE {
+        deltas
+            .into_iter()
+            .filter(|(_, change)| *change < -MIN_BALANCE_CHANGE)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    } else {
+        None
+    };
+
+    let (token_address, token_amount_change) = match token_change {
+        Some(change) => change,
+        None => {
+            info!("{} swap does not involve SOL, ignoring", venue);
+            return Ok((TransactionType::Unknown, String::new(), 0.0, 0.0, 0.0));
+        }
+    };
+
+    let transaction_type = if token_amount_change > 0.0 {
+        info!("Detected {} BUY (SOL -> token)", venue);
+        TransactionType::Buy
+    } else {
+        info!("Detected {} SELL (token -> SOL)", venue);
+        TransactionType::Sell
+    };
+
+    let amount_token = token_amount_change.abs();
+    let amount_sol = sol_change.abs();
+    let price_per_token = if amount_token > 0.0 {
+        amount_sol / amount_token
+    } else {
+        0.0
+    };
+
+    info!("Transaction summary:");
+    info!("  Token: {}", token_address);
+    info!("  Type: {:?}", transaction_type);
+    info!("  Amount token: {}", amount_token);
+    info!("  Amount SOL: {}", amount_sol);
+    info!("  Price per token: {}", price_per_token);
+
+    Ok((
+        transaction_type,
+        token_address,
+        amount_token,
+        amount_sol,
+        price_per_token,
+    ))
+}
+
+// Finds a token moving into or out of a tracked wallet without a swap. Returns
+// (mint, amount, sender, receiver)
+pub fn extract_token_transfer(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    tracked_wallets: &[TrackedWallet],
+) -> Option<(String, f64, String, String)> {
+    let meta = transaction.transaction.meta.as_ref()?;
+
+    let empty_token_balances = Vec::new();
+    let pre_balances = meta
+        .pre_token_balances
+        .as_ref()
+        .unwrap_or(&empty_token_balances);
+    let post_balances = meta
+        .post_token_balances
+        .as_ref()
+        .unwrap_or(&empty_token_balances);
+
+    // Net change per (owner, mint)
+    let mut deltas: HashMap<(String, String), f64> = HashMap::new();
+    for (balances, sign) in [(post_balances, 1.0), (pre_balances, -1.0)] {
+        for balance in balances.iter().filter(|b| b.mint != WSOL) {
+            if let Some(owner) = Option::<String>::from(balance.owner.clone()) {
+                *deltas.entry((owner, balance.mint.clone())).or_default() +=
+                    sign * balance.ui_token_amount.ui_amount.unwrap_or(0.0);
+            }
+        }
+    }
+
+    let (owner, mint, change) = deltas
+        .iter()
+        .filter(|((owner, _), change)| {
+            change.abs() > MIN_BALANCE_CHANGE
+                && tracked_wallets.iter().any(|w| &w.wallet_address == owner)
+        })
+        .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+        .map(|((owner, mint), change)| (owner.clone(), mint.clone(), *change))?;
+
+    // The counterparty moved the same mint in the opposite direction
+    let counterparty = deltas
+        .iter()
+        .filter(|((other, other_mint), other_change)| {
+            *other != owner && *other_mint == mint && other_change.signum() != change.signum()
+        })
+        .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+        .map(|((other, _), _)| other.clone())
+        .unwrap_or_default();
+
+    let (sender, receiver) = if change < 0.0 {
+        (owner, counterparty)
+    } else {
+        (counterparty, owner)
+    };
+
+    Some((mint, change.abs(), sender, receiver))
+}
+
+async fn create_transfer_tx_info(
+    transaction_data: &EncodedConfirmedTransactionWithStatusMeta,
+    signature: &str,
+    rpc_client: &Arc<RpcClient>,
+    tracked_wallets: &[TrackedWallet],
+) -> Result<Option<ClientTxInfo>> {
+    // Swaps that failed to parse shouldn't show up as transfers
+    if DexTransaction::detect_dex_type(transaction_data) != DexType::Unknown {
+        return Ok(None);
+    }
+
+    let (token_address, amount_token, seller, buyer) =
+        match extract_token_transfer(transaction_data, tracked_wallets) {
+            Some(transfer) => transfer,
+            None => return Ok(None),
+        };
+
+    info!(
+        "Detected token transfer of {} {} from {} to {}",
+        amount_token, token_address, seller, buyer
+    );
+
+    let token_pubkey = Pubkey::from_str(&token_address)?;
+    let token_metadata = get_token_metadata(rpc_client, &token_pubkey).await?;
+    let (fee_lamports, priority_fee_lamports) = transaction_fees(transaction_data);
+
+    Ok(Some(ClientTxInfo {
+        signature: signature.to_string(),
+        token_address,
+        token_name: token_metadata.name,
+        token_symbol: token_metadata.symbol,
+        transaction_type: TransactionType::Transfer,
+        amount_token,
+        amount_sol: 0.0,
+        price_per_token: 0.0,
+        token_image_uri: token_metadata.image_uri,
+        market_cap: 0.0,
+        usd_market_cap: 0.0,
+        timestamp: transaction_data.block_time.unwrap_or(0),
+        seller,
+        buyer,
+        dex_type: DexType::Unknown,
+        tracked_wallet_id: None,
+        tracked_wallet_address: String::new(),
+        tracked_wallet_label: None,
+        slot: transaction_data.slot,
+        pool_address: None,
+        fee_lamports,
+        priority_fee_lamports,
+        slot_lag: None,
+        observed_at: None,
+        backfilled: false,
+    }))
+}
+
+fn is_owned_by(balance: &UiTransactionTokenBalance, owner: &str) -> bool {
+    Option::<String>::from(balance.owner.clone()).as_deref() == Some(owner)
 }
 
 pub async fn create_client_tx_info(
@@ -103,6 +447,9 @@ pub async fn create_client_tx_info(
         match dex_type {
             DexType::PumpFun => crate::pumpdotfun::extract_transaction_details(transaction_data)?,
             DexType::Raydium => crate::raydium::extract_transaction_details(transaction_data)?,
+            DexType::Jupiter => crate::jupiter::extract_transaction_details(transaction_data)?,
+            DexType::OrcaWhirlpool => crate::orca::extract_transaction_details(transaction_data)?,
+            DexType::PumpSwap => crate::pumpswap::extract_transaction_details(transaction_data)?,
             DexType::Unknown => return Ok(None),
         };
 
@@ -112,18 +459,13 @@ pub async fn create_client_tx_info(
 
     // Get token metadata
     let token_pubkey = Pubkey::from_str(&token_address)?;
-    let token_metadata = get_metadata(rpc_client, &token_pubkey).await?;
+    let token_metadata = get_token_metadata(rpc_client, &token_pubkey).await?;
 
-    // Get buyer/seller based on DEX type
-    let (seller, buyer) = match dex_type {
-        DexType::PumpFun => {
-            pumpdotfun::transaction::extract_accounts(transaction_data, &transaction_type)?
-        }
-        DexType::Raydium => {
-            raydium::transaction::extract_accounts(transaction_data, &transaction_type)?
-        }
-        DexType::Unknown => (String::new(), String::new()),
-    };
+    // The counterparty comes from token balance changes on every venue
+    let (seller, buyer) = extract_swap_accounts(transaction_data, &transaction_type);
+
+    let pool_address = extract_pool_address(transaction_data, &dex_type);
+    let (fee_lamports, priority_fee_lamports) = transaction_fees(transaction_data);
 
     Ok(Some(ClientTxInfo {
         signature: signature.to_string(),
@@ -134,12 +476,128 @@ pub async fn create_client_tx_info(
         amount_token,
         amount_sol,
         price_per_token,
-        token_image_uri: token_metadata.uri,
+        token_image_uri: token_metadata.image_uri,
         market_cap: 0.0,
         usd_market_cap: 0.0,
         timestamp: transaction_data.block_time.unwrap_or(0),
         seller,
         buyer,
         dex_type,
+        tracked_wallet_id: None,
+        tracked_wallet_address: String::new(),
+        tracked_wallet_label: None,
+        slot: transaction_data.slot,
+        pool_address,
+        fee_lamports,
+        priority_fee_lamports,
+        slot_lag: None,
+        observed_at: None,
+        backfilled: false,
     }))
 }
+
+// Total fee and the part of it above the base signature fee
+fn transaction_fees(
+    transaction_data: &EncodedConfirmedTransactionWithStatusMeta,
+) -> (u64, Option<u64>) {
+    let fee = match &transaction_data.transaction.meta {
+        Some(meta) => meta.fee,
+        None => return (0, None),
+    };
+    match &transaction_data.transaction.transaction {
+        EncodedTransaction::Json(tx) => (
+            fee,
+            Some(fee.saturating_sub(tx.signatures.len() as u64 * BASE_FEE_PER_SIGNATURE)),
+        ),
+        _ => (fee, None),
+    }
+}
+
+// Position of the pool account in each program's swap instruction. Orca's swap
+// and swapV2 put the whirlpool at different positions and Jupiter routes can
+// span several pools, so neither gets one
+fn pool_account_index(dex_type: &DexType, program_id: &str) -> Option<usize> {
+    match dex_type {
+        DexType::PumpFun
+            if program_id == pumpdotfun::constants::PUMP_FUN_PROGRAM_ID.to_string() =>
+        {
+            Some(3)
+        }
+        DexType::PumpSwap
+            if program_id == crate::pumpswap::constants::PUMP_SWAP_PROGRAM_ID.to_string() =>
+        {
+            Some(0)
+        }
+        DexType::Raydium => match program_id {
+            raydium::constants::RAY_V4_PROGRAM_ID => Some(1),
+            raydium::constants::RAY_CPMM_PROGRAM_ID => Some(3),
+            raydium::constants::RAY_CLMM_PROGRAM_ID => Some(2),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The pool, or pump.fun bonding curve, of the first swap instruction for the venue
+fn extract_pool_address(
+    transaction_data: &EncodedConfirmedTransactionWithStatusMeta,
+    dex_type: &DexType,
+) -> Option<String> {
+    instruction_accounts(transaction_data)
+        .into_iter()
+        .find_map(|(program_id, accounts)| {
+            let index = pool_account_index(dex_type, &program_id)?;
+            accounts.get(index).cloned()
+        })
+}
+
+// Program and accounts of every instruction, top level first then inner ones.
+// Compiled instructions refer to accounts by index into the full key list
+fn instruction_accounts(
+    transaction_data: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Vec<(String, Vec<String>)> {
+    let account_keys = get_transaction_account_keys(transaction_data);
+    let resolve = |instruction: &UiInstruction| -> Option<(String, Vec<String>)> {
+        match instruction {
+            UiInstruction::Compiled(compiled) => Some((
+                account_keys
+                    .get(compiled.program_id_index as usize)?
+                    .clone(),
+                compiled
+                    .accounts
+                    .iter()
+                    .map(|index| account_keys.get(*index as usize).cloned())
+                    .collect::<Option<Vec<_>>>()?,
+            )),
+            UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(decoded)) => {
+                Some((decoded.program_id.clone(), decoded.accounts.clone()))
+            }
+            UiInstruction::Parsed(UiParsedInstruction::Parsed(_)) => None,
+        }
+    };
+
+    let top_level = match &transaction_data.transaction.transaction {
+        EncodedTransaction::Json(tx) => match &tx.message {
+            UiMessage::Parsed(message) => message.instructions.clone(),
+            UiMessage::Raw(message) => message
+                .instructions
+                .iter()
+                .cloned()
+                .map(UiInstruction::Compiled)
+                .collect(),
+        },
+        _ => Vec::new(),
+    };
+    let inner = transaction_data
+        .transaction
+        .meta
+        .as_ref()
+        .and_then(|meta| Option::<Vec<UiInnerInstructions>>::from(meta.inner_instructions.clone()))
+        .unwrap_or_default();
+
+    top_level
+        .iter()
+        .chain(inner.iter().flat_map(|inner| inner.instructions.iter()))
+        .filter_map(resolve)
+        .collect()
+}
@@ -0,0 +1,315 @@
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use solana_client::{rpc_client::RpcClient, rpc_request::RpcRequest};
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::warn;
+
+use crate::models::FetcherStats;
+
+type FetchedTransaction = Arc<EncodedConfirmedTransactionWithStatusMeta>;
+type FetchResult = std::result::Result<Option<FetchedTransaction>, String>;
+
+#[derive(Debug, Clone)]
+pub struct TransactionFetcherConfig {
+    pub batch_window: Duration,
+    pub max_batch_size: usize,
+    pub cache_capacity: usize,
+}
+
+impl Default for TransactionFetcherConfig {
+    fn default() -> Self {
+        Self {
+            batch_window: Duration::from_millis(50),
+            max_batch_size: 20,
+            cache_capacity: 1_000,
+        }
+    }
+}
+
+// Least recently used transactions are evicted first
+struct TransactionCache {
+    capacity: usize,
+    entries: HashMap<String, FetchedTransaction>,
+    order: VecDeque<String>,
+}
+
+impl TransactionCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn get(&mut self, signature: &str) -> Option<FetchedTransaction> {
+        let transaction = self.entries.get(signature)?.clone();
+        if let Some(position) = self.order.iter().position(|s| s == signature) {
+            self.order.remove(position);
+        }
+        self.order.push_back(signature.to_string());
+        Some(transaction)
+    }
+
+    fn insert(&mut self, signature: String, transaction: FetchedTransaction) {
+        if self
+            .entries
+            .insert(signature.clone(), transaction)
+            .is_some()
+        {
+            return;
+        }
+
+        self.order.push_back(signature);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct FetcherCounters {
+    requests: AtomicU64,
+    cache_hits: AtomicU64,
+    deduplicated: AtomicU64,
+    batches: AtomicU64,
+    batched_signatures: AtomicU64,
+    last_batch_size: AtomicU64,
+    rpc_errors: AtomicU64,
+}
+
+// Collects getTransaction lookups over a short window and sends them as one
+// JSON-RPC batch. Concurrent lookups of the same signature share a request
+pub struct TransactionFetcher {
+    rpc_client: Arc<RpcClient>,
+    config: TransactionFetcherConfig,
+    cache: Mutex<TransactionCache>,
+    pending: Mutex<HashMap<String, Vec<oneshot::Sender<FetchResult>>>>,
+    batch_scheduled: AtomicBool,
+    counters: FetcherCounters,
+}
+
+impl TransactionFetcher {
+    pub fn new(rpc_client: Arc<RpcClient>, config: TransactionFetcherConfig) -> Self {
+        Self {
+            rpc_client,
+            cache: Mutex::new(TransactionCache::new(config.cache_capacity)),
+            config,
+            pending: Mutex::new(HashMap::new()),
+            batch_scheduled: AtomicBool::new(false),
+            counters: FetcherCounters::default(),
+        }
+    }
+
+    // Ok(None) means the transaction isn't available yet
+    pub async fn fetch(self: &Arc<Self>, signature: &str) -> Result<Option<FetchedTransaction>> {
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(transaction) = self.cache.lock().get(signature) {
+            self.counters.cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(Some(transaction));
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let schedule_batch = {
+            let mut pending = self.pending.lock();
+            let waiters = pending.entry(signature.to_string()).or_default();
+            if !waiters.is_empty() {
+                self.counters.deduplicated.fetch_add(1, Ordering::Relaxed);
+            }
+            waiters.push(sender);
+            !self.batch_scheduled.swap(true, Ordering::SeqCst)
+        };
+
+        if schedule_batch {
+            tokio::spawn(Arc::clone(self).run_batches());
+        }
+
+        receiver
+            .await
+            .map_err(|_| anyhow!("Transaction fetch for {} was dropped", signature))?
+            .map_err(|e| anyhow!(e))
+    }
+
+    pub fn stats(&self) -> FetcherStats {
+        let requests = self.counters.requests.load(Ordering::Relaxed);
+        let cache_hits = self.counters.cache_hits.load(Ordering::Relaxed);
+        let batches = self.counters.batches.load(Ordering::Relaxed);
+        let batched_signatures = self.counters.batched_signatures.load(Ordering::Relaxed);
+
+        FetcherStats {
+            requests,
+            cache_hits,
+            cache_hit_rate: if requests > 0 {
+                cache_hits as f64 / requests as f64
+            } else {
+                0.0
+            },
+            deduplicated: self.counters.deduplicated.load(Ordering::Relaxed),
+            batches,
+            last_batch_size: self.counters.last_batch_size.load(Ordering::Relaxed),
+            avg_batch_size: if batches > 0 {
+                batched_signatures as f64 / batches as f64
+            } else {
+                0.0
+            },
+            rpc_errors: self.counters.rpc_errors.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn run_batches(self: Arc<Self>) {
+        tokio::time::sleep(self.config.batch_window).await;
+
+        loop {
+            let batch: Vec<(String, Vec<oneshot::Sender<FetchResult>>)> = {
+                let mut pending = self.pending.lock();
+                if pending.is_empty() {
+                    // Cleared under the lock so a concurrent fetch schedules the next batch
+                    self.batch_scheduled.store(false, Ordering::SeqCst);
+                    return;
+                }
+
+                let signatures: Vec<String> = pending
+                    .keys()
+                    .take(self.config.max_batch_size)
+                    .cloned()
+                    .collect();
+                signatures
+                    .into_iter()
+                    .filter_map(|signature| pending.remove_entry(&signature))
+                    .collect()
+            };
+
+            let signatures: Vec<String> = batch.iter().map(|(s, _)| s.clone()).collect();
+            self.counters.batches.fetch_add(1, Ordering::Relaxed);
+            self.counters
+                .batched_signatures
+                .fetch_add(signatures.len() as u64, Ordering::Relaxed);
+            self.counters
+                .last_batch_size
+                .store(signatures.len() as u64, Ordering::Relaxed);
+
+            let mut results = self.fetch_batch(&signatures).await;
+
+            for (signature, waiters) in batch {
+                let result = results
+                    .remove(&signature)
+                    .unwrap_or_else(|| Err(format!("No result for {}", signature)));
+                if let Ok(Some(transaction)) = &result {
+                    self.cache.lock().insert(signature, Arc::clone(transaction));
+                }
+                for waiter in waiters {
+                    let _ = waiter.send(result.clone());
+                }
+            }
+        }
+    }
+
+    async fn fetch_batch(&self, signatures: &[String]) -> HashMap<String, FetchResult> {
+        if signatures.len() > 1 {
+            match self.send_batch_request(signatures).await {
+                Ok(results) => return results,
+                Err(e) => {
+                    // Some providers don't accept batches, fall back to single requests
+                    self.counters.rpc_errors.fetch_add(1, Ordering::Relaxed);
+                    warn!("Batch getTransaction failed, fetching individually: {}", e);
+                }
+            }
+        }
+
+        let mut results = HashMap::new();
+        for signature in signatures {
+            let result = self
+                .rpc_client
+                .send::<Option<EncodedConfirmedTransactionWithStatusMeta>>(
+                    RpcRequest::GetTransaction,
+                    json!([signature, transaction_config()]),
+                )
+                .map(|transaction| transaction.map(Arc::new))
+                .map_err(|e| {
+                    self.counters.rpc_errors.fetch_add(1, Ordering::Relaxed);
+                    e.to_string()
+                });
+            results.insert(signature.clone(), result);
+        }
+        results
+    }
+
+    async fn send_batch_request(
+        &self,
+        signatures: &[String],
+    ) -> Result<HashMap<String, FetchResult>> {
+        let body: Vec<Value> = signatures
+            .iter()
+            .enumerate()
+            .map(|(id, signature)| {
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": "getTransaction",
+                    "params": [signature, transaction_config()],
+                })
+            })
+            .collect();
+
+        let mut response = surf::post(self.rpc_client.url())
+            .body_json(&body)
+            .map_err(|e| anyhow!("Failed to encode batch request: {}", e))?
+            .await
+            .map_err(|e| anyhow!("Batch request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Batch request returned {}", response.status()));
+        }
+
+        let responses: Vec<Value> = response
+            .body_json()
+            .await
+            .map_err(|e| anyhow!("Failed to parse batch response: {}", e))?;
+
+        let mut results = HashMap::new();
+        for response in responses {
+            let signature = match response
+                .get("id")
+                .and_then(|id| id.as_u64())
+                .and_then(|id| signatures.get(id as usize))
+            {
+                Some(signature) => signature.clone(),
+                None => continue,
+            };
+
+            let result = match response.get("error") {
+                Some(error) => {
+                    self.counters.rpc_errors.fetch_add(1, Ordering::Relaxed);
+                    Err(error.to_string())
+                }
+                None => {
+                    serde_json::from_value::<Option<EncodedConfirmedTransactionWithStatusMeta>>(
+                        response.get("result").cloned().unwrap_or(Value::Null),
+                    )
+                    .map(|transaction| transaction.map(Arc::new))
+                    .map_err(|e| e.to_string())
+                }
+            };
+            results.insert(signature, result);
+        }
+
+        Ok(results)
+    }
+}
+
+fn transaction_config() -> Value {
+    json!({
+        "encoding": "jsonParsed",
+        "commitment": "confirmed",
+        "maxSupportedTransactionVersion": 0,
+    })
+}
@@ -0,0 +1,669 @@
+use backoff::{backoff::Backoff, ExponentialBackoff};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::database::SupabaseClient;
+use crate::event_system::{Event, EventSystem};
+use crate::models::{
+    DatabaseOperationNotification, DatabaseOperationStats, TransactionLog,
+    TransactionLoggedNotification,
+};
+
+#[derive(Debug, Clone)]
+pub struct TransactionLogWriterConfig {
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // Rows kept in memory while the database is unreachable, the rest spill to disk
+    pub max_in_memory: usize,
+    pub spill_path: Option<PathBuf>,
+    pub max_spilled: usize,
+}
+
+impl Default for TransactionLogWriterConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 20,
+            flush_interval: Duration::from_secs(2),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_in_memory: 500,
+            spill_path: Some(PathBuf::from("transaction_log_spill.jsonl")),
+            max_spilled: 10_000,
+        }
+    }
+}
+
+impl TransactionLogWriterConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            batch_size: std::env::var("TRANSACTION_LOG_BATCH_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.batch_size),
+            flush_interval: std::env::var("TRANSACTION_LOG_FLUSH_INTERVAL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(default.flush_interval),
+            spill_path: match std::env::var("TRANSACTION_LOG_SPILL_PATH") {
+                Ok(path) if path.is_empty() => None,
+                Ok(path) => Some(PathBuf::from(path)),
+                Err(_) => default.spill_path,
+            },
+            max_spilled: std::env::var("TRANSACTION_LOG_MAX_SPILLED")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.max_spilled),
+            ..default
+        }
+    }
+}
+
+// Cheap to clone, every clone feeds the same background writer. The writer
+// flushes what it has and exits once all handles are dropped
+#[derive(Clone)]
+pub struct TransactionLogWriter {
+    sender: mpsc::UnboundedSender<TransactionLog>,
+}
+
+impl TransactionLogWriter {
+    pub fn spawn(
+        config: TransactionLogWriterConfig,
+        supabase_client: Arc<SupabaseClient>,
+        event_system: Arc<EventSystem>,
+    ) -> (Self, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let worker = WriterWorker::new(config, supabase_client, event_system);
+        let handle = tokio::spawn(worker.run(receiver));
+        (Self { sender }, handle)
+    }
+
+    pub fn log(&self, transaction: TransactionLog) {
+        if let Err(e) = self.sender.send(transaction) {
+            warn!("Transaction log writer stopped, dropping {}", e.0.signature);
+        }
+    }
+}
+
+struct WriterWorker {
+    config: TransactionLogWriterConfig,
+    supabase_client: Arc<SupabaseClient>,
+    event_system: Arc<EventSystem>,
+    pending: VecDeque<TransactionLog>,
+    backoff: ExponentialBackoff,
+    retry_at: Option<Instant>,
+    spilled: usize,
+    // Leading pending rows that came from the processing file
+    reloaded: usize,
+    flushed: u64,
+    dropped: u64,
+    failed_batches: u64,
+}
+
+fn processing_path(spill_path: &Path) -> PathBuf {
+    let mut path = spill_path.as_os_str().to_owned();
+    path.push(".processing");
+    PathBuf::from(path)
+}
+
+impl WriterWorker {
+    fn new(
+        config: TransactionLogWriterConfig,
+        supabase_client: Arc<SupabaseClient>,
+        event_system: Arc<EventSystem>,
+    ) -> Self {
+        let backoff = ExponentialBackoff {
+            max_elapsed_time: None,
+            initial_interval: config.initial_backoff,
+            max_interval: config.max_backoff,
+            ..ExponentialBackoff::default()
+        };
+
+        Self {
+            config,
+            supabase_client,
+            event_system,
+            pending: VecDeque::new(),
+            backoff,
+            retry_at: None,
+            spilled: 0,
+            reloaded: 0,
+            flushed: 0,
+            dropped: 0,
+            failed_batches: 0,
+        }
+    }
+
+    async fn run(mut self, mut receiver: mpsc::UnboundedReceiver<TransactionLog>) {
+        // Rows spilled by a previous run are retried first
+        self.reload_spilled().await;
+
+        let mut flush_interval = tokio::time::interval(self.config.flush_interval);
+        loop {
+            tokio::select! {
+                transaction = receiver.recv() => {
+                    match transaction {
+                        Some(transaction) => {
+                            self.pending.push_back(transaction);
+                            if self.pending.len() >= self.config.batch_size {
+                                self.flush().await;
+                            }
+                        }
+                        None => break,
+                    }
+                }
+                _ = flush_interval.tick() => {
+                    self.flush().await;
+                }
+            }
+        }
+
+        // Last attempt on shutdown, anything left over goes to disk
+        self.retry_at = None;
+        self.flush().await;
+        let remaining = self.pending.len();
+        self.spill(remaining).await;
+    }
+
+    async fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        if let Some(retry_at) = self.retry_at {
+            if Instant::now() < retry_at {
+                return;
+            }
+        }
+
+        while !self.pending.is_empty() {
+            let batch_len = self.pending.len().min(self.config.batch_size);
+            let batch = self.pending.range(..batch_len).cloned().collect::<Vec<_>>();
+
+            match self.supabase_client.insert_transaction_logs(&batch).await {
+                Ok(()) => {
+                    self.pending.drain(..batch_len);
+                    self.flushed += batch_len as u64;
+                    self.backoff.reset();
+                    self.retry_at = None;
+                    if self.reloaded > 0 {
+                        self.reloaded = self.reloaded.saturating_sub(batch_len);
+                        if self.reloaded == 0 {
+                            self.clear_processing().await;
+                        }
+                    }
+
+                    for transaction in batch {
+                        self.event_system
+                            .handle_transaction_logged(TransactionLoggedNotification {
+                                correlation_id: Some(transaction.signature.clone()),
+                                data: transaction,
+                                type_: "transaction_logged".to_string(),
+                                event_id: Uuid::new_v4(),
+                            })
+                            .await;
+                    }
+
+                    if self.pending.is_empty() && self.spilled > 0 {
+                        self.reload_spilled().await;
+                    }
+                }
+                Err(e) => {
+                    self.failed_batches += 1;
+                    let delay = self
+                        .backoff
+                        .next_backoff()
+                        .unwrap_or(self.config.max_backoff);
+                    warn!(
+                        "Failed to write {} transaction logs, retrying in {:?}: {}",
+                        batch_len, delay, e
+                    );
+                    self.retry_at = Some(Instant::now() + delay);
+
+                    let overflow = self.pending.len().saturating_sub(self.config.max_in_memory);
+                    self.spill(overflow).await;
+                    // Reloaded rows that spilled again are kept twice on disk,
+                    // which the upsert on id makes harmless
+                    self.reloaded = self.reloaded.min(self.pending.len());
+                    break;
+                }
+            }
+        }
+
+        self.emit_stats().await;
+    }
+
+    // Moves the newest `count` pending rows to the spill file
+    async fn spill(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+
+        let rows = self
+            .pending
+            .split_off(self.pending.len() - count)
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let path = match &self.config.spill_path {
+            Some(path) => path.clone(),
+            None => {
+                warn!(
+                    "No spill path configured, dropping {} transaction logs",
+                    count
+                );
+                self.dropped += count as u64;
+                return;
+            }
+        };
+
+        let room = self.config.max_spilled.saturating_sub(self.spilled);
+        if rows.len() > room {
+            let dropped = rows.len() - room;
+            warn!("Spill file full, dropping {} transaction logs", dropped);
+            self.dropped += dropped as u64;
+        }
+
+        let mut lines = String::new();
+        for row in rows.iter().take(room) {
+            if let Ok(line) = serde_json::to_string(row) {
+                lines.push_str(&line);
+                lines.push('\n');
+            }
+        }
+        if lines.is_empty() {
+            return;
+        }
+
+        let written = rows.len().min(room);
+        let result = async {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .await?;
+            file.write_all(lines.as_bytes()).await?;
+            file.flush().await
+        }
+        .await;
+
+        match result {
+            Ok(()) => self.spilled += written,
+            Err(e) => {
+                warn!("Failed to spill transaction logs to {:?}: {}", path, e);
+                self.dropped += written as u64;
+            }
+        }
+    }
+
+    // Spilled rows move to the processing file and stay there until they are
+    // written, so a crash in between reloads them on the next start. Only runs
+    // with nothing pending, which keeps reloaded rows at the front of the queue
+    async fn reload_spilled(&mut self) {
+        let path = match &self.config.spill_path {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        if self.reloaded > 0 {
+            return;
+        }
+        let processing = processing_path(&path);
+
+        if let Ok(contents) = tokio::fs::read_to_string(&path).await {
+            let moved = async {
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&processing)
+                    .await?;
+                file.write_all(contents.as_bytes()).await?;
+                file.flush().await?;
+                tokio::fs::remove_file(&path).await
+            }
+            .await;
+            if let Err(e) = moved {
+                warn!("Failed to move spill file {:?} for reloading: {}", path, e);
+                return;
+            }
+            self.spilled = 0;
+        }
+
+        let contents = match tokio::fs::read_to_string(&processing).await {
+            Ok(contents) => contents,
+            Err(_) => return,
+        };
+
+        // A row spilled again after its reload is in the file twice
+        let mut seen = self
+            .pending
+            .iter()
+            .map(|transaction| transaction.id)
+            .collect::<HashSet<_>>();
+        let mut reloaded = 0;
+        for line in contents.lines() {
+            match serde_json::from_str::<TransactionLog>(line) {
+                Ok(transaction) => {
+                    if seen.insert(transaction.id) {
+                        self.pending.push_back(transaction);
+                        reloaded += 1;
+                    }
+                }
+                Err(e) => warn!("Skipping unreadable spilled transaction log: {}", e),
+            }
+        }
+
+        self.reloaded = reloaded;
+        if reloaded == 0 {
+            self.clear_processing().await;
+        }
+        info!("Reloaded {} spilled transaction logs", reloaded);
+    }
+
+    async fn clear_processing(&self) {
+        let Some(path) = &self.config.spill_path else {
+            return;
+        };
+        let processing = processing_path(path);
+        if let Err(e) = tokio::fs::remove_file(&processing).await {
+            warn!("Failed to clear spill file {:?}: {}", processing, e);
+        }
+    }
+
+    async fn emit_stats(&self) {
+        self.event_system
+            .emit(Event::DatabaseOperation(DatabaseOperationNotification {
+                data: DatabaseOperationStats {
+                    operation: "transaction_log_write".to_string(),
+                    pending: self.pending.len(),
+                    spilled: self.spilled,
+                    flushed: self.flushed,
+                    dropped: self.dropped,
+                    failed_batches: self.failed_batches,
+                },
+                type_: "database_operation".to_string(),
+                event_id: Uuid::new_v4(),
+                correlation_id: None,
+            }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabasePolicy;
+    use axum::response::IntoResponse;
+    use chrono::Utc;
+    use parking_lot::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Clone, Default)]
+    struct MockState {
+        down: Arc<AtomicBool>,
+        // Ids of every row in every insert, successful or not
+        batches: Arc<Mutex<Vec<Vec<Uuid>>>>,
+    }
+
+    // PostgREST standing in for the transactions table, answering 503 while down
+    struct MockDatabase {
+        state: MockState,
+        client: Arc<SupabaseClient>,
+    }
+
+    impl MockDatabase {
+        async fn start() -> Self {
+            let state = MockState::default();
+            let router = axum::Router::new()
+                .fallback(mock_handler)
+                .with_state(state.clone());
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            tokio::spawn(async move {
+                axum::serve(listener, router).await.unwrap();
+            });
+            let client = Arc::new(SupabaseClient::with_policy(
+                &url,
+                "",
+                "service-role",
+                "user",
+                DatabasePolicy {
+                    max_retries: 0,
+                    failure_threshold: u32::MAX,
+                    ..DatabasePolicy::default()
+                },
+            ));
+            Self { state, client }
+        }
+
+        fn set_down(&self, down: bool) {
+            self.state.down.store(down, Ordering::SeqCst);
+        }
+
+        fn batches(&self) -> Vec<Vec<Uuid>> {
+            self.state.batches.lock().clone()
+        }
+
+        async fn wait_for_batches(&self, count: usize) {
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while self.batches().len() < count {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("batches not written in time");
+        }
+    }
+
+    async fn mock_handler(
+        axum::extract::State(state): axum::extract::State<MockState>,
+        body: String,
+    ) -> axum::response::Response {
+        let rows: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap_or_default();
+        state.batches.lock().push(
+            rows.iter()
+                .filter_map(|row| row["id"].as_str()?.parse().ok())
+                .collect(),
+        );
+        let status = if state.down.load(Ordering::SeqCst) {
+            axum::http::StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            axum::http::StatusCode::CREATED
+        };
+        (status, "[]").into_response()
+    }
+
+    fn transaction_log() -> TransactionLog {
+        TransactionLog {
+            id: Uuid::new_v4(),
+            user_id: "user".to_string(),
+            tracked_wallet_id: None,
+            signature: Uuid::new_v4().to_string(),
+            transaction_type: "buy".to_string(),
+            token_address: "token".to_string(),
+            amount: 1.0,
+            price_sol: 0.001,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn spill_path() -> PathBuf {
+        std::env::temp_dir().join(format!("transaction_log_spill_{}.jsonl", Uuid::new_v4()))
+    }
+
+    async fn line_count(path: &Path) -> usize {
+        tokio::fs::read_to_string(path)
+            .await
+            .map(|contents| contents.lines().count())
+            .unwrap_or(0)
+    }
+
+    fn worker(mock: &MockDatabase, config: TransactionLogWriterConfig) -> WriterWorker {
+        WriterWorker::new(
+            config,
+            Arc::clone(&mock.client),
+            Arc::new(EventSystem::new()),
+        )
+    }
+
+    #[tokio::test]
+    async fn full_batch_is_written_without_waiting_for_the_timer() {
+        let mock = MockDatabase::start().await;
+        let (writer, _handle) = TransactionLogWriter::spawn(
+            TransactionLogWriterConfig {
+                batch_size: 2,
+                flush_interval: Duration::from_secs(3600),
+                spill_path: None,
+                ..TransactionLogWriterConfig::default()
+            },
+            Arc::clone(&mock.client),
+            Arc::new(EventSystem::new()),
+        );
+        // Lets the interval's immediate first tick pass
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        writer.log(transaction_log());
+        writer.log(transaction_log());
+        mock.wait_for_batches(1).await;
+
+        assert_eq!(mock.batches()[0].len(), 2);
+    }
+
+    #[tokio::test]
+    async fn partial_batch_is_written_on_the_timer() {
+        let mock = MockDatabase::start().await;
+        let (writer, _handle) = TransactionLogWriter::spawn(
+            TransactionLogWriterConfig {
+                batch_size: 10,
+                flush_interval: Duration::from_millis(50),
+                spill_path: None,
+                ..TransactionLogWriterConfig::default()
+            },
+            Arc::clone(&mock.client),
+            Arc::new(EventSystem::new()),
+        );
+
+        let transaction = transaction_log();
+        let id = transaction.id;
+        writer.log(transaction);
+        mock.wait_for_batches(1).await;
+
+        assert_eq!(mock.batches(), vec![vec![id]]);
+    }
+
+    #[tokio::test]
+    async fn failed_write_backs_off_and_spills_past_max_in_memory() {
+        let mock = MockDatabase::start().await;
+        mock.set_down(true);
+        let path = spill_path();
+        let mut worker = worker(
+            &mock,
+            TransactionLogWriterConfig {
+                batch_size: 2,
+                initial_backoff: Duration::from_secs(3600),
+                max_backoff: Duration::from_secs(3600),
+                max_in_memory: 2,
+                spill_path: Some(path.clone()),
+                ..TransactionLogWriterConfig::default()
+            },
+        );
+        let transactions = (0..5).map(|_| transaction_log()).collect::<Vec<_>>();
+        worker.pending.extend(transactions.iter().cloned());
+
+        worker.flush().await;
+
+        // The oldest rows stay in memory, the newest go to disk
+        assert_eq!(mock.batches().len(), 1);
+        assert_eq!(
+            worker.pending.iter().map(|t| t.id).collect::<Vec<_>>(),
+            vec![transactions[0].id, transactions[1].id]
+        );
+        assert_eq!(worker.spilled, 3);
+        assert_eq!(line_count(&path).await, 3);
+        assert_eq!(worker.failed_batches, 1);
+
+        // Nothing is sent again before the backoff runs out
+        worker.flush().await;
+        assert_eq!(mock.batches().len(), 1);
+        assert!(worker
+            .retry_at
+            .is_some_and(|retry_at| retry_at > Instant::now()));
+
+        tokio::fs::remove_file(&path).await.ok();
+    }
+
+    #[tokio::test]
+    async fn recovered_database_receives_the_spilled_rows() {
+        let mock = MockDatabase::start().await;
+        mock.set_down(true);
+        let path = spill_path();
+        let config = TransactionLogWriterConfig {
+            batch_size: 2,
+            max_in_memory: 2,
+            spill_path: Some(path.clone()),
+            ..TransactionLogWriterConfig::default()
+        };
+        let mut worker = worker(&mock, config);
+        let transactions = (0..5).map(|_| transaction_log()).collect::<Vec<_>>();
+        worker.pending.extend(transactions.iter().cloned());
+        worker.flush().await;
+        assert_eq!(worker.spilled, 3);
+
+        mock.set_down(false);
+        worker.retry_at = None;
+        worker.flush().await;
+
+        let mut written = mock.batches()[1..].concat();
+        written.sort();
+        let mut expected = transactions.iter().map(|t| t.id).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(written, expected);
+        assert!(worker.pending.is_empty());
+        assert_eq!(worker.spilled, 0);
+        assert!(!path.exists());
+        assert!(!processing_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn reloaded_rows_stay_on_disk_until_written() {
+        let mock = MockDatabase::start().await;
+        mock.set_down(true);
+        let path = spill_path();
+        let config = TransactionLogWriterConfig {
+            batch_size: 2,
+            max_in_memory: 0,
+            spill_path: Some(path.clone()),
+            ..TransactionLogWriterConfig::default()
+        };
+        let mut first_run = worker(&mock, config.clone());
+        first_run
+            .pending
+            .extend([transaction_log(), transaction_log()]);
+        first_run.flush().await;
+        assert_eq!(line_count(&path).await, 2);
+
+        // The reload fails to write, as if the process died right after
+        let mut second_run = worker(&mock, config.clone());
+        second_run.reload_spilled().await;
+        assert_eq!(second_run.pending.len(), 2);
+        assert!(!path.exists());
+        assert_eq!(line_count(&processing_path(&path)).await, 2);
+
+        mock.set_down(false);
+        let mut third_run = worker(&mock, config);
+        third_run.reload_spilled().await;
+        assert_eq!(third_run.pending.len(), 2);
+        third_run.flush().await;
+
+        assert!(third_run.pending.is_empty());
+        assert!(!processing_path(&path).exists());
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::models::{
+    ClientTxInfo, TradeExecution, TradeSource, TradeStatus, TransactionType, WalletStats,
+};
+
+#[derive(Debug, Clone)]
+struct OpenPosition {
+    cost_sol: f64,
+    amount_token: f64,
+    opened_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct WalletRecord {
+    buys_observed: u64,
+    sells_observed: u64,
+    copied_trades: u64,
+    closed_positions: u64,
+    winning_positions: u64,
+    realized_pnl_sol: f64,
+    total_hold_secs: f64,
+    open_positions: HashMap<String, OpenPosition>,
+}
+
+// Per tracked wallet performance, updated incrementally as trades are observed
+// and copied. Copies always exit the full position, so a copied sell closes it
+#[derive(Debug, Clone, Default)]
+pub struct WalletStatsAccumulator {
+    wallets: HashMap<Uuid, WalletRecord>,
+    last_prices: HashMap<String, f64>,
+}
+
+impl WalletStatsAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Rebuilds the stats from stored copy executions. Only trades that reached
+    // the copy stage were stored, so those are what the observed counts cover,
+    // and open positions are marked at the last fill price
+    pub fn from_executions(executions: &[TradeExecution]) -> Self {
+        let mut accumulator = Self::new();
+        let mut executions = executions.iter().collect::<Vec<_>>();
+        executions.sort_by_key(|execution| execution.created_at);
+
+        for execution in executions {
+            if let Some(tracked_wallet_id) = execution.tracked_wallet_id {
+                accumulator.count_observed(tracked_wallet_id, &execution.transaction_type);
+            }
+            if execution.status == TradeStatus::Success && execution.amount_token > 0.0 {
+                accumulator.last_prices.insert(
+                    execution.token_address.clone(),
+                    execution.amount_sol / execution.amount_token,
+                );
+            }
+            accumulator.record_execution(execution);
+        }
+        accumulator
+    }
+
+    pub fn observe(&mut self, tx_info: &ClientTxInfo) {
+        if tx_info.price_per_token > 0.0 {
+            self.last_prices
+                .insert(tx_info.token_address.clone(), tx_info.price_per_token);
+        }
+        if let Some(tracked_wallet_id) = tx_info.tracked_wallet_id {
+            self.count_observed(tracked_wallet_id, &tx_info.transaction_type);
+        }
+    }
+
+    fn count_observed(&mut self, tracked_wallet_id: Uuid, transaction_type: &TransactionType) {
+        let record = self.wallets.entry(tracked_wallet_id).or_default();
+        match transaction_type {
+            TransactionType::Buy => record.buys_observed += 1,
+            TransactionType::Sell => record.sells_observed += 1,
+            _ => {}
+        }
+    }
+
+    // Returns the wallet's updated stats when the execution closed a position
+    pub fn record_execution(&mut self, execution: &TradeExecution) -> Option<WalletStats> {
+        if execution.trade_type != TradeSource::Copy || execution.status != TradeStatus::Success {
+            return None;
+        }
+        let tracked_wallet_id = execution.tracked_wallet_id?;
+        let record = self.wallets.entry(tracked_wallet_id).or_default();
+        record.copied_trades += 1;
+
+        match execution.transaction_type {
+            TransactionType::Buy => {
+                let position = record
+                    .open_positions
+                    .entry(execution.token_address.clone())
+                    .or_insert(OpenPosition {
+                        cost_sol: 0.0,
+                        amount_token: 0.0,
+                        opened_at: execution.created_at,
+                    });
+                position.cost_sol += execution.amount_sol;
+                position.amount_token += execution.amount_token;
+                None
+            }
+            TransactionType::Sell => {
+                // Positions opened before tracking started have no entry to compare against
+                let position = record.open_positions.remove(&execution.token_address)?;
+                let pnl = execution.amount_sol - position.cost_sol;
+
+                record.closed_positions += 1;
+                if pnl > 0.0 {
+                    record.winning_positions += 1;
+                }
+                record.realized_pnl_sol += pnl;
+                record.total_hold_secs += (execution.updated_at - position.opened_at)
+                    .num_seconds()
+                    .max(0) as f64;
+
+                Some(self.stats(tracked_wallet_id))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn stats(&self, tracked_wallet_id: Uuid) -> WalletStats {
+        let record = self
+            .wallets
+            .get(&tracked_wallet_id)
+            .cloned()
+            .unwrap_or_default();
+
+        let unrealized_pnl_sol = record
+            .open_positions
+            .iter()
+            .filter_map(|(token_address, position)| {
+                self.last_prices
+                    .get(token_address)
+                    .map(|price| price * position.amount_token - position.cost_sol)
+            })
+            .sum();
+
+        WalletStats {
+            tracked_wallet_id,
+            buys_observed: record.buys_observed,
+            sells_observed: record.sells_observed,
+            copied_trades: record.copied_trades,
+            closed_positions: record.closed_positions,
+            winning_positions: record.winning_positions,
+            win_rate: if record.closed_positions > 0 {
+                record.winning_positions as f64 / record.closed_positions as f64
+            } else {
+                0.0
+            },
+            realized_pnl_sol: record.realized_pnl_sol,
+            open_positions: record.open_positions.len(),
+            unrealized_pnl_sol,
+            avg_hold_secs: (record.closed_positions > 0)
+                .then(|| record.total_hold_secs / record.closed_positions as f64),
+        }
+    }
+
+    pub fn all_stats(&self) -> Vec<WalletStats> {
+        self.wallets
+            .keys()
+            .map(|tracked_wallet_id| self.stats(*tracked_wallet_id))
+            .collect()
+    }
+}
@@ -1,18 +1,65 @@
 use crate::event_system::{Event, EventSystem};
-use crate::models::WalletUpdateNotification;
-use crate::utils::data::{
-    extract_token_account_info, format_balance, format_token_amount, get_metadata,
+use crate::models::{
+    DustCleanupNotification, DustCleanupResult, InsufficientBalanceInfo,
+    InsufficientBalanceNotification, PortfolioNotification, PortfolioSnapshot, Position,
+    WalletUpdateNotification,
 };
-use crate::{ClientTxInfo, TransactionType};
+use crate::raydium::close_wsol_account_instruction;
+use crate::token_metadata::get_token_metadata;
+use crate::utils::data::{extract_token_account_info, format_balance, format_token_amount};
+use crate::{ClientTxInfo, TransactionType, WSOL};
 use anyhow::{Context, Result};
+use chrono::Utc;
 use serde::Serialize;
 use solana_client::rpc_client::RpcClient;
 use solana_client::rpc_request::TokenAccountsFilter;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
 use surf::Client;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const WSOL_CLOSE_BATCH_SIZE: usize = 8;
+// Differences below this are rounding, not tokens moving in or out
+const POSITION_AMOUNT_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Clone)]
+pub struct DustConfig {
+    // Tokens with a known price are dust below this value
+    pub min_value_sol: f64,
+    // Fallback for tokens without a price. Zero only treats empty accounts as dust
+    pub min_token_amount: f64,
+}
+
+impl Default for DustConfig {
+    fn default() -> Self {
+        Self {
+            min_value_sol: 0.001,
+            min_token_amount: 0.0,
+        }
+    }
+}
+
+impl DustConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            min_value_sol: std::env::var("DUST_MIN_VALUE_SOL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.min_value_sol),
+            min_token_amount: std::env::var("DUST_MIN_TOKEN_AMOUNT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.min_token_amount),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize)]
 pub struct TokenInfo {
@@ -31,6 +78,11 @@ pub struct ServerWalletManager {
     public_key: Pubkey,
     balance: f64,
     tokens: HashMap<String, TokenInfo>,
+    // On-chain UI amounts from the last refresh
+    token_amounts: HashMap<String, f64>,
+    positions: HashMap<String, Position>,
+    last_prices: HashMap<String, f64>,
+    dust: DustConfig,
     event_system: Arc<EventSystem>,
 }
 
@@ -46,6 +98,10 @@ impl ServerWalletManager {
             public_key,
             balance: 0.0,
             tokens: HashMap::new(),
+            token_amounts: HashMap::new(),
+            positions: HashMap::new(),
+            last_prices: HashMap::new(),
+            dust: DustConfig::from_env(),
             event_system,
         };
         manager.refresh_balances().await?;
@@ -58,9 +114,11 @@ impl ServerWalletManager {
 
         // Update token balances
         self.get_token_balances().await?;
+        self.reconcile_positions();
 
         // Emit wallet update event
         self.emit_wallet_update();
+        self.emit_portfolio();
 
         Ok(())
     }
@@ -73,6 +131,7 @@ impl ServerWalletManager {
     pub async fn get_token_balances(&mut self) -> Result<()> {
         // Clear existing tokens
         self.tokens.clear();
+        self.token_amounts.clear();
 
         // Get all token accounts
         let token_accounts = self.rpc_client.get_token_accounts_by_owner(
@@ -87,7 +146,9 @@ impl ServerWalletManager {
 
             if balance > 0 {
                 let mint_pubkey = Pubkey::from_str(&mint)?;
-                let metadata = get_metadata(&self.rpc_client, &mint_pubkey).await?;
+                let metadata = get_token_metadata(&self.rpc_client, &mint_pubkey).await?;
+                self.token_amounts
+                    .insert(mint.clone(), format_token_amount(balance, decimals));
 
                 self.tokens.insert(
                     mint.clone(),
@@ -150,17 +211,291 @@ impl ServerWalletManager {
         let notification = WalletUpdateNotification {
             data: self.get_wallet_info(),
             type_: "wallet_update".to_string(),
+            event_id: Uuid::new_v4(),
+            correlation_id: None,
         };
         self.event_system.emit(Event::WalletUpdate(notification));
     }
 
+    pub fn emit_insufficient_balance(&self, info: InsufficientBalanceInfo) {
+        let notification = InsufficientBalanceNotification {
+            data: info,
+            type_: "insufficient_balance".to_string(),
+            event_id: Uuid::new_v4(),
+            correlation_id: None,
+        };
+        self.event_system
+            .emit(Event::InsufficientBalance(notification));
+    }
+
+    // Dust is left out so it doesn't clutter every update
     pub fn get_wallet_info(&self) -> serde_json::Value {
+        let tokens = self
+            .tokens
+            .values()
+            .filter(|token| {
+                let amount = self.token_amounts.get(&token.address).copied();
+                !amount.is_some_and(|amount| self.is_dust(&token.address, amount))
+            })
+            .collect::<Vec<_>>();
+
         serde_json::json!({
             "balance": self.balance,
-            "tokens": self.tokens.values().collect::<Vec<_>>(),
+            "tokens": tokens,
         })
     }
 
+    pub fn is_dust(&self, mint: &str, amount: f64) -> bool {
+        if amount <= 0.0 {
+            return true;
+        }
+        match self.last_prices.get(mint) {
+            Some(price) => amount * price < self.dust.min_value_sol,
+            None => amount < self.dust.min_token_amount,
+        }
+    }
+
+    // Burns whatever dust is left in each account and closes it to reclaim the
+    // rent. Accounts above the threshold and WSOL accounts are never touched
+    pub async fn cleanup_dust(&mut self, owner: &Keypair) -> Result<Vec<DustCleanupResult>> {
+        if owner.pubkey() != self.public_key {
+            return Err(anyhow::anyhow!(
+                "Keypair {} does not own wallet {}",
+                owner.pubkey(),
+                self.public_key
+            ));
+        }
+
+        let token_accounts = self.rpc_client.get_token_accounts_by_owner(
+            &self.public_key,
+            TokenAccountsFilter::ProgramId(spl_token::id()),
+        )?;
+
+        let mut results = Vec::new();
+        for account in token_accounts {
+            let (mint, balance, decimals) = match extract_token_account_info(&account.account.data)
+            {
+                Some(info) => info,
+                None => continue,
+            };
+            let amount = format_token_amount(balance, decimals);
+            if mint == WSOL || !self.is_dust(&mint, amount) {
+                continue;
+            }
+
+            let result = self.close_dust_account(owner, &account.pubkey, &mint, balance);
+            let (signature, error) = match result {
+                Ok(signature) => {
+                    info!(
+                        "Closed dust account {} ({} {}), signature: {}",
+                        account.pubkey, amount, mint, signature
+                    );
+                    (Some(signature), None)
+                }
+                Err(e) => {
+                    warn!("Failed to close dust account {}: {}", account.pubkey, e);
+                    (None, Some(e.to_string()))
+                }
+            };
+
+            results.push(DustCleanupResult {
+                token_account: account.pubkey,
+                mint,
+                amount,
+                burned: signature.is_some() && balance > 0,
+                closed: signature.is_some(),
+                signature,
+                error,
+            });
+        }
+
+        if results.iter().any(|result| result.closed) {
+            self.refresh_balances().await?;
+        }
+
+        self.event_system
+            .emit(Event::DustCleanup(DustCleanupNotification {
+                data: results.clone(),
+                type_: "dust_cleanup".to_string(),
+                event_id: Uuid::new_v4(),
+                correlation_id: None,
+            }));
+
+        Ok(results)
+    }
+
+    fn close_dust_account(
+        &self,
+        owner: &Keypair,
+        token_account: &str,
+        mint: &str,
+        balance: u64,
+    ) -> Result<String> {
+        let token_account = Pubkey::from_str(token_account)?;
+        let mint = Pubkey::from_str(mint)?;
+
+        let mut instructions = Vec::new();
+        if balance > 0 {
+            instructions.push(spl_token::instruction::burn(
+                &spl_token::id(),
+                &token_account,
+                &mint,
+                &self.public_key,
+                &[],
+                balance,
+            )?);
+        }
+        instructions.push(spl_token::instruction::close_account(
+            &spl_token::id(),
+            &token_account,
+            &self.public_key,
+            &self.public_key,
+            &[],
+        )?);
+
+        let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&self.public_key),
+            &[owner],
+            recent_blockhash,
+        );
+        let signature = self
+            .rpc_client
+            .send_and_confirm_transaction(&transaction)
+            .context("Failed to close dust account")?;
+
+        Ok(signature.to_string())
+    }
+
+    // Replaces the in-memory ledger with persisted positions, then lines them up
+    // with what the wallet currently holds
+    pub fn load_positions(&mut self, positions: Vec<Position>) {
+        self.positions = positions
+            .into_iter()
+            .map(|position| (position.token_address.clone(), position))
+            .collect();
+        self.reconcile_positions();
+    }
+
+    // Buys add to the weighted average cost basis, sells realize PnL against it
+    pub fn record_fill(
+        &mut self,
+        transaction_type: &TransactionType,
+        token_address: &str,
+        amount_sol: f64,
+        amount_token: f64,
+    ) {
+        if amount_token <= 0.0 {
+            return;
+        }
+
+        let position = self
+            .positions
+            .entry(token_address.to_string())
+            .or_insert_with(|| empty_position(token_address));
+
+        match transaction_type {
+            TransactionType::Buy => {
+                position.amount_token += amount_token;
+                position.cost_basis_sol += amount_sol;
+            }
+            TransactionType::Sell => {
+                // Tokens sold beyond the recorded amount have no cost basis
+                let sold = amount_token.min(position.amount_token);
+                let cost = if position.amount_token > 0.0 {
+                    position.cost_basis_sol * sold / position.amount_token
+                } else {
+                    0.0
+                };
+                position.realized_pnl_sol += amount_sol - cost;
+                position.amount_token -= sold;
+                position.cost_basis_sol -= cost;
+            }
+            _ => return,
+        }
+        position.updated_at = Utc::now();
+    }
+
+    pub fn update_price(&mut self, token_address: &str, price_sol: f64) {
+        if price_sol > 0.0 {
+            self.last_prices
+                .insert(token_address.to_string(), price_sol);
+        }
+    }
+
+    // Tokens that arrive outside of recorded trades (airdrops, transfers) are
+    // added at zero cost. Tokens that leave take their share of the cost basis
+    // with them without realizing anything
+    fn reconcile_positions(&mut self) {
+        for token_address in self.token_amounts.keys() {
+            self.positions
+                .entry(token_address.clone())
+                .or_insert_with(|| empty_position(token_address));
+        }
+
+        for position in self.positions.values_mut() {
+            let held = self
+                .token_amounts
+                .get(&position.token_address)
+                .copied()
+                .unwrap_or(0.0);
+
+            if held > position.amount_token + POSITION_AMOUNT_TOLERANCE {
+                position.amount_token = held;
+                position.updated_at = Utc::now();
+            } else if held < position.amount_token - POSITION_AMOUNT_TOLERANCE {
+                position.cost_basis_sol *= held / position.amount_token;
+                position.amount_token = held;
+                position.updated_at = Utc::now();
+            }
+        }
+    }
+
+    pub fn portfolio(&self) -> PortfolioSnapshot {
+        let positions = self
+            .positions
+            .values()
+            .filter(|position| position.amount_token > 0.0)
+            .map(|position| {
+                let mut position = position.clone();
+                position.unrealized_pnl_sol = self
+                    .last_prices
+                    .get(&position.token_address)
+                    .map(|price| price * position.amount_token - position.cost_basis_sol);
+                position
+            })
+            .collect::<Vec<_>>();
+
+        PortfolioSnapshot {
+            sol_balance: self.balance,
+            unrealized_pnl_sol: positions
+                .iter()
+                .filter_map(|position| position.unrealized_pnl_sol)
+                .sum(),
+            realized_pnl_sol: self
+                .positions
+                .values()
+                .map(|position| position.realized_pnl_sol)
+                .sum(),
+            positions,
+        }
+    }
+
+    pub fn position(&self, token_address: &str) -> Option<&Position> {
+        self.positions.get(token_address)
+    }
+
+    pub fn emit_portfolio(&self) {
+        let notification = PortfolioNotification {
+            data: self.portfolio(),
+            type_: "portfolio".to_string(),
+            event_id: Uuid::new_v4(),
+            correlation_id: None,
+        };
+        self.event_system.emit(Event::Portfolio(notification));
+    }
+
     // Helper methods for querying state
     pub fn get_tokens(&self) -> &HashMap<String, TokenInfo> {
         &self.tokens
@@ -170,6 +505,60 @@ impl ServerWalletManager {
         self.tokens.values()
     }
 
+    // WSOL accounts left behind by failed or interrupted swaps hold rent and
+    // sometimes SOL. Closing them unwraps everything back to the wallet
+    pub async fn close_orphaned_wsol_accounts(&mut self, owner: &Keypair) -> Result<usize> {
+        if owner.pubkey() != self.public_key {
+            return Err(anyhow::anyhow!(
+                "Keypair {} does not own wallet {}",
+                owner.pubkey(),
+                self.public_key
+            ));
+        }
+
+        let wsol_accounts = self.rpc_client.get_token_accounts_by_owner(
+            &self.public_key,
+            TokenAccountsFilter::Mint(Pubkey::from_str(WSOL)?),
+        )?;
+
+        let account_keys = wsol_accounts
+            .iter()
+            .filter_map(|account| Pubkey::from_str(&account.pubkey).ok())
+            .collect::<Vec<_>>();
+
+        if account_keys.is_empty() {
+            return Ok(0);
+        }
+
+        for batch in account_keys.chunks(WSOL_CLOSE_BATCH_SIZE) {
+            let instructions = batch
+                .iter()
+                .map(|account| close_wsol_account_instruction(account, &self.public_key))
+                .collect::<Result<Vec<_>>>()?;
+
+            let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
+            let transaction = Transaction::new_signed_with_payer(
+                &instructions,
+                Some(&self.public_key),
+                &[owner],
+                recent_blockhash,
+            );
+            let signature = self
+                .rpc_client
+                .send_and_confirm_transaction(&transaction)
+                .context("Failed to close orphaned WSOL accounts")?;
+            info!(
+                "Closed {} orphaned WSOL accounts, signature: {}",
+                batch.len(),
+                signature
+            );
+        }
+
+        self.refresh_balances().await?;
+
+        Ok(account_keys.len())
+    }
+
     pub async fn handle_trade_execution(&mut self, tx_info: &ClientTxInfo) -> Result<()> {
         match tx_info.transaction_type {
             TransactionType::Buy => {
@@ -204,3 +593,14 @@ impl ServerWalletManager {
         Ok(())
     }
 }
+
+fn empty_position(token_address: &str) -> Position {
+    Position {
+        token_address: token_address.to_string(),
+        amount_token: 0.0,
+        cost_basis_sol: 0.0,
+        realized_pnl_sol: 0.0,
+        unrealized_pnl_sol: None,
+        updated_at: Utc::now(),
+    }
+}
@@ -1,7 +1,11 @@
 use crate::error::AppError;
+use crate::models::SubscriptionFailure;
+use crate::utils::rpc::parse_endpoint_urls;
+use async_trait::async_trait;
 use backoff::{backoff::Backoff, ExponentialBackoff};
 use futures_util::{stream::StreamExt, SinkExt};
 use serde_json::json;
+use std::collections::HashMap;
 use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
 use tokio_tungstenite::{
@@ -18,7 +22,12 @@ pub struct WebSocketConfig {
     pub connection_timeout: Duration,
     pub initial_backoff: Duration,
     pub max_backoff: Duration,
-    pub max_retries: u32,
+    // Consecutive failed connection attempts before giving up, None retries forever
+    pub max_retries: Option<u32>,
+    // Randomization applied to each backoff delay, 0.5 means +/- 50%
+    pub jitter: f64,
+    // A wallet silent for this long while others are active is considered stale
+    pub subscription_silence: Duration,
 }
 
 impl Default for WebSocketConfig {
@@ -28,11 +37,56 @@ impl Default for WebSocketConfig {
             connection_timeout: Duration::from_secs(5),
             initial_backoff: Duration::from_secs(1),
             max_backoff: Duration::from_secs(60),
-            max_retries: 3,
+            max_retries: None,
+            jitter: 0.5,
+            subscription_silence: Duration::from_secs(10 * 60),
         }
     }
 }
 
+impl WebSocketConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            max_backoff: std::env::var("WS_MAX_BACKOFF_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default.max_backoff),
+            // Unset or 0 keeps retrying
+            max_retries: std::env::var("WS_MAX_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|retries| *retries > 0),
+            jitter: std::env::var("WS_BACKOFF_JITTER")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.jitter),
+            subscription_silence: std::env::var("WS_SUBSCRIPTION_SILENCE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default.subscription_silence),
+            ..default
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PendingRequest {
+    Subscribe(String),
+    Unsubscribe(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscriptionInfo {
+    pub wallet_address: String,
+    pub subscription_id: Option<u64>,
+    // Confirmation time until the first notification arrives
+    pub last_activity: Option<Instant>,
+}
+
 #[derive(Debug)]
 enum ConnectionState {
     Connected(WsStream),
@@ -41,31 +95,51 @@ enum ConnectionState {
 }
 
 pub struct WebSocketConnectionManager {
-    ws_url: String,
+    ws_urls: Vec<String>,
+    current_url: usize,
     config: WebSocketConfig,
     backoff: ExponentialBackoff,
     last_connection_attempt: Option<Instant>,
+    failed_attempts: u32,
     last_health_check: Option<Instant>,
     state: ConnectionState,
+    // Wallet address -> subscription id (None while the confirmation is pending)
+    subscriptions: HashMap<String, Option<u64>>,
+    // Wallet address -> last confirmation or notification
+    activity: HashMap<String, Instant>,
+    subscription_failures: Vec<SubscriptionFailure>,
+    last_notification_slot: Option<u64>,
+    pending_requests: HashMap<u64, PendingRequest>,
+    next_request_id: u64,
 }
 
 impl WebSocketConnectionManager {
+    // ws_url may be a comma-separated list, later entries are used as fallbacks
     pub fn new(ws_url: String, config: Option<WebSocketConfig>) -> Self {
         let config = config.unwrap_or_default();
         let backoff = ExponentialBackoff {
             max_elapsed_time: None,
             initial_interval: config.initial_backoff,
             max_interval: config.max_backoff,
+            randomization_factor: config.jitter,
             ..ExponentialBackoff::default()
         };
 
         Self {
-            ws_url,
+            ws_urls: parse_endpoint_urls(&ws_url),
+            current_url: 0,
             config,
             backoff,
             last_connection_attempt: None,
+            failed_attempts: 0,
             last_health_check: None,
             state: ConnectionState::Disconnected,
+            subscriptions: HashMap::new(),
+            activity: HashMap::new(),
+            subscription_failures: Vec::new(),
+            last_notification_slot: None,
+            pending_requests: HashMap::new(),
+            next_request_id: 1,
         }
     }
 
@@ -111,12 +185,18 @@ impl WebSocketConnectionManager {
 
     pub async fn subscribe(&mut self, subscriptions: Vec<String>) -> Result<(), AppError> {
         let timeout_duration = self.config.connection_timeout;
-        let conn = self.ensure_connection().await?;
+        self.ensure_connection().await?;
+
+        for sub in subscriptions {
+            // Skip wallets already subscribed on this connection
+            if self.subscriptions.contains_key(&sub) {
+                continue;
+            }
 
-        for (idx, sub) in subscriptions.iter().enumerate() {
+            let request_id = self.next_request_id();
             let msg = json!({
                 "jsonrpc": "2.0",
-                "id": idx + 1,
+                "id": request_id,
                 "method": "logsSubscribe",
                 "params": [
                     {"mentions": [sub]},
@@ -124,41 +204,305 @@ impl WebSocketConnectionManager {
                 ]
             });
 
+            let conn = self.connection()?;
+
             // Send subscription request
             conn.send(Message::Text(msg.to_string().into()))
                 .await
                 .map_err(|e| AppError::WebSocketError(format!("Subscribe failed: {}", e)))?;
 
             // Wait for subscription confirmation
-            match tokio::time::timeout(timeout_duration, conn.next()).await {
-                Ok(Some(Ok(Message::Text(resp)))) => {
-                    // Verify subscription response
-                    if !resp.to_string().contains("\"result\"") {
-                        return Err(AppError::WebSocketError(format!(
-                            "Invalid subscription response: {}",
-                            resp
-                        )));
-                    }
-                }
+            let resp = match tokio::time::timeout(timeout_duration, conn.next()).await {
+                Ok(Some(Ok(Message::Text(resp)))) => resp.to_string(),
                 _ => {
                     return Err(AppError::WebSocketError(
                         "Failed to receive subscription confirmation".to_string(),
                     ));
                 }
+            };
+
+            // Verify subscription response, a rejected wallet doesn't fail the others
+            let value = serde_json::from_str::<serde_json::Value>(&resp).map_err(|_| {
+                AppError::WebSocketError(format!("Invalid subscription response: {}", resp))
+            })?;
+
+            match value.get("result").and_then(|r| r.as_u64()) {
+                Some(subscription_id) => {
+                    self.activity.insert(sub.clone(), Instant::now());
+                    self.subscriptions.insert(sub, Some(subscription_id));
+                }
+                None => self.record_subscription_failure(sub, &value, &resp),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Subscribes on the live connection without waiting for the confirmation,
+    // which is picked up by receive_message alongside regular notifications
+    pub async fn add_subscriptions(&mut self, wallets: Vec<String>) -> Result<(), AppError> {
+        for wallet in wallets {
+            if self.subscriptions.contains_key(&wallet) {
+                continue;
+            }
+
+            let params = json!([
+                {"mentions": [wallet]},
+                {"commitment": "confirmed"}
+            ]);
+            self.send_request(
+                "logsSubscribe",
+                params,
+                PendingRequest::Subscribe(wallet.clone()),
+            )
+            .await?;
+            self.subscriptions.insert(wallet, None);
+        }
+
+        Ok(())
+    }
+
+    pub async fn unsubscribe(&mut self, wallets: Vec<String>) -> Result<(), AppError> {
+        for wallet in wallets {
+            self.activity.remove(&wallet);
+            match self.subscriptions.remove(&wallet) {
+                Some(Some(subscription_id)) => {
+                    self.send_unsubscribe(&wallet, subscription_id).await?;
+                }
+                // Still pending, the confirmation handler unsubscribes once the id is known
+                Some(None) => {}
+                None => {}
             }
         }
 
         Ok(())
     }
 
+    // Consecutive failed connection attempts since the last successful connect
+    pub fn reconnect_attempts(&self) -> u32 {
+        self.failed_attempts
+    }
+
+    pub fn retries_exhausted(&self) -> bool {
+        self.config
+            .max_retries
+            .is_some_and(|max_retries| self.failed_attempts >= max_retries)
+    }
+
+    pub fn subscribed_wallets(&self) -> Vec<String> {
+        self.subscriptions.keys().cloned().collect()
+    }
+
+    pub fn subscription_table(&self) -> Vec<SubscriptionInfo> {
+        self.subscriptions
+            .iter()
+            .map(|(wallet, subscription_id)| SubscriptionInfo {
+                wallet_address: wallet.clone(),
+                subscription_id: *subscription_id,
+                last_activity: self.activity.get(wallet).copied(),
+            })
+            .collect()
+    }
+
+    pub fn take_subscription_failures(&mut self) -> Vec<SubscriptionFailure> {
+        std::mem::take(&mut self.subscription_failures)
+    }
+
+    // Confirmed wallets that have been silent for `subscription_silence` while at
+    // least one other wallet produced a notification in that window
+    pub fn stale_subscriptions(&self) -> Vec<String> {
+        let silence = self.config.subscription_silence;
+        let any_active = self
+            .activity
+            .values()
+            .any(|last_activity| last_activity.elapsed() < silence);
+        if !any_active {
+            return Vec::new();
+        }
+
+        self.subscriptions
+            .iter()
+            .filter(|(_, subscription_id)| subscription_id.is_some())
+            .filter(|(wallet, _)| {
+                !self
+                    .activity
+                    .get(*wallet)
+                    .is_some_and(|last_activity| last_activity.elapsed() < silence)
+            })
+            .map(|(wallet, _)| wallet.clone())
+            .collect()
+    }
+
+    pub async fn resubscribe(&mut self, wallets: Vec<String>) -> Result<(), AppError> {
+        self.unsubscribe(wallets.clone()).await?;
+        self.add_subscriptions(wallets).await
+    }
+
+    fn record_subscription_failure(
+        &mut self,
+        wallet: String,
+        response: &serde_json::Value,
+        raw: &str,
+    ) {
+        warn!("Subscription failed for {}: {}", wallet, raw);
+        let error = response
+            .get("error")
+            .map(|error| error.to_string())
+            .unwrap_or_else(|| raw.to_string());
+        self.subscriptions.remove(&wallet);
+        self.activity.remove(&wallet);
+        self.subscription_failures.push(SubscriptionFailure {
+            wallet_address: wallet,
+            error,
+        });
+    }
+
+    // Slot of the most recent log notification on this connection
+    pub fn last_notification_slot(&self) -> Option<u64> {
+        self.last_notification_slot
+    }
+
+    // Drops the current connection and moves to the next endpoint, used when
+    // the feed is alive but falling behind the chain
+    pub async fn failover(&mut self) {
+        self.cleanup_connection().await;
+        self.current_url = (self.current_url + 1) % self.ws_urls.len();
+        info!(
+            "Failing over to WebSocket endpoint {}",
+            self.ws_urls[self.current_url]
+        );
+    }
+
+    fn record_notification(&mut self, text: &str) {
+        let value = match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+
+        if let Some(slot) = value
+            .pointer("/params/result/context/slot")
+            .and_then(|slot| slot.as_u64())
+        {
+            self.last_notification_slot = Some(slot);
+        }
+
+        if let Some(subscription_id) = value
+            .pointer("/params/subscription")
+            .and_then(|id| id.as_u64())
+        {
+            if let Some(wallet) = self
+                .subscriptions
+                .iter()
+                .find(|(_, id)| **id == Some(subscription_id))
+                .map(|(wallet, _)| wallet.clone())
+            {
+                self.activity.insert(wallet, Instant::now());
+            }
+        }
+    }
+
+    async fn send_unsubscribe(
+        &mut self,
+        wallet: &str,
+        subscription_id: u64,
+    ) -> Result<(), AppError> {
+        self.send_request(
+            "logsUnsubscribe",
+            json!([subscription_id]),
+            PendingRequest::Unsubscribe(wallet.to_string()),
+        )
+        .await
+    }
+
+    async fn send_request(
+        &mut self,
+        method: &str,
+        params: serde_json::Value,
+        request: PendingRequest,
+    ) -> Result<(), AppError> {
+        let request_id = self.next_request_id();
+        let msg = json!({
+            "jsonrpc": "2.0",
+            "id": request_id,
+            "method": method,
+            "params": params
+        });
+
+        self.send_message(Message::Text(msg.to_string().into()))
+            .await?;
+        self.pending_requests.insert(request_id, request);
+        Ok(())
+    }
+
+    async fn handle_rpc_response(&mut self, text: &str) {
+        let value: serde_json::Value = match serde_json::from_str(text) {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+
+        let request = match value
+            .get("id")
+            .and_then(|id| id.as_u64())
+            .and_then(|id| self.pending_requests.remove(&id))
+        {
+            Some(request) => request,
+            None => return,
+        };
+
+        match request {
+            PendingRequest::Subscribe(wallet) => {
+                match value.get("result").and_then(|r| r.as_u64()) {
+                    Some(subscription_id) if self.subscriptions.contains_key(&wallet) => {
+                        info!("Subscribed to {} ({})", wallet, subscription_id);
+                        self.activity.insert(wallet.clone(), Instant::now());
+                        self.subscriptions.insert(wallet, Some(subscription_id));
+                    }
+                    Some(subscription_id) => {
+                        // Wallet was removed while the subscription was pending
+                        if let Err(e) = self.send_unsubscribe(&wallet, subscription_id).await {
+                            warn!("Failed to unsubscribe removed wallet {}: {}", wallet, e);
+                        }
+                    }
+                    None if self.subscriptions.contains_key(&wallet) => {
+                        self.record_subscription_failure(wallet, &value, text);
+                    }
+                    None => {}
+                }
+            }
+            PendingRequest::Unsubscribe(wallet) => {
+                info!("Unsubscribed from {}", wallet);
+            }
+        }
+    }
+
+    fn next_request_id(&mut self) -> u64 {
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        request_id
+    }
+
+    fn connection(&mut self) -> Result<&mut WsStream, AppError> {
+        match &mut self.state {
+            ConnectionState::Connected(conn) => Ok(conn),
+            _ => Err(AppError::WebSocketStateError(
+                "WebSocket is not connected".to_string(),
+            )),
+        }
+    }
+
     async fn establish_connection(&mut self) -> Result<&mut WsStream, AppError> {
-        let now = Instant::now();
+        if self.retries_exhausted() {
+            return Err(AppError::WebSocketConnectionError(format!(
+                "Giving up after {} failed connection attempts",
+                self.failed_attempts
+            )));
+        }
 
         // Only apply backoff if we're not recovering from Connecting state
         if matches!(self.state, ConnectionState::Disconnected) {
             if let Some(last_attempt) = self.last_connection_attempt {
                 if let Some(wait_time) = self.backoff.next_backoff() {
-                    let elapsed = now.duration_since(last_attempt);
+                    let elapsed = last_attempt.elapsed();
                     if elapsed < wait_time {
                         debug!("Applying backoff delay of {:?}", wait_time - elapsed);
                         tokio::time::sleep(wait_time - elapsed).await;
@@ -167,14 +511,29 @@ impl WebSocketConnectionManager {
             }
         }
 
+        let now = Instant::now();
         self.last_connection_attempt = Some(now);
         self.state = ConnectionState::Connecting;
 
-        match connect_async(&self.ws_url).await {
+        let ws_url =
+            self.ws_urls.get(self.current_url).cloned().ok_or_else(|| {
+                AppError::WebSocketConnectionError("No WebSocket URL".to_string())
+            })?;
+
+        match connect_async(&ws_url).await {
             Ok((stream, _)) => {
-                info!("Successfully established WebSocket connection");
+                info!(
+                    "Successfully established WebSocket connection to {}",
+                    ws_url
+                );
                 self.backoff.reset();
+                self.failed_attempts = 0;
                 self.last_health_check = Some(now);
+                // Subscriptions don't survive a new connection
+                self.subscriptions.clear();
+                self.activity.clear();
+                self.last_notification_slot = None;
+                self.pending_requests.clear();
                 self.state = ConnectionState::Connected(stream);
                 match &mut self.state {
                     ConnectionState::Connected(conn) => Ok(conn),
@@ -182,8 +541,14 @@ impl WebSocketConnectionManager {
                 }
             }
             Err(e) => {
-                error!("Failed to establish WebSocket connection: {}", e);
+                error!(
+                    "Failed to establish WebSocket connection to {}: {}",
+                    ws_url, e
+                );
                 self.state = ConnectionState::Disconnected;
+                self.failed_attempts += 1;
+                // Try the next endpoint on the following attempt
+                self.current_url = (self.current_url + 1) % self.ws_urls.len();
                 Err(AppError::WebSocketConnectionError(format!(
                     "Failed to establish connection: {}",
                     e
@@ -249,11 +614,21 @@ impl WebSocketConnectionManager {
 
     pub async fn receive_message(&mut self) -> Result<Option<Message>, AppError> {
         let conn = self.ensure_connection().await?;
-        match conn.next().await {
-            Some(Ok(msg)) => Ok(Some(msg)),
-            Some(Err(e)) => Err(AppError::WebSocketReceiveError(e.to_string())),
-            None => Ok(None),
+        let message = match conn.next().await {
+            Some(Ok(msg)) => msg,
+            Some(Err(e)) => return Err(AppError::WebSocketReceiveError(e.to_string())),
+            None => return Ok(None),
+        };
+
+        if let Message::Text(text) = &message {
+            if text.contains("logsNotification") {
+                self.record_notification(text.as_str());
+            } else if !self.pending_requests.is_empty() {
+                self.handle_rpc_response(text.as_str()).await;
+            }
         }
+
+        Ok(Some(message))
     }
 
     pub async fn cleanup_connection(&mut self) {
@@ -262,6 +637,9 @@ impl WebSocketConnectionManager {
         {
             let _ = conn.close(None).await;
         }
+        self.subscriptions.clear();
+        self.activity.clear();
+        self.pending_requests.clear();
     }
 
     pub async fn shutdown(&mut self) -> Result<(), AppError> {
@@ -270,3 +648,27 @@ impl WebSocketConnectionManager {
         Ok(())
     }
 }
+
+// The subscription calls the wallet monitor reconciles its tracked wallets
+// against, kept behind a trait so the reconciliation can run without a socket
+#[async_trait]
+pub trait WalletSubscriptions: Send {
+    fn subscribed_wallets(&self) -> Vec<String>;
+    async fn add_subscriptions(&mut self, wallets: Vec<String>) -> Result<(), AppError>;
+    async fn unsubscribe(&mut self, wallets: Vec<String>) -> Result<(), AppError>;
+}
+
+#[async_trait]
+impl WalletSubscriptions for WebSocketConnectionManager {
+    fn subscribed_wallets(&self) -> Vec<String> {
+        WebSocketConnectionManager::subscribed_wallets(self)
+    }
+
+    async fn add_subscriptions(&mut self, wallets: Vec<String>) -> Result<(), AppError> {
+        WebSocketConnectionManager::add_subscriptions(self, wallets).await
+    }
+
+    async fn unsubscribe(&mut self, wallets: Vec<String>) -> Result<(), AppError> {
+        WebSocketConnectionManager::unsubscribe(self, wallets).await
+    }
+}
@@ -1,2 +1,6 @@
 mod connection_manager;
-pub use connection_manager::{WebSocketConfig, WebSocketConnectionManager};
+mod slot_lag;
+pub use connection_manager::{
+    SubscriptionInfo, WalletSubscriptions, WebSocketConfig, WebSocketConnectionManager,
+};
+pub use slot_lag::{SlotLagConfig, SlotLagTracker};
@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+
+const SLOT_DURATION_MS: u128 = 400;
+
+#[derive(Debug, Clone)]
+pub struct SlotLagConfig {
+    pub check_interval: Duration,
+    pub max_lag_slots: u64,
+    // Lag has to stay above the threshold this long before we fail over
+    pub sustained_for: Duration,
+    pub failover_on_lag: bool,
+}
+
+impl Default for SlotLagConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_secs(10),
+            max_lag_slots: 20,
+            sustained_for: Duration::from_secs(30),
+            failover_on_lag: true,
+        }
+    }
+}
+
+impl SlotLagConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        Self {
+            check_interval: std::env::var("SLOT_LAG_CHECK_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default.check_interval),
+            max_lag_slots: std::env::var("SLOT_LAG_MAX_SLOTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.max_lag_slots),
+            sustained_for: std::env::var("SLOT_LAG_SUSTAINED_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default.sustained_for),
+            failover_on_lag: std::env::var("SLOT_LAG_FAILOVER")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.failover_on_lag),
+        }
+    }
+}
+
+// Compares the slot of websocket notifications against the RPC tip. The tip is
+// only fetched every check interval, in between it is extrapolated from slot time
+pub struct SlotLagTracker {
+    config: SlotLagConfig,
+    tip: Option<(u64, Instant)>,
+    last_lag: Option<u64>,
+    lagging_since: Option<Instant>,
+}
+
+impl SlotLagTracker {
+    pub fn new(config: SlotLagConfig) -> Self {
+        Self {
+            config,
+            tip: None,
+            last_lag: None,
+            lagging_since: None,
+        }
+    }
+
+    pub fn config(&self) -> &SlotLagConfig {
+        &self.config
+    }
+
+    pub fn record_tip(&mut self, slot: u64) {
+        self.tip = Some((slot, Instant::now()));
+    }
+
+    pub fn estimated_tip(&self) -> Option<u64> {
+        self.tip.map(|(slot, fetched_at)| {
+            slot + (fetched_at.elapsed().as_millis() / SLOT_DURATION_MS) as u64
+        })
+    }
+
+    // Returns the lag of a notification at `slot`, None until the tip is known
+    pub fn observe(&mut self, slot: u64) -> Option<u64> {
+        let lag = self.estimated_tip()?.saturating_sub(slot);
+        self.last_lag = Some(lag);
+
+        if lag > self.config.max_lag_slots {
+            self.lagging_since.get_or_insert_with(Instant::now);
+        } else {
+            self.lagging_since = None;
+        }
+
+        Some(lag)
+    }
+
+    pub fn current_lag(&self) -> Option<u64> {
+        self.last_lag
+    }
+
+    pub fn is_lagging(&self) -> bool {
+        self.lagging_since.is_some()
+    }
+
+    pub fn should_failover(&self) -> bool {
+        self.config.failover_on_lag
+            && self
+                .lagging_since
+                .is_some_and(|since| since.elapsed() >= self.config.sustained_for)
+    }
+
+    pub fn reset(&mut self) {
+        self.last_lag = None;
+        self.lagging_since = None;
+    }
+}